@@ -96,6 +96,7 @@ pub struct USBflyApp {
     status_message: Option<String>, // For displaying status messages to users
     dark_mode: bool,
     current_speed: crate::usb::Speed, // Current USB speed setting used for synchronization
+    capture_format: crate::usb::CaptureFormat, // What each received buffer holds
 }
 
 #[derive(Debug, Clone)]
@@ -147,6 +148,8 @@ impl Application for USBflyApp {
         // Create a decoder with the default speed
         let mut decoder = UsbDecoder::new();
         decoder.set_speed(default_speed);
+        let capture_format = crate::usb::CaptureFormat::from_env();
+        decoder.capture_format = capture_format;
         
         let app = Self {
             cynthion_handle: None,
@@ -162,6 +165,7 @@ impl Application for USBflyApp {
             status_message: None,
             dark_mode: true, // Default to dark mode for a hacker-friendly UI
             current_speed: default_speed, // Initialize with the default speed
+            capture_format,
         };
         
         // Map the device command to our application's message type
@@ -494,8 +498,26 @@ impl Application for USBflyApp {
                             self.connection = self.cynthion_handle.clone();
                         }
                         
-                        // Process the data with our enhanced decoder using the connection handle
-                        if let Some(handle) = &self.connection {
+                        // Packet-level captures deliver one bus event or raw USB packet per buffer and go
+                        // to packet analysis; capture streams are processed into transactions by the
+                        // connection handle
+                        let now_secs = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs_f64();
+                        if self.capture_format == crate::usb::CaptureFormat::Packets {
+                            if let Some(record) = crate::usb::bus_event::BusEventRecord::parse(now_secs, &data) {
+                                debug!("Bus event record: {}", record.name());
+                                for event in self.traffic_view.add_bus_event_record(record) {
+                                    crate::cynthion::device_detector::UsbDeviceConnectionDetector::process_bus_event(&event);
+                                }
+                            } else if let Some(packet) = crate::usb::pid::UsbPacket::parse(now_secs, &data) {
+                                debug!("Raw USB packet: {}", packet.summary());
+                                self.traffic_view.add_usb_packet(packet);
+                            } else {
+                                warn!("Buffer of {} bytes is neither a bus event nor a valid USB packet", data.len());
+                            }
+                        } else if let Some(handle) = &self.connection {
                             if let Ok(mut cynthion_handle) = handle.lock() {
                                 // Check if this data contains evidence of USB devices connected to Cynthion
                                 // This helps optimize packet processing for connected devices
@@ -540,6 +562,7 @@ impl Application for USBflyApp {
                         
                         // Continue with descriptor view update
                         self.descriptor_view.update_descriptors(decoded);
+                        self.descriptor_view.update_bus_devices(self.traffic_view.bus_model());
                     },
                     None => {
                         debug!("Failed to decode USB data");
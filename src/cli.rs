@@ -0,0 +1,133 @@
+//! Command-line tools that run without starting the GUI
+//!
+//!   usbfly --vendor-schemas                          List the loaded vendor request schemas
+//!   usbfly --decode-vendor VID:PID SETUP [DATA]      Decode one vendor request with them
+//!   usbfly --run-scripts CAPTURE                     Run the analyzer scripts over a saved capture
+//!
+//! SETUP is the 8-byte setup packet and DATA the data stage, both as hex.
+//! CAPTURE is a .usb file saved from the traffic view.
+
+use crate::gui::views::traffic_view::TrafficItem;
+use crate::usb::analyzer::AnalyzerRegistry;
+use crate::usb::mitm_traffic::UsbSetupPacket;
+use crate::usb::replay::CaptureReplay;
+use crate::usb::CaptureFormat;
+use crate::usb::script::{ScriptSet, SCRIPT_DIR_VAR};
+use crate::usb::vendor_schema::{VendorSchemaRegistry, SCHEMA_DIR_VAR};
+
+/// Run a command-line tool if one was requested. Returns the exit code, or None to start the GUI.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.get(1)?;
+    let result = match command.as_str() {
+        "--vendor-schemas" => list_vendor_schemas(),
+        "--decode-vendor" => decode_vendor(&args[2..]),
+        "--run-scripts" => run_scripts(&args[2..]),
+        _ => return None,
+    };
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    })
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace() && *c != ':').collect();
+    hex::decode(digits.trim_start_matches("0x")).map_err(|e| format!("invalid hex \"{}\": {}", text, e))
+}
+
+fn parse_ids(text: &str) -> Result<(u16, u16), String> {
+    let (vendor, product) = text.split_once(':')
+        .ok_or_else(|| format!("expected VID:PID, got \"{}\"", text))?;
+    let parse = |id: &str| u16::from_str_radix(id.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid ID \"{}\"", id));
+    Ok((parse(vendor)?, parse(product)?))
+}
+
+fn load_registry() -> Result<VendorSchemaRegistry, String> {
+    let registry = VendorSchemaRegistry::load_default();
+    for error in registry.errors() {
+        eprintln!("warning: {}", error);
+    }
+    if registry.schemas().is_empty() {
+        return Err(match VendorSchemaRegistry::schema_dir() {
+            Some(dir) => format!("no vendor schemas found in {} (set {} to use another directory)",
+                                 dir.display(), SCHEMA_DIR_VAR),
+            None => format!("no schema directory; set {}", SCHEMA_DIR_VAR),
+        });
+    }
+    Ok(registry)
+}
+
+fn list_vendor_schemas() -> Result<(), String> {
+    let registry = load_registry()?;
+    for schema in registry.schemas() {
+        println!("{}", schema);
+        for request in &schema.requests {
+            println!("    0x{:02X} {}", request.request, request.name);
+        }
+    }
+    Ok(())
+}
+
+fn decode_vendor(args: &[String]) -> Result<(), String> {
+    let usage = "usage: usbfly --decode-vendor VID:PID SETUP [DATA]";
+    let (vendor_id, product_id) = parse_ids(args.first().ok_or(usage)?)?;
+    let setup_bytes = parse_hex(args.get(1).ok_or(usage)?)?;
+    let data = match args.get(2) {
+        Some(data) => parse_hex(data)?,
+        None => Vec::new(),
+    };
+    let setup = UsbSetupPacket::new(&setup_bytes).ok_or("setup packet must be 8 bytes")?;
+
+    let registry = load_registry()?;
+    let request = registry.decode_request(vendor_id, product_id, &setup)
+        .ok_or_else(|| format!("no schema request matches {:04X}:{:04X} bRequest 0x{:02X}",
+                               vendor_id, product_id, setup.bRequest))?;
+
+    println!("{}", request.description);
+    for (name, value) in request.fields.iter()
+        .chain(registry.decode_data(vendor_id, product_id, &setup, &data).iter()) {
+        println!("    {}: {}", name, value);
+    }
+    Ok(())
+}
+
+fn run_scripts(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or("usage: usbfly --run-scripts CAPTURE")?;
+    let capture = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let items: Vec<TrafficItem> = serde_json::from_str(&capture).map_err(|e| format!("{}: {}", path, e))?;
+
+    let scripts = ScriptSet::load_default();
+    for error in scripts.errors() {
+        eprintln!("warning: {}", error);
+    }
+    if scripts.is_empty() {
+        return Err(match ScriptSet::script_dir() {
+            Some(dir) => format!("no analyzer scripts found in {} (set {} to use another directory)",
+                                 dir.display(), SCRIPT_DIR_VAR),
+            None => format!("no script directory; set {}", SCRIPT_DIR_VAR),
+        });
+    }
+    let mut analyzers = AnalyzerRegistry::new();
+    analyzers.set_scripts(scripts.into_analyzers());
+
+    let mut replay = CaptureReplay::new(CaptureFormat::from_env());
+    for item in &items {
+        for transfer in replay.process_record(item.timestamp, &item.raw_data) {
+            for output in analyzers.process(&transfer) {
+                println!("[{:.6}] Addr {} EP {} {}: {}", transfer.timestamp, transfer.device.address,
+                         transfer.endpoint, output.analyzer, output.summary.as_deref().unwrap_or(""));
+                for note in &output.notes {
+                    println!("    {}", note);
+                }
+                for line in &output.log {
+                    println!("    | {}", line);
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -9,6 +9,8 @@ use std::sync::Mutex;
 
 // Import the Speed enum from the usb module instead of the deprecated module
 use crate::usb::Speed;
+use crate::usb::bus_event::{BusEvent, BusEventKind};
+use crate::usb::enumeration::EnumerationStep;
 
 // Global state variables for enhanced device and capture detection
 lazy_static! {
@@ -24,6 +26,10 @@ lazy_static! {
     // Track if a device timeout has occurred
     static ref DEVICE_TIMEOUT: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
     
+    // Track if the capture reports bus events; when it does they replace the
+    // GET_DESCRIPTOR pattern scan for connection detection
+    static ref BUS_EVENTS_SEEN: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    
     // Keep track of the last successful USB speed setting for improved reconnection
     static ref LAST_SUCCESSFUL_SPEED: Arc<Mutex<Option<Speed>>> = Arc::new(Mutex::new(None));
 }
@@ -57,6 +63,8 @@ impl UsbDeviceConnectionDetector {
     pub fn set_capture_active(active: bool) {
         CAPTURE_ACTIVE.store(active, Ordering::Relaxed);
         if active {
+            // A new capture may come from an analyzer that doesn't report bus events
+            BUS_EVENTS_SEEN.store(false, Ordering::Relaxed);
             info!("📥 USB capture session started - traffic monitoring active");
         } else {
             info!("⏹️ USB capture session ended");
@@ -107,9 +115,44 @@ impl UsbDeviceConnectionDetector {
         }
     }
     
+    /// Update connection state from a bus event reported by the analyzer
+    pub fn process_bus_event(event: &BusEvent) {
+        BUS_EVENTS_SEEN.store(true, Ordering::Relaxed);
+        
+        match &event.kind {
+            BusEventKind::DeviceAttached(speed) => {
+                info!("🔌 USB Device Attached (bus event){}",
+                      speed.map(|s| format!(" at {:?} speed", s)).unwrap_or_default());
+                UsbDeviceConnectionDetector::set_device_connected(true);
+            },
+            BusEventKind::DeviceDetached | BusEventKind::VbusDisconnected => {
+                UsbDeviceConnectionDetector::set_device_connected(false);
+            },
+            BusEventKind::SpeedNegotiated(speed) => {
+                UsbDeviceConnectionDetector::set_last_successful_speed(*speed);
+            },
+            BusEventKind::BusReset => {
+                debug!("USB bus reset: {}", event);
+            },
+            _ => {
+                debug!("USB bus event: {}", event);
+            }
+        }
+    }
+    
+    /// Check if connection state is driven by bus events rather than traffic patterns
+    pub fn has_bus_events() -> bool {
+        BUS_EVENTS_SEEN.load(Ordering::Relaxed)
+    }
+    
     /// Enhanced analysis of raw USB data looking for device connection sequences
     /// This method specifically focuses on finding connected devices on a Cynthion
     pub fn check_for_usb_device_connection(data: &[u8]) {
+        // Real attach/detach events take precedence over enumeration patterns
+        if UsbDeviceConnectionDetector::has_bus_events() {
+            return;
+        }
+        
         // We need at least a full USB packet to analyze
         if data.len() < 8 {
             return;
@@ -147,51 +190,41 @@ impl UsbDeviceConnectionDetector {
                 let b_request = setup_data[1];
                 let w_value = u16::from_le_bytes([setup_data[2], setup_data[3]]);
                 
-                // Check if this is a standard request (bit 5-6 == 0)
-                let is_standard = (bm_request_type >> 5) & 0x03 == 0;
-                
-                // GET_DESCRIPTOR (0x06) requests are particularly interesting for device detection
-                if is_standard && b_request == 0x06 {
-                    let desc_type = (w_value >> 8) as u8;
-                    let desc_index = (w_value & 0xFF) as u8;
-                    
-                    match desc_type {
-                        1 => {
-                            // Device Descriptor - major indicator of USB device connection
-                            info!("🔌 USB Device Connection Detected! Host requesting Device Descriptor");
-                            info!("   Device Address: {} on endpoint {}", device_addr, endpoint & 0x7F);
-                            has_found_device = true;
-                            // Update global connection state
-                            UsbDeviceConnectionDetector::set_device_connected(true);
-                        },
-                        2 => {
-                            // Configuration Descriptor - follows device descriptor in enumeration
-                            info!("📝 USB Device Configuration: Host requesting Configuration Descriptor");
-                            info!("   Device Address: {} on endpoint {}", device_addr, endpoint & 0x7F);
-                        },
-                        3 => {
-                            // String Descriptor - indicates device identification in progress
-                            debug!("USB String Descriptor requested: index={}", desc_index);
-                        },
-                        _ => {
-                            // Other descriptor types
-                            debug!("USB Descriptor request: type={}, index={}", desc_type, desc_index);
-                        }
-                    }
-                }
-                // SET_ADDRESS (0x05) is also a key part of USB enumeration
-                else if is_standard && b_request == 0x05 {
-                    let address = w_value & 0x7F;
-                    info!("📍 USB Address Assignment: Host setting device address to {}", address);
-                }
-                // SET_CONFIGURATION (0x09) completes the basic USB enumeration process
-                else if is_standard && b_request == 0x09 {
-                    let config = w_value & 0xFF;
-                    info!("✅ USB Configuration Complete: Device {} configured with config {}", device_addr, config);
-                    info!("   USB device is now fully enumerated and ready for operation");
-                    has_found_device = true;
-                    // Update global connection state
-                    UsbDeviceConnectionDetector::set_device_connected(true);
+                match EnumerationStep::from_setup(bm_request_type, b_request, w_value) {
+                    Some(EnumerationStep::GetDeviceDescriptor) => {
+                        // Device Descriptor - major indicator of USB device connection
+                        info!("🔌 USB Device Connection Detected! Host requesting Device Descriptor");
+                        info!("   Device Address: {} on endpoint {}", device_addr, endpoint & 0x7F);
+                        has_found_device = true;
+                        // Update global connection state
+                        UsbDeviceConnectionDetector::set_device_connected(true);
+                    },
+                    Some(EnumerationStep::GetConfigurationDescriptor) => {
+                        // Configuration Descriptor - follows device descriptor in enumeration
+                        info!("📝 USB Device Configuration: Host requesting Configuration Descriptor");
+                        info!("   Device Address: {} on endpoint {}", device_addr, endpoint & 0x7F);
+                    },
+                    Some(EnumerationStep::GetStringDescriptor(desc_index)) => {
+                        // String Descriptor - indicates device identification in progress
+                        debug!("USB String Descriptor requested: index={}", desc_index);
+                    },
+                    Some(EnumerationStep::GetDescriptor { descriptor_type, index }) => {
+                        // Other descriptor types
+                        debug!("USB Descriptor request: type={}, index={}", descriptor_type, index);
+                    },
+                    // SET_ADDRESS is also a key part of USB enumeration
+                    Some(EnumerationStep::SetAddress(address)) => {
+                        info!("📍 USB Address Assignment: Host setting device address to {}", address);
+                    },
+                    // SET_CONFIGURATION completes the basic USB enumeration process
+                    Some(EnumerationStep::SetConfiguration(config)) => {
+                        info!("✅ USB Configuration Complete: Device {} configured with config {}", device_addr, config);
+                        info!("   USB device is now fully enumerated and ready for operation");
+                        has_found_device = true;
+                        // Update global connection state
+                        UsbDeviceConnectionDetector::set_device_connected(true);
+                    },
+                    None => {},
                 }
             }
             
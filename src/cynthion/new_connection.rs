@@ -1469,12 +1469,11 @@ impl CynthionHandle {
                     0x23 => UsbTransferType::Interrupt,
                     0x69 => UsbTransferType::Bulk,
                     _ => {
-                        // Use endpoint to guess transfer type
+                        // EP0 is always control; other endpoints are resolved against
+                        // the device's descriptors when the transaction is displayed
                         match endpoint & 0x7F {
                             0 => UsbTransferType::Control,
-                            1 => UsbTransferType::Isochronous,
-                            2..=3 => UsbTransferType::Bulk,
-                            _ => UsbTransferType::Interrupt
+                            _ => UsbTransferType::Unknown,
                         }
                     }
                 };
@@ -1486,6 +1485,8 @@ impl CynthionHandle {
                     setup_packet: None,
                     data_packet,
                     status_packet,
+                    data_pid: None,
+                    endpoint_info: None,
                     timestamp: std::time::SystemTime::now()
                         .duration_since(std::time::UNIX_EPOCH)
                         .unwrap_or_default()
@@ -1579,6 +1580,8 @@ impl CynthionHandle {
                             0
                         )),
                         status_packet: None,
+                        data_pid: None,
+                        endpoint_info: None,
                         timestamp: std::time::SystemTime::now()
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
@@ -1623,22 +1626,12 @@ impl CynthionHandle {
                 0x69 => UsbTransferType::Bulk,      // Based on observed patterns
                 
                 _ => {
-                    info!("Unknown packet type: 0x{:02X}, using heuristics to determine type", packet_type);
-                    // Enhanced heuristics that look at both packet type and endpoint
+                    info!("Unknown packet type: 0x{:02X}, transfer type will come from descriptors", packet_type);
+                    // EP0 is always control; other endpoints are resolved against
+                    // the device's descriptors when the transaction is displayed
                     match endpoint & 0x7F {
-                        0 => UsbTransferType::Control,  // EP0 is always control
-                        1 => UsbTransferType::Isochronous, // Often used for isochronous
-                        2..=3 => UsbTransferType::Bulk, // Often used for bulk
-                        _ => {
-                            // Additional endpoint pattern heuristics
-                            if endpoint >= 0x80 && endpoint <= 0x8F {
-                                UsbTransferType::Bulk // Often higher IN endpoints are bulk
-                            } else if endpoint >= 0xA0 && endpoint <= 0xAF {
-                                UsbTransferType::Interrupt // Often IN endpoints with bit 5 set are interrupt
-                            } else {
-                                UsbTransferType::Interrupt // Default for other pattern
-                            }
-                        }
+                        0 => UsbTransferType::Control,
+                        _ => UsbTransferType::Unknown,
                     }
                 }
             };
@@ -1678,6 +1671,8 @@ impl CynthionHandle {
                             status: crate::usb::mitm_traffic::UsbTransferStatus::ACK,
                             endpoint: endpoint & 0x7F,
                         }),
+                        data_pid: None,
+                        endpoint_info: None,
                         timestamp: std::time::SystemTime::now()
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
@@ -1720,6 +1715,8 @@ impl CynthionHandle {
                 setup_packet: None, // Will be filled below for control transfers
                 data_packet: Some(data_packet),
                 status_packet: Some(status_packet),
+                data_pid: None,
+                endpoint_info: None,
                 timestamp: std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
@@ -1927,6 +1924,8 @@ impl CynthionHandle {
                     0 // Default endpoint
                 )),
                 status_packet: None,
+                data_pid: None,
+                endpoint_info: None,
                 timestamp: std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
@@ -2091,6 +2090,8 @@ impl CynthionHandle {
                 setup_packet: None, // We'd parse this from the data for control transfers
                 data_packet: Some(data_packet),
                 status_packet: Some(status_packet),
+                data_pid: None,
+                endpoint_info: None,
                 timestamp: std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
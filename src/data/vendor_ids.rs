@@ -1,12 +1,20 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
+// Vendors whose devices get their own protocol decoding
+pub const FTDI_VID: u16 = 0x0403;
+pub const SILABS_VID: u16 = 0x10C4;
+pub const WCH_VID: u16 = 0x1A86;
+pub const WCH_ALT_VID: u16 = 0x4348;
+pub const PROLIFIC_VID: u16 = 0x067B;
+
 lazy_static! {
     static ref VENDOR_MAP: HashMap<u16, &'static str> = {
         let mut m = HashMap::new();
         
         // USB-IF assigned vendor IDs (partial list - common vendors)
         m.insert(0x0001, "Apple, Inc.");
+        m.insert(0x0403, "Future Technology Devices International, Ltd");
         m.insert(0x0409, "NEC Corporation");
         m.insert(0x045E, "Microsoft Corporation");
         m.insert(0x046D, "Logitech, Inc.");
@@ -57,6 +65,7 @@ lazy_static! {
         m.insert(0x192F, "Sunrise Telecom");
         m.insert(0x19D2, "ZTE WCDMA Technologies MSM");
         m.insert(0x1A40, "Terminus Technology, Inc.");
+        m.insert(0x1A86, "QinHeng Electronics");
         m.insert(0x1B1C, "Corsair");
         m.insert(0x1D6B, "Linux Foundation");
         m.insert(0x1D50, "Great Scott Gadgets"); // Cynthion / Great Scott Gadgets
@@ -72,7 +81,6 @@ lazy_static! {
     };
 }
 
-#[allow(dead_code)]
 pub fn lookup_vendor(vendor_id: u16) -> Option<String> {
     VENDOR_MAP.get(&vendor_id).map(|s| s.to_string())
 }
@@ -1,23 +1,40 @@
-use iced::widget::{button, column, container, row, scrollable, text, Column};
+use std::collections::HashSet;
+use iced::widget::{button, column, container, row, scrollable, text, Column, Space};
 use iced::{Command, Element, Length};
 use crate::usb::USBDescriptor;
-use crate::usb::hints::{get_descriptor_hints, UsbStandardReferences};
+use crate::usb::descriptors::DescriptorNode;
+use crate::usb::hints::{get_descriptor_hints, UsbHints, UsbStandardReferences};
+use crate::usb::standard_request::describe_langid;
 use crate::usb::UsbDescriptorType;
 use crate::usb::UsbEndpointType;
 use crate::gui::styles;
+use crate::usb::bus::BusModel;
+
+// Descriptors collected for one device on the bus
+#[derive(Debug, Clone)]
+struct BusDeviceDescriptors {
+    address: u8,
+    label: String,
+    descriptors: Vec<DescriptorNode>,
+}
 
 pub struct DescriptorView {
-    descriptors: Vec<USBDescriptor>,
-    selected_descriptor: Option<usize>,
+    descriptors: Vec<DescriptorNode>,
+    selected_descriptor: Option<Vec<usize>>, // Child index at each level of the tree
+    collapsed: HashSet<Vec<usize>>, // Tree nodes whose children are hidden
     decoded_data: Vec<crate::usb::DecodedUSBData>,
     dark_mode: bool,
+    bus_devices: Vec<BusDeviceDescriptors>, // Per-address descriptors from the bus model
+    selected_device: Option<u8>, // None shows every descriptor decoded so far
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    DescriptorSelected(usize),
+    DescriptorSelected(Vec<usize>),
+    ToggleCollapsed(Vec<usize>),
     ClearDescriptors,
     ToggleDarkMode(bool),
+    DeviceSelected(Option<u8>),
 }
 
 impl DescriptorView {
@@ -25,27 +42,40 @@ impl DescriptorView {
         Self {
             descriptors: Vec::new(),
             selected_descriptor: None,
+            collapsed: HashSet::new(),
             decoded_data: Vec::new(),
             dark_mode: true, // Default to dark mode for hacker-friendly UI
+            bus_devices: Vec::new(),
+            selected_device: None,
         }
     }
     
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::DescriptorSelected(index) => {
-                self.selected_descriptor = Some(index);
+            Message::DescriptorSelected(path) => {
+                self.selected_descriptor = Some(path);
+                Command::none()
+            },
+            Message::ToggleCollapsed(path) => {
+                if !self.collapsed.remove(&path) {
+                    self.collapsed.insert(path);
+                }
                 Command::none()
             },
             Message::ClearDescriptors => {
-                self.descriptors.clear();
-                self.decoded_data.clear();
-                self.selected_descriptor = None;
+                self.clear();
                 Command::none()
             },
             Message::ToggleDarkMode(enabled) => {
                 self.dark_mode = enabled;
                 Command::none()
             },
+            Message::DeviceSelected(address) => {
+                self.selected_device = address;
+                self.collapsed.clear();
+                self.selected_descriptor = if self.shown_descriptors().is_empty() { None } else { Some(vec![0]) };
+                Command::none()
+            },
         }
     }
     
@@ -53,16 +83,46 @@ impl DescriptorView {
         // Store the complete decoded data for context and hints
         self.decoded_data.push(decoded_data.clone());
         
-        // Add new descriptors to our list
-        for descriptor in decoded_data.descriptors {
-            if !self.descriptors.iter().any(|d| format!("{:?}", d) == format!("{:?}", descriptor)) {
-                self.descriptors.push(descriptor);
-            }
+        // The decoder accumulates every descriptor it has seen, so the newest tree covers the older ones
+        if !decoded_data.descriptor_tree.is_empty() {
+            self.descriptors = decoded_data.descriptor_tree;
         }
         
         // If no descriptor is selected, select the first one
         if self.selected_descriptor.is_none() && !self.descriptors.is_empty() {
-            self.selected_descriptor = Some(0);
+            self.selected_descriptor = Some(vec![0]);
+        }
+    }
+    
+    // Refresh the per-device descriptor trees from the bus model
+    pub fn update_bus_devices(&mut self, bus: &BusModel) {
+        self.bus_devices = bus.devices()
+            .filter(|device| device.device.device.is_some())
+            .map(|device| BusDeviceDescriptors {
+                address: device.address,
+                label: format!("{}", device),
+                descriptors: device.device.descriptor_tree(),
+            })
+            .collect();
+        
+        // Forget a selection whose device is gone (e.g. it moved to a new address)
+        if let Some(address) = self.selected_device {
+            if !self.bus_devices.iter().any(|device| device.address == address) {
+                self.selected_device = None;
+                self.collapsed.clear();
+                self.selected_descriptor = if self.descriptors.is_empty() { None } else { Some(vec![0]) };
+            }
+        }
+    }
+    
+    // Descriptors of the selected device, or all decoded descriptors
+    fn shown_descriptors(&self) -> &[DescriptorNode] {
+        match self.selected_device {
+            Some(address) => self.bus_devices.iter()
+                .find(|device| device.address == address)
+                .map(|device| device.descriptors.as_slice())
+                .unwrap_or(&[]),
+            None => &self.descriptors,
         }
     }
     
@@ -70,6 +130,74 @@ impl DescriptorView {
         self.descriptors.clear();
         self.decoded_data.clear();
         self.selected_descriptor = None;
+        self.collapsed.clear();
+        self.bus_devices.clear();
+        self.selected_device = None;
+    }
+    
+    // Append one row per visible node, indenting children under their parent
+    fn push_tree_rows<'a>(&self, nodes: &'a [DescriptorNode], parent: &[usize], rows: &mut Vec<Element<'a, Message>>) {
+        for (index, node) in nodes.iter().enumerate() {
+            let mut path = parent.to_vec();
+            path.push(index);
+            let expanded = !self.collapsed.contains(&path);
+            
+            let toggle: Element<Message> = if node.children.is_empty() {
+                Space::with_width(Length::Fixed(24.0)).into()
+            } else {
+                button(text(if expanded { "▼ " } else { "▶ " }).size(14))
+                    .on_press(Message::ToggleCollapsed(path.clone()))
+                    .style(if self.dark_mode {
+                        iced::theme::Button::Custom(Box::new(styles::DarkModeTreeNodeButton))
+                    } else {
+                        iced::theme::Button::Custom(Box::new(styles::TreeNodeButton))
+                    })
+                    .width(Length::Fixed(24.0))
+                    .into()
+            };
+            
+            let label = text(&node.label).width(Length::Fill);
+            let entry: Element<Message> = if Some(&path) == self.selected_descriptor.as_ref() {
+                container(label)
+                    .style(if self.dark_mode {
+                        iced::theme::Container::Custom(Box::new(styles::DarkModeSelectedContainer))
+                    } else {
+                        iced::theme::Container::Custom(Box::new(styles::SelectedContainer))
+                    })
+                    .width(Length::Fill)
+                    .padding(5)
+                    .into()
+            } else {
+                button(
+                    container(label)
+                        .width(Length::Fill)
+                        .padding(0)
+                )
+                .width(Length::Fill)
+                .style(if self.dark_mode {
+                    iced::theme::Button::Custom(Box::new(styles::DarkModeTreeNodeButton))
+                } else {
+                    iced::theme::Button::Text
+                })
+                .on_press(Message::DescriptorSelected(path.clone()))
+                .into()
+            };
+            
+            rows.push(
+                row![
+                    Space::with_width(Length::Fixed(20.0 * parent.len() as f32)),
+                    toggle,
+                    entry,
+                ]
+                .spacing(2)
+                .align_items(iced::Alignment::Center)
+                .into()
+            );
+            
+            if expanded {
+                self.push_tree_rows(&node.children, &path, rows);
+            }
+        }
     }
     
     pub fn view(&self) -> Element<Message> {
@@ -95,7 +223,9 @@ impl DescriptorView {
             .width(Length::Fill);
             
         // Define descriptor_list as Element to handle different return types
-        let descriptor_list: Element<Message> = if self.descriptors.is_empty() {
+        let descriptors = self.shown_descriptors();
+        
+        let descriptor_list: Element<Message> = if descriptors.is_empty() {
             container(
                 text("No descriptors decoded yet")
                     .width(Length::Fill)
@@ -107,71 +237,9 @@ impl DescriptorView {
             .center_y()
             .into()
         } else {
-            let items = Column::with_children(
-                self.descriptors
-                    .iter()
-                    .enumerate()
-                    .map(|(index, descriptor)| {
-                        let descriptor_type = match descriptor {
-                            USBDescriptor::Device(_) => "Device Descriptor",
-                            USBDescriptor::Configuration(_) => "Configuration Descriptor",
-                            USBDescriptor::Interface(_) => "Interface Descriptor",
-                            USBDescriptor::Endpoint(_) => "Endpoint Descriptor",
-                            USBDescriptor::String(_) => "String Descriptor",
-                            USBDescriptor::HID(_) => "HID Descriptor",
-                            USBDescriptor::DeviceQualifier(_) => "Device Qualifier Descriptor",
-                            USBDescriptor::BOS(_) => "BOS Descriptor",
-                            USBDescriptor::DeviceCapability(_) => "Device Capability Descriptor",
-                            USBDescriptor::SuperSpeedEndpointCompanion(_) => "SuperSpeed Endpoint Companion",
-                            USBDescriptor::CDC(_) => "CDC Class Descriptor",
-                            USBDescriptor::MSC(_) => "Mass Storage Class Descriptor",
-                            USBDescriptor::AudioControl(_) => "Audio Control Descriptor",
-                            USBDescriptor::AudioStreaming(_) => "Audio Streaming Descriptor",
-                            USBDescriptor::VideoControl(_) => "Video Control Descriptor",
-                            USBDescriptor::VideoStreaming(_) => "Video Streaming Descriptor",
-                            USBDescriptor::Unknown { descriptor_type, .. } => 
-                                return text(format!("Unknown Descriptor (0x{:02X})", descriptor_type))
-                                    .width(Length::Fill)
-                                    .into(),
-                        };
-                        
-                        let row = text(descriptor_type)
-                            .width(Length::Fill);
-                        
-                        if Some(index) == self.selected_descriptor {
-                            container(row)
-                                .style(if self.dark_mode {
-                                    iced::theme::Container::Custom(Box::new(styles::DarkModeSelectedContainer))
-                                } else {
-                                    iced::theme::Container::Custom(Box::new(styles::SelectedContainer))
-                                })
-                                .width(Length::Fill)
-                                .padding(10)
-                                .into()
-                        } else {
-                            // Use a button instead of container with on_press
-                            button(
-                                container(row)
-                                    .style(if self.dark_mode {
-                                        iced::theme::Container::Custom(Box::new(styles::DarkModeContainer)) 
-                                    } else {
-                                        iced::theme::Container::Box
-                                    })
-                                    .width(Length::Fill)
-                                    .padding(5)
-                            )
-                            .width(Length::Fill)
-                            .style(if self.dark_mode {
-                                iced::theme::Button::Custom(Box::new(styles::DarkModeTreeNodeButton))
-                            } else {
-                                iced::theme::Button::Text
-                            })
-                            .on_press(Message::DescriptorSelected(index))
-                            .into()
-                        }
-                    })
-                    .collect()
-            );
+            let mut rows = Vec::new();
+            self.push_tree_rows(descriptors, &[], &mut rows);
+            let items = Column::with_children(rows).spacing(2);
             
             scrollable(
                 container(items)
@@ -182,15 +250,18 @@ impl DescriptorView {
             .into()
         };
         
-        let selected_descriptor_view = if let Some(index) = self.selected_descriptor {
-            if index < self.descriptors.len() {
-                let descriptor = &self.descriptors[index];
+        let selected_node = self.selected_descriptor.as_ref()
+            .and_then(|path| DescriptorNode::find(descriptors, path));
+        
+        let selected_descriptor_view = if let Some(node) = selected_node {
+            if let Some(descriptor) = &node.descriptor {
                 let descriptor_str = format!("{}", descriptor);
                 
                 // Get hints for this descriptor type
                 let descriptor_type = match descriptor {
                     USBDescriptor::Device(desc) => &desc.descriptor_type,
                     USBDescriptor::Configuration(desc) => &desc.descriptor_type,
+                    USBDescriptor::InterfaceAssociation(desc) => &desc.descriptor_type,
                     USBDescriptor::Interface(desc) => &desc.descriptor_type,
                     USBDescriptor::Endpoint(desc) => &desc.descriptor_type,
                     USBDescriptor::String(desc) => &desc.descriptor_type,
@@ -198,6 +269,12 @@ impl DescriptorView {
                     USBDescriptor::BOS(desc) => &desc.descriptor_type,
                     USBDescriptor::DeviceCapability(desc) => &desc.descriptor_type,
                     USBDescriptor::SuperSpeedEndpointCompanion(desc) => &desc.descriptor_type,
+                    USBDescriptor::SuperSpeedPlusIsochronousEndpointCompanion(desc) => &desc.descriptor_type,
+                    USBDescriptor::Eusb2IsochronousEndpointCompanion(desc) => &desc.descriptor_type,
+                    USBDescriptor::Otg(desc) => &desc.descriptor_type,
+                    USBDescriptor::Debug(desc) => &desc.descriptor_type,
+                    USBDescriptor::InterfacePower(desc) => &desc.descriptor_type,
+                    USBDescriptor::Hub(desc) => &desc.descriptor_type,
                     USBDescriptor::CDC(desc) => &desc.descriptor_type,
                     USBDescriptor::MSC(desc) => &desc.descriptor_type,
                     USBDescriptor::AudioControl(desc) => &desc.descriptor_type,
@@ -335,6 +412,20 @@ impl DescriptorView {
                                 config_desc.total_length, total_length_ref));
                         }
                     },
+                    // Interface association descriptor handling
+                    USBDescriptor::InterfaceAssociation(iad_desc) => {
+                        general_hints.push(format!("Function Class: {}", iad_desc.function_class.name()));
+                        general_hints.push(format!("Interfaces: {} to {}", iad_desc.first_interface, iad_desc.last_interface()));
+                        if let Some(function_str) = &iad_desc.function_string {
+                            general_hints.push(format!("Function: {}", function_str));
+                        }
+                        
+                        details_hints.push(format!("Function SubClass: 0x{:02X}", iad_desc.function_subclass));
+                        details_hints.push(format!("Function Protocol: 0x{:02X}", iad_desc.function_protocol));
+                        
+                        specs_hints.push("Hosts bind one driver to the whole function instead of to each interface".to_string());
+                        specs_hints.push("Devices using IADs report class 0xEF/0x02/0x01 (Miscellaneous, Common Class, IAD) in the device descriptor".to_string());
+                    },
                     // Interface descriptor special handling
                     USBDescriptor::Interface(iface_desc) => {
                         general_hints.push(format!("Interface Number: {}", iface_desc.interface_number));
@@ -409,6 +500,16 @@ impl DescriptorView {
                         // Add endpoint number info
                         details_hints.push(format!("Endpoint Number: {}", ep_desc.endpoint_number));
                         
+                        if let Some(companion) = &ep_desc.ss_companion {
+                            details_hints.push(format!("SuperSpeed Max Burst: {} packets", companion.max_burst as u16 + 1));
+                        }
+                        if let Some(companion) = &ep_desc.ssp_isochronous_companion {
+                            details_hints.push(format!("SuperSpeedPlus Bytes per Interval: {} bytes", companion.bytes_per_interval));
+                        }
+                        if let Some(companion) = &ep_desc.eusb2_isochronous_companion {
+                            details_hints.push(format!("eUSB2 Bytes per Interval: {} bytes", companion.bytes_per_interval));
+                        }
+                        
                         // Add standard reference information
                         if let Some(addr_ref) = UsbStandardReferences::for_field("bEndpointAddress") {
                             specs_hints.push(format!("Endpoint Address: {}", addr_ref));
@@ -478,15 +579,25 @@ impl DescriptorView {
                     },
                     // String descriptor handling
                     USBDescriptor::String(string_desc) => {
-                        general_hints.push(format!("String Index: {}", string_desc.string_index));
-                        general_hints.push(format!("String: \"{}\"", string_desc.string));
+                        if string_desc.unknown_index {
+                            general_hints.push("String Index: unknown (captured without its GET_DESCRIPTOR request)".to_string());
+                        } else {
+                            general_hints.push(format!("String Index: {}", string_desc.string_index));
+                        }
                         specs_hints.push("String descriptors provide human-readable information for the device".to_string());
                         
-                        // Add language info for string descriptor 0 (special case)
-                        if string_desc.string_index == 0 {
+                        // String descriptor 0 lists the languages instead of holding text
+                        if string_desc.is_language_list() {
                             specs_hints.push("String descriptor 0 contains the language IDs supported by the device".to_string());
-                            // Since we don't have langids field in StringDescriptor, just add general info
-                            details_hints.push("Note: Language IDs are encoded in the raw descriptor data".to_string());
+                            for langid in &string_desc.language_ids {
+                                details_hints.push(format!("Language: {}", describe_langid(*langid)));
+                            }
+                        } else {
+                            general_hints.push(format!("String: \"{}\"", string_desc.string));
+                            if string_desc.language_id != 0 {
+                                details_hints.push(format!("Language: {}", describe_langid(string_desc.language_id)));
+                            }
+                            specs_hints.push("The host picks the language by passing a LANGID from string 0 in wIndex".to_string());
                         }
                     },
                     // Device qualifier descriptor handling
@@ -558,37 +669,12 @@ impl DescriptorView {
                     USBDescriptor::DeviceCapability(cap_desc) => {
                         general_hints.push("Device Capability Descriptor".to_string());
                         
-                        // Decode capability type
-                        let cap_type_name = match cap_desc.capability_type {
-                            1 => "USB 2.0 Extension",
-                            2 => "SuperSpeed USB Device",
-                            3 => "Container ID",
-                            4 => "Platform",
-                            5 => "Power Delivery",
-                            6 => "Battery Info",
-                            7 => "PD Consumer Port",
-                            8 => "PD Provider Port",
-                            9 => "SuperSpeed Plus",
-                            10 => "Precision Time Measurement",
-                            11 => "Wireless USB Extension",
-                            _ => "Unknown",
-                        };
-                        general_hints.push(format!("Capability Type: {}", cap_type_name));
+                        general_hints.push(format!("Capability Type: {}", cap_desc.capability_name()));
                         details_hints.push(format!("Data Length: {} bytes", cap_desc.capability_data.len()));
                         
-                        // Add more specific hints based on the capability type
-                        match cap_desc.capability_type {
-                            1 => { // USB 2.0 Extension
-                                specs_hints.push("USB 2.0 Extension capability indicates LPM (Link Power Management) support".to_string());
-                            },
-                            2 => { // SuperSpeed USB Device
-                                specs_hints.push("SuperSpeed capability describes USB 3.0 specific features and support".to_string());
-                            },
-                            3 => { // Container ID
-                                specs_hints.push("Container ID uniquely identifies a multi-function device across different connection methods".to_string());
-                            },
-                            _ => {}
-                        }
+                        let capability = cap_desc.capability();
+                        details_hints.extend(capability.to_string().lines().map(|line| line.trim().to_string()));
+                        usage_hints.extend(UsbHints::for_device_capability(&capability));
                     },
                     
                     USBDescriptor::SuperSpeedEndpointCompanion(ss_desc) => {
@@ -598,17 +684,89 @@ impl DescriptorView {
                         details_hints.push(format!("Bytes per Interval: {} bytes", ss_desc.bytes_per_interval));
                         
                         // Decode attributes
-                        let bulk_max_streams = match ss_desc.attributes & 0x1F {
+                        let bulk_max_streams = match ss_desc.max_streams() {
                             0 => "No streams".to_string(),
-                            n => format!("Max {} streams", 1 << n),
+                            n => format!("Max {} streams", n),
                         };
                         
                         details_hints.push(format!("Bulk: {}", bulk_max_streams));
+                        details_hints.push(format!("Isochronous: up to {} bursts per service interval", ss_desc.mult()));
+                        if ss_desc.has_ssp_isochronous_companion() {
+                            details_hints.push("Followed by a SuperSpeedPlus isochronous companion".to_string());
+                        }
                         
                         specs_hints.push("SuperSpeed Endpoint Companion descriptors provide USB 3.0+ specific endpoint details".to_string());
                         specs_hints.push("They complement standard endpoint descriptors for high-speed operations".to_string());
                     },
                     
+                    USBDescriptor::SuperSpeedPlusIsochronousEndpointCompanion(ssp_desc) => {
+                        general_hints.push("SuperSpeedPlus Isochronous Endpoint Companion Descriptor".to_string());
+                        details_hints.push(format!("Bytes per Interval: {} bytes", ssp_desc.bytes_per_interval));
+                        
+                        specs_hints.push("Used when an isochronous endpoint needs more than the 16-bit wBytesPerInterval of the SuperSpeed companion".to_string());
+                    },
+                    
+                    USBDescriptor::Eusb2IsochronousEndpointCompanion(eusb2_desc) => {
+                        general_hints.push("eUSB2 Isochronous Endpoint Companion Descriptor".to_string());
+                        details_hints.push(format!("Bytes per Interval: {} bytes", eusb2_desc.bytes_per_interval));
+                        
+                        specs_hints.push("Lets a high-speed isochronous IN endpoint move more than 3 x 1024 bytes per microframe".to_string());
+                    },
+                    
+                    USBDescriptor::Otg(otg_desc) => {
+                        let protocols = otg_desc.protocols();
+                        general_hints.push(format!("OTG Protocols: {}", if protocols.is_empty() { "none".to_string() } else { protocols.join(", ") }));
+                        if let Some(version) = otg_desc.otg_version {
+                            details_hints.push(format!("OTG Version: {}.{}", version >> 8, (version >> 4) & 0xF));
+                        }
+                        
+                        usage_hints.push("HNP lets the device take over the host role when the host enables it with SET_FEATURE(b_hnp_enable)".to_string());
+                        specs_hints.push("The OTG descriptor is returned as part of the configuration of a dual-role device".to_string());
+                    },
+                    
+                    USBDescriptor::Debug(debug_desc) => {
+                        general_hints.push(format!("Debug IN Endpoint: 0x{:02X}", debug_desc.debug_in_endpoint));
+                        general_hints.push(format!("Debug OUT Endpoint: 0x{:02X}", debug_desc.debug_out_endpoint));
+                        
+                        usage_hints.push("The host enables the debug port with SET_FEATURE(DEBUG_MODE)".to_string());
+                    },
+                    
+                    USBDescriptor::InterfacePower(power_desc) => {
+                        general_hints.push(format!("Capabilities Flags: 0x{:02X}", power_desc.capabilities_flags));
+                        details_hints.push(format!("Power Data: {} bytes", power_desc.data.len()));
+                        
+                        specs_hints.push("Defined by the draft Interface Power Management specification, which was never finalized".to_string());
+                    },
+                    
+                    USBDescriptor::Hub(hub_desc) => {
+                        general_hints.push(format!("Downstream Ports: {}", hub_desc.num_ports));
+                        if hub_desc.is_compound_device() {
+                            general_hints.push("Hub is part of a compound device".to_string());
+                        }
+                        
+                        usage_hints.push(hub_desc.power_switching_mode().to_string());
+                        usage_hints.push(hub_desc.over_current_protection().to_string());
+                        usage_hints.push(format!("Power-on to power-good: {}ms", hub_desc.power_on_to_power_good_ms()));
+                        usage_hints.push(format!("Hub controller current: {}mA", hub_desc.hub_control_current));
+                        
+                        if let Some(delay) = hub_desc.hub_delay {
+                            details_hints.push(format!("Hub Delay: {}ns", delay));
+                        } else {
+                            details_hints.push(format!("TT Think Time: {} FS bit times", hub_desc.tt_think_time()));
+                        }
+                        
+                        let fixed_ports: Vec<String> = (1..=hub_desc.num_ports)
+                            .filter(|port| !hub_desc.is_port_removable(*port))
+                            .map(|port| port.to_string())
+                            .collect();
+                        if !fixed_ports.is_empty() {
+                            details_hints.push(format!("Non-removable devices on ports: {}", fixed_ports.join(", ")));
+                        }
+                        
+                        specs_hints.push("Hub port state is read with GET_PORT_STATUS and changed with SET_PORT_FEATURE/CLEAR_PORT_FEATURE".to_string());
+                        specs_hints.push("Port changes are reported through the hub's status-change interrupt endpoint".to_string());
+                    },
+                    
                     // Class-specific descriptors
                     USBDescriptor::CDC(cdc_desc) => {
                         general_hints.push("Communications Device Class (CDC) Descriptor".to_string());
@@ -870,7 +1028,16 @@ impl DescriptorView {
                 .width(Length::Fill)
                 .height(Length::Fill)
             } else {
-                container(text("Invalid selection"))
+                // Grouping node (e.g. every alternate setting of one interface)
+                let contents: Vec<String> = node.children.iter().map(|child| child.label.clone()).collect();
+                container(
+                    column![
+                        text(&node.label).size(18),
+                        text(contents.join("\n")).style(iced::theme::Text::Default),
+                    ]
+                    .spacing(10)
+                    .padding(10)
+                )
                     .style(if self.dark_mode {
                         iced::theme::Container::Custom(Box::new(styles::DarkModeContainer))
                     } else {
@@ -890,8 +1057,45 @@ impl DescriptorView {
                 .height(Length::Fill)
         };
         
+        // One button per device on the bus, shown once more than the merged list is available
+        let device_selector: Element<Message> = if self.bus_devices.is_empty() {
+            Column::new().into()
+        } else {
+            let mut selector = row![
+                button(text("All").size(13))
+                    .on_press(Message::DeviceSelected(None))
+                    .style(if self.selected_device.is_none() {
+                        iced::theme::Button::Primary
+                    } else if self.dark_mode {
+                        iced::theme::Button::Custom(Box::new(styles::DarkModeTreeNodeButton))
+                    } else {
+                        iced::theme::Button::Secondary
+                    })
+            ]
+            .spacing(5);
+            
+            for device in &self.bus_devices {
+                selector = selector.push(
+                    button(text(&device.label).size(13))
+                        .on_press(Message::DeviceSelected(Some(device.address)))
+                        .style(if self.selected_device == Some(device.address) {
+                            iced::theme::Button::Primary
+                        } else if self.dark_mode {
+                            iced::theme::Button::Custom(Box::new(styles::DarkModeTreeNodeButton))
+                        } else {
+                            iced::theme::Button::Secondary
+                        })
+                );
+            }
+            
+            scrollable(selector)
+                .direction(scrollable::Direction::Horizontal(scrollable::Properties::default()))
+                .into()
+        };
+        
         let content = column![
             header,
+            device_selector,
             row![
                 container(descriptor_list)
                     .style(if self.dark_mode {
@@ -7,7 +7,82 @@ use crate::gui::styles::color;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::marker::PhantomData;
-use crate::usb::mitm_traffic::{UsbTransaction, UsbTransferType, UsbDirection};
+use crate::usb::mitm_traffic::{UsbTransaction, UsbTransferType, UsbDirection, UsbControlRequestType, UsbControlRecipient, UsbSetupPacket};
+use crate::usb::hub::{self, HubTracker, HubPortStatus};
+use crate::usb::pid::{UsbPacket, UsbPid};
+use crate::usb::split::SplitTracker;
+use crate::usb::sof::SofTracker;
+use crate::usb::bus_event::{BusEvent, BusEventRecord, BusEventTracker};
+use crate::usb::transaction::TransactionAssembler;
+use crate::usb::toggle::{DataToggleTracker, ToggleCheck};
+use crate::usb::analyzer::{AnalyzerNote, AnalyzerRegistry, AnalyzerTransfer, ProtocolAnalyzer};
+use crate::usb::bus::{BusModel, BusModelEvent};
+use crate::usb::class_request;
+use crate::usb::control::ControlTransferAssembler;
+use crate::usb::device_state;
+use crate::usb::enumeration::EnumerationTimingAnalyzer;
+use crate::usb::fingerprint::HostFingerprinter;
+use crate::usb::ms_os;
+use crate::usb::stall::{HaltEvent, HaltTracker, StallKind};
+use crate::usb::request_fields::RequestField;
+use crate::usb::standard_request;
+use crate::usb::vendor_schema::VendorSchemaRegistry;
+use crate::usb::script::ScriptSet;
+use crate::usb::mitm_traffic::UsbTransferStatus;
+use std::collections::{HashMap, HashSet};
+
+// Device address and endpoint number used to key per-endpoint display settings
+type EndpointKey = (u8, u8);
+
+// A run of consecutive NAKed polls on one endpoint, shown as a single expandable row.
+// The individual transaction nodes stay in the tree as children of the run.
+#[derive(Debug, Clone)]
+struct NakRun {
+    endpoint: EndpointKey,
+    token: &'static str, // "IN" or "PING"
+    transaction_nodes: Vec<TreeNodeId>,
+    first_transaction: u64,
+    last_transaction: u64,
+    start: f64,
+    end: f64,
+}
+
+impl NakRun {
+    fn summary(&self) -> String {
+        format!("{} NAK x{} on Addr {} EP {} ({:.3} ms, transactions {}-{})",
+                self.token, self.transaction_nodes.len(),
+                self.endpoint.0, self.endpoint.1,
+                (self.end - self.start) * 1000.0,
+                self.first_transaction, self.last_transaction)
+    }
+}
+
+// SOF anomalies shown under the collapsed SOF node; the summary counts the rest
+const MAX_SOF_ANOMALY_NODES: usize = 100;
+
+// Load the user's vendor request schemas, reporting files that could not be used
+fn load_vendor_schemas() -> VendorSchemaRegistry {
+    let registry = VendorSchemaRegistry::load_default();
+    for error in registry.errors() {
+        log::warn!("Vendor schema not loaded: {}", error);
+    }
+    if !registry.schemas().is_empty() {
+        log::info!("Loaded {} vendor request schema(s)", registry.schemas().len());
+    }
+    registry
+}
+
+// Load the user's analyzer scripts, reporting files that could not be used
+fn load_scripts() -> Vec<Box<dyn ProtocolAnalyzer>> {
+    let scripts = ScriptSet::load_default();
+    for error in scripts.errors() {
+        log::warn!("Analyzer script not loaded: {}", error);
+    }
+    if !scripts.is_empty() {
+        log::info!("Loaded {} analyzer script(s)", scripts.len());
+    }
+    scripts.into_analyzers()
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrafficItem {
@@ -63,14 +138,11 @@ pub enum TreeNodeType {
     BulkTransfer, // Bulk transfer
     InterruptTransfer, // Interrupt transfer
     IsochronousTransfer, // Isochronous transfer
-    #[allow(dead_code)]
     ClassRequest, // Class-specific request
-    #[allow(dead_code)]
+    BusEvent,     // Bus reset, chirp, suspend/resume, VBUS and attach/detach
     VendorRequest, // Vendor-specific request
-    #[allow(dead_code)]
     StandardRequest, // Standard request
     Unknown,
-    #[allow(dead_code)]
     Other,       // Other/Unknown transaction types
 }
 
@@ -84,6 +156,26 @@ pub struct TrafficView {
     dark_mode: bool,
     capture_active: bool, // Whether traffic capture is currently active
     speed_selection_open: bool, // Whether the speed selection dialog is open
+    hub_tracker: HubTracker, // Per-port connection history for hubs seen in the traffic
+    split_tracker: SplitTracker, // Pairs SSPLIT/CSPLIT phases into FS/LS transactions
+    sof_tracker: SofTracker, // Frame numbering from the SOF stream, used as a time base
+    bus_event_tracker: BusEventTracker, // Pairs bus-state records into events with durations
+    bus_event_count: u64, // Sequence number for bus event nodes
+    transaction_assembler: TransactionAssembler, // Groups raw token/data/handshake packets
+    toggle_tracker: DataToggleTracker, // DATA0/DATA1 sequence per endpoint
+    control_assembler: ControlTransferAssembler, // Rebuilds control data stages split over transactions
+    timing_analyzer: EnumerationTimingAnalyzer, // Enumeration timing checks per device
+    host_fingerprinter: HostFingerprinter, // Guesses the host OS from its enumeration pattern
+    halt_tracker: HaltTracker, // Endpoint halt state and every STALL with its recovery
+    vendor_schemas: VendorSchemaRegistry, // User vendor request schemas, loaded once at startup
+    analyzers: AnalyzerRegistry, // Stateful protocol decoders fed with complete transfers
+    bus_model: BusModel, // Devices on the bus keyed by address
+    nak_runs: HashMap<TreeNodeId, NakRun>, // Collapsed NAK runs by run node
+    open_nak_runs: HashMap<(EndpointKey, &'static str), TreeNodeId>, // Runs still growing
+    nak_run_count: u64, // Sequence number for NAK run nodes
+    nak_collapse_disabled: HashSet<EndpointKey>, // Endpoints whose NAKed polls are listed individually
+    nak_transaction_endpoints: HashMap<TreeNodeId, EndpointKey>, // NAKed transactions shown individually
+    transaction_count: u64, // Sequence number for transaction nodes (capture IDs can repeat)
 }
 
 #[derive(Debug, Clone)]
@@ -100,6 +192,8 @@ pub enum Message {
     OpenSpeedDialog,
     CloseSpeedDialog,
     ChangeSpeed(crate::usb::Speed),
+    ToggleNakCollapse(u8, u8), // Device address, endpoint number
+    ReloadScripts,
     NoOp,
 }
 
@@ -115,6 +209,30 @@ impl TrafficView {
             dark_mode: true, // Default to dark mode for hacker-friendly UI
             capture_active: false, // Default to capture not active
             speed_selection_open: false, // Default to speed selection dialog closed
+            hub_tracker: HubTracker::new(),
+            split_tracker: SplitTracker::new(),
+            sof_tracker: SofTracker::new(),
+            bus_event_tracker: BusEventTracker::new(),
+            bus_event_count: 0,
+            transaction_assembler: TransactionAssembler::new(),
+            toggle_tracker: DataToggleTracker::new(),
+            control_assembler: ControlTransferAssembler::new(),
+            timing_analyzer: EnumerationTimingAnalyzer::new(),
+            host_fingerprinter: HostFingerprinter::new(),
+            halt_tracker: HaltTracker::new(),
+            vendor_schemas: load_vendor_schemas(),
+            analyzers: {
+                let mut analyzers = AnalyzerRegistry::with_builtin();
+                analyzers.set_scripts(load_scripts());
+                analyzers
+            },
+            bus_model: BusModel::new(),
+            nak_runs: HashMap::new(),
+            open_nak_runs: HashMap::new(),
+            nak_run_count: 0,
+            nak_collapse_disabled: HashSet::new(),
+            nak_transaction_endpoints: HashMap::new(),
+            transaction_count: 0,
         }
     }
     
@@ -134,6 +252,24 @@ impl TrafficView {
         self.selected_item = None;
         self.tree_nodes.clear();
         self.root_nodes.clear();
+        self.hub_tracker.clear();
+        self.split_tracker.clear();
+        self.sof_tracker.clear();
+        self.bus_event_tracker.clear();
+        self.bus_event_count = 0;
+        self.transaction_assembler.clear();
+        self.toggle_tracker.clear();
+        self.control_assembler.clear();
+        self.timing_analyzer.clear();
+        self.host_fingerprinter.clear();
+        self.halt_tracker.clear();
+        self.analyzers.reset();
+        self.bus_model.clear();
+        self.nak_runs.clear();
+        self.open_nak_runs.clear();
+        self.nak_run_count = 0;
+        self.nak_transaction_endpoints.clear();
+        self.transaction_count = 0;
     }
     
     // Add a packet to the traffic view
@@ -158,16 +294,95 @@ impl TrafficView {
         }
     }
     
+    // Decode a class or vendor request's setup stage and give it a descriptive name
+    fn decode_request(&self, transaction: &mut UsbTransaction) -> Vec<(RequestField, TreeNodeType)> {
+        let address = transaction.device_address;
+        let setup = match transaction.setup_packet.as_mut() {
+            Some(setup) => setup,
+            None => return Vec::new(),
+        };
+        
+        // The Microsoft OS string probe and the vendor-code requests that follow it
+        let ms_os = self.bus_model.device(address)
+            .and_then(|device| ms_os::decode_request(&device.device.ms_os, setup))
+            .map(|request| {
+                let item_type = match setup.request_type {
+                    UsbControlRequestType::Vendor => TreeNodeType::VendorRequest,
+                    _ => TreeNodeType::StandardRequest,
+                };
+                (request.description, request.fields, item_type)
+            });
+        
+        // WebUSB GET_URL, sent with the vendor code from the BOS platform capability
+        let webusb = self.bus_model.device(address)
+            .and_then(|device| device.device.webusb.as_ref())
+            .and_then(|webusb| webusb.decode_request(setup))
+            .map(|(description, fields)| (description, fields, TreeNodeType::VendorRequest));
+        
+        let decoded = ms_os.or(webusb).or_else(|| match setup.request_type {
+            UsbControlRequestType::Class => self.bus_model.class_context(address, setup)
+                .and_then(|context| class_request::decode_class_request(&context, setup))
+                .map(|request| (request.description, request.fields, TreeNodeType::ClassRequest)),
+            UsbControlRequestType::Vendor => self.bus_model.device(address).and_then(|device| device.ids())
+                .and_then(|(vendor_id, product_id)| self.vendor_schemas.decode_request(vendor_id, product_id, setup))
+                .map(|request| (request.description, request.fields, TreeNodeType::VendorRequest)),
+            _ => None,
+        });
+        
+        match decoded {
+            Some((description, fields, item_type)) => {
+                setup.request_description = description;
+                fields.into_iter().map(|field| (field, item_type.clone())).collect()
+            },
+            None => Vec::new(),
+        }
+    }
+    
+    // Decoded data stage of a standard, class or vendor request
+    fn response_fields(&self, address: u8, setup: &UsbSetupPacket, data: &[u8]) -> Vec<(RequestField, TreeNodeType)> {
+        let mut fields: Vec<(RequestField, TreeNodeType)> = standard_request::response_fields(setup, data).into_iter()
+            .map(|field| (field, TreeNodeType::StandardRequest))
+            .collect();
+        if let Some(context) = self.bus_model.class_context(address, setup) {
+            fields.extend(class_request::decode_class_response(&context, setup, data).into_iter()
+                .map(|field| (field, TreeNodeType::ClassRequest)));
+        }
+        if let Some(device) = self.bus_model.device(address) {
+            fields.extend(ms_os::decode_response(&device.device.ms_os, setup, data).into_iter()
+                .map(|field| (field, TreeNodeType::VendorRequest)));
+            if let Some(webusb) = &device.device.webusb {
+                fields.extend(webusb.decode_response(setup, data).into_iter()
+                    .map(|field| (field, TreeNodeType::VendorRequest)));
+            }
+        }
+        if let Some((vendor_id, product_id)) = self.bus_model.device(address).and_then(|device| device.ids()) {
+            fields.extend(self.vendor_schemas.decode_data(vendor_id, product_id, setup, data).into_iter()
+                .map(|field| (field, TreeNodeType::VendorRequest)));
+        }
+        fields
+    }
+    
     // Add a USB transaction to the traffic view (for MitM traffic)
-    pub fn add_transaction(&mut self, transaction: UsbTransaction) {
+    pub fn add_transaction(&mut self, mut transaction: UsbTransaction) {
         use log::debug;
         
         debug!("Adding transaction ID {} of type {:?}", transaction.id, transaction.transfer_type);
         
         // Create a transaction node
-        let transaction_id = format!("tx_{}", transaction.id);
+        self.transaction_count += 1;
+        let node_index = self.transaction_count;
+        let transaction_id = format!("tx_{}", node_index);
         let node_id = TreeNodeId::new(transaction_id);
         
+        // Take the transfer type from the device's descriptors instead of the capture's guess
+        if let Some(info) = self.bus_model.resolve_endpoint(&transaction) {
+            transaction.transfer_type = info.transfer_type;
+            transaction.endpoint_info = Some(info);
+        }
+        
+        // Give class and vendor requests their meaning from the recipient interface or the product's schema
+        let request_fields = self.decode_request(&mut transaction);
+        
         // Determine transaction type label and color
         let (type_label, node_type) = match transaction.transfer_type {
             UsbTransferType::Control => {
@@ -192,31 +407,99 @@ impl TrafficView {
             },
         };
         
+        // Update hub port state before building the nodes so hub data can be decoded
+        let hub_events = self.hub_tracker.process_transaction(&transaction);
+        
+        // Class requests only get hub request names when they go to a hub
+        if self.hub_tracker.is_hub(transaction.device_address) {
+            if let Some(setup) = transaction.setup_packet.as_mut() {
+                if let Some(description) = hub::describe_hub_request(setup.request_type, setup.recipient, setup.bRequest,
+                                                                     setup.wValue, setup.wIndex, setup.wLength) {
+                    setup.request_description = description;
+                }
+            }
+        }
+        
+        // Check the request against the device's chapter 9 state before the model moves it on
+        let state_violations = device_state::check_transaction(&self.bus_model, &transaction,
+                                                               self.bus_event_tracker.is_suspended());
+        
+        // Follow enumeration so each address maps to the right device
+        let mut device_events = self.bus_model.process_transaction(&transaction);
+        
+        // Once a multi-packet data stage is complete, hand the whole response to the bus model
+        let max_packet_size0 = self.bus_model.device(transaction.device_address).and_then(|device| device.max_packet_size0());
+        let control_transfer = self.control_assembler.process_transaction(&transaction, max_packet_size0);
+        if let Some(transfer) = &control_transfer {
+            device_events.extend(self.bus_model.process_control_transfer(&transfer.to_transaction()));
+        }
+        
+        // Offer the completed transfer to the protocol analyzers registered for its device
+        let analyzer_outputs = match AnalyzerTransfer::from_transaction(&self.bus_model, &transaction, control_transfer.as_ref()) {
+            Some(transfer) => self.analyzers.process(&transfer),
+            None => Vec::new(),
+        };
+        
         // Create node data with direction and endpoint info
         let summary = transaction.get_summary();
         debug!("Transaction summary: {}", &summary);
         
-        let data = format!("{}: {} (Endpoint: 0x{:02X})", 
+        let mut data = format!("{}: {} (Endpoint: 0x{:02X})", 
                          type_label,
                          summary,
                          transaction.endpoint);
         
-        // Create root node if it doesn't exist
-        if self.root_nodes.is_empty() {
-            let root_id = TreeNodeId::new("mitm_root");
-            self.root_nodes.push(root_id.clone());
-            
-            let root_node = TreeNode {
-                id: root_id.clone(),
-                children: Vec::new(),
-                expanded: true,
-                data: "USB Transactions".to_string(),
-                item_type: TreeNodeType::Root,
-            };
-            
-            self.tree_nodes.insert(root_id, root_node);
+        if let Some(name) = self.bus_model.device(transaction.device_address).and_then(|device| device.name()) {
+            data = format!("{} [Addr {}: {}]", data, transaction.device_address, name);
+        }
+        
+        // Check the data toggle when the capture carries data PIDs
+        let toggle = self.toggle_tracker.process_transaction(&transaction, self.bus_model.device(transaction.device_address));
+        if let Some(check) = &toggle {
+            transaction.fields.insert("Data Toggle".to_string(), format!("{}", check));
+            match check {
+                ToggleCheck::Retransmission(_) => data = format!("{} [Retransmission]", data),
+                ToggleCheck::Mismatch { .. } => data = format!("{} [Toggle Error]", data),
+                _ => {},
+            }
         }
         
+        // Follow endpoint halts across transactions
+        let halt_events = self.halt_tracker.process_transaction(&transaction, self.bus_model.device(transaction.device_address));
+        for event in &halt_events {
+            match event {
+                HaltEvent::Stalled(StallKind::Protocol) => data = format!("{} [Protocol STALL]", data),
+                HaltEvent::Stalled(_) => data = format!("{} [Endpoint Halted]", data),
+                HaltEvent::ToHaltedEndpoint => data = format!("{} [Halted Endpoint]", data),
+                HaltEvent::Cleared { .. } => {},
+            }
+        }
+        
+        if !state_violations.is_empty() {
+            data = format!("{} [State Violation]", data);
+        }
+        
+        if control_transfer.as_ref().and_then(|transfer| transfer.length_error()).is_some() {
+            data = format!("{} [Length Error]", data);
+        }
+        
+        for output in &analyzer_outputs {
+            if let Some(summary) = &output.summary {
+                data = format!("{} [{}: {}]", data, output.analyzer, summary);
+            }
+            if output.has_warnings() {
+                data = format!("{} [{} Warning]", data, output.analyzer);
+            }
+        }
+        
+        // Place the transaction in bus time when a SOF stream is available
+        if let Some(frame_time) = self.sof_tracker.frame_at(transaction.timestamp) {
+            data = format!("{} @ {}", data, frame_time);
+            transaction.fields.insert("Frame".to_string(), format!("{}", frame_time));
+        }
+        
+        self.ensure_transaction_root();
+        
         // Create the transaction node
         let mut transaction_node = TreeNode {
             id: node_id.clone(),
@@ -228,7 +511,7 @@ impl TrafficView {
         
         // Add setup packet as child if present
         if let Some(setup) = &transaction.setup_packet {
-            let setup_id = TreeNodeId::new(format!("setup_{}", transaction.id));
+            let setup_id = TreeNodeId::new(format!("setup_{}", node_index));
             let direction_str = match setup.direction {
                 UsbDirection::HostToDevice => "Host to Device",
                 UsbDirection::DeviceToHost => "Device to Host",
@@ -238,9 +521,26 @@ impl TrafficView {
             let setup_data = format!("Setup Packet: {} (bmRequestType: 0x{:02X}, bRequest: 0x{:02X})",
                                  direction_str, setup.bmRequestType, setup.bRequest);
             
+            // Decoded fields of standard, class and vendor requests, one row each
+            let mut field_ids = Vec::new();
+            let fields = standard_request::request_fields(setup).into_iter()
+                .map(|field| (field, TreeNodeType::StandardRequest))
+                .chain(request_fields);
+            for (index, ((name, value), item_type)) in fields.enumerate() {
+                let field_id = TreeNodeId::new(format!("setup_field_{}_{}", node_index, index));
+                self.tree_nodes.insert(field_id.clone(), TreeNode {
+                    id: field_id.clone(),
+                    children: Vec::new(),
+                    expanded: true,
+                    data: format!("{}: {}", name, value),
+                    item_type,
+                });
+                field_ids.push(field_id);
+            }
+            
             let setup_node = TreeNode {
                 id: setup_id.clone(),
-                children: Vec::new(),
+                children: field_ids,
                 expanded: true,
                 data: setup_data,
                 item_type: TreeNodeType::Setup,
@@ -252,19 +552,38 @@ impl TrafficView {
         
         // Add data packet as child if present
         if let Some(data_pkt) = &transaction.data_packet {
-            let data_id = TreeNodeId::new(format!("data_{}", transaction.id));
+            let data_id = TreeNodeId::new(format!("data_{}", node_index));
             let direction_str = match data_pkt.direction {
                 UsbDirection::HostToDevice => "Host to Device",
                 UsbDirection::DeviceToHost => "Device to Host",
                 UsbDirection::Unknown => "Unknown Direction",
             };
             
-            let data_node_data = format!("Data Packet: {} ({} bytes)",
+            let mut data_node_data = format!("Data Packet: {} ({} bytes)",
                                      direction_str, data_pkt.data.len());
             
+            if let Some(hub_summary) = self.hub_data_summary(&transaction) {
+                data_node_data = format!("{} - {}", data_node_data, hub_summary);
+            }
+            
+            let mut field_ids = Vec::new();
+            if let Some(setup) = &transaction.setup_packet {
+                for (index, ((name, value), item_type)) in self.response_fields(transaction.device_address, setup, &data_pkt.data).into_iter().enumerate() {
+                    let field_id = TreeNodeId::new(format!("data_field_{}_{}", node_index, index));
+                    self.tree_nodes.insert(field_id.clone(), TreeNode {
+                        id: field_id.clone(),
+                        children: Vec::new(),
+                        expanded: true,
+                        data: format!("{}: {}", name, value),
+                        item_type,
+                    });
+                    field_ids.push(field_id);
+                }
+            }
+            
             let data_node = TreeNode {
                 id: data_id.clone(),
-                children: Vec::new(),
+                children: field_ids,
                 expanded: true,
                 data: data_node_data,
                 item_type: TreeNodeType::Data,
@@ -276,7 +595,7 @@ impl TrafficView {
         
         // Add status packet as child if present
         if let Some(status) = &transaction.status_packet {
-            let status_id = TreeNodeId::new(format!("status_{}", transaction.id));
+            let status_id = TreeNodeId::new(format!("status_{}", node_index));
             
             let status_data = format!("Status: {} (Endpoint: 0x{:02X})",
                                   status.status, status.endpoint);
@@ -293,13 +612,729 @@ impl TrafficView {
             self.tree_nodes.insert(status_id, status_node);
         }
         
-        // Add the transaction node to the root
-        if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
-            root.children.push(node_id.clone());
+        // Add the endpoint's owning interface and class
+        if let Some(info) = &transaction.endpoint_info {
+            let endpoint_id = TreeNodeId::new(format!("endpoint_{}", node_index));
+            self.tree_nodes.insert(endpoint_id.clone(), TreeNode {
+                id: endpoint_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("Endpoint: {}", info),
+                item_type: TreeNodeType::Endpoint,
+            });
+            transaction_node.children.push(endpoint_id);
+        }
+        
+        // Add the data toggle result; errors and duplicates get their own status node
+        if let Some(check) = &toggle {
+            let toggle_id = TreeNodeId::new(format!("toggle_{}", node_index));
+            let item_type = match check {
+                ToggleCheck::Retransmission(_) | ToggleCheck::Mismatch { .. } => TreeNodeType::Status,
+                _ => TreeNodeType::Data,
+            };
+            
+            self.tree_nodes.insert(toggle_id.clone(), TreeNode {
+                id: toggle_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("Data Toggle: {}", check),
+                item_type,
+            });
+            transaction_node.children.push(toggle_id);
+        }
+        
+        // Add the halt state changes caused by this transaction
+        for (index, event) in halt_events.iter().enumerate() {
+            let halt_id = TreeNodeId::new(format!("halt_{}_{}", node_index, index));
+            self.tree_nodes.insert(halt_id.clone(), TreeNode {
+                id: halt_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("Halt: {}", event),
+                item_type: TreeNodeType::Status,
+            });
+            transaction_node.children.push(halt_id);
+        }
+        
+        // Add a warning for each request that isn't allowed in the device's state
+        for (index, violation) in state_violations.iter().enumerate() {
+            let violation_id = TreeNodeId::new(format!("state_violation_{}_{}", node_index, index));
+            self.tree_nodes.insert(violation_id.clone(), TreeNode {
+                id: violation_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("Warning: {}", violation),
+                item_type: TreeNodeType::Status,
+            });
+            transaction_node.children.push(violation_id);
+        }
+        
+        // Add the reassembled control transfer this transaction completed
+        if let Some(transfer) = &control_transfer {
+            let control_id = TreeNodeId::new(format!("control_{}", node_index));
+            let mut control_node = TreeNode {
+                id: control_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("Control Transfer: {}", transfer),
+                item_type: TreeNodeType::Transaction,
+            };
+            
+            // Decoded response of the request, now that the whole data stage is known
+            let fields = transfer.setup()
+                .map(|setup| self.response_fields(transaction.device_address, setup, &transfer.data))
+                .unwrap_or_default();
+            for (index, ((name, value), item_type)) in fields.into_iter().enumerate() {
+                let field_id = TreeNodeId::new(format!("control_field_{}_{}", node_index, index));
+                self.tree_nodes.insert(field_id.clone(), TreeNode {
+                    id: field_id.clone(),
+                    children: Vec::new(),
+                    expanded: true,
+                    data: format!("{}: {}", name, value),
+                    item_type,
+                });
+                control_node.children.push(field_id);
+            }
+            
+            if let Some(error) = transfer.length_error() {
+                let error_id = TreeNodeId::new(format!("control_error_{}", node_index));
+                self.tree_nodes.insert(error_id.clone(), TreeNode {
+                    id: error_id.clone(),
+                    children: Vec::new(),
+                    expanded: true,
+                    data: format!("Length Error: {}", error),
+                    item_type: TreeNodeType::Status,
+                });
+                control_node.children.push(error_id);
+            }
+            
+            transaction_node.children.push(control_id.clone());
+            self.tree_nodes.insert(control_id, control_node);
+        }
+        
+        // Add what the protocol analyzers decoded, one node per analyzer
+        for (index, output) in analyzer_outputs.iter().enumerate() {
+            let analyzer_id = TreeNodeId::new(format!("analyzer_{}_{}", node_index, index));
+            let mut analyzer_node = TreeNode {
+                id: analyzer_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: match &output.summary {
+                    Some(summary) => format!("{}: {}", output.analyzer, summary),
+                    None => output.analyzer.clone(),
+                },
+                item_type: TreeNodeType::VendorRequest,
+            };
+            
+            for (note_index, note) in output.notes.iter().enumerate() {
+                let note_id = TreeNodeId::new(format!("analyzer_note_{}_{}_{}", node_index, index, note_index));
+                self.tree_nodes.insert(note_id.clone(), TreeNode {
+                    id: note_id.clone(),
+                    children: Vec::new(),
+                    expanded: true,
+                    data: format!("{}", note),
+                    item_type: match note {
+                        AnalyzerNote::Field(_) => TreeNodeType::VendorRequest,
+                        AnalyzerNote::Annotation(_) => TreeNodeType::Other,
+                        AnalyzerNote::Warning(_) => TreeNodeType::Status,
+                    },
+                });
+                analyzer_node.children.push(note_id);
+            }
+            
+            transaction_node.children.push(analyzer_id.clone());
+            self.tree_nodes.insert(analyzer_id, analyzer_node);
+        }
+        
+        // Add split-transaction details for FS/LS devices behind a high-speed hub
+        if let (Some(hub_address), Some(port)) = (transaction.fields.get("Split Hub"), transaction.fields.get("Split Port")) {
+            let split_id = TreeNodeId::new(format!("split_{}", node_index));
+            let mut split_data = format!("Split: Hub {} Port {}", hub_address, port);
+            if let Some(speed) = transaction.fields.get("Speed") {
+                split_data = format!("{} ({} speed)", split_data, speed);
+            }
+            if let Some(attempts) = transaction.fields.get("Split Attempts") {
+                split_data = format!("{} - {}", split_data, attempts);
+            }
+            
+            let mut split_node = TreeNode {
+                id: split_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: split_data,
+                item_type: TreeNodeType::Other,
+            };
+            
+            if let Some(violations) = transaction.fields.get("Split Violations") {
+                let violation_id = TreeNodeId::new(format!("split_violation_{}", node_index));
+                self.tree_nodes.insert(violation_id.clone(), TreeNode {
+                    id: violation_id.clone(),
+                    children: Vec::new(),
+                    expanded: true,
+                    data: format!("Split timing: {}", violations),
+                    item_type: TreeNodeType::Status,
+                });
+                split_node.children.push(violation_id);
+            }
+            
+            transaction_node.children.push(split_id.clone());
+            self.tree_nodes.insert(split_id, split_node);
+        }
+        
+        // Add hub port events derived from this transaction
+        for (index, entry) in hub_events.iter().enumerate() {
+            let event_id = TreeNodeId::new(format!("hub_{}_{}", node_index, index));
+            
+            let event_node = TreeNode {
+                id: event_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("{}", entry),
+                item_type: TreeNodeType::ClassRequest,
+            };
+            
+            transaction_node.children.push(event_id.clone());
+            self.tree_nodes.insert(event_id, event_node);
         }
         
+        // Add device state changes caused by this transaction
+        self.add_device_event_nodes(&mut transaction_node, &format!("device_{}", node_index), &device_events);
+        
         // Add the transaction node to the tree
-        self.tree_nodes.insert(node_id, transaction_node);
+        self.tree_nodes.insert(node_id.clone(), transaction_node);
+        
+        // Add the transaction node to the root, folding NAKed polls into runs
+        self.attach_transaction_node(node_id, &transaction);
+        
+        if self.timing_analyzer.process_transaction(&transaction, self.sof_tracker.bus_time()) {
+            self.update_timing_node();
+        }
+        if self.host_fingerprinter.process_transaction(&transaction) {
+            self.update_fingerprint_node();
+        }
+        if !halt_events.is_empty() {
+            self.update_stall_node();
+        }
+        for output in &analyzer_outputs {
+            if !output.log.is_empty() {
+                self.update_analyzer_log_node(&output.analyzer, &output.log);
+            }
+        }
+    }
+    
+    // Token of a NAKed IN or PING poll, the only transactions folded into NAK runs
+    fn nak_poll_token(transaction: &UsbTransaction) -> Option<&'static str> {
+        if transaction.status_packet.as_ref()?.status != UsbTransferStatus::NAK {
+            return None;
+        }
+        if !matches!(transaction.transfer_type,
+                     UsbTransferType::Bulk | UsbTransferType::Interrupt | UsbTransferType::Unknown) {
+            return None;
+        }
+        
+        match transaction.fields.get("Token").map(|token| token.as_str()) {
+            Some("IN") => Some("IN"),
+            Some("PING") => Some("PING"),
+            Some(_) => None,
+            // Transactions decoded from the capture format carry direction in the data stage
+            None => match &transaction.data_packet {
+                Some(data) if data.direction == UsbDirection::DeviceToHost && data.data.is_empty() => Some("IN"),
+                _ => None,
+            },
+        }
+    }
+    
+    // Place a transaction node under the root, or into a NAK run for its endpoint
+    fn attach_transaction_node(&mut self, node_id: TreeNodeId, transaction: &UsbTransaction) {
+        let endpoint = (transaction.device_address, transaction.endpoint);
+        
+        let token = match Self::nak_poll_token(transaction) {
+            Some(token) => token,
+            None => {
+                // Any other traffic on the endpoint ends its runs
+                self.open_nak_runs.retain(|(key, _), _| *key != endpoint);
+                if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                    root.children.push(node_id);
+                }
+                return;
+            },
+        };
+        
+        if self.nak_collapse_disabled.contains(&endpoint) {
+            self.nak_transaction_endpoints.insert(node_id.clone(), endpoint);
+            if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                root.children.push(node_id);
+            }
+            return;
+        }
+        
+        let run_id = match self.open_nak_runs.get(&(endpoint, token)) {
+            Some(run_id) => run_id.clone(),
+            None => {
+                self.nak_run_count += 1;
+                let run_id = TreeNodeId::new(format!("nak_run_{}", self.nak_run_count));
+                self.nak_runs.insert(run_id.clone(), NakRun {
+                    endpoint,
+                    token,
+                    transaction_nodes: Vec::new(),
+                    first_transaction: transaction.id,
+                    last_transaction: transaction.id,
+                    start: transaction.timestamp,
+                    end: transaction.timestamp,
+                });
+                self.tree_nodes.insert(run_id.clone(), TreeNode {
+                    id: run_id.clone(),
+                    children: Vec::new(),
+                    expanded: false,
+                    data: String::new(),
+                    item_type: TreeNodeType::Status,
+                });
+                if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                    root.children.push(run_id.clone());
+                }
+                self.open_nak_runs.insert((endpoint, token), run_id.clone());
+                run_id
+            },
+        };
+        
+        if let Some(run) = self.nak_runs.get_mut(&run_id) {
+            run.transaction_nodes.push(node_id.clone());
+            run.last_transaction = transaction.id;
+            run.end = transaction.timestamp;
+            
+            let summary = run.summary();
+            if let Some(run_node) = self.tree_nodes.get_mut(&run_id) {
+                run_node.children.push(node_id);
+                run_node.data = summary;
+            }
+        }
+    }
+    
+    // Switch NAK run collapsing for one endpoint. Disabling it puts the
+    // transactions of existing runs back into the list in their original order.
+    fn toggle_nak_collapse(&mut self, endpoint: EndpointKey) {
+        if self.nak_collapse_disabled.remove(&endpoint) {
+            // Re-enabled: new NAKed polls will be folded into runs again
+            self.nak_transaction_endpoints.retain(|_, key| *key != endpoint);
+            return;
+        }
+        
+        self.nak_collapse_disabled.insert(endpoint);
+        self.open_nak_runs.retain(|(key, _), _| *key != endpoint);
+        
+        let run_ids: Vec<TreeNodeId> = self.nak_runs.iter()
+            .filter(|(_, run)| run.endpoint == endpoint)
+            .map(|(id, _)| id.clone())
+            .collect();
+        if run_ids.is_empty() || self.root_nodes.is_empty() {
+            return;
+        }
+        
+        let mut expanded_runs = HashMap::new();
+        for run_id in run_ids {
+            if let Some(run) = self.nak_runs.remove(&run_id) {
+                for node_id in &run.transaction_nodes {
+                    self.nak_transaction_endpoints.insert(node_id.clone(), endpoint);
+                }
+                self.tree_nodes.remove(&run_id);
+                expanded_runs.insert(run_id, run.transaction_nodes);
+            }
+        }
+        
+        if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+            let children = std::mem::take(&mut root.children);
+            for child in children {
+                match expanded_runs.remove(&child) {
+                    Some(transaction_nodes) => root.children.extend(transaction_nodes),
+                    None => root.children.push(child),
+                }
+            }
+        }
+    }
+    
+    // Devices on the bus, for views that show each device separately
+    pub fn bus_model(&self) -> &BusModel {
+        &self.bus_model
+    }
+    
+    // Add bus model changes as children of the node that caused them
+    fn add_device_event_nodes(&mut self, parent: &mut TreeNode, prefix: &str, events: &[BusModelEvent]) {
+        for (index, event) in events.iter().enumerate() {
+            let event_id = TreeNodeId::new(format!("{}_{}", prefix, index));
+            self.tree_nodes.insert(event_id.clone(), TreeNode {
+                id: event_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("{}", event),
+                item_type: TreeNodeType::Device,
+            });
+            parent.children.push(event_id);
+        }
+    }
+    
+    // Create the root node for transactions if it doesn't exist
+    fn ensure_transaction_root(&mut self) {
+        if !self.root_nodes.is_empty() {
+            return;
+        }
+        
+        let root_id = TreeNodeId::new("mitm_root");
+        self.root_nodes.push(root_id.clone());
+        
+        let root_node = TreeNode {
+            id: root_id.clone(),
+            children: Vec::new(),
+            expanded: true,
+            data: "USB Transactions".to_string(),
+            item_type: TreeNodeType::Root,
+        };
+        
+        self.tree_nodes.insert(root_id, root_node);
+    }
+    
+    // Keep a single collapsed node for the SOF stream instead of one row per SOF
+    fn update_sof_node(&mut self) {
+        self.ensure_transaction_root();
+        
+        let sof_id = TreeNodeId::new("sof_summary");
+        let summary = self.sof_tracker.summary();
+        
+        if let Some(node) = self.tree_nodes.get_mut(&sof_id) {
+            node.data = summary;
+        } else {
+            if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                root.children.insert(0, sof_id.clone());
+            }
+            self.tree_nodes.insert(sof_id.clone(), TreeNode {
+                id: sof_id.clone(),
+                children: Vec::new(),
+                expanded: false,
+                data: summary,
+                item_type: TreeNodeType::Other,
+            });
+        }
+        
+        // Add nodes for anomalies that haven't been shown yet
+        let shown = self.tree_nodes.get(&sof_id).map(|node| node.children.len()).unwrap_or(0);
+        let anomalies: Vec<String> = self.sof_tracker.anomalies().iter()
+            .take(MAX_SOF_ANOMALY_NODES)
+            .skip(shown)
+            .map(|anomaly| match self.sof_tracker.frame_at(anomaly.timestamp()) {
+                Some(frame_time) => format!("{} @ {}", anomaly, frame_time),
+                None => format!("{}", anomaly),
+            })
+            .collect();
+        
+        for (index, anomaly) in anomalies.into_iter().enumerate() {
+            let anomaly_id = TreeNodeId::new(format!("sof_anomaly_{}", shown + index));
+            self.tree_nodes.insert(anomaly_id.clone(), TreeNode {
+                id: anomaly_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: anomaly,
+                item_type: TreeNodeType::Status,
+            });
+            if let Some(node) = self.tree_nodes.get_mut(&sof_id) {
+                node.children.push(anomaly_id);
+            }
+        }
+    }
+    
+    // Keep a single collapsed node with the enumeration timing report of every device
+    fn update_timing_node(&mut self) {
+        self.ensure_transaction_root();
+        
+        let timing_id = TreeNodeId::new("timing_report");
+        
+        // Rebuild the device and check nodes; reports move when a device changes address
+        let old_children = self.tree_nodes.get(&timing_id).map(|node| node.children.clone()).unwrap_or_default();
+        let mut expanded = HashMap::new();
+        for device_id in old_children {
+            if let Some(device_node) = self.tree_nodes.remove(&device_id) {
+                expanded.insert(device_id, device_node.expanded);
+                for check_id in device_node.children {
+                    self.tree_nodes.remove(&check_id);
+                }
+            }
+        }
+        
+        let mut device_ids = Vec::new();
+        let mut failures = 0;
+        let reports: Vec<_> = self.timing_analyzer.reports().cloned().collect();
+        for report in reports {
+            failures += report.failure_count();
+            let device_id = TreeNodeId::new(format!("timing_device_{}", report.address));
+            let mut check_ids = Vec::new();
+            
+            for (index, check) in report.checks.iter().enumerate() {
+                let check_id = TreeNodeId::new(format!("timing_check_{}_{}", report.address, index));
+                self.tree_nodes.insert(check_id.clone(), TreeNode {
+                    id: check_id.clone(),
+                    children: Vec::new(),
+                    expanded: true,
+                    data: match self.sof_tracker.frame_at(check.timestamp) {
+                        Some(frame_time) => format!("{} @ {}", check, frame_time),
+                        None => format!("{}", check),
+                    },
+                    item_type: if check.passed() { TreeNodeType::Other } else { TreeNodeType::Status },
+                });
+                check_ids.push(check_id);
+            }
+            
+            let mut data = format!("{}", report);
+            if let Some(name) = self.bus_model.device(report.address).and_then(|device| device.name()) {
+                data = format!("{} ({})", data, name);
+            }
+            self.tree_nodes.insert(device_id.clone(), TreeNode {
+                id: device_id.clone(),
+                children: check_ids,
+                expanded: expanded.get(&device_id).copied().unwrap_or(report.failure_count() > 0),
+                data,
+                item_type: TreeNodeType::Device,
+            });
+            device_ids.push(device_id);
+        }
+        
+        let summary = format!("Enumeration Timing: {} device{}, {} failed check{}",
+                              device_ids.len(), if device_ids.len() == 1 { "" } else { "s" },
+                              failures, if failures == 1 { "" } else { "s" });
+        
+        if let Some(node) = self.tree_nodes.get_mut(&timing_id) {
+            node.data = summary;
+            node.children = device_ids;
+        } else {
+            if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                root.children.insert(0, timing_id.clone());
+            }
+            self.tree_nodes.insert(timing_id.clone(), TreeNode {
+                id: timing_id,
+                children: device_ids,
+                expanded: false,
+                data: summary,
+                item_type: TreeNodeType::Other,
+            });
+        }
+    }
+    
+    // Keep a single collapsed node listing every stall with its cause and recovery
+    fn update_stall_node(&mut self) {
+        self.ensure_transaction_root();
+        
+        let stall_id = TreeNodeId::new("stall_summary");
+        let summary = self.halt_tracker.summary();
+        
+        // Recovery details change after the fact, so every entry is rewritten
+        let mut stall_ids = Vec::new();
+        for (index, stall) in self.halt_tracker.stalls().iter().enumerate() {
+            let entry_id = TreeNodeId::new(format!("stall_{}", index));
+            let mut data = format!("{}", stall);
+            if let Some(frame_time) = self.sof_tracker.frame_at(stall.timestamp) {
+                data = format!("{} @ {}", data, frame_time);
+            }
+            self.tree_nodes.insert(entry_id.clone(), TreeNode {
+                id: entry_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data,
+                item_type: if stall.recovery.is_some() { TreeNodeType::Other } else { TreeNodeType::Status },
+            });
+            stall_ids.push(entry_id);
+        }
+        
+        if let Some(node) = self.tree_nodes.get_mut(&stall_id) {
+            node.data = summary;
+            node.children = stall_ids;
+        } else {
+            if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                root.children.insert(0, stall_id.clone());
+            }
+            self.tree_nodes.insert(stall_id.clone(), TreeNode {
+                id: stall_id,
+                children: stall_ids,
+                expanded: false,
+                data: summary,
+                item_type: TreeNodeType::Other,
+            });
+        }
+    }
+    
+    // Keep one collapsed node per analyzer with its running log, e.g. a serial transcript
+    fn update_analyzer_log_node(&mut self, analyzer: &str, lines: &[String]) {
+        self.ensure_transaction_root();
+        
+        let log_id = TreeNodeId::new(format!("analyzer_log_{}", analyzer));
+        let title = self.analyzers.log_title(analyzer).unwrap_or_else(|| format!("{} log", analyzer));
+        
+        if !self.tree_nodes.contains_key(&log_id) {
+            if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                root.children.insert(0, log_id.clone());
+            }
+            self.tree_nodes.insert(log_id.clone(), TreeNode {
+                id: log_id.clone(),
+                children: Vec::new(),
+                expanded: false,
+                data: String::new(),
+                item_type: TreeNodeType::Other,
+            });
+        }
+        
+        // Lines only ever get appended, so earlier entries are left alone
+        let first = self.tree_nodes.get(&log_id).map(|node| node.children.len()).unwrap_or(0);
+        let mut line_ids = Vec::new();
+        for (offset, line) in lines.iter().enumerate() {
+            let line_id = TreeNodeId::new(format!("analyzer_log_{}_{}", analyzer, first + offset));
+            self.tree_nodes.insert(line_id.clone(), TreeNode {
+                id: line_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: line.clone(),
+                item_type: TreeNodeType::VendorRequest,
+            });
+            line_ids.push(line_id);
+        }
+        
+        if let Some(node) = self.tree_nodes.get_mut(&log_id) {
+            node.data = title;
+            node.children.extend(line_ids);
+        }
+    }
+    
+    // Keep a single collapsed node with the host OS guess and every scored signature
+    fn update_fingerprint_node(&mut self) {
+        self.ensure_transaction_root();
+        
+        let fingerprint_id = TreeNodeId::new("host_fingerprint");
+        let summary = self.host_fingerprinter.summary();
+        
+        let mut match_ids = Vec::new();
+        for (index, candidate) in self.host_fingerprinter.matches().iter().enumerate() {
+            let match_id = TreeNodeId::new(format!("host_match_{}", index));
+            self.tree_nodes.insert(match_id.clone(), TreeNode {
+                id: match_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data: format!("{}", candidate),
+                item_type: TreeNodeType::Other,
+            });
+            match_ids.push(match_id);
+        }
+        
+        if let Some(node) = self.tree_nodes.get_mut(&fingerprint_id) {
+            node.data = summary;
+            node.children = match_ids;
+        } else {
+            if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                root.children.insert(0, fingerprint_id.clone());
+            }
+            self.tree_nodes.insert(fingerprint_id.clone(), TreeNode {
+                id: fingerprint_id,
+                children: match_ids,
+                expanded: false,
+                data: summary,
+                item_type: TreeNodeType::Other,
+            });
+        }
+    }
+    
+    // Add a bus-state record from the analyzer. Returns the bus events it completed
+    // so connection tracking can follow real attach/detach events.
+    pub fn add_bus_event_record(&mut self, record: BusEventRecord) -> Vec<BusEvent> {
+        let events = self.bus_event_tracker.process_record(&record);
+        
+        if !events.is_empty() {
+            self.ensure_transaction_root();
+        }
+        
+        for event in &events {
+            self.bus_event_count += 1;
+            let event_id = TreeNodeId::new(format!("bus_event_{}", self.bus_event_count));
+            
+            let mut data = format!("Bus Event: {}", event);
+            if let Some(frame_time) = self.sof_tracker.frame_at(event.timestamp) {
+                data = format!("{} @ {}", data, frame_time);
+            }
+            
+            let mut event_node = TreeNode {
+                id: event_id.clone(),
+                children: Vec::new(),
+                expanded: true,
+                data,
+                item_type: TreeNodeType::BusEvent,
+            };
+            
+            let device_events = self.bus_model.process_bus_event(event);
+            self.add_device_event_nodes(&mut event_node, &format!("bus_device_{}", self.bus_event_count), &device_events);
+            self.tree_nodes.insert(event_id.clone(), event_node);
+            
+            if let Some(root) = self.tree_nodes.get_mut(&self.root_nodes[0]) {
+                root.children.push(event_id);
+            }
+            
+            if self.timing_analyzer.process_bus_event(event) {
+                self.update_timing_node();
+            }
+            self.host_fingerprinter.process_bus_event(event);
+            self.halt_tracker.process_bus_event(event);
+            if !self.halt_tracker.stalls().is_empty() {
+                self.update_stall_node();
+            }
+        }
+        
+        events
+    }
+    
+    // Add a raw USB packet from a packet-level capture
+    pub fn add_usb_packet(&mut self, packet: UsbPacket) {
+        // SOFs are collapsed into one summary node but kept for timing
+        if packet.pid == UsbPid::Sof {
+            self.sof_tracker.process_packet(&packet);
+            if let Some(bus_time) = self.sof_tracker.bus_time() {
+                self.timing_analyzer.process_sof(bus_time, self.sof_tracker.nominal_interval());
+            }
+            self.update_sof_node();
+        }
+        
+        // Split phases become logical transactions attributed to the downstream device
+        for split in self.split_tracker.process_packet(&packet) {
+            let transaction = split.to_transaction(self.transaction_count + 1);
+            self.add_transaction(transaction);
+        }
+        
+        // Everything else is grouped into token + data + handshake transactions
+        if let Some(packet_transaction) = self.transaction_assembler.process_packet(&packet) {
+            let transaction = packet_transaction.to_transaction(self.transaction_count + 1);
+            self.add_transaction(transaction);
+        }
+    }
+    
+    // Decode the data stage of hub status requests and status-change interrupts
+    fn hub_data_summary(&self, transaction: &UsbTransaction) -> Option<String> {
+        let data = transaction.data_packet.as_ref()?.get_data();
+        
+        match transaction.transfer_type {
+            UsbTransferType::Control => {
+                let setup = transaction.setup_packet.as_ref()?;
+                if setup.request_type != UsbControlRequestType::Class || setup.bRequest != hub::HUB_GET_STATUS
+                    || !self.hub_tracker.is_hub(transaction.device_address) {
+                    return None;
+                }
+                
+                match setup.recipient {
+                    UsbControlRecipient::Other => {
+                        let superspeed = self.hub_tracker.hub(transaction.device_address)
+                            .map(|h| h.superspeed)
+                            .unwrap_or(false);
+                        HubPortStatus::parse(data, superspeed).map(|status| format!("{}", status))
+                    },
+                    UsbControlRecipient::Device => hub::describe_hub_status(data),
+                    _ => None,
+                }
+            },
+            UsbTransferType::Interrupt if self.hub_tracker.is_hub(transaction.device_address) => {
+                Some(hub::describe_status_change_bitmap(data))
+            },
+            _ => None,
+        }
     }
     
     // Get traffic data for saving
@@ -328,10 +1363,7 @@ impl TrafficView {
                 Command::none()
             },
             Message::ClearTraffic => {
-                self.traffic_data.clear();
-                self.selected_item = None;
-                self.tree_nodes.clear();
-                self.root_nodes.clear();
+                self.clear_traffic();
                 Command::none()
             },
             Message::LoadData(data) => {
@@ -370,6 +1402,15 @@ impl TrafficView {
                 self.speed_selection_open = false;
                 Command::none()
             },
+            Message::ToggleNakCollapse(address, endpoint) => {
+                self.toggle_nak_collapse((address, endpoint));
+                Command::none()
+            },
+            Message::ReloadScripts => {
+                // Edited scripts apply to transfers from here on
+                self.analyzers.set_scripts(load_scripts());
+                Command::none()
+            },
             Message::NoOp => Command::none(),
         }
     }
@@ -529,6 +1570,24 @@ impl TrafficView {
     pub fn clear(&mut self) {
         self.traffic_data.clear();
         self.selected_item = None;
+        self.hub_tracker.clear();
+        self.split_tracker.clear();
+        self.sof_tracker.clear();
+        self.bus_event_tracker.clear();
+        self.bus_event_count = 0;
+        self.transaction_assembler.clear();
+        self.toggle_tracker.clear();
+        self.control_assembler.clear();
+        self.timing_analyzer.clear();
+        self.host_fingerprinter.clear();
+        self.halt_tracker.clear();
+        self.analyzers.reset();
+        self.bus_model.clear();
+        self.nak_runs.clear();
+        self.open_nak_runs.clear();
+        self.nak_run_count = 0;
+        self.nak_transaction_endpoints.clear();
+        self.transaction_count = 0;
         self.clear_tree_view();
     }
     
@@ -612,6 +1671,7 @@ impl TrafficView {
                     TreeNodeType::ClassRequest => color::dark::USB_YELLOW,
                     TreeNodeType::VendorRequest => color::dark::USB_CYAN,
                     TreeNodeType::StandardRequest => color::dark::PRIMARY,
+                    TreeNodeType::BusEvent => color::dark::USB_CYAN,
                     _ => color::dark::TEXT_SECONDARY,
                 }
             } else {
@@ -631,6 +1691,7 @@ impl TrafficView {
                     TreeNodeType::ClassRequest => color::USB_YELLOW,
                     TreeNodeType::VendorRequest => color::USB_CYAN,
                     TreeNodeType::StandardRequest => color::PRIMARY,
+                    TreeNodeType::BusEvent => color::USB_CYAN,
                     _ => color::TEXT_SECONDARY,
                 }
             };
@@ -738,6 +1799,26 @@ impl TrafficView {
             .align_items(iced::Alignment::Center) // Better vertical alignment
             .width(Length::Fill);
             
+            // NAK runs and individually listed NAKs get a per-endpoint collapse toggle
+            let nak_toggle = match (self.nak_runs.get(node_id), self.nak_transaction_endpoints.get(node_id)) {
+                (Some(run), _) => Some(("Show all", run.endpoint)),
+                (None, Some(endpoint)) => Some(("Collapse NAKs", *endpoint)),
+                _ => None,
+            };
+            let node_row = match nak_toggle {
+                Some((label, (address, endpoint))) => node_row.push(
+                    button(text(label).size(12))
+                        .on_press(Message::ToggleNakCollapse(address, endpoint))
+                        .style(if self.dark_mode {
+                            iced::theme::Button::Custom(Box::new(styles::DarkModeTreeNodeButton))
+                        } else {
+                            iced::theme::Button::Custom(Box::new(styles::TreeNodeButton))
+                        })
+                        .padding([2, 6])
+                ),
+                None => node_row,
+            };
+            
             // Choose the appropriate style based on node type
             let style = match node.item_type {
                 TreeNodeType::Root => {
@@ -875,6 +1956,14 @@ impl TrafficView {
         let clear_button = button("Clear")
             .on_press(Message::ClearTraffic)
             .style(iced::theme::Button::Destructive);
+        
+        let reload_scripts_button = button("Reload Scripts")
+            .on_press(Message::ReloadScripts)
+            .style(if self.dark_mode {
+                iced::theme::Button::Custom(Box::new(styles::DarkModeSecondaryButton))
+            } else {
+                iced::theme::Button::Secondary
+            });
             
         // Add Change Speed button when capture is active
         let change_speed_button = if self.capture_active {
@@ -898,6 +1987,7 @@ impl TrafficView {
         let action_buttons = row![
             auto_scroll_button,
             change_speed_button,
+            reload_scripts_button,
             clear_button,
         ]
         .spacing(10)
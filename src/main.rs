@@ -1,4 +1,5 @@
 mod app;
+mod cli;
 mod cynthion;
 mod data;
 mod gui;
@@ -29,6 +30,11 @@ fn main() -> iced::Result {
     info!("Starting USBfly application v{}", env!("CARGO_PKG_VERSION"));
     info!("Platform: {}", std::env::consts::OS);
     
+    // Command-line tools exit here without touching USB or the GUI
+    if let Some(code) = cli::run(&env::args().collect::<Vec<String>>()) {
+        std::process::exit(code);
+    }
+    
     // Check if USBFLY_FORCE_HARDWARE is explicitly set to 1 by the user
     let force_hardware = env::var("USBFLY_FORCE_HARDWARE")
         .map(|val| val == "1")
@@ -0,0 +1,232 @@
+// Stateful protocol analyzers
+// Declarative schemas can't follow protocols whose meaning depends on earlier traffic.
+// An analyzer registers for the devices it understands by VID/PID and interface, is handed
+// every complete transfer to them (whole control transfers, and each bulk or interrupt
+// data transaction), keeps whatever state it needs and returns named fields, annotations
+// and warnings that are shown with the transaction.
+
+use std::fmt;
+use super::bus::BusModel;
+use super::control::ControlTransfer;
+use super::android::AndroidAnalyzer;
+use super::serial_bridge::SerialBridgeAnalyzer;
+use super::mitm_traffic::{
+    UsbControlRecipient,
+    UsbDirection,
+    UsbSetupPacket,
+    UsbTransaction,
+    UsbTransferStatus,
+    UsbTransferType,
+};
+use super::request_fields::RequestField;
+use super::UsbDeviceClass;
+
+// The interface a transfer belongs to, when the descriptors identify it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceIdentity {
+    pub number: u8,
+    pub class: UsbDeviceClass,
+    pub subclass: u8,
+    pub protocol: u8,
+}
+
+// The device a transfer was exchanged with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    pub address: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub interface: Option<InterfaceIdentity>,
+}
+
+// A complete transfer as seen by an analyzer
+#[derive(Debug, Clone)]
+pub struct AnalyzerTransfer {
+    pub device: DeviceIdentity,
+    pub endpoint: u8,
+    pub direction: UsbDirection,
+    pub transfer_type: UsbTransferType,
+    pub setup: Option<UsbSetupPacket>, // Control transfers only
+    pub data: Vec<u8>,
+    pub max_packet_size: Option<u16>,  // From the endpoint descriptor, when known
+    pub timestamp: f64,
+}
+
+impl AnalyzerTransfer {
+    // Build the transfer a transaction completes, if any. Devices whose descriptors
+    // haven't been seen can't be matched and are skipped.
+    pub fn from_transaction(bus_model: &BusModel, transaction: &UsbTransaction,
+                            control_transfer: Option<&ControlTransfer>) -> Option<AnalyzerTransfer> {
+        let bus_device = bus_model.device(transaction.device_address)?;
+        let (vendor_id, product_id) = bus_device.ids()?;
+        let mut device = DeviceIdentity {
+            address: transaction.device_address,
+            vendor_id,
+            product_id,
+            interface: None,
+        };
+
+        let (setup, data) = match (control_transfer, &transaction.setup_packet) {
+            // A data stage reassembled from several transactions
+            (Some(transfer), _) => (transfer.setup().cloned(), transfer.data.clone()),
+            // A setup that carries its data stage, or has none
+            (None, Some(setup)) if setup.wLength == 0 || transaction.data_packet.is_some() => {
+                let data = transaction.data_packet.as_ref().map(|data| data.get_data().to_vec()).unwrap_or_default();
+                (Some(setup.clone()), data)
+            },
+            // Waiting for the rest of the data stage
+            (None, Some(_)) => return None,
+            (None, None) => {
+                let accepted = matches!(transaction.status_packet.as_ref().map(|status| status.status),
+                                        None | Some(UsbTransferStatus::ACK) | Some(UsbTransferStatus::NYET));
+                let data = transaction.data_packet.as_ref().map(|data| data.get_data().to_vec())?;
+                if !accepted || transaction.endpoint == 0 {
+                    return None;
+                }
+                device.interface = transaction.endpoint_info.as_ref().map(|info| InterfaceIdentity {
+                    number: info.interface_number,
+                    class: info.interface_class,
+                    subclass: info.interface_subclass,
+                    protocol: info.interface_protocol,
+                });
+                (None, data)
+            },
+        };
+
+        if let Some(setup) = &setup {
+            if matches!(setup.recipient, UsbControlRecipient::Interface | UsbControlRecipient::Endpoint) {
+                device.interface = bus_device.request_interface(setup.recipient, setup.wIndex)
+                    .map(|interface| InterfaceIdentity {
+                        number: interface.interface_number,
+                        class: interface.interface_class,
+                        subclass: interface.interface_subclass,
+                        protocol: interface.interface_protocol,
+                    });
+            }
+        }
+
+        Some(AnalyzerTransfer {
+            device,
+            endpoint: transaction.endpoint,
+            direction: setup.as_ref().map(|setup| setup.direction).unwrap_or_else(|| transaction.direction()),
+            transfer_type: if setup.is_some() { UsbTransferType::Control } else { transaction.transfer_type },
+            setup,
+            data,
+            max_packet_size: transaction.endpoint_info.as_ref().map(|info| info.max_packet_size),
+            timestamp: transaction.timestamp,
+        })
+    }
+}
+
+// One piece of analyzer output
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalyzerNote {
+    Field(RequestField),
+    Annotation(String),
+    Warning(String),
+}
+
+impl fmt::Display for AnalyzerNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalyzerNote::Field((name, value)) => write!(f, "{}: {}", name, value),
+            AnalyzerNote::Annotation(text) => write!(f, "{}", text),
+            AnalyzerNote::Warning(text) => write!(f, "Warning: {}", text),
+        }
+    }
+}
+
+// What one analyzer made of one transfer
+#[derive(Debug, Clone)]
+pub struct AnalyzerOutput {
+    pub analyzer: String,
+    pub summary: Option<String>, // Short description for the transfer's row
+    pub notes: Vec<AnalyzerNote>,
+    pub log: Vec<String>,        // Lines appended to the analyzer's running log (e.g. a transcript)
+}
+
+impl AnalyzerOutput {
+    pub fn has_warnings(&self) -> bool {
+        self.notes.iter().any(|note| matches!(note, AnalyzerNote::Warning(_)))
+    }
+}
+
+// A protocol decoder that keeps state across transfers
+pub trait ProtocolAnalyzer {
+    fn name(&self) -> &str;
+
+    // Whether the analyzer wants this device's transfers
+    fn matches(&self, device: &DeviceIdentity) -> bool;
+
+    // Decode one transfer; None when it has nothing to say about it
+    fn process(&mut self, transfer: &AnalyzerTransfer) -> Option<AnalyzerOutput>;
+
+    // Forget all state, e.g. when the capture is cleared
+    fn reset(&mut self);
+
+    // Title of the analyzer's running log
+    fn log_title(&self) -> String {
+        format!("{} log", self.name())
+    }
+}
+
+// The analyzers in use, each offered every transfer to a device it matches
+#[derive(Default)]
+pub struct AnalyzerRegistry {
+    analyzers: Vec<Box<dyn ProtocolAnalyzer>>,
+    scripts: Vec<Box<dyn ProtocolAnalyzer>>, // User scripts, replaced as a whole on reload
+}
+
+impl AnalyzerRegistry {
+    pub fn new() -> Self {
+        AnalyzerRegistry::default()
+    }
+
+    // The analyzers that ship with USBfly
+    pub fn with_builtin() -> Self {
+        let mut registry = AnalyzerRegistry::new();
+        registry.register(Box::new(SerialBridgeAnalyzer::new()));
+        registry.register(Box::new(AndroidAnalyzer::new()));
+        registry
+    }
+
+    pub fn register(&mut self, analyzer: Box<dyn ProtocolAnalyzer>) {
+        self.analyzers.push(analyzer);
+    }
+
+    // Swap in a freshly loaded set of user scripts; the old scripts' state is dropped
+    pub fn set_scripts(&mut self, scripts: Vec<Box<dyn ProtocolAnalyzer>>) {
+        self.scripts = scripts;
+    }
+
+    fn all(&self) -> impl Iterator<Item = &Box<dyn ProtocolAnalyzer>> {
+        self.analyzers.iter().chain(self.scripts.iter())
+    }
+
+    fn all_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn ProtocolAnalyzer>> {
+        self.analyzers.iter_mut().chain(self.scripts.iter_mut())
+    }
+
+    #[allow(dead_code)]
+    pub fn names(&self) -> Vec<&str> {
+        self.all().map(|analyzer| analyzer.name()).collect()
+    }
+
+    // Current title of an analyzer's running log
+    pub fn log_title(&self, analyzer: &str) -> Option<String> {
+        self.all().find(|candidate| candidate.name() == analyzer).map(|analyzer| analyzer.log_title())
+    }
+
+    pub fn reset(&mut self) {
+        for analyzer in self.all_mut() {
+            analyzer.reset();
+        }
+    }
+
+    pub fn process(&mut self, transfer: &AnalyzerTransfer) -> Vec<AnalyzerOutput> {
+        self.all_mut()
+            .filter(|analyzer| analyzer.matches(&transfer.device))
+            .filter_map(|analyzer| analyzer.process(transfer))
+            .collect()
+    }
+}
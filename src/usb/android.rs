@@ -0,0 +1,558 @@
+// Android Open Accessory and ADB decoding
+// AOA: an accessory acting as USB host asks a phone for its protocol version, sends its
+// identifying strings and tells it to start accessory mode with vendor requests 51-58.
+// The phone then drops off the bus and comes back with Google's VID and an accessory PID;
+// the analyzer links the two so both show as the same physical device.
+// ADB: the transport on interface class 0xFF/0x42/0x01 carries 24-byte message headers
+// (command, two arguments, payload length and checksum, magic) followed by the payload.
+// Messages are decoded, checked, and WRTE payloads are reassembled per stream.
+
+use std::collections::HashMap;
+use std::fmt;
+use super::analyzer::{AnalyzerNote, AnalyzerOutput, AnalyzerTransfer, DeviceIdentity, ProtocolAnalyzer};
+use super::mitm_traffic::{UsbControlRecipient, UsbControlRequestType, UsbDirection, UsbTransferType};
+use super::serial_bridge::escape_serial;
+use super::UsbDeviceClass;
+
+const ANALYZER_NAME: &str = "Android";
+
+// Accessory-mode identity a phone re-enumerates with
+pub const GOOGLE_VID: u16 = 0x18D1;
+const ACCESSORY_PIDS: std::ops::RangeInclusive<u16> = 0x2D00..=0x2D05;
+
+// AOA vendor requests (AOA 1.0 and 2.0)
+pub const ACCESSORY_GET_PROTOCOL: u8 = 51;
+pub const ACCESSORY_SEND_STRING: u8 = 52;
+pub const ACCESSORY_START: u8 = 53;
+pub const ACCESSORY_REGISTER_HID: u8 = 54;
+pub const ACCESSORY_UNREGISTER_HID: u8 = 55;
+pub const ACCESSORY_SET_HID_REPORT_DESC: u8 = 56;
+pub const ACCESSORY_SEND_HID_EVENT: u8 = 57;
+pub const ACCESSORY_SET_AUDIO_MODE: u8 = 58;
+
+// ADB interface: vendor-specific class, subclass 0x42, protocol 1
+const ADB_SUBCLASS: u8 = 0x42;
+const ADB_PROTOCOL: u8 = 0x01;
+const ADB_HEADER_LENGTH: usize = 24;
+
+// Largest payload an ADB peer may announce (MAX_PAYLOAD); CNXN can lower it
+const ADB_MAX_PAYLOAD: u32 = 1024 * 1024;
+
+// Largest reassembled payload repeated in a stream's close note
+const STREAM_PREVIEW_CHARS: usize = 2048;
+
+fn accessory_string_name(index: u16) -> &'static str {
+    match index {
+        0 => "Manufacturer",
+        1 => "Model",
+        2 => "Description",
+        3 => "Version",
+        4 => "URI",
+        5 => "Serial",
+        _ => "Unknown",
+    }
+}
+
+fn accessory_pid_name(product_id: u16) -> &'static str {
+    match product_id {
+        0x2D00 => "accessory",
+        0x2D01 => "accessory + ADB",
+        0x2D02 => "audio",
+        0x2D03 => "audio + ADB",
+        0x2D04 => "accessory + audio",
+        0x2D05 => "accessory + audio + ADB",
+        _ => "unknown",
+    }
+}
+
+// ADB message commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdbCommand {
+    Cnxn,
+    Auth,
+    Open,
+    Okay,
+    Clse,
+    Wrte,
+    Sync,
+    Stls,
+}
+
+impl AdbCommand {
+    pub fn from_u32(value: u32) -> Option<AdbCommand> {
+        Some(match &value.to_le_bytes() {
+            b"CNXN" => AdbCommand::Cnxn,
+            b"AUTH" => AdbCommand::Auth,
+            b"OPEN" => AdbCommand::Open,
+            b"OKAY" => AdbCommand::Okay,
+            b"CLSE" => AdbCommand::Clse,
+            b"WRTE" => AdbCommand::Wrte,
+            b"SYNC" => AdbCommand::Sync,
+            b"STLS" => AdbCommand::Stls,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for AdbCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AdbCommand::Cnxn => "CNXN",
+            AdbCommand::Auth => "AUTH",
+            AdbCommand::Open => "OPEN",
+            AdbCommand::Okay => "OKAY",
+            AdbCommand::Clse => "CLSE",
+            AdbCommand::Wrte => "WRTE",
+            AdbCommand::Sync => "SYNC",
+            AdbCommand::Stls => "STLS",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A 24-byte ADB message header
+#[derive(Debug, Clone, Copy)]
+pub struct AdbHeader {
+    pub command: AdbCommand,
+    pub arg0: u32,
+    pub arg1: u32,
+    pub data_length: u32,
+    pub data_check: u32,
+    pub magic_valid: bool,
+}
+
+impl AdbHeader {
+    pub fn parse(data: &[u8]) -> Option<AdbHeader> {
+        if data.len() < ADB_HEADER_LENGTH {
+            return None;
+        }
+        let word = |index: usize| u32::from_le_bytes([data[index * 4], data[index * 4 + 1], data[index * 4 + 2], data[index * 4 + 3]]);
+        let raw_command = word(0);
+        Some(AdbHeader {
+            command: AdbCommand::from_u32(raw_command)?,
+            arg0: word(1),
+            arg1: word(2),
+            data_length: word(3),
+            data_check: word(4),
+            magic_valid: word(5) == !raw_command,
+        })
+    }
+
+    // Payload checksum is the byte sum; newer peers send 0 and skip the check
+    pub fn check_payload(&self, payload: &[u8]) -> Option<String> {
+        if self.data_check == 0 {
+            return None;
+        }
+        let sum = payload.iter().fold(0u32, |sum, byte| sum.wrapping_add(*byte as u32));
+        if sum != self.data_check {
+            Some(format!("payload checksum 0x{:08X}, header says 0x{:08X}", sum, self.data_check))
+        } else {
+            None
+        }
+    }
+
+    fn describe(&self, payload: &[u8]) -> String {
+        let text = || escape_serial(payload.strip_suffix(&[0]).unwrap_or(payload));
+        match self.command {
+            AdbCommand::Cnxn => format!("CNXN version 0x{:08X}, max data {} \"{}\"", self.arg0, self.arg1, text()),
+            AdbCommand::Auth => {
+                let kind = match self.arg0 {
+                    1 => "TOKEN",
+                    2 => "SIGNATURE",
+                    3 => "RSAPUBLICKEY",
+                    _ => "unknown",
+                };
+                format!("AUTH {} ({} bytes)", kind, payload.len())
+            },
+            AdbCommand::Open => format!("OPEN local {} \"{}\"", self.arg0, text()),
+            AdbCommand::Okay => format!("OKAY local {}, remote {}", self.arg0, self.arg1),
+            AdbCommand::Clse => format!("CLSE local {}, remote {}", self.arg0, self.arg1),
+            AdbCommand::Wrte => format!("WRTE local {}, remote {} ({} bytes)", self.arg0, self.arg1, payload.len()),
+            AdbCommand::Sync => format!("SYNC online {}, sequence {}", self.arg0, self.arg1),
+            AdbCommand::Stls => format!("STLS version 0x{:08X}", self.arg0),
+        }
+    }
+}
+
+// A header waiting for its payload to arrive in later transfers
+#[derive(Debug, Clone)]
+struct PendingMessage {
+    header: AdbHeader,
+    payload: Vec<u8>,
+}
+
+// One ADB stream, keyed by the host's local ID
+#[derive(Debug, Clone, Default)]
+struct AdbStream {
+    destination: String,
+    to_device: Vec<u8>,
+    from_device: Vec<u8>,
+}
+
+// An accessory handshake seen on a phone before it re-enumerated
+#[derive(Debug, Clone)]
+struct AccessoryHandshake {
+    address: u8,
+    vendor_id: u16,
+    product_id: u16,
+    strings: Vec<(String, String)>,
+    protocol: Option<u16>,
+    started_at: Option<f64>,
+}
+
+// Decodes the AOA handshake and the ADB transport
+#[derive(Debug, Clone, Default)]
+pub struct AndroidAnalyzer {
+    handshakes: HashMap<u8, AccessoryHandshake>,    // Address -> handshake in progress
+    accessories: HashMap<u8, AccessoryHandshake>,   // Accessory-mode address -> the handshake that started it
+    pending: HashMap<(u8, bool), PendingMessage>,   // (address, is IN) -> header awaiting payload
+    streams: HashMap<(u8, u32), AdbStream>,         // (address, host local ID) -> stream
+    max_data: HashMap<u8, u32>,                     // Address -> largest max-data announced in CNXN
+    messages: usize,
+}
+
+impl AndroidAnalyzer {
+    pub fn new() -> Self {
+        AndroidAnalyzer::default()
+    }
+
+    fn output(summary: String, notes: Vec<AnalyzerNote>, log: Vec<String>) -> Option<AnalyzerOutput> {
+        Some(AnalyzerOutput { analyzer: ANALYZER_NAME.to_string(), summary: Some(summary), notes, log })
+    }
+
+    // Vendor requests 51-58 mean something else on most devices, so AOA decoding only starts
+    // once the host has asked for the protocol version the way an accessory does
+    fn process_aoa(&mut self, transfer: &AnalyzerTransfer) -> Option<AnalyzerOutput> {
+        let setup = transfer.setup.as_ref()?;
+        if setup.request_type != UsbControlRequestType::Vendor || setup.recipient != UsbControlRecipient::Device
+            || !(ACCESSORY_GET_PROTOCOL..=ACCESSORY_SET_AUDIO_MODE).contains(&setup.bRequest) {
+            return None;
+        }
+        // GET_PROTOCOL reads the 16-bit version; every other request writes to the phone
+        let expected_direction = if setup.bRequest == ACCESSORY_GET_PROTOCOL {
+            UsbDirection::DeviceToHost
+        } else {
+            UsbDirection::HostToDevice
+        };
+        if setup.direction != expected_direction {
+            return None;
+        }
+        let device = &transfer.device;
+        let handshake = if setup.bRequest == ACCESSORY_GET_PROTOCOL {
+            if setup.wLength != 2 {
+                return None;
+            }
+            self.handshakes.entry(device.address).or_insert_with(|| AccessoryHandshake {
+                address: device.address,
+                vendor_id: device.vendor_id,
+                product_id: device.product_id,
+                strings: Vec::new(),
+                protocol: None,
+                started_at: None,
+            })
+        } else {
+            self.handshakes.get_mut(&device.address)?
+        };
+
+        let mut notes = Vec::new();
+        let mut log = Vec::new();
+        let summary = match setup.bRequest {
+            ACCESSORY_GET_PROTOCOL => {
+                if transfer.data.len() >= 2 {
+                    let version = u16::from_le_bytes([transfer.data[0], transfer.data[1]]);
+                    handshake.protocol = Some(version);
+                    notes.push(AnalyzerNote::Field(("Protocol Version".to_string(), format!("{}", version))));
+                    if version == 0 {
+                        notes.push(AnalyzerNote::Warning("device does not support accessory mode".to_string()));
+                    }
+                }
+                "AOA GET_PROTOCOL".to_string()
+            },
+            ACCESSORY_SEND_STRING => {
+                let name = accessory_string_name(setup.wIndex);
+                let value = String::from_utf8_lossy(transfer.data.split(|byte| *byte == 0).next().unwrap_or(&[])).to_string();
+                notes.push(AnalyzerNote::Field((name.to_string(), format!("\"{}\"", value))));
+                handshake.strings.push((name.to_string(), value));
+                format!("AOA SEND_STRING {}", name)
+            },
+            ACCESSORY_START => {
+                handshake.started_at = Some(transfer.timestamp);
+                log.push(format!("[{:.6}] Addr {} ({:04X}:{:04X}) asked to start accessory mode", transfer.timestamp,
+                                 device.address, device.vendor_id, device.product_id));
+                notes.push(AnalyzerNote::Annotation("Device should re-enumerate in accessory mode".to_string()));
+                "AOA START".to_string()
+            },
+            ACCESSORY_REGISTER_HID => {
+                notes.push(AnalyzerNote::Field(("HID ID".to_string(), format!("{}", setup.wValue))));
+                notes.push(AnalyzerNote::Field(("Report Descriptor Length".to_string(), format!("{} bytes", setup.wIndex))));
+                "AOA REGISTER_HID".to_string()
+            },
+            ACCESSORY_UNREGISTER_HID => {
+                notes.push(AnalyzerNote::Field(("HID ID".to_string(), format!("{}", setup.wValue))));
+                "AOA UNREGISTER_HID".to_string()
+            },
+            ACCESSORY_SET_HID_REPORT_DESC => {
+                notes.push(AnalyzerNote::Field(("HID ID".to_string(), format!("{}", setup.wValue))));
+                notes.push(AnalyzerNote::Field(("Offset".to_string(), format!("{}", setup.wIndex))));
+                notes.push(AnalyzerNote::Field(("Chunk".to_string(), format!("{} bytes", transfer.data.len()))));
+                "AOA SET_HID_REPORT_DESC".to_string()
+            },
+            ACCESSORY_SEND_HID_EVENT => {
+                let event: Vec<String> = transfer.data.iter().map(|byte| format!("{:02X}", byte)).collect();
+                notes.push(AnalyzerNote::Field(("HID ID".to_string(), format!("{}", setup.wValue))));
+                notes.push(AnalyzerNote::Field(("Event".to_string(), event.join(" "))));
+                "AOA SEND_HID_EVENT".to_string()
+            },
+            _ => {
+                let mode = match setup.wValue {
+                    0 => "No audio",
+                    1 => "2 channel, 16-bit PCM, 44100 Hz",
+                    _ => "Unknown",
+                };
+                notes.push(AnalyzerNote::Field(("Audio Mode".to_string(), mode.to_string())));
+                "AOA SET_AUDIO_MODE".to_string()
+            },
+        };
+        Self::output(summary, notes, log)
+    }
+
+    // First traffic from a phone in accessory mode: link it to the handshake that started it
+    fn link_accessory(&mut self, device: &DeviceIdentity, timestamp: f64) -> Option<AnalyzerOutput> {
+        if device.vendor_id != GOOGLE_VID || !ACCESSORY_PIDS.contains(&device.product_id)
+            || self.accessories.contains_key(&device.address) {
+            return None;
+        }
+        let started = self.handshakes.values()
+            .filter(|handshake| handshake.started_at.is_some())
+            .max_by(|a, b| a.started_at.partial_cmp(&b.started_at).unwrap_or(std::cmp::Ordering::Equal))?
+            .address;
+        let handshake = self.handshakes.remove(&started)?;
+
+        let delay = handshake.started_at.map(|started| (timestamp - started) * 1000.0).unwrap_or(0.0);
+        let description = format!("Addr {} is Addr {} ({:04X}:{:04X}) in {} mode, {:.1} ms after START",
+                                  device.address, handshake.address, handshake.vendor_id, handshake.product_id,
+                                  accessory_pid_name(device.product_id), delay);
+        let mut notes = vec![AnalyzerNote::Annotation(description.clone())];
+        notes.extend(handshake.strings.iter()
+            .map(|(name, value)| AnalyzerNote::Field((format!("Accessory {}", name), format!("\"{}\"", value)))));
+        let log = vec![format!("[{:.6}] {}", timestamp, description)];
+        self.accessories.insert(device.address, handshake);
+        Self::output(format!("Accessory mode ({})", accessory_pid_name(device.product_id)), notes, log)
+    }
+
+    fn process_adb(&mut self, transfer: &AnalyzerTransfer) -> Option<AnalyzerOutput> {
+        let interface = transfer.device.interface?;
+        if transfer.transfer_type != UsbTransferType::Bulk || interface.class != UsbDeviceClass::VendorSpecific
+            || interface.subclass != ADB_SUBCLASS || interface.protocol != ADB_PROTOCOL {
+            return None;
+        }
+        let address = transfer.device.address;
+        let is_in = transfer.direction == UsbDirection::DeviceToHost;
+        let key = (address, is_in);
+
+        // A valid header while a payload is still outstanding means the rest of that payload
+        // was lost; drop the message and resync on the new header
+        let header = AdbHeader::parse(&transfer.data).filter(|header| header.magic_valid);
+        let mut notes = Vec::new();
+        if header.is_some() {
+            if let Some(dropped) = self.pending.remove(&key) {
+                notes.push(AnalyzerNote::Warning(format!("ADB {} dropped after {} of {} payload bytes",
+                    dropped.header.command, dropped.payload.len(), dropped.header.data_length)));
+            }
+        }
+
+        // Payload continuing an earlier header, or a new header (with its payload, if sent together)
+        let mut message = match self.pending.remove(&key) {
+            Some(mut pending) => {
+                pending.payload.extend_from_slice(&transfer.data);
+                pending
+            },
+            None => {
+                let header = match header.or_else(|| AdbHeader::parse(&transfer.data)) {
+                    Some(header) => header,
+                    None => return Self::output("ADB data without header".to_string(),
+                                                vec![AnalyzerNote::Warning(format!("{} bytes outside any ADB message", transfer.data.len()))],
+                                                Vec::new()),
+                };
+                let limit = self.max_data.get(&address).copied().unwrap_or(ADB_MAX_PAYLOAD).min(ADB_MAX_PAYLOAD);
+                if header.data_length > limit {
+                    notes.push(AnalyzerNote::Warning(format!("payload length {} exceeds max data {}", header.data_length, limit)));
+                    return Self::output(format!("ADB {} with oversized payload", header.command), notes, Vec::new());
+                }
+                PendingMessage { header, payload: transfer.data[ADB_HEADER_LENGTH..].to_vec() }
+            },
+        };
+
+        let expected = message.header.data_length as usize;
+        if message.payload.len() < expected {
+            let received = message.payload.len();
+            let command = message.header.command;
+            self.pending.insert(key, message);
+            return Self::output(format!("ADB {} ({} of {} bytes)", command, received, expected), notes, Vec::new());
+        }
+        message.payload.truncate(expected);
+        self.messages += 1;
+        self.process_adb_message(address, is_in, &message.header, &message.payload, transfer.timestamp, notes)
+    }
+
+    fn process_adb_message(&mut self, address: u8, is_in: bool, header: &AdbHeader, payload: &[u8],
+                           timestamp: f64, mut notes: Vec<AnalyzerNote>) -> Option<AnalyzerOutput> {
+        let direction = if is_in { "device→host" } else { "host→device" };
+        notes.extend([
+            AnalyzerNote::Field(("Command".to_string(), format!("{}", header.command))),
+            AnalyzerNote::Field(("Arg0".to_string(), format!("0x{:08X}", header.arg0))),
+            AnalyzerNote::Field(("Arg1".to_string(), format!("0x{:08X}", header.arg1))),
+            AnalyzerNote::Field(("Payload".to_string(), format!("{} bytes", payload.len()))),
+        ]);
+        if !header.magic_valid {
+            notes.push(AnalyzerNote::Warning("magic is not the inverted command".to_string()));
+        }
+        if let Some(error) = header.check_payload(payload) {
+            notes.push(AnalyzerNote::Warning(error));
+        }
+
+        // The host's local ID is arg0 on messages it sends and arg1 on the device's replies
+        let host_id = if is_in { header.arg1 } else { header.arg0 };
+        let mut log = Vec::new();
+        match header.command {
+            AdbCommand::Open if !is_in => {
+                let destination = escape_serial(payload.strip_suffix(&[0]).unwrap_or(payload));
+                log.push(format!("[{:.6}] Addr {} stream {} opened: {}", timestamp, address, host_id, destination));
+                self.streams.insert((address, host_id), AdbStream { destination, ..AdbStream::default() });
+            },
+            AdbCommand::Wrte => {
+                if let Some(stream) = self.streams.get_mut(&(address, host_id)) {
+                    let buffer = if is_in { &mut stream.from_device } else { &mut stream.to_device };
+                    buffer.extend_from_slice(payload);
+                    notes.push(AnalyzerNote::Field(("Stream".to_string(), format!("{} ({}), {} bytes {} so far",
+                        host_id, stream.destination, buffer.len(), direction))));
+                    notes.push(AnalyzerNote::Field(("Data".to_string(), format!("\"{}\"", escape_serial(payload)))));
+                }
+            },
+            AdbCommand::Clse => {
+                if let Some(stream) = self.streams.remove(&(address, host_id)) {
+                    log.push(format!("[{:.6}] Addr {} stream {} ({}) closed: {} bytes to device, {} bytes from device",
+                                     timestamp, address, host_id, stream.destination,
+                                     stream.to_device.len(), stream.from_device.len()));
+                    for (label, data) in [("Reassembled to device", &stream.to_device), ("Reassembled from device", &stream.from_device)] {
+                        if data.is_empty() {
+                            continue;
+                        }
+                        let mut text = escape_serial(data);
+                        if text.chars().count() > STREAM_PREVIEW_CHARS {
+                            text = format!("{}...", text.chars().take(STREAM_PREVIEW_CHARS).collect::<String>());
+                        }
+                        notes.push(AnalyzerNote::Field((label.to_string(), format!("\"{}\"", text))));
+                    }
+                }
+            },
+            AdbCommand::Cnxn => {
+                // Either side may send up to the larger of the two announced sizes
+                if header.arg1 > 0 {
+                    let max_data = self.max_data.entry(address).or_insert(0);
+                    *max_data = (*max_data).max(header.arg1);
+                }
+                log.push(format!("[{:.6}] Addr {} {} {}", timestamp, address, direction, header.describe(payload)));
+            },
+            _ => {},
+        }
+
+        Self::output(format!("ADB {}", header.describe(payload)), notes, log)
+    }
+
+    pub fn summary(&self) -> String {
+        format!("{} ADB message{}, {} open stream{}, {} accessory device{}",
+                self.messages, if self.messages == 1 { "" } else { "s" },
+                self.streams.len(), if self.streams.len() == 1 { "" } else { "s" },
+                self.accessories.len(), if self.accessories.len() == 1 { "" } else { "s" })
+    }
+}
+
+impl ProtocolAnalyzer for AndroidAnalyzer {
+    fn name(&self) -> &str {
+        ANALYZER_NAME
+    }
+
+    // AOA requests can go to any phone, so every device is offered; process_aoa() only
+    // decodes a device once an accessory-style GET_PROTOCOL has been seen on it
+    fn matches(&self, _device: &DeviceIdentity) -> bool {
+        true
+    }
+
+    fn process(&mut self, transfer: &AnalyzerTransfer) -> Option<AnalyzerOutput> {
+        let link = self.link_accessory(&transfer.device, transfer.timestamp);
+        let decoded = match transfer.setup {
+            Some(_) => self.process_aoa(transfer),
+            None => self.process_adb(transfer),
+        };
+        match (link, decoded) {
+            (Some(mut link), Some(decoded)) => {
+                link.summary = decoded.summary;
+                link.notes.extend(decoded.notes);
+                link.log.extend(decoded.log);
+                Some(link)
+            },
+            (link, decoded) => link.or(decoded),
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = AndroidAnalyzer::default();
+    }
+
+    fn log_title(&self) -> String {
+        format!("Android: {}", self.summary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(command: &[u8; 4], arg0: u32, arg1: u32, payload: &[u8], data_check: u32) -> Vec<u8> {
+        let command = u32::from_le_bytes(*command);
+        [command, arg0, arg1, payload.len() as u32, data_check, !command]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn parses_connect_header() {
+        let payload = b"host::\0";
+        let bytes = header(b"CNXN", 0x0100_0001, 256 * 1024, payload, 0);
+        let header = AdbHeader::parse(&bytes).unwrap();
+        assert_eq!(header.command, AdbCommand::Cnxn);
+        assert_eq!(header.arg0, 0x0100_0001);
+        assert_eq!(header.arg1, 262144);
+        assert_eq!(header.data_length, 7);
+        assert!(header.magic_valid);
+        assert_eq!(header.describe(payload), "CNXN version 0x01000001, max data 262144 \"host::\"");
+    }
+
+    #[test]
+    fn flags_bad_magic_and_rejects_non_headers() {
+        let mut bytes = header(b"WRTE", 1, 2, &[], 0);
+        bytes[20] ^= 0xFF;
+        assert!(!AdbHeader::parse(&bytes).unwrap().magic_valid);
+
+        assert!(AdbHeader::parse(&header(b"OKAY", 1, 2, &[], 0)[..23]).is_none());
+        assert!(AdbHeader::parse(&header(b"XXXX", 1, 2, &[], 0)).is_none());
+    }
+
+    #[test]
+    fn checks_payload_sum() {
+        let payload = b"shell:ls\0";
+        let sum = payload.iter().map(|byte| *byte as u32).sum();
+        let good = AdbHeader::parse(&header(b"OPEN", 3, 0, payload, sum)).unwrap();
+        assert_eq!(good.check_payload(payload), None);
+        assert_eq!(good.describe(payload), "OPEN local 3 \"shell:ls\"");
+
+        let bad = AdbHeader::parse(&header(b"OPEN", 3, 0, payload, sum + 1)).unwrap();
+        assert!(bad.check_payload(payload).is_some());
+
+        // Newer peers send no checksum
+        let unchecked = AdbHeader::parse(&header(b"OPEN", 3, 0, payload, 0)).unwrap();
+        assert_eq!(unchecked.check_payload(payload), None);
+    }
+}
@@ -0,0 +1,562 @@
+// Bus model: the devices seen on the bus, keyed by device address
+// Built from enumeration traffic: SET_ADDRESS moves a device from the default address,
+// GET_DESCRIPTOR responses fill in its descriptors and SET_CONFIGURATION/SET_INTERFACE
+// track the active configuration and alternate settings (USB 2.0 chapter 9).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use super::bus_event::{BusEvent, BusEventKind};
+use super::decoder::Speed;
+use super::class_request::ClassContext;
+use super::descriptors::{ConfigurationDescriptor, InterfaceDescriptor, UsbDevice};
+use super::device_state::DeviceState;
+use super::ms_os::MsOsFeature;
+use super::descriptor_types::UsbEndpointDirection;
+use super::mitm_traffic::{
+    UsbControlRecipient,
+    UsbControlRequestType,
+    UsbDirection,
+    UsbEndpointInfo,
+    UsbSetupPacket,
+    UsbStandardRequest,
+    UsbTransaction,
+    UsbTransferStatus,
+    UsbTransferType,
+};
+use super::UsbDescriptorType;
+
+// Address every device answers on until SET_ADDRESS
+pub const DEFAULT_ADDRESS: u8 = 0;
+
+// A device on the bus and everything learned about it from its traffic
+#[derive(Debug, Clone)]
+pub struct BusDevice {
+    pub address: u8,
+    pub device: UsbDevice,                        // Rebuilt from the collected descriptors
+    pub configuration: Option<u8>,                // Active bConfigurationValue (Some(0) = unconfigured)
+    pub alternate_settings: BTreeMap<u8, u8>,     // bInterfaceNumber -> bAlternateSetting
+    pub speed: Option<Speed>,
+    #[allow(dead_code)]
+    pub attached_at: f64,
+    pub detached_at: Option<f64>,
+    pub previous_addresses: Vec<u8>,
+    descriptor_data: BTreeMap<(u8, u8, u16), Vec<u8>>, // (descriptor type, index, LANGID for strings) -> latest response
+    ms_os_data: BTreeMap<MsOsFeature, Vec<u8>>,   // Microsoft OS feature descriptor responses
+    webusb_url_data: BTreeMap<u8, Vec<u8>>,       // WebUSB URL index -> GET_URL response
+}
+
+impl BusDevice {
+    fn new(address: u8, timestamp: f64) -> Self {
+        BusDevice {
+            address,
+            device: UsbDevice::new(),
+            configuration: None,
+            alternate_settings: BTreeMap::new(),
+            speed: None,
+            attached_at: timestamp,
+            detached_at: None,
+            previous_addresses: Vec::new(),
+            descriptor_data: BTreeMap::new(),
+            ms_os_data: BTreeMap::new(),
+            webusb_url_data: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.detached_at.is_none()
+    }
+
+    // bMaxPacketSize0 once the device descriptor has been seen
+    pub fn max_packet_size0(&self) -> Option<u8> {
+        self.device.device.as_ref().map(|device| device.max_packet_size0)
+    }
+
+    // Vendor and product ID once the device descriptor has been seen
+    pub fn ids(&self) -> Option<(u16, u16)> {
+        self.device.device.as_ref().map(|device| (device.vendor_id, device.product_id))
+    }
+
+    // Product string if known, otherwise VID:PID
+    pub fn name(&self) -> Option<String> {
+        let device = self.device.device.as_ref()?;
+        Some(match &device.product_string {
+            Some(product) => product.clone(),
+            None => format!("{:04X}:{:04X}", device.vendor_id, device.product_id),
+        })
+    }
+
+    // Descriptor of the active configuration. Before SET_CONFIGURATION is seen,
+    // a device with a single configuration is assumed to use it.
+    pub fn active_configuration(&self) -> Option<&ConfigurationDescriptor> {
+        match self.configuration {
+            Some(0) => None,
+            Some(value) => self.device.configurations.iter()
+                .find(|config| config.configuration_value == value),
+            None if self.device.configurations.len() == 1 => self.device.configurations.first(),
+            None => None,
+        }
+    }
+
+    // Chapter 9 state, when the traffic seen so far is enough to tell.
+    // A device first seen on a non-zero address may have been configured before the capture.
+    pub fn state(&self) -> Option<DeviceState> {
+        if self.address == DEFAULT_ADDRESS {
+            return Some(DeviceState::Default);
+        }
+        match self.configuration {
+            Some(0) => Some(DeviceState::Address),
+            Some(_) => Some(DeviceState::Configured),
+            None if !self.previous_addresses.is_empty() => Some(DeviceState::Address),
+            None => None,
+        }
+    }
+
+    // Find an endpoint in the active configuration and alternate settings. With no
+    // direction (the capture didn't say), the first endpoint with that number matches.
+    pub fn resolve_endpoint(&self, endpoint: u8, is_in: Option<bool>) -> Option<UsbEndpointInfo> {
+        let configuration = self.active_configuration()?;
+
+        let direction = is_in.map(|is_in| if is_in { UsbEndpointDirection::In } else { UsbEndpointDirection::Out });
+
+        configuration.interfaces.iter()
+            .filter(|interface| {
+                let active = self.alternate_settings.get(&interface.interface_number).copied().unwrap_or(0);
+                interface.alternate_setting == active
+            })
+            .find_map(|interface| {
+                interface.endpoints.iter()
+                    .find(|ep| ep.endpoint_number == endpoint && direction.is_none_or(|direction| ep.direction == direction))
+                    .map(|ep| UsbEndpointInfo {
+                        endpoint_address: ep.endpoint_address,
+                        transfer_type: UsbTransferType::from(ep.transfer_type),
+                        max_packet_size: ep.max_packet_size,
+                        interval: ep.interval,
+                        interface_number: interface.interface_number,
+                        alternate_setting: interface.alternate_setting,
+                        interface_class: interface.interface_class,
+                        interface_subclass: interface.interface_subclass,
+                        interface_protocol: interface.interface_protocol,
+                    })
+            })
+    }
+
+    // Interface that receives a class request addressed to an interface or endpoint.
+    // Falls back to any known configuration when the active one can't be determined.
+    pub fn request_interface(&self, recipient: UsbControlRecipient, w_index: u16) -> Option<&InterfaceDescriptor> {
+        let configurations: Vec<&ConfigurationDescriptor> = match self.active_configuration() {
+            Some(configuration) => vec![configuration],
+            None => self.device.configurations.iter().collect(),
+        };
+        let active = |interface: &InterfaceDescriptor| {
+            interface.alternate_setting == self.alternate_settings.get(&interface.interface_number).copied().unwrap_or(0)
+        };
+
+        let interfaces = configurations.into_iter().flat_map(|configuration| configuration.interfaces.iter());
+        match recipient {
+            UsbControlRecipient::Interface => {
+                let number = (w_index & 0xFF) as u8;
+                interfaces.filter(|interface| interface.interface_number == number)
+                    .max_by_key(|interface| active(interface))
+            },
+            UsbControlRecipient::Endpoint => {
+                let address = (w_index & 0x8F) as u8;
+                interfaces.filter(|interface| interface.endpoints.iter().any(|ep| ep.endpoint_address == address))
+                    .max_by_key(|interface| active(interface))
+            },
+            _ => None,
+        }
+    }
+
+    // Endpoints (number, is IN) of every alternate setting of an interface, e.g. the ones
+    // SET_INTERFACE resets. None when the interface isn't in the known descriptors.
+    pub fn interface_endpoints(&self, interface_number: u8) -> Option<Vec<(u8, bool)>> {
+        let configurations: Vec<&ConfigurationDescriptor> = match self.active_configuration() {
+            Some(configuration) => vec![configuration],
+            None => self.device.configurations.iter().collect(),
+        };
+        let interfaces: Vec<&InterfaceDescriptor> = configurations.into_iter()
+            .flat_map(|configuration| configuration.interfaces.iter())
+            .filter(|interface| interface.interface_number == interface_number)
+            .collect();
+        if interfaces.is_empty() {
+            return None;
+        }
+        Some(interfaces.iter()
+            .flat_map(|interface| interface.endpoints.iter())
+            .map(|ep| (ep.endpoint_number, ep.direction == UsbEndpointDirection::In))
+            .collect())
+    }
+
+    // Store a GET_DESCRIPTOR response and rebuild the parsed device from everything collected
+    fn add_descriptor(&mut self, descriptor_type: u8, index: u8, language_id: u16, data: &[u8]) {
+        // A longer response (e.g. the full configuration after the 9-byte header) replaces a shorter one
+        let entry = self.descriptor_data.entry((descriptor_type, index, language_id)).or_default();
+        if data.len() >= entry.len() {
+            *entry = data.to_vec();
+        }
+
+        // Device descriptor first so strings can be linked, strings last in index order
+        let mut ordered: Vec<&(u8, u8, u16)> = self.descriptor_data.keys().collect();
+        ordered.sort_by_key(|(descriptor_type, index, language_id)| {
+            let rank = match UsbDescriptorType::from(*descriptor_type) {
+                UsbDescriptorType::Device => 0,
+                UsbDescriptorType::String => 2,
+                _ => 1,
+            };
+            (rank, *descriptor_type, *index, *language_id)
+        });
+
+        let mut device = UsbDevice::new();
+        for key in ordered {
+            let (descriptor_type, index, language_id) = *key;
+            if let Some(data) = self.descriptor_data.get(key) {
+                if UsbDescriptorType::from(descriptor_type) == UsbDescriptorType::String {
+                    device.add_string_descriptor(data, index, language_id);
+                } else {
+                    let _ = device.parse_descriptors(data);
+                }
+            }
+        }
+        for (feature, data) in &self.ms_os_data {
+            device.ms_os.add_response(*feature, data);
+        }
+        if let Some(webusb) = device.webusb.as_mut() {
+            for (index, data) in &self.webusb_url_data {
+                webusb.add_url_response(*index, data);
+            }
+        }
+        self.device = device;
+    }
+
+    // Store a WebUSB GET_URL response
+    fn add_webusb_url(&mut self, index: u8, data: &[u8]) {
+        self.webusb_url_data.insert(index, data.to_vec());
+        if let Some(webusb) = self.device.webusb.as_mut() {
+            webusb.urls.remove(&index);
+            webusb.add_url_response(index, data);
+        }
+    }
+
+    // Store a Microsoft OS feature descriptor response, fetched with the device's vendor code
+    fn add_ms_os_response(&mut self, feature: MsOsFeature, data: &[u8]) {
+        let entry = self.ms_os_data.entry(feature).or_default();
+        if data.len() >= entry.len() {
+            *entry = data.to_vec();
+        }
+        self.device.ms_os.clear_responses();
+        for (feature, data) in &self.ms_os_data {
+            self.device.ms_os.add_response(*feature, data);
+        }
+    }
+}
+
+impl fmt::Display for BusDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address {}", self.address)?;
+        if let Some(name) = self.name() {
+            write!(f, ": {}", name)?;
+        }
+        if let Some(speed) = self.speed {
+            write!(f, " ({:?} speed)", speed)?;
+        }
+        match self.configuration {
+            Some(0) => write!(f, ", unconfigured")?,
+            Some(configuration) => write!(f, ", configuration {}", configuration)?,
+            None => {},
+        }
+        if let Some(landing_page) = self.device.webusb.as_ref().and_then(|webusb| webusb.landing_page()) {
+            write!(f, ", {}", landing_page)?;
+        }
+        if !self.is_attached() {
+            write!(f, ", detached")?;
+        }
+        Ok(())
+    }
+}
+
+// Changes to the bus model worth showing in the traffic list
+#[derive(Debug, Clone, PartialEq)]
+pub enum BusModelEvent {
+    Attached { address: u8 },
+    AddressAssigned { from: u8, to: u8 },
+    DescriptorCollected { address: u8, descriptor_type: u8, index: u8 },
+    MsOsDescriptorCollected { address: u8, feature: MsOsFeature },
+    WebUsbUrlCollected { address: u8, index: u8 },
+    Configured { address: u8, configuration: u8 },
+    AlternateSetting { address: u8, interface: u8, alternate_setting: u8 },
+    Detached { address: u8 },
+}
+
+impl fmt::Display for BusModelEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusModelEvent::Attached { address } => write!(f, "Device seen at address {}", address),
+            BusModelEvent::AddressAssigned { from, to } => write!(f, "Device moved from address {} to {}", from, to),
+            BusModelEvent::DescriptorCollected { address, descriptor_type, index } => {
+                write!(f, "Address {}: {} descriptor {} collected", address,
+                       UsbDescriptorType::from(*descriptor_type).name(), index)
+            },
+            BusModelEvent::MsOsDescriptorCollected { address, feature } => {
+                write!(f, "Address {}: Microsoft OS {} descriptor collected", address, feature)
+            },
+            BusModelEvent::WebUsbUrlCollected { address, index } => {
+                write!(f, "Address {}: WebUSB URL {} collected", address, index)
+            },
+            BusModelEvent::Configured { address, configuration } => {
+                write!(f, "Address {}: configuration {} active", address, configuration)
+            },
+            BusModelEvent::AlternateSetting { address, interface, alternate_setting } => {
+                write!(f, "Address {}: interface {} alternate setting {}", address, interface, alternate_setting)
+            },
+            BusModelEvent::Detached { address } => write!(f, "Device at address {} detached", address),
+        }
+    }
+}
+
+// All devices seen on the bus
+#[derive(Debug, Clone, Default)]
+pub struct BusModel {
+    devices: BTreeMap<u8, BusDevice>,
+    pending_address: Option<(u8, u8)>, // SET_ADDRESS seen, waiting for traffic on the new address
+    negotiated_speed: Option<Speed>,   // From the most recent reset handshake
+}
+
+impl BusModel {
+    pub fn new() -> Self {
+        BusModel::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.devices.clear();
+        self.pending_address = None;
+        self.negotiated_speed = None;
+    }
+
+    pub fn device(&self, address: u8) -> Option<&BusDevice> {
+        self.devices.get(&address)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &BusDevice> {
+        self.devices.values()
+    }
+
+    // Resolve a transaction's endpoint against its device's descriptors
+    pub fn resolve_endpoint(&self, transaction: &UsbTransaction) -> Option<UsbEndpointInfo> {
+        if transaction.endpoint == 0 {
+            return None;
+        }
+        let is_in = match transaction.direction() {
+            UsbDirection::DeviceToHost => Some(true),
+            UsbDirection::HostToDevice => Some(false),
+            UsbDirection::Unknown => None,
+        };
+        self.devices.get(&transaction.device_address)?.resolve_endpoint(transaction.endpoint, is_in)
+    }
+
+    // Interface class context for a class request sent to an interface or endpoint
+    pub fn class_context(&self, address: u8, setup: &UsbSetupPacket) -> Option<ClassContext> {
+        if setup.request_type != UsbControlRequestType::Class {
+            return None;
+        }
+        let device = self.devices.get(&address)?;
+        device.request_interface(setup.recipient, setup.wIndex).map(ClassContext::from_interface)
+    }
+
+    #[allow(dead_code)]
+    pub fn attached_devices(&self) -> impl Iterator<Item = &BusDevice> {
+        self.devices.values().filter(|device| device.is_attached())
+    }
+
+    fn ensure_device(&mut self, address: u8, timestamp: f64, events: &mut Vec<BusModelEvent>) {
+        let attached = self.devices.get(&address).map(|device| device.is_attached()).unwrap_or(false);
+        if !attached {
+            let mut device = BusDevice::new(address, timestamp);
+            device.speed = self.negotiated_speed;
+            self.devices.insert(address, device);
+            events.push(BusModelEvent::Attached { address });
+        }
+    }
+
+    // Move the device from its old address once SET_ADDRESS has taken effect
+    fn commit_address(&mut self, events: &mut Vec<BusModelEvent>) {
+        if let Some((from, to)) = self.pending_address.take() {
+            if let Some(mut device) = self.devices.remove(&from) {
+                device.previous_addresses.push(from);
+                device.address = to;
+                self.devices.insert(to, device);
+                events.push(BusModelEvent::AddressAssigned { from, to });
+            }
+        }
+    }
+
+    // Update the model from one transaction
+    pub fn process_transaction(&mut self, transaction: &UsbTransaction) -> Vec<BusModelEvent> {
+        let mut events = Vec::new();
+        let address = transaction.device_address;
+
+        match self.pending_address {
+            // First traffic on the new address: SET_ADDRESS has completed
+            Some((_, to)) if address == to => self.commit_address(&mut events),
+            // A new request on the old address means the status stage has completed
+            Some((from, _)) if address == from && transaction.setup_packet.is_some() => {
+                self.commit_address(&mut events)
+            },
+            // Status stage of SET_ADDRESS still runs on the old address
+            Some((from, _)) if address == from => return events,
+            _ => {},
+        }
+
+        self.ensure_device(address, transaction.timestamp, &mut events);
+
+        let setup = match &transaction.setup_packet {
+            Some(setup) => setup,
+            None => return events,
+        };
+
+        // A stalled request didn't change anything
+        if transaction.status_packet.as_ref().map(|status| status.status) == Some(UsbTransferStatus::STALL) {
+            return events;
+        }
+
+        self.store_descriptor(transaction, &mut events);
+        self.store_vendor_descriptor(transaction, &mut events);
+
+        match (setup.request_type, setup.standard_request) {
+            (UsbControlRequestType::Standard, Some(UsbStandardRequest::SetAddress)) => {
+                let new_address = (setup.wValue & 0x7F) as u8;
+                if new_address != address {
+                    self.pending_address = Some((address, new_address));
+                }
+            },
+            (UsbControlRequestType::Standard, Some(UsbStandardRequest::SetConfiguration)) => {
+                let configuration = (setup.wValue & 0xFF) as u8;
+                if let Some(device) = self.devices.get_mut(&address) {
+                    device.configuration = Some(configuration);
+                    device.alternate_settings.clear();
+                    events.push(BusModelEvent::Configured { address, configuration });
+                }
+            },
+            (UsbControlRequestType::Standard, Some(UsbStandardRequest::SetInterface)) => {
+                let interface = (setup.wIndex & 0xFF) as u8;
+                let alternate_setting = (setup.wValue & 0xFF) as u8;
+                if let Some(device) = self.devices.get_mut(&address) {
+                    device.alternate_settings.insert(interface, alternate_setting);
+                    events.push(BusModelEvent::AlternateSetting { address, interface, alternate_setting });
+                }
+            },
+            _ => {},
+        }
+
+        events
+    }
+
+    // Update the model from a control transfer whose data stage was reassembled from
+    // several transactions. Its setup stage has already been through process_transaction.
+    pub fn process_control_transfer(&mut self, transaction: &UsbTransaction) -> Vec<BusModelEvent> {
+        let mut events = Vec::new();
+        self.store_descriptor(transaction, &mut events);
+        self.store_vendor_descriptor(transaction, &mut events);
+        events
+    }
+
+    // Store the response to a vendor request for a Microsoft OS feature descriptor or a WebUSB URL
+    fn store_vendor_descriptor(&mut self, transaction: &UsbTransaction, events: &mut Vec<BusModelEvent>) {
+        let (setup, data) = match (&transaction.setup_packet, &transaction.data_packet) {
+            (Some(setup), Some(data)) if !data.get_data().is_empty() => (setup, data.get_data()),
+            _ => return,
+        };
+        let address = transaction.device_address;
+        if let Some(device) = self.devices.get_mut(&address) {
+            if let Some(feature) = device.device.ms_os.feature_for_request(setup) {
+                device.add_ms_os_response(feature, data);
+                events.push(BusModelEvent::MsOsDescriptorCollected { address, feature });
+            }
+            let url_index = device.device.webusb.as_ref().and_then(|webusb| webusb.platform.url_request_index(setup));
+            if let Some(index) = url_index {
+                device.add_webusb_url(index, data);
+                events.push(BusModelEvent::WebUsbUrlCollected { address, index });
+            }
+        }
+    }
+
+    // Store the response to a device's standard GET_DESCRIPTOR request or to the hub's
+    // class GET_DESCRIPTOR. Interface-recipient descriptors (HID report and physical
+    // descriptors) aren't framed as bLength/bDescriptorType records and are left out.
+    fn store_descriptor(&mut self, transaction: &UsbTransaction, events: &mut Vec<BusModelEvent>) {
+        let setup = match &transaction.setup_packet {
+            Some(setup) => setup,
+            None => return,
+        };
+        let data = match &transaction.data_packet {
+            Some(data) if !data.get_data().is_empty() => data.get_data(),
+            _ => return,
+        };
+
+        if setup.recipient != UsbControlRecipient::Device {
+            return;
+        }
+        let descriptor_type = (setup.wValue >> 8) as u8;
+        // HID class descriptors belong to an interface even when a host asks the device for them
+        let hid = matches!(UsbDescriptorType::from(descriptor_type),
+                           UsbDescriptorType::Hid | UsbDescriptorType::Report | UsbDescriptorType::PhysicalDescriptor);
+        let standard = setup.request_type == UsbControlRequestType::Standard
+            && setup.standard_request == Some(UsbStandardRequest::GetDescriptor)
+            && !hid;
+        let hub = setup.request_type == UsbControlRequestType::Class
+            && setup.bRequest == UsbStandardRequest::GetDescriptor as u8
+            && matches!(UsbDescriptorType::from(descriptor_type), UsbDescriptorType::Hub | UsbDescriptorType::SuperSpeedHub);
+        if !standard && !hub {
+            return;
+        }
+
+        let address = transaction.device_address;
+        let index = (setup.wValue & 0xFF) as u8;
+        // wIndex carries the LANGID for strings; other standard descriptors leave it zero
+        let language_id = if descriptor_type == UsbDescriptorType::String.get_value() { setup.wIndex } else { 0 };
+        if let Some(device) = self.devices.get_mut(&address) {
+            device.add_descriptor(descriptor_type, index, language_id, data);
+            events.push(BusModelEvent::DescriptorCollected { address, descriptor_type, index });
+        }
+    }
+
+    // Update the model from a bus event (attach/detach, reset and speed negotiation)
+    pub fn process_bus_event(&mut self, event: &BusEvent) -> Vec<BusModelEvent> {
+        let mut events = Vec::new();
+
+        match &event.kind {
+            BusEventKind::SpeedNegotiated(speed) => {
+                self.negotiated_speed = Some(*speed);
+            },
+            BusEventKind::DeviceAttached(speed) => {
+                if speed.is_some() {
+                    self.negotiated_speed = *speed;
+                }
+                // A newly attached device starts at the default address
+                if let Some(device) = self.devices.get_mut(&DEFAULT_ADDRESS) {
+                    device.detached_at.get_or_insert(event.timestamp);
+                }
+                self.ensure_device(DEFAULT_ADDRESS, event.timestamp, &mut events);
+            },
+            BusEventKind::BusReset => {
+                // Reset returns devices to the default state; they get configured again
+                self.pending_address = None;
+                for device in self.devices.values_mut() {
+                    device.configuration = None;
+                    device.alternate_settings.clear();
+                }
+            },
+            BusEventKind::DeviceDetached | BusEventKind::VbusDisconnected => {
+                self.pending_address = None;
+                for device in self.devices.values_mut() {
+                    if device.detached_at.is_none() {
+                        device.detached_at = Some(event.timestamp);
+                        events.push(BusModelEvent::Detached { address: device.address });
+                    }
+                }
+            },
+            _ => {},
+        }
+
+        events
+    }
+}
@@ -0,0 +1,387 @@
+// Bus-state events: reset, high-speed chirp, suspend/resume and VBUS changes
+// The Cynthion analyzer reports these beside captured packets as event records
+// starting with a 0xFF marker byte. Line-state changes let us recognise a reset
+// by its long SE0 and a resume or remote wakeup by its K state (USB 2.0 section 7.1.7).
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::decoder::Speed;
+
+// Marker byte that starts an analyzer event record: [0xFF, code] or [0xFF, code, ts_lo, ts_hi]
+pub const EVENT_MARKER: u8 = 0xFF;
+
+// The 4-byte form carries the analyzer's 16-bit microsecond counter, which wraps every 65.536 ms
+const EVENT_TICK_SECONDS: f64 = 0.000001;
+const EVENT_TICK_WRAP_SECONDS: f64 = 65536.0 * EVENT_TICK_SECONDS;
+
+// Analyzer event codes
+pub const EVENT_SPEED_DETECT_HIGH: u8 = 0x10;
+pub const EVENT_SPEED_DETECT_FULL: u8 = 0x11;
+pub const EVENT_SPEED_DETECT_LOW: u8 = 0x12;
+pub const EVENT_VBUS_CONNECTED: u8 = 0x18;
+pub const EVENT_VBUS_DISCONNECTED: u8 = 0x19;
+pub const EVENT_BUS_RESET: u8 = 0x1A;
+pub const EVENT_DEVICE_CHIRP_VALID: u8 = 0x1B;
+pub const EVENT_HOST_CHIRP_VALID: u8 = 0x1C;
+pub const EVENT_SUSPEND_STARTED: u8 = 0x1D;
+pub const EVENT_SUSPEND_ENDED: u8 = 0x1E;
+pub const EVENT_LINESTATE_SE0: u8 = 0x20;
+pub const EVENT_LINESTATE_J: u8 = 0x21;
+pub const EVENT_LINESTATE_K: u8 = 0x22;
+pub const EVENT_LINESTATE_SE1: u8 = 0x23;
+
+// A downstream port recognises reset after 2.5 µs of SE0 (TDETRST)
+const RESET_MIN_SE0_SECONDS: f64 = 0.0000025;
+
+// The host drives resume K for at least 20 ms (TDRSMDN); a device
+// signals remote wakeup for 1-15 ms (TDRSMUP)
+const RESUME_MIN_K_SECONDS: f64 = 0.020;
+const REMOTE_WAKEUP_MIN_K_SECONDS: f64 = 0.001;
+
+// Line states reported by the analyzer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineState {
+    SE0,
+    J,
+    K,
+    SE1,
+}
+
+impl fmt::Display for LineState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineState::SE0 => write!(f, "SE0"),
+            LineState::J => write!(f, "J"),
+            LineState::K => write!(f, "K"),
+            LineState::SE1 => write!(f, "SE1"),
+        }
+    }
+}
+
+// A single raw event record from the capture stream
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BusEventRecord {
+    pub received: f64,      // Host receive time
+    pub code: u8,
+    pub ticks: Option<u16>, // Analyzer timestamp, only in the 4-byte form
+}
+
+impl BusEventRecord {
+    // Parse an event record, rejecting unknown event codes
+    pub fn parse(received: f64, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 2 && bytes.len() != 4 {
+            return None;
+        }
+        if bytes[0] != EVENT_MARKER || Self::code_name(bytes[1]).is_none() {
+            return None;
+        }
+
+        Some(BusEventRecord {
+            received,
+            code: bytes[1],
+            ticks: (bytes.len() == 4).then(|| u16::from_le_bytes([bytes[2], bytes[3]])),
+        })
+    }
+
+    pub fn code_name(code: u8) -> Option<&'static str> {
+        match code {
+            EVENT_SPEED_DETECT_HIGH => Some("Speed Detected: High"),
+            EVENT_SPEED_DETECT_FULL => Some("Speed Detected: Full"),
+            EVENT_SPEED_DETECT_LOW => Some("Speed Detected: Low"),
+            EVENT_VBUS_CONNECTED => Some("VBUS Connected"),
+            EVENT_VBUS_DISCONNECTED => Some("VBUS Disconnected"),
+            EVENT_BUS_RESET => Some("Bus Reset"),
+            EVENT_DEVICE_CHIRP_VALID => Some("Device Chirp K"),
+            EVENT_HOST_CHIRP_VALID => Some("Host Chirp K-J"),
+            EVENT_SUSPEND_STARTED => Some("Suspend Started"),
+            EVENT_SUSPEND_ENDED => Some("Suspend Ended"),
+            EVENT_LINESTATE_SE0 => Some("Line State SE0"),
+            EVENT_LINESTATE_J => Some("Line State J"),
+            EVENT_LINESTATE_K => Some("Line State K"),
+            EVENT_LINESTATE_SE1 => Some("Line State SE1"),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::code_name(self.code).unwrap_or("Unknown Event")
+    }
+
+    pub fn line_state(&self) -> Option<LineState> {
+        match self.code {
+            EVENT_LINESTATE_SE0 => Some(LineState::SE0),
+            EVENT_LINESTATE_J => Some(LineState::J),
+            EVENT_LINESTATE_K => Some(LineState::K),
+            EVENT_LINESTATE_SE1 => Some(LineState::SE1),
+            _ => None,
+        }
+    }
+}
+
+// Bus events surfaced in the traffic list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BusEventKind {
+    BusReset,
+    DeviceChirp,                  // Device chirp K: device is high-speed capable
+    HostChirp,                    // Host chirp K-J: host accepted high speed
+    SpeedNegotiated(Speed),       // Outcome of reset/chirp handshake
+    Suspend,
+    Resume,                       // Host-driven resume
+    RemoteWakeup,                 // Device-driven resume
+    VbusConnected,
+    VbusDisconnected,
+    DeviceAttached(Option<Speed>),
+    DeviceDetached,
+}
+
+impl fmt::Display for BusEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusEventKind::BusReset => write!(f, "Bus Reset"),
+            BusEventKind::DeviceChirp => write!(f, "Device Chirp K"),
+            BusEventKind::HostChirp => write!(f, "Host Chirp K-J"),
+            BusEventKind::SpeedNegotiated(speed) => write!(f, "Speed Negotiated: {:?}", speed),
+            BusEventKind::Suspend => write!(f, "Suspend"),
+            BusEventKind::Resume => write!(f, "Resume"),
+            BusEventKind::RemoteWakeup => write!(f, "Remote Wakeup"),
+            BusEventKind::VbusConnected => write!(f, "VBUS Connected"),
+            BusEventKind::VbusDisconnected => write!(f, "VBUS Disconnected"),
+            BusEventKind::DeviceAttached(Some(speed)) => write!(f, "Device Attached ({:?} speed)", speed),
+            BusEventKind::DeviceAttached(None) => write!(f, "Device Attached"),
+            BusEventKind::DeviceDetached => write!(f, "Device Detached"),
+        }
+    }
+}
+
+// A bus event with its start time and, once it has ended, its duration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusEvent {
+    pub timestamp: f64,
+    pub kind: BusEventKind,
+    pub duration: Option<f64>, // Seconds
+}
+
+impl BusEvent {
+    fn new(timestamp: f64, kind: BusEventKind, duration: Option<f64>) -> Self {
+        BusEvent { timestamp, kind, duration }
+    }
+}
+
+impl fmt::Display for BusEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(duration) = self.duration {
+            if duration >= 0.001 {
+                write!(f, " ({:.2} ms)", duration * 1000.0)?;
+            } else {
+                write!(f, " ({:.1} µs)", duration * 1_000_000.0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Extends the analyzer's 16-bit event counter into seconds. The host receive time only
+// decides how many whole wraps passed between two records, so receive jitter well under
+// a wrap period doesn't reach the result. Times are anchored to the receive time of the
+// first timed record so they stay comparable with host-stamped transactions.
+#[derive(Debug, Clone, Copy, Default)]
+struct EventClock {
+    last: Option<(u16, f64, f64)>, // Raw ticks, receive time and extended time of the last record
+}
+
+impl EventClock {
+    fn extend(&mut self, ticks: u16, received: f64) -> f64 {
+        let extended = match self.last {
+            Some((last_ticks, last_received, last_extended)) => {
+                let delta = ticks.wrapping_sub(last_ticks) as f64 * EVENT_TICK_SECONDS;
+                let missed = ((received - last_received - delta) / EVENT_TICK_WRAP_SECONDS).round().max(0.0);
+                last_extended + delta + missed * EVENT_TICK_WRAP_SECONDS
+            },
+            None => received,
+        };
+        self.last = Some((ticks, received, extended));
+        extended
+    }
+}
+
+// Turns raw event records into bus events, pairing starts and ends to get durations
+#[derive(Debug, Clone, Default)]
+pub struct BusEventTracker {
+    line_state: Option<(LineState, f64)>, // Current line state and when it started
+    reset_start: Option<f64>,             // Reset reported by the analyzer, not yet ended
+    suspend_start: Option<f64>,
+    vbus_connected_at: Option<f64>,
+    attached_at: Option<f64>,
+    clock: EventClock,
+    untimed: bool,        // Saw a 2-byte record, so durations are unavailable
+    chirp_expected: bool, // An SE0-detected reset just ended in K, the device chirp
+}
+
+impl BusEventTracker {
+    pub fn new() -> Self {
+        BusEventTracker::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = BusEventTracker::default();
+    }
+
+    #[allow(dead_code)]
+    pub fn is_attached(&self) -> bool {
+        self.attached_at.is_some()
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspend_start.is_some()
+    }
+
+    // Time between two events, or None when the capture has no analyzer timestamps
+    fn span(&self, start: f64, end: f64) -> Option<f64> {
+        (!self.untimed).then_some(end - start)
+    }
+
+    // Feed one event record. Returns the bus events it completes.
+    pub fn process_record(&mut self, record: &BusEventRecord) -> Vec<BusEvent> {
+        let now = match record.ticks {
+            Some(ticks) => self.clock.extend(ticks, record.received),
+            None => {
+                self.untimed = true;
+                record.received
+            },
+        };
+        let mut events = Vec::new();
+
+        if let Some(state) = record.line_state() {
+            self.process_line_state(state, now, &mut events);
+            return events;
+        }
+
+        match record.code {
+            EVENT_BUS_RESET => {
+                // A reset also ends suspend
+                if let Some(start) = self.suspend_start.take() {
+                    events.push(BusEvent::new(start, BusEventKind::Suspend, self.span(start, now)));
+                }
+                self.reset_start.get_or_insert(now);
+            },
+            EVENT_DEVICE_CHIRP_VALID => {
+                events.push(BusEvent::new(now, BusEventKind::DeviceChirp, None));
+            },
+            EVENT_HOST_CHIRP_VALID => {
+                events.push(BusEvent::new(now, BusEventKind::HostChirp, None));
+            },
+            EVENT_SPEED_DETECT_HIGH | EVENT_SPEED_DETECT_FULL | EVENT_SPEED_DETECT_LOW => {
+                let speed = match record.code {
+                    EVENT_SPEED_DETECT_HIGH => Speed::High,
+                    EVENT_SPEED_DETECT_FULL => Speed::Full,
+                    _ => Speed::Low,
+                };
+                self.finish_reset(now, &mut events);
+                events.push(BusEvent::new(now, BusEventKind::SpeedNegotiated(speed), None));
+
+                if self.attached_at.is_none() {
+                    self.attached_at = Some(now);
+                    events.push(BusEvent::new(now, BusEventKind::DeviceAttached(Some(speed)), None));
+                }
+            },
+            EVENT_SUSPEND_STARTED => {
+                self.suspend_start.get_or_insert(now);
+            },
+            EVENT_SUSPEND_ENDED => {
+                if let Some(start) = self.suspend_start.take() {
+                    events.push(BusEvent::new(start, BusEventKind::Suspend, self.span(start, now)));
+                }
+            },
+            EVENT_VBUS_CONNECTED => {
+                self.vbus_connected_at = Some(now);
+                events.push(BusEvent::new(now, BusEventKind::VbusConnected, None));
+            },
+            EVENT_VBUS_DISCONNECTED => {
+                // Session length is reported as the duration of the disconnect entry
+                let session = self.vbus_connected_at.take().and_then(|start| self.span(start, now));
+                events.push(BusEvent::new(now, BusEventKind::VbusDisconnected, session));
+                self.detach(now, &mut events);
+            },
+            _ => {},
+        }
+
+        events
+    }
+
+    fn process_line_state(&mut self, state: LineState, now: f64, events: &mut Vec<BusEvent>) {
+        let previous = self.line_state.replace((state, now));
+        let (previous_state, since) = match previous {
+            Some(previous) if previous.0 != state => previous,
+            // Repeated reports of the same state don't end anything
+            Some(previous) => {
+                self.line_state = Some(previous);
+                return;
+            },
+            None => return,
+        };
+        let duration = self.span(since, now);
+
+        match previous_state {
+            LineState::SE0 => {
+                if self.reset_start.is_some() {
+                    // SE0 ended after a reset the analyzer already reported; a following K
+                    // is the device chirp, so the reset ends with the speed detection event
+                    if state == LineState::J {
+                        self.finish_reset(now, events);
+                    }
+                } else if let (Some(duration), Some(_)) = (duration, self.attached_at) {
+                    // Long SE0 seen without a reset event
+                    if duration >= RESET_MIN_SE0_SECONDS {
+                        events.push(BusEvent::new(since, BusEventKind::BusReset, Some(duration)));
+                        self.chirp_expected = state == LineState::K;
+                    }
+                } else if self.attached_at.is_none() && state == LineState::J {
+                    // Pull-up appeared on an idle bus
+                    self.attached_at = Some(now);
+                    events.push(BusEvent::new(now, BusEventKind::DeviceAttached(None), None));
+                }
+            },
+            LineState::K if self.reset_start.is_none() => {
+                if std::mem::take(&mut self.chirp_expected) {
+                    events.push(BusEvent::new(since, BusEventKind::DeviceChirp, duration));
+                    return;
+                }
+                // Only a suspended bus can be resumed or woken up; a K anywhere else is signalling
+                let start = match self.suspend_start.take() {
+                    Some(start) => start,
+                    None => return,
+                };
+                events.push(BusEvent::new(start, BusEventKind::Suspend, self.span(start, since)));
+                match duration {
+                    Some(duration) if duration >= RESUME_MIN_K_SECONDS => {
+                        events.push(BusEvent::new(since, BusEventKind::Resume, Some(duration)));
+                    },
+                    Some(duration) if duration >= REMOTE_WAKEUP_MIN_K_SECONDS => {
+                        events.push(BusEvent::new(since, BusEventKind::RemoteWakeup, Some(duration)));
+                    },
+                    // Without timestamps a K after suspend can only be reported as a resume
+                    None => {
+                        events.push(BusEvent::new(since, BusEventKind::Resume, None));
+                    },
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn finish_reset(&mut self, now: f64, events: &mut Vec<BusEvent>) {
+        if let Some(start) = self.reset_start.take() {
+            events.push(BusEvent::new(start, BusEventKind::BusReset, self.span(start, now)));
+        }
+    }
+
+    fn detach(&mut self, now: f64, events: &mut Vec<BusEvent>) {
+        if let Some(start) = self.attached_at.take() {
+            events.push(BusEvent::new(now, BusEventKind::DeviceDetached, self.span(start, now)));
+        }
+        self.reset_start = None;
+        self.suspend_start = None;
+    }
+}
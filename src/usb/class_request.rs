@@ -0,0 +1,429 @@
+// Class-specific request decoding
+// Class requests directed at an interface or endpoint are only meaningful once the
+// recipient's interface class is known. The bus model supplies the class from the
+// device's configuration descriptor; this table gives bRequest, wValue and wIndex their
+// class meaning for HID, Audio (UAC1/UAC2), Mass Storage, CDC, Printer and DFU.
+
+use std::collections::HashMap;
+use super::mitm_traffic::{UsbControlRecipient, UsbDirection, UsbSetupPacket};
+use super::descriptors::InterfaceDescriptor;
+use super::request_fields::{describe_endpoint, field, flag, RequestField};
+use super::UsbDeviceClass;
+
+// Class-specific interface descriptor type
+const CS_INTERFACE: u8 = 0x24;
+
+// Audio subclasses and protocols
+const AUDIO_CONTROL_SUBCLASS: u8 = 0x01;
+const AUDIO_STREAMING_SUBCLASS: u8 = 0x02;
+const UAC2_PROTOCOL: u8 = 0x20;
+
+// DFU is an application-specific class with subclass 1
+const DFU_SUBCLASS: u8 = 0x01;
+
+// The recipient interface of a class request, as resolved by the bus model
+#[derive(Debug, Clone)]
+pub struct ClassContext {
+    pub interface_number: u8,
+    pub class: UsbDeviceClass,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub audio_entities: HashMap<u8, u8>, // Audio Control entity ID -> descriptor subtype
+}
+
+// A class request decoded with its class's meaning
+#[derive(Debug, Clone)]
+pub struct ClassRequest {
+    pub description: String,
+    pub fields: Vec<RequestField>, // Class and request name first, then the decoded wValue/wIndex
+}
+
+impl ClassContext {
+    pub fn from_interface(interface: &InterfaceDescriptor) -> Self {
+        let audio_entities = if interface.interface_class == UsbDeviceClass::Audio
+            && interface.interface_subclass == AUDIO_CONTROL_SUBCLASS {
+            audio_entities(&interface.class_specific)
+        } else {
+            HashMap::new()
+        };
+        ClassContext {
+            interface_number: interface.interface_number,
+            class: interface.interface_class,
+            subclass: interface.interface_subclass,
+            protocol: interface.interface_protocol,
+            audio_entities,
+        }
+    }
+}
+
+// Collect the Audio Control entities (units, terminals and clocks) from an interface's
+// class-specific descriptors, keyed by entity ID
+fn audio_entities(descriptors: &[Vec<u8>]) -> HashMap<u8, u8> {
+    descriptors.iter()
+        // Subtype 0x01 is the class-specific header; every later subtype has its ID at byte 3
+        .filter(|descriptor| descriptor.len() >= 4 && descriptor[1] == CS_INTERFACE && descriptor[2] > 0x01)
+        .map(|descriptor| (descriptor[3], descriptor[2]))
+        .collect()
+}
+
+// Decode a class request for the interface class that receives it
+pub fn decode_class_request(context: &ClassContext, setup: &UsbSetupPacket) -> Option<ClassRequest> {
+    match context.class {
+        UsbDeviceClass::HumanInterfaceDevice => decode_hid(setup),
+        UsbDeviceClass::Audio => decode_audio(context, setup),
+        UsbDeviceClass::MassStorage => decode_mass_storage(setup),
+        UsbDeviceClass::Communications => decode_cdc(setup),
+        UsbDeviceClass::Printer => decode_printer(context, setup),
+        UsbDeviceClass::ApplicationSpecific if context.subclass == DFU_SUBCLASS => decode_dfu(setup),
+        _ => None,
+    }
+}
+
+// Decode the data stage of a class request
+pub fn decode_class_response(context: &ClassContext, setup: &UsbSetupPacket, data: &[u8]) -> Vec<RequestField> {
+    let mut fields = Vec::new();
+
+    match (context.class, setup.bRequest) {
+        (UsbDeviceClass::HumanInterfaceDevice, 0x02) if !data.is_empty() => {
+            fields.push(field("Idle Rate", describe_idle_rate(data[0])));
+        },
+        (UsbDeviceClass::HumanInterfaceDevice, 0x03) if !data.is_empty() => {
+            fields.push(field("Protocol", describe_hid_protocol(data[0] as u16)));
+        },
+        (UsbDeviceClass::MassStorage, 0xFE) if !data.is_empty() => {
+            fields.push(field("Max LUN", format!("{} ({} logical unit{})", data[0], data[0] as u16 + 1,
+                                                 if data[0] == 0 { "" } else { "s" })));
+        },
+        (UsbDeviceClass::Communications, 0x20) | (UsbDeviceClass::Communications, 0x21) if data.len() >= 7 => {
+            fields.extend(describe_line_coding(data));
+        },
+        (UsbDeviceClass::Printer, 0x00) if data.len() >= 2 => {
+            // IEEE 1284 device ID: big-endian length followed by the ID string
+            let text = String::from_utf8_lossy(&data[2..]).to_string();
+            fields.push(field("Device ID", text));
+        },
+        (UsbDeviceClass::Printer, 0x01) if !data.is_empty() => {
+            fields.push(field("Paper Empty", flag(data[0] & 0x20 != 0)));
+            fields.push(field("Selected", flag(data[0] & 0x10 != 0)));
+            fields.push(field("Not Error", flag(data[0] & 0x08 != 0)));
+        },
+        (UsbDeviceClass::ApplicationSpecific, 0x03) if context.subclass == DFU_SUBCLASS && data.len() >= 6 => {
+            fields.push(field("Status", format!("0x{:02X}", data[0])));
+            fields.push(field("Poll Timeout", format!("{} ms", u32::from_le_bytes([data[1], data[2], data[3], 0]))));
+            fields.push(field("State", dfu_state_name(data[4]).to_string()));
+        },
+        (UsbDeviceClass::ApplicationSpecific, 0x05) if context.subclass == DFU_SUBCLASS && !data.is_empty() => {
+            fields.push(field("State", dfu_state_name(data[0]).to_string()));
+        },
+        _ => {},
+    }
+
+    fields
+}
+
+fn request(setup: &UsbSetupPacket, class_name: &str, name: &str, description: String,
+           fields: Vec<RequestField>) -> Option<ClassRequest> {
+    let mut all_fields = vec![
+        field("Class", class_name.to_string()),
+        field("Request", format!("{} (0x{:02X})", name, setup.bRequest)),
+    ];
+    all_fields.extend(fields);
+    Some(ClassRequest { description, fields: all_fields })
+}
+
+// HID 1.11 section 7.2
+fn decode_hid(setup: &UsbSetupPacket) -> Option<ClassRequest> {
+    let report_id = (setup.wValue & 0xFF) as u8;
+    let interface = setup.wIndex & 0xFF;
+    let report_type = match setup.wValue >> 8 {
+        1 => "Input",
+        2 => "Output",
+        3 => "Feature",
+        _ => "Reserved",
+    };
+
+    match setup.bRequest {
+        0x01 | 0x09 => {
+            let name = if setup.bRequest == 0x01 { "GET_REPORT" } else { "SET_REPORT" };
+            request(setup, "HID", name,
+                    format!("HID {} ({} Report, ID {}, Interface {})", name, report_type, report_id, interface),
+                    vec![field("Report Type", report_type.to_string()),
+                         field("Report ID", format!("{}", report_id)),
+                         field("Report Length", format!("{} bytes", setup.wLength))])
+        },
+        0x02 => request(setup, "HID", "GET_IDLE", format!("HID GET_IDLE (Report ID {}, Interface {})", report_id, interface),
+                        vec![field("Report ID", format!("{}", report_id))]),
+        0x0A => {
+            let rate = describe_idle_rate((setup.wValue >> 8) as u8);
+            request(setup, "HID", "SET_IDLE", format!("HID SET_IDLE ({}, Report ID {}, Interface {})", rate, report_id, interface),
+                    vec![field("Idle Rate", rate), field("Report ID", format!("{}", report_id))])
+        },
+        0x03 => request(setup, "HID", "GET_PROTOCOL", format!("HID GET_PROTOCOL (Interface {})", interface), Vec::new()),
+        0x0B => {
+            let protocol = describe_hid_protocol(setup.wValue);
+            request(setup, "HID", "SET_PROTOCOL", format!("HID SET_PROTOCOL ({}, Interface {})", protocol, interface),
+                    vec![field("Protocol", protocol)])
+        },
+        _ => None,
+    }
+}
+
+fn describe_idle_rate(rate: u8) -> String {
+    if rate == 0 {
+        "Indefinite".to_string()
+    } else {
+        format!("{} ms", rate as u32 * 4)
+    }
+}
+
+fn describe_hid_protocol(protocol: u16) -> String {
+    match protocol {
+        0 => "Boot Protocol".to_string(),
+        1 => "Report Protocol".to_string(),
+        _ => format!("Unknown Protocol {}", protocol),
+    }
+}
+
+// Audio Device Class 1.0 section 5.2 and 2.0 section 5.2
+fn decode_audio(context: &ClassContext, setup: &UsbSetupPacket) -> Option<ClassRequest> {
+    let uac2 = context.protocol == UAC2_PROTOCOL;
+    let get = setup.direction == UsbDirection::DeviceToHost;
+
+    let name = if uac2 {
+        match (setup.bRequest, get) {
+            (0x01, true) => "GET_CUR",
+            (0x01, false) => "SET_CUR",
+            (0x02, true) => "GET_RANGE",
+            (0x02, false) => "SET_RANGE",
+            (0x03, true) => "GET_MEM",
+            (0x03, false) => "SET_MEM",
+            _ => return None,
+        }
+    } else {
+        match setup.bRequest {
+            0x01 => "SET_CUR",
+            0x02 => "SET_MIN",
+            0x03 => "SET_MAX",
+            0x04 => "SET_RES",
+            0x05 => "SET_MEM",
+            0x81 => "GET_CUR",
+            0x82 => "GET_MIN",
+            0x83 => "GET_MAX",
+            0x84 => "GET_RES",
+            0x85 => "GET_MEM",
+            0xFF => "GET_STAT",
+            _ => return None,
+        }
+    };
+
+    let selector = (setup.wValue >> 8) as u8;
+    let channel = (setup.wValue & 0xFF) as u8;
+    let mut fields = Vec::new();
+
+    let (target, control) = if setup.recipient == UsbControlRecipient::Endpoint {
+        (describe_endpoint(setup.wIndex), audio_endpoint_control(selector, uac2))
+    } else {
+        let entity_id = (setup.wIndex >> 8) as u8;
+        if entity_id == 0 {
+            let control = if context.subclass == AUDIO_STREAMING_SUBCLASS && uac2 {
+                audio_streaming_control(selector)
+            } else {
+                None
+            };
+            (format!("Interface {}", context.interface_number), control)
+        } else {
+            let subtype = context.audio_entities.get(&entity_id).copied();
+            let entity = subtype.map(|subtype| audio_entity_name(subtype, uac2)).unwrap_or("Entity");
+            fields.push(field("Entity", format!("{} {}", entity, entity_id)));
+            (format!("{} {}", entity, entity_id), subtype.and_then(|subtype| audio_entity_control(subtype, selector, uac2)))
+        }
+    };
+
+    let control = control.map(|name| name.to_string()).unwrap_or_else(|| format!("Control 0x{:02X}", selector));
+    fields.push(field("Control Selector", format!("{} (0x{:02X})", control, selector)));
+    fields.push(field("Channel", if channel == 0 { "Master (0)".to_string() } else { format!("{}", channel) }));
+    fields.push(field("Parameter Length", format!("{} bytes", setup.wLength)));
+
+    request(setup, "Audio", name, format!("Audio {} {} ({}, Channel {})", name, control, target, channel), fields)
+}
+
+fn audio_entity_name(subtype: u8, uac2: bool) -> &'static str {
+    match (subtype, uac2) {
+        (0x02, _) => "Input Terminal",
+        (0x03, _) => "Output Terminal",
+        (0x04, _) => "Mixer Unit",
+        (0x05, _) => "Selector Unit",
+        (0x06, _) => "Feature Unit",
+        (0x07, false) => "Processing Unit",
+        (0x08, false) => "Extension Unit",
+        (0x07, true) => "Effect Unit",
+        (0x08, true) => "Processing Unit",
+        (0x09, true) => "Extension Unit",
+        (0x0A, true) => "Clock Source",
+        (0x0B, true) => "Clock Selector",
+        (0x0C, true) => "Clock Multiplier",
+        (0x0D, true) => "Sample Rate Converter",
+        _ => "Entity",
+    }
+}
+
+fn audio_entity_control(subtype: u8, selector: u8, uac2: bool) -> Option<&'static str> {
+    Some(match (subtype, uac2, selector) {
+        // Feature unit controls are shared by both versions apart from the UAC2 additions
+        (0x06, _, 0x01) => "MUTE_CONTROL",
+        (0x06, _, 0x02) => "VOLUME_CONTROL",
+        (0x06, _, 0x03) => "BASS_CONTROL",
+        (0x06, _, 0x04) => "MID_CONTROL",
+        (0x06, _, 0x05) => "TREBLE_CONTROL",
+        (0x06, _, 0x06) => "GRAPHIC_EQUALIZER_CONTROL",
+        (0x06, _, 0x07) => "AUTOMATIC_GAIN_CONTROL",
+        (0x06, _, 0x08) => "DELAY_CONTROL",
+        (0x06, _, 0x09) => "BASS_BOOST_CONTROL",
+        (0x06, _, 0x0A) => "LOUDNESS_CONTROL",
+        (0x06, true, 0x0B) => "INPUT_GAIN_CONTROL",
+        (0x06, true, 0x0C) => "INPUT_GAIN_PAD_CONTROL",
+        (0x06, true, 0x0D) => "PHASE_INVERTER_CONTROL",
+        (0x05, _, 0x01) => "SELECTOR_CONTROL",
+        (0x04, true, 0x01) => "MIXER_CONTROL",
+        (0x02, false, 0x01) | (0x03, false, 0x01) => "COPY_PROTECT_CONTROL",
+        (0x02 | 0x03, true, 0x01) => "COPY_PROTECT_CONTROL",
+        (0x02 | 0x03, true, 0x02) => "CONNECTOR_CONTROL",
+        (0x02 | 0x03, true, 0x03) => "OVERLOAD_CONTROL",
+        (0x02, true, 0x04) => "CLUSTER_CONTROL",
+        (0x0A, true, 0x01) => "SAM_FREQ_CONTROL",
+        (0x0A, true, 0x02) => "CLOCK_VALID_CONTROL",
+        (0x0B, true, 0x01) => "CLOCK_SELECTOR_CONTROL",
+        (0x0C, true, 0x01) => "NUMERATOR_CONTROL",
+        (0x0C, true, 0x02) => "DENOMINATOR_CONTROL",
+        _ => return None,
+    })
+}
+
+fn audio_endpoint_control(selector: u8, uac2: bool) -> Option<&'static str> {
+    Some(match (uac2, selector) {
+        (false, 0x01) => "SAMPLING_FREQ_CONTROL",
+        (false, 0x02) => "PITCH_CONTROL",
+        (true, 0x01) => "PITCH_CONTROL",
+        (true, 0x02) => "DATA_OVERRUN_CONTROL",
+        (true, 0x03) => "DATA_UNDERRUN_CONTROL",
+        _ => return None,
+    })
+}
+
+fn audio_streaming_control(selector: u8) -> Option<&'static str> {
+    Some(match selector {
+        0x01 => "ACT_ALT_SETTING_CONTROL",
+        0x02 => "VAL_ALT_SETTINGS_CONTROL",
+        0x03 => "AUDIO_DATA_FORMAT_CONTROL",
+        _ => return None,
+    })
+}
+
+// Mass Storage Bulk-Only Transport section 3 and CBI
+fn decode_mass_storage(setup: &UsbSetupPacket) -> Option<ClassRequest> {
+    let interface = setup.wIndex & 0xFF;
+    let name = match setup.bRequest {
+        0xFF => "Bulk-Only Mass Storage Reset",
+        0xFE => "Get Max LUN",
+        0xFD => "Put Requests",
+        0xFC => "Get Requests",
+        0x00 => "Accept Device-Specific Command",
+        _ => return None,
+    };
+    request(setup, "Mass Storage", name, format!("MSC {} (Interface {})", name, interface), Vec::new())
+}
+
+// CDC 1.2 PSTN section 6.3
+fn decode_cdc(setup: &UsbSetupPacket) -> Option<ClassRequest> {
+    let interface = setup.wIndex & 0xFF;
+    let (name, fields) = match setup.bRequest {
+        0x00 => ("SEND_ENCAPSULATED_COMMAND", Vec::new()),
+        0x01 => ("GET_ENCAPSULATED_RESPONSE", Vec::new()),
+        0x20 => ("SET_LINE_CODING", Vec::new()),
+        0x21 => ("GET_LINE_CODING", Vec::new()),
+        0x22 => ("SET_CONTROL_LINE_STATE", vec![
+            field("DTR", flag(setup.wValue & 0x01 != 0)),
+            field("RTS", flag(setup.wValue & 0x02 != 0)),
+        ]),
+        0x23 => ("SEND_BREAK", vec![field("Duration", match setup.wValue {
+            0xFFFF => "Until cleared".to_string(),
+            0 => "Stop".to_string(),
+            duration => format!("{} ms", duration),
+        })]),
+        _ => return None,
+    };
+    request(setup, "CDC", name, format!("CDC {} (Interface {})", name, interface), fields)
+}
+
+// Decode the 7-byte CDC line coding structure
+pub fn describe_line_coding(data: &[u8]) -> Vec<RequestField> {
+    if data.len() < 7 {
+        return Vec::new();
+    }
+    let baud = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let stop_bits = match data[4] {
+        0 => "1",
+        1 => "1.5",
+        2 => "2",
+        _ => "Reserved",
+    };
+    let parity = match data[5] {
+        0 => "None",
+        1 => "Odd",
+        2 => "Even",
+        3 => "Mark",
+        4 => "Space",
+        _ => "Reserved",
+    };
+    vec![
+        field("Baud Rate", format!("{}", baud)),
+        field("Stop Bits", stop_bits.to_string()),
+        field("Parity", parity.to_string()),
+        field("Data Bits", format!("{}", data[6])),
+    ]
+}
+
+// Printer class 1.1 section 4.2
+fn decode_printer(context: &ClassContext, setup: &UsbSetupPacket) -> Option<ClassRequest> {
+    let name = match setup.bRequest {
+        0x00 => "GET_DEVICE_ID",
+        0x01 => "GET_PORT_STATUS",
+        0x02 => "SOFT_RESET",
+        _ => return None,
+    };
+    request(setup, "Printer", name, format!("Printer {} (Interface {})", name, context.interface_number), Vec::new())
+}
+
+// DFU 1.1 section 3
+fn decode_dfu(setup: &UsbSetupPacket) -> Option<ClassRequest> {
+    let (name, fields) = match setup.bRequest {
+        0x00 => ("DFU_DETACH", vec![field("Timeout", format!("{} ms", setup.wValue))]),
+        0x01 => ("DFU_DNLOAD", vec![field("Block", format!("{}", setup.wValue)),
+                                    field("Length", format!("{} bytes", setup.wLength))]),
+        0x02 => ("DFU_UPLOAD", vec![field("Block", format!("{}", setup.wValue)),
+                                    field("Length", format!("{} bytes", setup.wLength))]),
+        0x03 => ("DFU_GETSTATUS", Vec::new()),
+        0x04 => ("DFU_CLRSTATUS", Vec::new()),
+        0x05 => ("DFU_GETSTATE", Vec::new()),
+        0x06 => ("DFU_ABORT", Vec::new()),
+        _ => return None,
+    };
+    request(setup, "DFU", name, format!("{} (Interface {})", name, setup.wIndex & 0xFF), fields)
+}
+
+fn dfu_state_name(state: u8) -> &'static str {
+    match state {
+        0 => "appIDLE",
+        1 => "appDETACH",
+        2 => "dfuIDLE",
+        3 => "dfuDNLOAD-SYNC",
+        4 => "dfuDNBUSY",
+        5 => "dfuDNLOAD-IDLE",
+        6 => "dfuMANIFEST-SYNC",
+        7 => "dfuMANIFEST",
+        8 => "dfuMANIFEST-WAIT-RESET",
+        9 => "dfuUPLOAD-IDLE",
+        10 => "dfuERROR",
+        _ => "Unknown",
+    }
+}
@@ -0,0 +1,312 @@
+// Control transfer data stage reassembly
+// A control transfer's data stage can span several DATA packets on endpoint 0. It ends
+// when wLength bytes have been moved or when a packet shorter than bMaxPacketSize0
+// arrives (USB 2.0 sections 5.5.3 and 8.5.3). Captures that report each stage as its
+// own transaction are stitched back together here so the descriptor parsers see the
+// whole response.
+
+use std::collections::HashMap;
+use std::fmt;
+use super::mitm_traffic::{
+    UsbDataPacket,
+    UsbDirection,
+    UsbSetupPacket,
+    UsbTransaction,
+    UsbTransferStatus,
+};
+use super::pid::UsbPid;
+
+// Valid bMaxPacketSize0 values, used to spot short packets before the device descriptor is known
+const EP0_PACKET_SIZES: [usize; 5] = [8, 16, 32, 64, 512];
+
+// Why the data stage ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataStageEnd {
+    Length,      // wLength bytes were transferred
+    ShortPacket, // A packet shorter than the maximum packet size ended the stage early
+    StatusStage, // The status stage started before either of the above
+    Interrupted, // A new SETUP arrived on the endpoint before the transfer finished
+}
+
+impl fmt::Display for DataStageEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataStageEnd::Length => write!(f, "complete"),
+            DataStageEnd::ShortPacket => write!(f, "short packet"),
+            DataStageEnd::StatusStage => write!(f, "ended by status stage"),
+            DataStageEnd::Interrupted => write!(f, "interrupted by SETUP"),
+        }
+    }
+}
+
+// A control transfer with its data stage rebuilt from the individual DATA packets
+#[derive(Debug, Clone)]
+pub struct ControlTransfer {
+    pub setup_transaction: UsbTransaction,
+    pub data: Vec<u8>,
+    pub packet_count: u32,
+    pub end: DataStageEnd,
+}
+
+impl ControlTransfer {
+    // The assembler only starts transfers from transactions that carry a setup packet
+    pub fn setup(&self) -> Option<&UsbSetupPacket> {
+        self.setup_transaction.setup_packet.as_ref()
+    }
+
+    fn requested_length(&self) -> usize {
+        self.setup().map(|setup| setup.wLength as usize).unwrap_or(0)
+    }
+
+    // Check the reassembled length against wLength. A device may return less than
+    // requested (ended by a short packet) but never more; the host always sends exactly wLength.
+    pub fn length_error(&self) -> Option<String> {
+        let setup = self.setup()?;
+        let length = self.data.len();
+        let requested = setup.wLength as usize;
+
+        if length > requested {
+            return Some(format!("{} bytes transferred, more than wLength {}", length, requested));
+        }
+        match (setup.direction, self.end) {
+            (_, DataStageEnd::Interrupted) => {
+                Some(format!("Data stage interrupted after {} of {} bytes", length, requested))
+            },
+            (UsbDirection::HostToDevice, _) if length < requested => {
+                Some(format!("Host sent {} of {} bytes", length, requested))
+            },
+            (UsbDirection::DeviceToHost, DataStageEnd::StatusStage) => {
+                Some(format!("Status stage after {} of {} bytes without a short packet", length, requested))
+            },
+            _ => None,
+        }
+    }
+
+    // The transfer as a single transaction carrying the whole data stage
+    pub fn to_transaction(&self) -> UsbTransaction {
+        let mut transaction = self.setup_transaction.clone();
+        let direction = self.setup().map(|setup| setup.direction).unwrap_or(UsbDirection::Unknown);
+        transaction.data_packet = Some(UsbDataPacket::new(self.data.clone(), direction, transaction.endpoint));
+        transaction.fields.insert("Data Stage".to_string(), self.data_stage_summary());
+        transaction
+    }
+
+    pub fn data_stage_summary(&self) -> String {
+        format!("{} of {} bytes in {} packet{} ({})",
+                self.data.len(), self.requested_length(), self.packet_count,
+                if self.packet_count == 1 { "" } else { "s" }, self.end)
+    }
+}
+
+impl fmt::Display for ControlTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = self.setup().map(|setup| setup.request_description.as_str()).unwrap_or("Control transfer");
+        write!(f, "{}: {}", description, self.data_stage_summary())
+    }
+}
+
+// Data stage in progress on one control endpoint
+#[derive(Debug, Clone)]
+struct PendingTransfer {
+    transfer: ControlTransfer,
+    last_pid: Option<UsbPid>, // Repeated PIDs are retransmissions, not new data
+    max_packet_size: Option<usize>,
+}
+
+impl PendingTransfer {
+    fn finish(mut self, end: DataStageEnd) -> ControlTransfer {
+        self.transfer.end = end;
+        self.transfer
+    }
+}
+
+// Follows control endpoints and rebuilds data stages split over several transactions
+#[derive(Debug, Clone, Default)]
+pub struct ControlTransferAssembler {
+    pending: HashMap<(u8, u8), PendingTransfer>, // Keyed by device address and endpoint
+}
+
+impl ControlTransferAssembler {
+    pub fn new() -> Self {
+        ControlTransferAssembler::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    // Feed one transaction. max_packet_size is the device's bMaxPacketSize0 when known.
+    // Returns a transfer once its data stage is complete. Transactions that already
+    // carry their data stage, and requests without one, are left as they are.
+    pub fn process_transaction(&mut self, transaction: &UsbTransaction, max_packet_size: Option<u8>) -> Option<ControlTransfer> {
+        let key = (transaction.device_address, transaction.endpoint);
+
+        if let Some(setup) = &transaction.setup_packet {
+            let interrupted = self.pending.remove(&key).map(|pending| pending.finish(DataStageEnd::Interrupted));
+
+            if setup.wLength > 0 && transaction.data_packet.is_none() {
+                self.pending.insert(key, PendingTransfer {
+                    transfer: ControlTransfer {
+                        setup_transaction: transaction.clone(),
+                        data: Vec::new(),
+                        packet_count: 0,
+                        end: DataStageEnd::Length,
+                    },
+                    last_pid: None,
+                    max_packet_size: max_packet_size.map(|size| size as usize),
+                });
+            }
+            return interrupted;
+        }
+
+        let pending = self.pending.get_mut(&key)?;
+
+        // NAKed, stalled and unanswered transactions don't move data
+        let accepted = matches!(transaction.status_packet.as_ref().map(|status| status.status),
+                                Some(UsbTransferStatus::ACK) | Some(UsbTransferStatus::NYET));
+        if !accepted {
+            if transaction.status_packet.as_ref().map(|status| status.status) == Some(UsbTransferStatus::STALL) {
+                // The request was refused; nothing more will arrive for it
+                self.pending.remove(&key);
+            }
+            return None;
+        }
+
+        let stage_direction = pending.transfer.setup()?.direction;
+        if transaction.direction() != stage_direction {
+            let pending = self.pending.remove(&key)?;
+            return Some(pending.finish(DataStageEnd::StatusStage));
+        }
+
+        let data = transaction.data_packet.as_ref().map(|data| data.get_data()).unwrap_or(&[]);
+
+        if transaction.data_pid.is_some() && transaction.data_pid == pending.last_pid {
+            return None;
+        }
+        pending.last_pid = transaction.data_pid;
+        pending.transfer.data.extend_from_slice(data);
+        pending.transfer.packet_count += 1;
+
+        // Without the device descriptor, the first packet's size stands in for bMaxPacketSize0
+        let max_packet_size = *pending.max_packet_size.get_or_insert(data.len());
+        let short = data.len() < max_packet_size || !EP0_PACKET_SIZES.contains(&data.len());
+
+        if pending.transfer.data.len() >= pending.transfer.requested_length() {
+            let pending = self.pending.remove(&key)?;
+            Some(pending.finish(DataStageEnd::Length))
+        } else if short {
+            let pending = self.pending.remove(&key)?;
+            Some(pending.finish(DataStageEnd::ShortPacket))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usb::mitm_traffic::{UsbStatusPacket, UsbTransferType};
+
+    // GET_DESCRIPTOR(CONFIGURATION) asking for wLength bytes
+    fn setup(id: u64, w_length: u16) -> UsbTransaction {
+        let length = w_length.to_le_bytes();
+        let mut transaction = UsbTransaction::new(id, id as f64);
+        transaction.transfer_type = UsbTransferType::Control;
+        transaction.device_address = 1;
+        transaction.setup_packet = UsbSetupPacket::new(&[0x80, 0x06, 0x00, 0x02, 0x00, 0x00, length[0], length[1]]);
+        transaction
+    }
+
+    fn data(id: u64, direction: UsbDirection, bytes: &[u8], pid: UsbPid, status: UsbTransferStatus) -> UsbTransaction {
+        let mut transaction = UsbTransaction::new(id, id as f64);
+        transaction.transfer_type = UsbTransferType::Control;
+        transaction.device_address = 1;
+        transaction.data_packet = Some(UsbDataPacket::new(bytes.to_vec(), direction, 0));
+        transaction.data_pid = Some(pid);
+        transaction.status_packet = Some(UsbStatusPacket { status, endpoint: 0 });
+        transaction
+    }
+
+    fn data_in(id: u64, bytes: &[u8], pid: UsbPid) -> UsbTransaction {
+        data(id, UsbDirection::DeviceToHost, bytes, pid, UsbTransferStatus::ACK)
+    }
+
+    #[test]
+    fn ends_data_stage_at_wlength() {
+        let mut assembler = ControlTransferAssembler::new();
+        assert!(assembler.process_transaction(&setup(1, 16), Some(8)).is_none());
+        assert!(assembler.process_transaction(&data_in(2, &[0x09; 8], UsbPid::Data1), Some(8)).is_none());
+        let transfer = assembler.process_transaction(&data_in(3, &[0x02; 8], UsbPid::Data0), Some(8)).unwrap();
+
+        assert_eq!(transfer.end, DataStageEnd::Length);
+        assert_eq!(transfer.data.len(), 16);
+        assert_eq!(transfer.packet_count, 2);
+        assert_eq!(transfer.length_error(), None);
+        assert_eq!(transfer.data_stage_summary(), "16 of 16 bytes in 2 packets (complete)");
+        assert_eq!(transfer.to_transaction().data_packet.unwrap().get_data().len(), 16);
+    }
+
+    #[test]
+    fn ends_data_stage_at_short_packet() {
+        let mut assembler = ControlTransferAssembler::new();
+        assembler.process_transaction(&setup(1, 255), None);
+        // Without bMaxPacketSize0 the first packet's size stands in for it
+        assert!(assembler.process_transaction(&data_in(2, &[0; 64], UsbPid::Data1), None).is_none());
+        let transfer = assembler.process_transaction(&data_in(3, &[0; 10], UsbPid::Data0), None).unwrap();
+
+        assert_eq!(transfer.end, DataStageEnd::ShortPacket);
+        assert_eq!(transfer.data.len(), 74);
+        assert_eq!(transfer.length_error(), None);
+    }
+
+    #[test]
+    fn skips_naks_and_retransmissions() {
+        let mut assembler = ControlTransferAssembler::new();
+        assembler.process_transaction(&setup(1, 16), Some(8));
+        let nak = data(2, UsbDirection::DeviceToHost, &[], UsbPid::Data1, UsbTransferStatus::NAK);
+        assert!(assembler.process_transaction(&nak, Some(8)).is_none());
+        assembler.process_transaction(&data_in(3, &[1; 8], UsbPid::Data1), Some(8));
+        // The host missed the handshake and the device sent the same DATA1 again
+        assert!(assembler.process_transaction(&data_in(4, &[1; 8], UsbPid::Data1), Some(8)).is_none());
+        let transfer = assembler.process_transaction(&data_in(5, &[2; 8], UsbPid::Data0), Some(8)).unwrap();
+
+        assert_eq!(transfer.packet_count, 2);
+        assert_eq!(transfer.data[8..], [2; 8]);
+    }
+
+    #[test]
+    fn stall_drops_the_transfer() {
+        let mut assembler = ControlTransferAssembler::new();
+        assembler.process_transaction(&setup(1, 16), Some(8));
+        let stall = data(2, UsbDirection::DeviceToHost, &[], UsbPid::Data1, UsbTransferStatus::STALL);
+        assert!(assembler.process_transaction(&stall, Some(8)).is_none());
+        assert!(assembler.process_transaction(&data_in(3, &[0; 8], UsbPid::Data1), Some(8)).is_none());
+    }
+
+    #[test]
+    fn flags_status_stage_and_interrupted_transfers() {
+        let mut assembler = ControlTransferAssembler::new();
+        assembler.process_transaction(&setup(1, 32), Some(8));
+        assembler.process_transaction(&data_in(2, &[0; 8], UsbPid::Data1), Some(8));
+        let status = data(3, UsbDirection::HostToDevice, &[], UsbPid::Data1, UsbTransferStatus::ACK);
+        let transfer = assembler.process_transaction(&status, Some(8)).unwrap();
+        assert_eq!(transfer.end, DataStageEnd::StatusStage);
+        assert_eq!(transfer.length_error(), Some("Status stage after 8 of 32 bytes without a short packet".to_string()));
+
+        assembler.process_transaction(&setup(4, 32), Some(8));
+        assembler.process_transaction(&data_in(5, &[0; 8], UsbPid::Data1), Some(8));
+        let transfer = assembler.process_transaction(&setup(6, 18), Some(8)).unwrap();
+        assert_eq!(transfer.end, DataStageEnd::Interrupted);
+        assert_eq!(transfer.length_error(), Some("Data stage interrupted after 8 of 32 bytes".to_string()));
+    }
+
+    #[test]
+    fn leaves_complete_transactions_alone() {
+        let mut assembler = ControlTransferAssembler::new();
+        let mut complete = setup(1, 18);
+        complete.data_packet = Some(UsbDataPacket::new(vec![0; 18], UsbDirection::DeviceToHost, 0));
+        assert!(assembler.process_transaction(&complete, Some(64)).is_none());
+        assert!(assembler.process_transaction(&data_in(2, &[0; 18], UsbPid::Data1), Some(64)).is_none());
+    }
+}
@@ -3,6 +3,9 @@ use log::{debug, info, warn};
 use crate::usb::descriptors::UsbDevice;
 use crate::usb::UsbDescriptorType;
 use crate::usb::packet_types::recognize_packet_type;
+use crate::usb::bus_event::BusEventRecord;
+use crate::usb::pid::{UsbPacket, UsbPid};
+use crate::usb::split::SplitToken;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
@@ -36,6 +39,30 @@ impl std::fmt::Display for Speed {
     }
 }
 
+// Environment variable selecting the capture format
+pub const CAPTURE_FORMAT_VAR: &str = "USBFLY_CAPTURE_FORMAT";
+
+// How the capture source frames the buffers it delivers. A raw packet or bus event record
+// can't be told from transaction data by its bytes alone, so the source has to say.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum CaptureFormat {
+    // Capture stream buffers, turned into transactions by the connection
+    #[default]
+    Transactions,
+    // One raw USB packet (PID first) or bus event record per buffer
+    Packets,
+}
+
+impl CaptureFormat {
+    // The format named by $USBFLY_CAPTURE_FORMAT ("packets" or "transactions")
+    pub fn from_env() -> Self {
+        match std::env::var(CAPTURE_FORMAT_VAR) {
+            Ok(value) if value.eq_ignore_ascii_case("packets") => CaptureFormat::Packets,
+            _ => CaptureFormat::Transactions,
+        }
+    }
+}
+
 // Data structure to hold decoded USB data for display in the UI
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DecodedUSBData {
@@ -49,6 +76,9 @@ pub struct DecodedUSBData {
     pub details: Option<String>,
     // USB descriptors parsed from the data
     pub descriptors: Vec<crate::usb::descriptors::USBDescriptor>,
+    // The same descriptors nested by configuration, function, interface and endpoint
+    #[serde(default)]
+    pub descriptor_tree: Vec<crate::usb::descriptors::DescriptorNode>,
 }
 
 // The decoder module is responsible for decoding USB protocol data
@@ -67,6 +97,9 @@ pub struct UsbDecoder {
     // USB Speed setting for parsing packets correctly
     pub current_speed: Speed,
     
+    // What each buffer handed to decode() holds
+    pub capture_format: CaptureFormat,
+    
     // State flags
     initialized: bool,
 }
@@ -79,6 +112,7 @@ impl UsbDecoder {
             vendor_names: Self::load_vendor_database(),
             device_names: Self::load_device_database(),
             current_speed: Speed::High, // Default to High speed instead of Auto
+            capture_format: CaptureFormat::default(),
             initialized: false,
         }
     }
@@ -214,6 +248,20 @@ impl UsbDecoder {
         decoder_clone.set_speed(self.current_speed);
         info!("✓ Using speed {:?} for USB packet decoding - crucial for proper protocol interpretation", self.current_speed);
         
+        if self.capture_format == CaptureFormat::Packets {
+            // Bus-state event records (reset, chirp, suspend, VBUS) from the analyzer
+            if let Some(record) = BusEventRecord::parse(0.0, data) {
+                debug!("Decoding bus event: {}", record.name());
+                return Some(self.decode_bus_event_record(&record));
+            }
+            
+            // Raw USB packets (PID byte first, CRC verified)
+            if let Some(packet) = UsbPacket::parse(0.0, data) {
+                debug!("Decoding raw USB packet: {}", packet.pid);
+                return Some(self.decode_usb_packet(&packet));
+            }
+        }
+        
         // Enhanced packet detection with complete coverage of all known packet types
         // This ensures we can recognize and decode all possible packets from Cynthion
         let is_standard_packet = data.len() > 2 && (
@@ -310,6 +358,7 @@ impl UsbDecoder {
         
         // Extract descriptors from processed data
         let descriptors = decoder_clone.device.get_all_descriptors();
+        let descriptor_tree = decoder_clone.device.descriptor_tree();
         if descriptors.is_empty() {
             debug!("No USB descriptors found in data, trying alternative decoding");
             
@@ -334,6 +383,7 @@ impl UsbDecoder {
             fields: HashMap::new(),
             details: None,
             descriptors,
+            descriptor_tree,
         };
         
         // Add the speed information used for decoding
@@ -489,6 +539,7 @@ impl UsbDecoder {
             fields: HashMap::new(),
             details: None,
             descriptors: Vec::new(),
+            descriptor_tree: Vec::new(),
         };
         
         // Add the speed information used for decoding - essential for understanding packet structure
@@ -654,6 +705,85 @@ impl UsbDecoder {
         decoded
     }
     
+    // Decode an analyzer bus-state event record (reset, chirp, suspend, line state, VBUS)
+    pub fn decode_bus_event_record(&self, record: &BusEventRecord) -> DecodedUSBData {
+        let mut decoded = DecodedUSBData {
+            data_type: "Bus Event".to_string(),
+            description: record.name().to_string(),
+            fields: HashMap::new(),
+            details: None,
+            descriptors: Vec::new(),
+            descriptor_tree: Vec::new(),
+        };
+        
+        decoded.fields.insert("Event Code".to_string(), format!("0x{:02X}", record.code));
+        decoded.fields.insert("Analyzer Timestamp".to_string(), match record.ticks {
+            Some(ticks) => format!("{} µs (16-bit)", ticks),
+            None => "Not reported, durations unavailable".to_string(),
+        });
+        if let Some(state) = record.line_state() {
+            decoded.fields.insert("Line State".to_string(), format!("{}", state));
+        }
+        
+        decoded
+    }
+    
+    // Decode a single raw USB packet (token, data, handshake or special PID)
+    pub fn decode_usb_packet(&self, packet: &UsbPacket) -> DecodedUSBData {
+        let mut decoded = DecodedUSBData {
+            data_type: "USB Packet".to_string(),
+            description: packet.summary(),
+            fields: HashMap::new(),
+            details: None,
+            descriptors: Vec::new(),
+            descriptor_tree: Vec::new(),
+        };
+        
+        decoded.fields.insert("PID".to_string(), format!("{} (0x{:02X})", packet.pid, packet.pid.get_value()));
+        decoded.fields.insert("USB Speed".to_string(), format!("{:?}", self.current_speed));
+        
+        match packet.pid {
+            UsbPid::Split => {
+                if let Ok(split) = SplitToken::parse(&packet.bytes) {
+                    decoded.description = format!("{}", split);
+                    decoded.fields.insert("Split Type".to_string(), split.name().to_string());
+                    decoded.fields.insert("Hub Address".to_string(), format!("{}", split.hub_address));
+                    decoded.fields.insert("Hub Port".to_string(), format!("{}", split.port));
+                    decoded.fields.insert("S Bit".to_string(), format!("{}", split.start_bit as u8));
+                    decoded.fields.insert("E Bit".to_string(), format!("{}", split.end_bit as u8));
+                    decoded.fields.insert("Endpoint Type".to_string(), format!("{}", split.endpoint_type));
+                }
+            },
+            UsbPid::Sof => {
+                if let Some(frame) = packet.frame_number() {
+                    decoded.fields.insert("Frame Number".to_string(), format!("{}", frame));
+                }
+            },
+            UsbPid::Out | UsbPid::In | UsbPid::Setup | UsbPid::Ping => {
+                if let Some(address) = packet.token_address() {
+                    decoded.fields.insert("Address".to_string(), format!("{}", address));
+                }
+                if let Some(endpoint) = packet.token_endpoint() {
+                    decoded.fields.insert("Endpoint".to_string(), format!("{}", endpoint));
+                }
+            },
+            UsbPid::Data0 | UsbPid::Data1 | UsbPid::Data2 | UsbPid::MData => {
+                let payload = packet.payload();
+                decoded.fields.insert("Data Length".to_string(), format!("{} bytes", payload.len()));
+                if !payload.is_empty() {
+                    let hex_dump = payload.iter()
+                        .map(|b| format!("{:02X}", b))
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    decoded.details = Some(format!("Hex: {}", hex_dump));
+                }
+            },
+            _ => {}
+        }
+        
+        decoded
+    }
+    
     // Decode raw USB data into structured format for display
     pub fn decode_raw_data(&self, data: &[u8]) -> DecodedUSBData {
         let mut decoded = DecodedUSBData {
@@ -662,6 +792,7 @@ impl UsbDecoder {
             fields: HashMap::new(),
             details: None,
             descriptors: Vec::new(),
+            descriptor_tree: Vec::new(),
         };
         
         // Add the speed information used for decoding - essential context for raw packet data
@@ -807,6 +938,7 @@ impl UsbDecoder {
             fields: HashMap::new(),
             details: None,
             descriptors: Vec::new(),
+            descriptor_tree: Vec::new(),
         };
         
         // Add the speed information used for decoding
@@ -966,6 +1098,7 @@ impl UsbDecoder {
             fields: HashMap::new(),
             details: None,
             descriptors: Vec::new(),
+            descriptor_tree: Vec::new(),
         };
         
         // Convert data to string to try to extract JSON-like field data
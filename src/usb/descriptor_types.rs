@@ -15,12 +15,17 @@ pub enum UsbDescriptorType {
     DeviceQualifier = 0x06,
     OtherSpeedConfiguration = 0x07,
     InterfacePower = 0x08,
+    Otg = 0x09,
+    Debug = 0x0A,
+    InterfaceAssociation = 0x0B,
+    Eusb2IsochronousEndpointCompanion = 0x12,
     
     // Class-specific descriptor types
     Hid = 0x21,
     Report = 0x22,
     PhysicalDescriptor = 0x23,
     Hub = 0x29,
+    SuperSpeedHub = 0x2A,
     
     // USB 3.0 descriptor types
     SuperspeedUsbEndpointCompanion = 0x30,
@@ -45,10 +50,15 @@ impl From<u8> for UsbDescriptorType {
             0x06 => UsbDescriptorType::DeviceQualifier,
             0x07 => UsbDescriptorType::OtherSpeedConfiguration,
             0x08 => UsbDescriptorType::InterfacePower,
+            0x09 => UsbDescriptorType::Otg,
+            0x0A => UsbDescriptorType::Debug,
+            0x0B => UsbDescriptorType::InterfaceAssociation,
+            0x12 => UsbDescriptorType::Eusb2IsochronousEndpointCompanion,
             0x21 => UsbDescriptorType::Hid,
             0x22 => UsbDescriptorType::Report,
             0x23 => UsbDescriptorType::PhysicalDescriptor,
             0x29 => UsbDescriptorType::Hub,
+            0x2A => UsbDescriptorType::SuperSpeedHub,
             0x0F => UsbDescriptorType::Bos,
             0x10 => UsbDescriptorType::DeviceCapability,
             0x30 => UsbDescriptorType::SuperspeedUsbEndpointCompanion,
@@ -76,10 +86,15 @@ impl UsbDescriptorType {
             UsbDescriptorType::DeviceQualifier => 0x06,
             UsbDescriptorType::OtherSpeedConfiguration => 0x07,
             UsbDescriptorType::InterfacePower => 0x08,
+            UsbDescriptorType::Otg => 0x09,
+            UsbDescriptorType::Debug => 0x0A,
+            UsbDescriptorType::InterfaceAssociation => 0x0B,
+            UsbDescriptorType::Eusb2IsochronousEndpointCompanion => 0x12,
             UsbDescriptorType::Hid => 0x21,
             UsbDescriptorType::Report => 0x22,
             UsbDescriptorType::PhysicalDescriptor => 0x23,
             UsbDescriptorType::Hub => 0x29,
+            UsbDescriptorType::SuperSpeedHub => 0x2A,
             UsbDescriptorType::Bos => 0x0F,
             UsbDescriptorType::DeviceCapability => 0x10,
             UsbDescriptorType::SuperspeedUsbEndpointCompanion => 0x30,
@@ -98,10 +113,15 @@ impl UsbDescriptorType {
             UsbDescriptorType::DeviceQualifier => "Device Qualifier Descriptor",
             UsbDescriptorType::OtherSpeedConfiguration => "Other Speed Configuration Descriptor",
             UsbDescriptorType::InterfacePower => "Interface Power Descriptor",
+            UsbDescriptorType::Otg => "OTG Descriptor",
+            UsbDescriptorType::Debug => "Debug Descriptor",
+            UsbDescriptorType::InterfaceAssociation => "Interface Association Descriptor",
+            UsbDescriptorType::Eusb2IsochronousEndpointCompanion => "eUSB2 Isochronous Endpoint Companion Descriptor",
             UsbDescriptorType::Hid => "HID Descriptor",
             UsbDescriptorType::Report => "Report Descriptor",
             UsbDescriptorType::PhysicalDescriptor => "Physical Descriptor",
             UsbDescriptorType::Hub => "Hub Descriptor",
+            UsbDescriptorType::SuperSpeedHub => "SuperSpeed Hub Descriptor",
             UsbDescriptorType::Bos => "BOS Descriptor",
             UsbDescriptorType::DeviceCapability => "Device Capability Descriptor",
             UsbDescriptorType::SuperspeedUsbEndpointCompanion => "SuperSpeed USB Endpoint Companion Descriptor",
@@ -121,10 +141,15 @@ impl UsbDescriptorType {
             UsbDescriptorType::DeviceQualifier => "Describes device information for an alternate USB speed (high/full speed compatibility).",
             UsbDescriptorType::OtherSpeedConfiguration => "Describes a configuration for an alternate speed operation.",
             UsbDescriptorType::InterfacePower => "Provides information about interface power management capabilities.",
+            UsbDescriptorType::Otg => "Advertises the On-The-Go features (SRP, HNP, ADP) a dual-role device supports.",
+            UsbDescriptorType::Debug => "Names the endpoints of the device's debug port.",
+            UsbDescriptorType::InterfaceAssociation => "Groups consecutive interfaces that together form one device function.",
+            UsbDescriptorType::Eusb2IsochronousEndpointCompanion => "Extends a high-speed isochronous IN endpoint beyond three transactions per microframe.",
             UsbDescriptorType::Hid => "Describes a Human Interface Device class and its properties.",
             UsbDescriptorType::Report => "Provides detailed information about a HID device's data format.",
             UsbDescriptorType::PhysicalDescriptor => "Describes the physical aspects of a human input device.",
             UsbDescriptorType::Hub => "Describes a USB hub and its characteristics.",
+            UsbDescriptorType::SuperSpeedHub => "Describes a SuperSpeed USB hub, including its header decode latency and hub delay.",
             UsbDescriptorType::Bos => "USB 3.0 Binary Device Object Store descriptor that provides device-level capabilities.",
             UsbDescriptorType::DeviceCapability => "Describes specific device capabilities (USB 3.0).",
             UsbDescriptorType::SuperspeedUsbEndpointCompanion => "Additional information for SuperSpeed USB endpoints.",
@@ -1,5 +1,11 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use super::descriptor_types::*;
+use super::hub::HubDescriptor;
+use super::mitm_traffic::{UsbControlRequestType, UsbDirection, UsbSetupPacket};
+use super::ms_os::{self, MsOs20PlatformInfo, MsOsDescriptors};
+use super::request_fields::RequestField;
+use super::standard_request::describe_langid;
 use serde::{Deserialize, Serialize};
 
 // Main enum to represent different USB descriptor types for UI display
@@ -7,6 +13,7 @@ use serde::{Deserialize, Serialize};
 pub enum USBDescriptor {
     Device(DeviceDescriptor),
     Configuration(ConfigurationDescriptor),
+    InterfaceAssociation(InterfaceAssociationDescriptor),
     Interface(InterfaceDescriptor),
     Endpoint(EndpointDescriptor),
     String(StringDescriptor),
@@ -16,6 +23,14 @@ pub enum USBDescriptor {
     BOS(BOSDescriptor),
     DeviceCapability(DeviceCapabilityDescriptor),
     SuperSpeedEndpointCompanion(SuperSpeedEndpointCompanionDescriptor),
+    SuperSpeedPlusIsochronousEndpointCompanion(SuperSpeedPlusIsochronousEndpointCompanionDescriptor),
+    Eusb2IsochronousEndpointCompanion(Eusb2IsochronousEndpointCompanionDescriptor),
+    // Other standard descriptors
+    Otg(OtgDescriptor),
+    Debug(DebugDescriptor),
+    InterfacePower(InterfacePowerDescriptor),
+    // Hub class descriptor (USB 2.0 and SuperSpeed)
+    Hub(HubDescriptor),
     // Additional class-specific descriptors
     CDC(CDCDescriptor),
     MSC(MSCDescriptor),
@@ -35,6 +50,7 @@ impl fmt::Display for USBDescriptor {
         match self {
             USBDescriptor::Device(desc) => write!(f, "{}", desc),
             USBDescriptor::Configuration(desc) => write!(f, "{}", desc),
+            USBDescriptor::InterfaceAssociation(desc) => write!(f, "{}", desc),
             USBDescriptor::Interface(desc) => write!(f, "{}", desc),
             USBDescriptor::Endpoint(desc) => write!(f, "{}", desc),
             USBDescriptor::String(desc) => write!(f, "{}", desc),
@@ -44,6 +60,12 @@ impl fmt::Display for USBDescriptor {
             USBDescriptor::BOS(desc) => write!(f, "{}", desc),
             USBDescriptor::DeviceCapability(desc) => write!(f, "{}", desc),
             USBDescriptor::SuperSpeedEndpointCompanion(desc) => write!(f, "{}", desc),
+            USBDescriptor::SuperSpeedPlusIsochronousEndpointCompanion(desc) => write!(f, "{}", desc),
+            USBDescriptor::Eusb2IsochronousEndpointCompanion(desc) => write!(f, "{}", desc),
+            USBDescriptor::Otg(desc) => write!(f, "{}", desc),
+            USBDescriptor::Debug(desc) => write!(f, "{}", desc),
+            USBDescriptor::InterfacePower(desc) => write!(f, "{}", desc),
+            USBDescriptor::Hub(desc) => write!(f, "{}", desc),
             // Class-specific descriptors
             USBDescriptor::CDC(desc) => write!(f, "{}", desc),
             USBDescriptor::MSC(desc) => write!(f, "{}", desc),
@@ -61,6 +83,38 @@ impl fmt::Display for USBDescriptor {
     }
 }
 
+impl USBDescriptor {
+    // Short name used in descriptor lists
+    pub fn name(&self) -> String {
+        match self {
+            USBDescriptor::Device(_) => "Device Descriptor".to_string(),
+            USBDescriptor::Configuration(_) => "Configuration Descriptor".to_string(),
+            USBDescriptor::InterfaceAssociation(_) => "Interface Association Descriptor".to_string(),
+            USBDescriptor::Interface(_) => "Interface Descriptor".to_string(),
+            USBDescriptor::Endpoint(_) => "Endpoint Descriptor".to_string(),
+            USBDescriptor::String(_) => "String Descriptor".to_string(),
+            USBDescriptor::HID(_) => "HID Descriptor".to_string(),
+            USBDescriptor::DeviceQualifier(_) => "Device Qualifier Descriptor".to_string(),
+            USBDescriptor::BOS(_) => "BOS Descriptor".to_string(),
+            USBDescriptor::DeviceCapability(_) => "Device Capability Descriptor".to_string(),
+            USBDescriptor::SuperSpeedEndpointCompanion(_) => "SuperSpeed Endpoint Companion".to_string(),
+            USBDescriptor::SuperSpeedPlusIsochronousEndpointCompanion(_) => "SuperSpeedPlus Isochronous Companion".to_string(),
+            USBDescriptor::Eusb2IsochronousEndpointCompanion(_) => "eUSB2 Isochronous Companion".to_string(),
+            USBDescriptor::Otg(_) => "OTG Descriptor".to_string(),
+            USBDescriptor::Debug(_) => "Debug Descriptor".to_string(),
+            USBDescriptor::InterfacePower(_) => "Interface Power Descriptor".to_string(),
+            USBDescriptor::Hub(desc) => if desc.is_superspeed() { "SuperSpeed Hub Descriptor" } else { "Hub Descriptor" }.to_string(),
+            USBDescriptor::CDC(_) => "CDC Class Descriptor".to_string(),
+            USBDescriptor::MSC(_) => "Mass Storage Class Descriptor".to_string(),
+            USBDescriptor::AudioControl(_) => "Audio Control Descriptor".to_string(),
+            USBDescriptor::AudioStreaming(_) => "Audio Streaming Descriptor".to_string(),
+            USBDescriptor::VideoControl(_) => "Video Control Descriptor".to_string(),
+            USBDescriptor::VideoStreaming(_) => "Video Streaming Descriptor".to_string(),
+            USBDescriptor::Unknown { descriptor_type, .. } => format!("Unknown Descriptor (0x{:02X})", descriptor_type.get_value()),
+        }
+    }
+}
+
 // USB Standard Device Descriptor
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceDescriptor {
@@ -199,7 +253,9 @@ pub struct ConfigurationDescriptor {
     pub remote_wakeup: bool,
     
     // Child descriptors
-    pub interfaces: Vec<InterfaceDescriptor>,
+    pub otg: Option<OtgDescriptor>,    // Present on dual-role (On-The-Go) devices
+    pub associations: Vec<InterfaceAssociationDescriptor>, // IADs grouping interfaces into functions
+    pub interfaces: Vec<InterfaceDescriptor>, // One entry per alternate setting, in descriptor order
 }
 
 impl ConfigurationDescriptor {
@@ -236,7 +292,9 @@ impl ConfigurationDescriptor {
                     configuration_string: None,
                     self_powered,
                     remote_wakeup,
-                    interfaces: Vec::new(), // Will be filled later
+                    otg: None, // Will be filled later
+                    associations: Vec::new(),
+                    interfaces: Vec::new(),
                 })
             },
             _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
@@ -246,6 +304,29 @@ impl ConfigurationDescriptor {
     pub fn power_consumption_ma(&self) -> u16 {
         self.max_power as u16 * 2
     }
+    
+    // Interface numbers in the order they first appear
+    pub fn interface_numbers(&self) -> Vec<u8> {
+        let mut numbers: Vec<u8> = Vec::new();
+        for interface in &self.interfaces {
+            if !numbers.contains(&interface.interface_number) {
+                numbers.push(interface.interface_number);
+            }
+        }
+        numbers
+    }
+    
+    // Every alternate setting of one interface
+    pub fn alternate_settings(&self, interface_number: u8) -> Vec<&InterfaceDescriptor> {
+        self.interfaces.iter()
+            .filter(|interface| interface.interface_number == interface_number)
+            .collect()
+    }
+    
+    // The IAD whose function includes this interface, if any
+    pub fn association_for(&self, interface_number: u8) -> Option<&InterfaceAssociationDescriptor> {
+        self.associations.iter().find(|association| association.contains(interface_number))
+    }
 }
 
 impl fmt::Display for ConfigurationDescriptor {
@@ -264,9 +345,17 @@ impl fmt::Display for ConfigurationDescriptor {
         writeln!(f, "    Self Powered: {}", if self.self_powered { "Yes" } else { "No" })?;
         writeln!(f, "    Remote Wakeup: {}", if self.remote_wakeup { "Yes" } else { "No" })?;
         writeln!(f, "  bMaxPower: {}mA", self.power_consumption_ma())?;
+        if let Some(ref otg) = self.otg {
+            writeln!(f, "  OTG: {}", if otg.protocols().is_empty() { "no protocols".to_string() } else { otg.protocols().join(", ") })?;
+        }
         
-        // Display interfaces
+        // Display interfaces, each function's IAD ahead of its first interface
         for interface in &self.interfaces {
+            if interface.alternate_setting == 0 {
+                if let Some(association) = self.associations.iter().find(|a| a.first_interface == interface.interface_number) {
+                    write!(f, "{}", association)?;
+                }
+            }
             write!(f, "{}", interface)?;
         }
         
@@ -274,6 +363,74 @@ impl fmt::Display for ConfigurationDescriptor {
     }
 }
 
+// USB Interface Association Descriptor (groups interfaces into one function)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceAssociationDescriptor {
+    pub length: u8,                    // Descriptor size in bytes (8)
+    pub descriptor_type: UsbDescriptorType, // INTERFACE ASSOCIATION descriptor type (11)
+    pub first_interface: u8,           // Number of the first interface in the function
+    pub interface_count: u8,           // Number of contiguous interfaces in the function
+    pub function_class: UsbDeviceClass, // Class code of the function
+    pub function_subclass: u8,         // Subclass code of the function
+    pub function_protocol: u8,         // Protocol code of the function
+    pub function_index: u8,            // Index of string descriptor describing the function
+    
+    // Derived data
+    pub function_string: Option<String>,
+}
+
+impl InterfaceAssociationDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 8 {
+            return Err(format!("Invalid interface association descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        
+        match descriptor_type {
+            UsbDescriptorType::InterfaceAssociation => Ok(InterfaceAssociationDescriptor {
+                length: data[0],
+                descriptor_type,
+                first_interface: data[2],
+                interface_count: data[3],
+                function_class: UsbDeviceClass::from(data[4]),
+                function_subclass: data[5],
+                function_protocol: data[6],
+                function_index: data[7],
+                function_string: None,
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+    
+    pub fn contains(&self, interface_number: u8) -> bool {
+        interface_number >= self.first_interface
+            && (interface_number as u16) < self.first_interface as u16 + self.interface_count as u16
+    }
+    
+    pub fn last_interface(&self) -> u8 {
+        self.first_interface.saturating_add(self.interface_count.saturating_sub(1))
+    }
+}
+
+impl fmt::Display for InterfaceAssociationDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  Interface Association Descriptor:")?;
+        writeln!(f, "    bLength: {} bytes", self.length)?;
+        writeln!(f, "    bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
+        writeln!(f, "    bFirstInterface: {}", self.first_interface)?;
+        writeln!(f, "    bInterfaceCount: {}", self.interface_count)?;
+        writeln!(f, "    bFunctionClass: 0x{:02x} ({})", self.function_class.get_value(), self.function_class.name())?;
+        writeln!(f, "    bFunctionSubClass: 0x{:02x}", self.function_subclass)?;
+        writeln!(f, "    bFunctionProtocol: 0x{:02x}", self.function_protocol)?;
+        writeln!(f, "    iFunction: {}", self.function_index)?;
+        if let Some(ref s) = self.function_string {
+            writeln!(f, "      Function: {}", s)?;
+        }
+        Ok(())
+    }
+}
+
 // USB Interface Descriptor
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InterfaceDescriptor {
@@ -291,6 +448,7 @@ pub struct InterfaceDescriptor {
     pub interface_string: Option<String>,
     
     // Child descriptors
+    pub interface_power: Option<InterfacePowerDescriptor>,
     pub endpoints: Vec<EndpointDescriptor>,
     pub class_specific: Vec<Vec<u8>>,  // Raw class-specific descriptors
 }
@@ -325,6 +483,7 @@ impl InterfaceDescriptor {
                     interface_protocol,
                     interface_index,
                     interface_string: None,
+                    interface_power: None,
                     endpoints: Vec::new(),
                     class_specific: Vec::new(),
                 })
@@ -349,6 +508,9 @@ impl fmt::Display for InterfaceDescriptor {
         if let Some(ref s) = self.interface_string {
             writeln!(f, "      Interface: {}", s)?;
         }
+        if let Some(ref power) = self.interface_power {
+            writeln!(f, "    Interface Power: bmCapabilitiesFlags 0x{:02X}", power.capabilities_flags)?;
+        }
         
         // Display class-specific descriptors if any
         if !self.class_specific.is_empty() {
@@ -383,6 +545,12 @@ pub struct EndpointDescriptor {
     pub transfer_type: UsbEndpointType, // Transfer type (Control, Isochronous, Bulk, Interrupt)
     pub sync_type: Option<UsbIsoSyncType>, // Synchronization type (only for isochronous)
     pub usage_type: Option<UsbIsoUsageType>, // Usage type (only for isochronous)
+    
+    // Child descriptors
+    pub ss_companion: Option<SuperSpeedEndpointCompanionDescriptor>,
+    pub ssp_isochronous_companion: Option<SuperSpeedPlusIsochronousEndpointCompanionDescriptor>,
+    pub eusb2_isochronous_companion: Option<Eusb2IsochronousEndpointCompanionDescriptor>,
+    pub class_specific: Vec<Vec<u8>>,  // Raw class-specific descriptors that follow this endpoint (e.g. CS_ENDPOINT)
 }
 
 impl EndpointDescriptor {
@@ -433,6 +601,10 @@ impl EndpointDescriptor {
                     transfer_type,
                     sync_type,
                     usage_type,
+                    ss_companion: None,
+                    ssp_isochronous_companion: None,
+                    eusb2_isochronous_companion: None,
+                    class_specific: Vec::new(),
                 })
             },
             _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
@@ -471,6 +643,26 @@ impl fmt::Display for EndpointDescriptor {
                 "ms"
             })?;
         
+        // Display companion descriptors if any
+        if let Some(ref companion) = self.ss_companion {
+            writeln!(f, "      SuperSpeed Companion: bMaxBurst {}, bmAttributes 0x{:02X}, wBytesPerInterval {}",
+                companion.max_burst, companion.attributes, companion.bytes_per_interval)?;
+        }
+        if let Some(ref companion) = self.ssp_isochronous_companion {
+            writeln!(f, "      SuperSpeedPlus Isochronous Companion: dwBytesPerInterval {}", companion.bytes_per_interval)?;
+        }
+        if let Some(ref companion) = self.eusb2_isochronous_companion {
+            writeln!(f, "      eUSB2 Isochronous Companion: dwBytesPerInterval {}", companion.bytes_per_interval)?;
+        }
+        
+        // Display class-specific descriptors if any
+        if !self.class_specific.is_empty() {
+            writeln!(f, "      Class-Specific Descriptors:")?;
+            for (i, descriptor) in self.class_specific.iter().enumerate() {
+                writeln!(f, "        Descriptor {}: {} bytes", i, descriptor.len())?;
+            }
+        }
+        
         Ok(())
     }
 }
@@ -480,12 +672,18 @@ impl fmt::Display for EndpointDescriptor {
 pub struct StringDescriptor {
     pub length: u8,                    // Descriptor size in bytes
     pub descriptor_type: UsbDescriptorType, // STRING descriptor type (3)
-    pub string: String,                // Unicode string
-    pub string_index: u8,              // Index of this string descriptor
+    pub string: String,                // Unicode string (the language names for string 0)
+    pub string_index: u8,              // Index of this string descriptor (low byte of wValue)
+    #[serde(default)]
+    pub language_id: u16,              // LANGID the string was requested in (wIndex), 0 for string 0
+    #[serde(default)]
+    pub language_ids: Vec<u16>,        // String 0 only: the LANGIDs the device supports
+    #[serde(default)]
+    pub unknown_index: bool,           // Seen without its GET_DESCRIPTOR request, so string_index is meaningless
 }
 
 impl StringDescriptor {
-    pub fn parse(data: &[u8], index: u8) -> Result<Self, String> {
+    pub fn parse(data: &[u8], index: u8, language_id: u16) -> Result<Self, String> {
         if data.len() < 2 {
             return Err(format!("Invalid string descriptor length: {}", data.len()));
         }
@@ -499,9 +697,26 @@ impl StringDescriptor {
                     return Err(format!("String descriptor truncated: {} < {}", data.len(), length));
                 }
                 
+                let str_data = &data[2..length as usize];
+                
+                // String 0 holds a list of LANGIDs rather than text
+                if index == 0 {
+                    let language_ids: Vec<u16> = str_data.chunks_exact(2)
+                        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                        .collect();
+                    return Ok(StringDescriptor {
+                        length,
+                        descriptor_type,
+                        string: language_ids.iter().map(|langid| describe_langid(*langid)).collect::<Vec<_>>().join(", "),
+                        string_index: 0,
+                        language_id: 0,
+                        language_ids,
+                        unknown_index: false,
+                    });
+                }
+                
                 // Convert UTF-16LE to Rust String
                 let mut string = String::new();
-                let str_data = &data[2..length as usize];
                 
                 for i in (0..str_data.len()).step_by(2) {
                     if i + 1 < str_data.len() {
@@ -517,20 +732,51 @@ impl StringDescriptor {
                     descriptor_type,
                     string,
                     string_index: index,
+                    language_id,
+                    language_ids: Vec::new(),
+                    unknown_index: false,
                 })
             },
             _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
         }
     }
+    
+    // Parse a string seen without its GET_DESCRIPTOR request, so neither the index nor
+    // the language is known. It is decoded as text.
+    pub fn parse_unindexed(data: &[u8]) -> Result<Self, String> {
+        let mut string = Self::parse(data, 1, 0)?;
+        string.string_index = 0;
+        string.unknown_index = true;
+        Ok(string)
+    }
+    
+    // String 0 lists LANGIDs instead of holding text
+    pub fn is_language_list(&self) -> bool {
+        self.string_index == 0 && !self.unknown_index
+    }
 }
 
 impl fmt::Display for StringDescriptor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "String Descriptor:")?;
-        writeln!(f, "  Index: {}", self.string_index)?;
+        if self.unknown_index {
+            writeln!(f, "  Index: unknown")?;
+        } else {
+            writeln!(f, "  Index: {}", self.string_index)?;
+        }
         writeln!(f, "  bLength: {} bytes", self.length)?;
         writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
-        writeln!(f, "  String: \"{}\"", self.string)
+        if self.is_language_list() {
+            for (i, langid) in self.language_ids.iter().enumerate() {
+                writeln!(f, "  wLANGID[{}]: {}", i, describe_langid(*langid))?;
+            }
+            Ok(())
+        } else {
+            if self.language_id != 0 {
+                writeln!(f, "  LANGID: {}", describe_langid(self.language_id))?;
+            }
+            writeln!(f, "  String: \"{}\"", self.string)
+        }
     }
 }
 
@@ -619,6 +865,36 @@ pub struct BOSDescriptor {
     pub device_capabilities: Vec<DeviceCapabilityDescriptor>,
 }
 
+impl BOSDescriptor {
+    // Parse the 5-byte BOS header; the capabilities that follow it are added as they are parsed
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 5 {
+            return Err(format!("Invalid BOS descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        match descriptor_type {
+            UsbDescriptorType::Bos => Ok(BOSDescriptor {
+                length: data[0],
+                descriptor_type,
+                total_length: (data[3] as u16) << 8 | (data[2] as u16),
+                num_device_caps: data[4],
+                device_capabilities: Vec::new(),
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+    
+    // The Platform capability with the given UUID, if the device has one
+    #[allow(dead_code)]
+    pub fn platform_capability(&self, uuid: &str) -> Option<&DeviceCapabilityDescriptor> {
+        self.device_capabilities.iter().find(|cap| match cap.capability() {
+            DeviceCapability::Platform { uuid: platform_uuid, .. } => platform_uuid.eq_ignore_ascii_case(uuid),
+            _ => false,
+        })
+    }
+}
+
 impl fmt::Display for BOSDescriptor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Binary Device Object Store (BOS) Descriptor:")?;
@@ -636,6 +912,543 @@ impl fmt::Display for BOSDescriptor {
     }
 }
 
+// Device capability type codes (USB 3.2 table 9-14)
+pub const CAPABILITY_USB_2_0_EXTENSION: u8 = 0x02;
+pub const CAPABILITY_SUPERSPEED_USB: u8 = 0x03;
+pub const CAPABILITY_CONTAINER_ID: u8 = 0x04;
+pub const CAPABILITY_PLATFORM: u8 = 0x05;
+pub const CAPABILITY_POWER_DELIVERY: u8 = 0x06;
+pub const CAPABILITY_SUPERSPEED_PLUS: u8 = 0x0A;
+pub const CAPABILITY_BILLBOARD: u8 = 0x0D;
+pub const CAPABILITY_CONFIGURATION_SUMMARY: u8 = 0x10;
+
+pub fn capability_type_name(capability_type: u8) -> &'static str {
+    match capability_type {
+        0x01 => "Wireless USB",
+        0x02 => "USB 2.0 Extension",
+        0x03 => "SuperSpeed USB Device",
+        0x04 => "Container ID",
+        0x05 => "Platform",
+        0x06 => "Power Delivery",
+        0x07 => "Battery Info",
+        0x08 => "PD Consumer Port",
+        0x09 => "PD Provider Port",
+        0x0A => "SuperSpeed Plus",
+        0x0B => "Precision Time Measurement",
+        0x0C => "Wireless USB Extension",
+        0x0D => "Billboard",
+        0x0E => "Authentication",
+        0x0F => "Billboard Extension",
+        0x10 => "Configuration Summary",
+        0x11 => "Firmware Status",
+        _ => "Unknown",
+    }
+}
+
+// Format a UUID stored in the little-endian GUID layout USB descriptors use
+pub fn format_uuid(bytes: &[u8]) -> String {
+    if bytes.len() < 16 {
+        return String::new();
+    }
+    format!("{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+            bytes[3], bytes[2], bytes[1], bytes[0], bytes[5], bytes[4], bytes[7], bytes[6],
+            bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15])
+}
+
+// Platform capability UUIDs with a known meaning
+pub const WEBUSB_PLATFORM_UUID: &str = "3408B638-09A9-47A0-8BFD-A0768815B665";
+pub const MS_OS_20_PLATFORM_UUID: &str = "D8DD60DF-4589-4CC7-9CD2-659D9E648A9F";
+
+pub fn platform_uuid_name(uuid: &str) -> Option<&'static str> {
+    if uuid.eq_ignore_ascii_case(WEBUSB_PLATFORM_UUID) {
+        Some("WebUSB")
+    } else if uuid.eq_ignore_ascii_case(MS_OS_20_PLATFORM_UUID) {
+        Some("Microsoft OS 2.0")
+    } else {
+        None
+    }
+}
+
+// WebUSB (WebUSB spec section 4): the Platform capability gives the vendor code for the
+// GET_URL vendor request (wIndex 2, wValue = URL index) and the index of the landing page URL
+pub const WEBUSB_GET_URL: u16 = 2;
+pub const WEBUSB_URL_DESCRIPTOR_TYPE: u8 = 3;
+
+// CapabilityData of the WebUSB Platform capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebUsbPlatform {
+    pub version: u16,                  // bcdVersion (0x0100)
+    pub vendor_code: u8,               // bRequest of GET_URL
+    pub landing_page_index: u8,        // iLandingPage, 0 if none
+}
+
+impl WebUsbPlatform {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 4 {
+            return None;
+        }
+        Some(WebUsbPlatform {
+            version: read_u16(data, 0),
+            vendor_code: data[2],
+            landing_page_index: data[3],
+        })
+    }
+    
+    // URL index a GET_URL request asks for
+    pub fn url_request_index(&self, setup: &UsbSetupPacket) -> Option<u8> {
+        if setup.request_type == UsbControlRequestType::Vendor && setup.direction == UsbDirection::DeviceToHost
+            && setup.bRequest == self.vendor_code && setup.wIndex == WEBUSB_GET_URL {
+            Some((setup.wValue & 0xFF) as u8)
+        } else {
+            None
+        }
+    }
+}
+
+// WebUSB URL descriptor, returned by GET_URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebUsbUrlDescriptor {
+    pub length: u8,
+    pub scheme: u8,                    // 0 "http://", 1 "https://", 255 no prefix
+    pub url: String,                   // UTF-8, without the scheme
+}
+
+impl WebUsbUrlDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 3 {
+            return Err(format!("URL descriptor is {} bytes, expected at least 3", data.len()));
+        }
+        let length = data[0];
+        if data[1] != WEBUSB_URL_DESCRIPTOR_TYPE {
+            return Err(format!("URL descriptor has bDescriptorType 0x{:02X}, expected 0x03", data[1]));
+        }
+        if (length as usize) < 3 || length as usize > data.len() {
+            return Err(format!("URL descriptor bLength {} doesn't match the {} bytes returned", length, data.len()));
+        }
+        let scheme = data[2];
+        if Self::scheme_prefix(scheme).is_none() {
+            return Err(format!("URL descriptor has unknown bScheme {}", scheme));
+        }
+        let url = std::str::from_utf8(&data[3..length as usize])
+            .map_err(|_| "URL descriptor URL is not valid UTF-8".to_string())?;
+        if url.is_empty() {
+            return Err("URL descriptor has an empty URL".to_string());
+        }
+        Ok(WebUsbUrlDescriptor { length, scheme, url: url.to_string() })
+    }
+    
+    pub fn scheme_prefix(scheme: u8) -> Option<&'static str> {
+        match scheme {
+            0 => Some("http://"),
+            1 => Some("https://"),
+            255 => Some(""),
+            _ => None,
+        }
+    }
+    
+    pub fn full_url(&self) -> String {
+        format!("{}{}", Self::scheme_prefix(self.scheme).unwrap_or(""), self.url)
+    }
+}
+
+// WebUSB state of a device: its platform capability and the URL descriptors read from it
+#[derive(Debug, Clone)]
+pub struct WebUsb {
+    pub platform: WebUsbPlatform,
+    pub urls: BTreeMap<u8, WebUsbUrlDescriptor>,
+    pub errors: Vec<String>,
+}
+
+impl WebUsb {
+    pub fn new(platform: WebUsbPlatform) -> Self {
+        WebUsb { platform, urls: BTreeMap::new(), errors: Vec::new() }
+    }
+    
+    // Store a GET_URL response
+    pub fn add_url_response(&mut self, index: u8, data: &[u8]) {
+        match WebUsbUrlDescriptor::parse(data) {
+            Ok(url) => {
+                self.urls.insert(index, url);
+            },
+            Err(error) => self.errors.push(format!("URL {}: {}", index, error)),
+        }
+    }
+    
+    pub fn landing_page(&self) -> Option<String> {
+        self.urls.get(&self.platform.landing_page_index).map(|url| url.full_url())
+    }
+    
+    // Description and fields of a GET_URL request
+    pub fn decode_request(&self, setup: &UsbSetupPacket) -> Option<(String, Vec<RequestField>)> {
+        let index = self.platform.url_request_index(setup)?;
+        let url_index = if index == self.platform.landing_page_index {
+            format!("{} (landing page)", index)
+        } else {
+            format!("{}", index)
+        };
+        Some(("WebUSB GET_URL".to_string(), vec![
+            ("Vendor Code".to_string(), format!("0x{:02X}", setup.bRequest)),
+            ("URL Index".to_string(), url_index),
+        ]))
+    }
+    
+    // Fields of a GET_URL response, with a warning if the URL descriptor is malformed
+    pub fn decode_response(&self, setup: &UsbSetupPacket, data: &[u8]) -> Vec<RequestField> {
+        if self.platform.url_request_index(setup).is_none() {
+            return Vec::new();
+        }
+        match WebUsbUrlDescriptor::parse(data) {
+            Ok(url) => vec![
+                ("bScheme".to_string(), format!("{} ({})", url.scheme, WebUsbUrlDescriptor::scheme_prefix(url.scheme).unwrap_or(""))),
+                ("URL".to_string(), url.full_url()),
+            ],
+            Err(error) => vec![("Warning".to_string(), error)],
+        }
+    }
+}
+
+// BCD release number as "2.10"
+fn bcd_string(value: u16) -> String {
+    format!("{:X}.{:02X}", value >> 8, value & 0xFF)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    (data[offset + 1] as u16) << 8 | (data[offset] as u16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+// One SuperSpeed Plus sublink speed attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SublinkSpeed {
+    pub speed_id: u8,          // SSID, shared by the RX and TX attributes of one speed
+    pub exponent: u8,          // LSE: 0 b/s, 1 Kb/s, 2 Mb/s, 3 Gb/s
+    pub asymmetric: bool,
+    pub transmit: bool,        // Only meaningful for asymmetric sublinks
+    pub protocol: u8,          // LP: 0 SuperSpeed, 1 SuperSpeed Plus
+    pub mantissa: u16,
+}
+
+impl SublinkSpeed {
+    pub fn from_attribute(value: u32) -> Self {
+        SublinkSpeed {
+            speed_id: (value & 0x0F) as u8,
+            exponent: ((value >> 4) & 0x03) as u8,
+            asymmetric: value & (1 << 6) != 0,
+            transmit: value & (1 << 7) != 0,
+            protocol: ((value >> 14) & 0x03) as u8,
+            mantissa: (value >> 16) as u16,
+        }
+    }
+    
+    pub fn bits_per_second(&self) -> u64 {
+        self.mantissa as u64 * 1000u64.pow(self.exponent as u32)
+    }
+}
+
+impl fmt::Display for SublinkSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.exponent {
+            0 => "b/s",
+            1 => "Kb/s",
+            2 => "Mb/s",
+            _ => "Gb/s",
+        };
+        let link = match (self.asymmetric, self.transmit) {
+            (false, _) => "symmetric",
+            (true, false) => "asymmetric RX",
+            (true, true) => "asymmetric TX",
+        };
+        let protocol = match self.protocol {
+            0 => "SuperSpeed",
+            1 => "SuperSpeed Plus",
+            _ => "reserved protocol",
+        };
+        write!(f, "ID {}: {} {} {}, {}", self.speed_id, self.mantissa, unit, link, protocol)
+    }
+}
+
+// One alternate mode listed by a Billboard capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BillboardAlternateMode {
+    pub svid: u16,
+    pub mode: u8,
+    pub string_index: u8,
+    pub state: u8,             // 2-bit bmConfigured entry for this mode
+}
+
+impl BillboardAlternateMode {
+    pub fn state_name(&self) -> &'static str {
+        match self.state {
+            0 => "Unspecified error",
+            1 => "Not attempted or exited",
+            2 => "Configuration attempted but failed",
+            _ => "Configured",
+        }
+    }
+}
+
+// A device capability decoded by type
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceCapability {
+    Usb2Extension {
+        lpm: bool,                      // Link Power Management (L1) supported
+        besl: bool,                     // BESL and alternate HIRD definitions supported
+        baseline_besl: Option<u8>,      // Recommended baseline BESL, when valid
+        deep_besl: Option<u8>,          // Recommended deep BESL, when valid
+    },
+    SuperSpeed {
+        ltm: bool,                      // Latency Tolerance Messages supported
+        speeds_supported: u16,          // Bit 0 low, 1 full, 2 high, 3 5 Gb/s
+        functionality_support: u8,      // Lowest speed with full functionality
+        u1_exit_latency: u8,            // Microseconds
+        u2_exit_latency: u16,           // Microseconds
+    },
+    SuperSpeedPlus {
+        sublink_speed_id_count: u8,
+        min_speed_id: u8,               // SSID of the lowest speed with full functionality
+        min_rx_lanes: u8,
+        min_tx_lanes: u8,
+        sublink_speeds: Vec<SublinkSpeed>,
+    },
+    ContainerId(String),
+    Platform {
+        uuid: String,
+        data: Vec<u8>,
+    },
+    PowerDelivery {
+        attributes: u32,
+        provider_ports: u16,
+        consumer_ports: u16,
+        bc_version: u16,
+        pd_version: u16,
+        type_c_version: u16,
+    },
+    Billboard {
+        additional_info_url_index: u8,
+        preferred_mode: u8,
+        vconn_power: u16,
+        version: u16,
+        additional_failure_info: u8,
+        alternate_modes: Vec<BillboardAlternateMode>,
+    },
+    ConfigurationSummary {
+        version: u16,
+        class: UsbDeviceClass,
+        subclass: u8,
+        protocol: u8,
+        configuration_indices: Vec<u8>,
+    },
+    // Types not decoded, or too short for their type
+    Other {
+        capability_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl DeviceCapability {
+    // Decode a capability from bDevCapabilityType and the bytes after it
+    pub fn decode(capability_type: u8, data: &[u8]) -> Self {
+        let other = || DeviceCapability::Other { capability_type, data: data.to_vec() };
+        match capability_type {
+            CAPABILITY_USB_2_0_EXTENSION if data.len() >= 4 => {
+                let attributes = read_u32(data, 0);
+                DeviceCapability::Usb2Extension {
+                    lpm: attributes & (1 << 1) != 0,
+                    besl: attributes & (1 << 2) != 0,
+                    baseline_besl: (attributes & (1 << 3) != 0).then_some(((attributes >> 8) & 0x0F) as u8),
+                    deep_besl: (attributes & (1 << 4) != 0).then_some(((attributes >> 12) & 0x0F) as u8),
+                }
+            },
+            CAPABILITY_SUPERSPEED_USB if data.len() >= 7 => DeviceCapability::SuperSpeed {
+                ltm: data[0] & (1 << 1) != 0,
+                speeds_supported: read_u16(data, 1),
+                functionality_support: data[3],
+                u1_exit_latency: data[4],
+                u2_exit_latency: read_u16(data, 5),
+            },
+            CAPABILITY_SUPERSPEED_PLUS if data.len() >= 9 => {
+                let attributes = read_u32(data, 1);
+                let functionality = read_u16(data, 5);
+                let attribute_count = (attributes & 0x1F) as usize + 1;
+                let sublink_speeds = data[9..].chunks_exact(4)
+                    .take(attribute_count)
+                    .map(|chunk| SublinkSpeed::from_attribute(read_u32(chunk, 0)))
+                    .collect();
+                DeviceCapability::SuperSpeedPlus {
+                    sublink_speed_id_count: ((attributes >> 5) & 0x0F) as u8 + 1,
+                    min_speed_id: (functionality & 0x0F) as u8,
+                    min_rx_lanes: ((functionality >> 8) & 0x0F) as u8,
+                    min_tx_lanes: ((functionality >> 12) & 0x0F) as u8,
+                    sublink_speeds,
+                }
+            },
+            CAPABILITY_CONTAINER_ID if data.len() >= 17 => DeviceCapability::ContainerId(format_uuid(&data[1..17])),
+            CAPABILITY_PLATFORM if data.len() >= 17 => DeviceCapability::Platform {
+                uuid: format_uuid(&data[1..17]),
+                data: data[17..].to_vec(),
+            },
+            CAPABILITY_POWER_DELIVERY if data.len() >= 15 => DeviceCapability::PowerDelivery {
+                attributes: read_u32(data, 1),
+                provider_ports: read_u16(data, 5),
+                consumer_ports: read_u16(data, 7),
+                bc_version: read_u16(data, 9),
+                pd_version: read_u16(data, 11),
+                type_c_version: read_u16(data, 13),
+            },
+            CAPABILITY_BILLBOARD if data.len() >= 41 => {
+                let configured = &data[5..37];
+                let alternate_modes = data[41..].chunks_exact(4)
+                    .take(data[1] as usize)
+                    .enumerate()
+                    .map(|(i, chunk)| BillboardAlternateMode {
+                        svid: read_u16(chunk, 0),
+                        mode: chunk[2],
+                        string_index: chunk[3],
+                        state: (configured[i / 4] >> ((i % 4) * 2)) & 0x03,
+                    })
+                    .collect();
+                DeviceCapability::Billboard {
+                    additional_info_url_index: data[0],
+                    preferred_mode: data[2],
+                    vconn_power: read_u16(data, 3),
+                    version: read_u16(data, 37),
+                    additional_failure_info: data[39],
+                    alternate_modes,
+                }
+            },
+            CAPABILITY_CONFIGURATION_SUMMARY if data.len() >= 6 => DeviceCapability::ConfigurationSummary {
+                version: read_u16(data, 0),
+                class: UsbDeviceClass::from(data[2]),
+                subclass: data[3],
+                protocol: data[4],
+                configuration_indices: data[6..].iter().take(data[5] as usize).copied().collect(),
+            },
+            _ => other(),
+        }
+    }
+    
+    // The WebUSB Platform capability's data, if this is one
+    pub fn webusb(&self) -> Option<WebUsbPlatform> {
+        match self {
+            DeviceCapability::Platform { uuid, data } if uuid == WEBUSB_PLATFORM_UUID => WebUsbPlatform::parse(data),
+            _ => None,
+        }
+    }
+    
+    // Names of the speeds set in a SuperSpeed capability's wSpeedsSupported
+    pub fn speed_names(speeds_supported: u16) -> Vec<&'static str> {
+        [(0, "Low Speed"), (1, "Full Speed"), (2, "High Speed"), (3, "SuperSpeed (5 Gb/s)")]
+            .iter()
+            .filter(|(bit, _)| speeds_supported & (1 << bit) != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+    
+    // Billboard VCONN power needed by the alternate modes
+    pub fn vconn_power_name(vconn_power: u16) -> &'static str {
+        if vconn_power & 0x8000 != 0 {
+            return "VCONN not required";
+        }
+        match vconn_power & 0x07 {
+            0 => "1 W",
+            1 => "1.5 W",
+            2 => "2 W",
+            3 => "3 W",
+            4 => "4 W",
+            5 => "5 W",
+            6 => "6 W",
+            _ => "Reserved",
+        }
+    }
+}
+
+impl fmt::Display for DeviceCapability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceCapability::Usb2Extension { lpm, besl, baseline_besl, deep_besl } => {
+                writeln!(f, "    LPM: {}", if *lpm { "Supported" } else { "Not supported" })?;
+                writeln!(f, "    BESL/Alternate HIRD: {}", if *besl { "Supported" } else { "Not supported" })?;
+                if let Some(besl) = baseline_besl {
+                    writeln!(f, "    Baseline BESL: {}", besl)?;
+                }
+                if let Some(besl) = deep_besl {
+                    writeln!(f, "    Deep BESL: {}", besl)?;
+                }
+            },
+            DeviceCapability::SuperSpeed { ltm, speeds_supported, functionality_support, u1_exit_latency, u2_exit_latency } => {
+                writeln!(f, "    LTM: {}", if *ltm { "Supported" } else { "Not supported" })?;
+                writeln!(f, "    wSpeedsSupported: 0x{:04X} ({})", speeds_supported,
+                         DeviceCapability::speed_names(*speeds_supported).join(", "))?;
+                // bFunctionalitySupport comes from the device, so shift it checked
+                let lowest = 1u16.checked_shl(u32::from(*functionality_support))
+                    .and_then(|bit| DeviceCapability::speed_names(bit).first().copied())
+                    .unwrap_or("Reserved");
+                writeln!(f, "    bFunctionalitySupport: {} ({})", functionality_support, lowest)?;
+                writeln!(f, "    bU1DevExitLat: {} µs", u1_exit_latency)?;
+                writeln!(f, "    wU2DevExitLat: {} µs", u2_exit_latency)?;
+            },
+            DeviceCapability::SuperSpeedPlus { sublink_speed_id_count, min_speed_id, min_rx_lanes, min_tx_lanes, sublink_speeds } => {
+                writeln!(f, "    Sublink Speed IDs: {}", sublink_speed_id_count)?;
+                writeln!(f, "    Minimum Functional Speed ID: {}", min_speed_id)?;
+                writeln!(f, "    Minimum Lanes: {} RX, {} TX", min_rx_lanes, min_tx_lanes)?;
+                for speed in sublink_speeds {
+                    writeln!(f, "    Sublink Speed {}", speed)?;
+                }
+            },
+            DeviceCapability::ContainerId(uuid) => writeln!(f, "    ContainerID: {{{}}}", uuid)?,
+            DeviceCapability::Platform { uuid, data } => {
+                writeln!(f, "    PlatformCapabilityUUID: {{{}}} ({})", uuid, platform_uuid_name(uuid).unwrap_or("Unknown"))?;
+                writeln!(f, "    CapabilityData: {} bytes", data.len())?;
+                if let Some(webusb) = self.webusb() {
+                    writeln!(f, "    bcdVersion: {}", bcd_string(webusb.version))?;
+                    writeln!(f, "    bVendorCode: 0x{:02X}", webusb.vendor_code)?;
+                    writeln!(f, "    iLandingPage: {}", webusb.landing_page_index)?;
+                }
+            },
+            DeviceCapability::PowerDelivery { attributes, provider_ports, consumer_ports, bc_version, pd_version, type_c_version } => {
+                writeln!(f, "    bmAttributes: 0x{:08X}", attributes)?;
+                writeln!(f, "    bmProviderPorts: 0x{:04X}", provider_ports)?;
+                writeln!(f, "    bmConsumerPorts: 0x{:04X}", consumer_ports)?;
+                writeln!(f, "    bcdBCVersion: {}", bcd_string(*bc_version))?;
+                writeln!(f, "    bcdPDVersion: {}", bcd_string(*pd_version))?;
+                writeln!(f, "    bcdUSBTypeCVersion: {}", bcd_string(*type_c_version))?;
+            },
+            DeviceCapability::Billboard { additional_info_url_index, preferred_mode, vconn_power, version, additional_failure_info, alternate_modes } => {
+                writeln!(f, "    iAdditionalInfoURL: {}", additional_info_url_index)?;
+                writeln!(f, "    bNumberOfAlternateModes: {}", alternate_modes.len())?;
+                writeln!(f, "    bPreferredAlternateMode: {}", preferred_mode)?;
+                writeln!(f, "    VCONNPower: {}", DeviceCapability::vconn_power_name(*vconn_power))?;
+                writeln!(f, "    bcdVersion: {}", bcd_string(*version))?;
+                writeln!(f, "    bAdditionalFailureInfo: 0x{:02X}", additional_failure_info)?;
+                for (i, mode) in alternate_modes.iter().enumerate() {
+                    writeln!(f, "    Alternate Mode {}: SVID 0x{:04X} mode {} - {}", i, mode.svid, mode.mode, mode.state_name())?;
+                }
+            },
+            DeviceCapability::ConfigurationSummary { version, class, subclass, protocol, configuration_indices } => {
+                writeln!(f, "    bcdVersion: {}", bcd_string(*version))?;
+                writeln!(f, "    Function: {} (0x{:02X}/0x{:02X}/0x{:02X})", class.name(), class.get_value(), subclass, protocol)?;
+                let indices: Vec<String> = configuration_indices.iter().map(|index| index.to_string()).collect();
+                writeln!(f, "    Configuration Indices: {}", indices.join(", "))?;
+            },
+            DeviceCapability::Other { data, .. } => {
+                // Display capability data in hex format
+                write!(f, "    Capability Data: ")?;
+                for (i, byte) in data.iter().enumerate() {
+                    if i > 0 && i % 8 == 0 {
+                        write!(f, "\n                    ")?;
+                    }
+                    write!(f, "{:02X} ", byte)?;
+                }
+                writeln!(f)?;
+            },
+        }
+        Ok(())
+    }
+}
+
 // Device Capability Descriptor - for USB 3.0 and above
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceCapabilityDescriptor {
@@ -645,41 +1458,44 @@ pub struct DeviceCapabilityDescriptor {
     pub capability_data: Vec<u8>,      // Capability-specific data
 }
 
+impl DeviceCapabilityDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 3 {
+            return Err(format!("Invalid device capability descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        match descriptor_type {
+            UsbDescriptorType::DeviceCapability => {
+                let end = (data[0] as usize).clamp(3, data.len());
+                Ok(DeviceCapabilityDescriptor {
+                    length: data[0],
+                    descriptor_type,
+                    capability_type: data[2],
+                    capability_data: data[3..end].to_vec(),
+                })
+            },
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+    
+    pub fn capability_name(&self) -> &'static str {
+        capability_type_name(self.capability_type)
+    }
+    
+    pub fn capability(&self) -> DeviceCapability {
+        DeviceCapability::decode(self.capability_type, &self.capability_data)
+    }
+}
+
 impl fmt::Display for DeviceCapabilityDescriptor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Device Capability Descriptor:")?;
         writeln!(f, "  bLength: {} bytes", self.length)?;
         writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
         writeln!(f, "  bDevCapabilityType: 0x{:02X}", self.capability_type)?;
-        
-        // Decode capability type
-        let cap_type_name = match self.capability_type {
-            1 => "USB 2.0 Extension",
-            2 => "SuperSpeed USB Device",
-            3 => "Container ID",
-            4 => "Platform",
-            5 => "Power Delivery",
-            6 => "Battery Info",
-            7 => "PD Consumer Port",
-            8 => "PD Provider Port",
-            9 => "SuperSpeed Plus",
-            10 => "Precision Time Measurement",
-            11 => "Wireless USB Extension",
-            _ => "Unknown",
-        };
-        writeln!(f, "    Capability Type: {}", cap_type_name)?;
-        
-        // Display capability data in hex format
-        write!(f, "    Capability Data: ")?;
-        for (i, byte) in self.capability_data.iter().enumerate() {
-            if i > 0 && i % 8 == 0 {
-                write!(f, "\n                    ")?;
-            }
-            write!(f, "{:02X} ", byte)?;
-        }
-        writeln!(f)?;
-        
-        Ok(())
+        writeln!(f, "    Capability Type: {}", self.capability_name())?;
+        write!(f, "{}", self.capability())
     }
 }
 
@@ -693,7 +1509,46 @@ pub struct SuperSpeedEndpointCompanionDescriptor {
     pub bytes_per_interval: u16,       // Periodic endpoints only - bytes per service interval
 }
 
-impl fmt::Display for SuperSpeedEndpointCompanionDescriptor {
+impl SuperSpeedEndpointCompanionDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 6 {
+            return Err(format!("Invalid SuperSpeed endpoint companion descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        
+        match descriptor_type {
+            UsbDescriptorType::SuperspeedUsbEndpointCompanion => Ok(SuperSpeedEndpointCompanionDescriptor {
+                length: data[0],
+                descriptor_type,
+                max_burst: data[2],
+                attributes: data[3],
+                bytes_per_interval: read_u16(data, 4),
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+    
+    // Bulk endpoints: number of streams (bits 4..0 hold log2 of the count)
+    pub fn max_streams(&self) -> u32 {
+        match self.attributes & 0x1F {
+            0 => 0,
+            n => 1 << n,
+        }
+    }
+    
+    // Isochronous endpoints: bursts per service interval (bits 1..0 hold the count - 1)
+    pub fn mult(&self) -> u8 {
+        (self.attributes & 0x03) + 1
+    }
+    
+    // Isochronous endpoints: a SuperSpeedPlus isochronous companion follows
+    pub fn has_ssp_isochronous_companion(&self) -> bool {
+        self.attributes & 0x80 != 0
+    }
+}
+
+impl fmt::Display for SuperSpeedEndpointCompanionDescriptor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "SuperSpeed Endpoint Companion Descriptor:")?;
         writeln!(f, "  bLength: {} bytes", self.length)?;
@@ -701,27 +1556,234 @@ impl fmt::Display for SuperSpeedEndpointCompanionDescriptor {
         writeln!(f, "  bMaxBurst: {} (max {} packets per burst)", self.max_burst, self.max_burst as u16 + 1)?;
         writeln!(f, "  bmAttributes: 0x{:02X}", self.attributes)?;
         
-        // Decode attributes
-        let bulk_max_streams = match self.attributes & 0x1F {
+        // Decode attributes; which half applies depends on the endpoint's transfer type
+        let bulk_max_streams = match self.max_streams() {
             0 => "No streams".to_string(),
-            n => format!("Max {} streams", 1 << n),
-        };
-        
-        let mult = match (self.attributes >> 4) & 0x3 {
-            0 => 1,
-            1 => 2,
-            2 => 3,
-            _ => 0, // Reserved
+            n => format!("Max {} streams", n),
         };
         
         writeln!(f, "    Bulk: {}", bulk_max_streams)?;
-        writeln!(f, "    Mult: {} (max {} transactions per service interval)", (self.attributes >> 4) & 0x3, mult)?;
+        writeln!(f, "    Isochronous Mult: {} (max {} bursts per service interval)", self.attributes & 0x03, self.mult())?;
+        if self.has_ssp_isochronous_companion() {
+            writeln!(f, "    Isochronous: SuperSpeedPlus isochronous companion follows")?;
+        }
         writeln!(f, "  wBytesPerInterval: {} bytes", self.bytes_per_interval)?;
         
         Ok(())
     }
 }
 
+// SuperSpeedPlus Isochronous Endpoint Companion Descriptor - follows the SuperSpeed companion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuperSpeedPlusIsochronousEndpointCompanionDescriptor {
+    pub length: u8,                    // Descriptor size in bytes (8)
+    pub descriptor_type: UsbDescriptorType, // SSP_ISOCHRONOUS_ENDPOINT_COMPANION descriptor type (0x31)
+    pub bytes_per_interval: u32,       // Bytes per service interval, replacing wBytesPerInterval
+}
+
+impl SuperSpeedPlusIsochronousEndpointCompanionDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 8 {
+            return Err(format!("Invalid SuperSpeedPlus isochronous endpoint companion descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        
+        match descriptor_type {
+            UsbDescriptorType::SuperspeedPlusIsochronousEndpointCompanion => Ok(SuperSpeedPlusIsochronousEndpointCompanionDescriptor {
+                length: data[0],
+                descriptor_type,
+                bytes_per_interval: read_u32(data, 4),
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+}
+
+impl fmt::Display for SuperSpeedPlusIsochronousEndpointCompanionDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "SuperSpeedPlus Isochronous Endpoint Companion Descriptor:")?;
+        writeln!(f, "  bLength: {} bytes", self.length)?;
+        writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
+        writeln!(f, "  dwBytesPerInterval: {} bytes", self.bytes_per_interval)
+    }
+}
+
+// eUSB2 Isochronous Endpoint Companion Descriptor - high-speed isochronous IN endpoints
+// using the USB 2.0 double isochronous IN bandwidth ECN
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Eusb2IsochronousEndpointCompanionDescriptor {
+    pub length: u8,                    // Descriptor size in bytes (8)
+    pub descriptor_type: UsbDescriptorType, // EUSB2_ISOCHRONOUS_ENDPOINT_COMPANION descriptor type (0x12)
+    pub bytes_per_interval: u32,       // Bytes per service interval
+}
+
+impl Eusb2IsochronousEndpointCompanionDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 8 {
+            return Err(format!("Invalid eUSB2 isochronous endpoint companion descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        
+        match descriptor_type {
+            UsbDescriptorType::Eusb2IsochronousEndpointCompanion => Ok(Eusb2IsochronousEndpointCompanionDescriptor {
+                length: data[0],
+                descriptor_type,
+                bytes_per_interval: read_u32(data, 4),
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+}
+
+impl fmt::Display for Eusb2IsochronousEndpointCompanionDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "eUSB2 Isochronous Endpoint Companion Descriptor:")?;
+        writeln!(f, "  bLength: {} bytes", self.length)?;
+        writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
+        writeln!(f, "  dwBytesPerInterval: {} bytes", self.bytes_per_interval)
+    }
+}
+
+// OTG Descriptor - part of the configuration of a dual-role (On-The-Go) device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OtgDescriptor {
+    pub length: u8,                    // Descriptor size in bytes (3, or 5 from OTG 2.0)
+    pub descriptor_type: UsbDescriptorType, // OTG descriptor type (9)
+    pub attributes: u8,                // Supported OTG protocols
+    pub otg_version: Option<u16>,      // OTG specification release number (BCD), OTG 2.0 and later
+}
+
+impl OtgDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 3 {
+            return Err(format!("Invalid OTG descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        
+        match descriptor_type {
+            UsbDescriptorType::Otg => Ok(OtgDescriptor {
+                length: data[0],
+                descriptor_type,
+                attributes: data[2],
+                otg_version: if data.len() >= 5 { Some(read_u16(data, 3)) } else { None },
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+    
+    // Names of the protocols set in bmAttributes
+    pub fn protocols(&self) -> Vec<&'static str> {
+        [(0x01, "SRP"), (0x02, "HNP"), (0x04, "ADP"), (0x08, "RSP")]
+            .iter()
+            .filter(|(bit, _)| self.attributes & bit != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl fmt::Display for OtgDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "OTG Descriptor:")?;
+        writeln!(f, "  bLength: {} bytes", self.length)?;
+        writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
+        writeln!(f, "  bmAttributes: 0x{:02X}", self.attributes)?;
+        writeln!(f, "    Session Request Protocol (SRP): {}", if self.attributes & 0x01 != 0 { "Yes" } else { "No" })?;
+        writeln!(f, "    Host Negotiation Protocol (HNP): {}", if self.attributes & 0x02 != 0 { "Yes" } else { "No" })?;
+        writeln!(f, "    Attach Detection Protocol (ADP): {}", if self.attributes & 0x04 != 0 { "Yes" } else { "No" })?;
+        writeln!(f, "    Role Swap Protocol (RSP): {}", if self.attributes & 0x08 != 0 { "Yes" } else { "No" })?;
+        if let Some(version) = self.otg_version {
+            writeln!(f, "  bcdOTG: {}", bcd_string(version))?;
+        }
+        Ok(())
+    }
+}
+
+// Debug Descriptor - endpoints of the device's debug port
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugDescriptor {
+    pub length: u8,                    // Descriptor size in bytes (4)
+    pub descriptor_type: UsbDescriptorType, // DEBUG descriptor type (10)
+    pub debug_in_endpoint: u8,         // Endpoint address of the debug IN endpoint
+    pub debug_out_endpoint: u8,        // Endpoint address of the debug OUT endpoint
+}
+
+impl DebugDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 4 {
+            return Err(format!("Invalid debug descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        
+        match descriptor_type {
+            UsbDescriptorType::Debug => Ok(DebugDescriptor {
+                length: data[0],
+                descriptor_type,
+                debug_in_endpoint: data[2],
+                debug_out_endpoint: data[3],
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+}
+
+impl fmt::Display for DebugDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Debug Descriptor:")?;
+        writeln!(f, "  bLength: {} bytes", self.length)?;
+        writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
+        writeln!(f, "  bDebugInEndpoint: 0x{:02X}", self.debug_in_endpoint)?;
+        writeln!(f, "  bDebugOutEndpoint: 0x{:02X}", self.debug_out_endpoint)
+    }
+}
+
+// Interface Power Descriptor - from the Interface Power Management draft, which was never
+// finalized, so only the capability flags are broken out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfacePowerDescriptor {
+    pub length: u8,                    // Descriptor size in bytes
+    pub descriptor_type: UsbDescriptorType, // INTERFACE_POWER descriptor type (8)
+    pub capabilities_flags: u8,        // bmCapabilitiesFlags
+    pub data: Vec<u8>,                 // Power state and transition time fields
+}
+
+impl InterfacePowerDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 3 {
+            return Err(format!("Invalid interface power descriptor length: {}", data.len()));
+        }
+        
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        
+        match descriptor_type {
+            UsbDescriptorType::InterfacePower => Ok(InterfacePowerDescriptor {
+                length: data[0],
+                descriptor_type,
+                capabilities_flags: data[2],
+                data: data[3..].to_vec(),
+            }),
+            _ => Err(format!("Invalid descriptor type: {:?}", descriptor_type)),
+        }
+    }
+}
+
+impl fmt::Display for InterfacePowerDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Interface Power Descriptor:")?;
+        writeln!(f, "  bLength: {} bytes", self.length)?;
+        writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
+        writeln!(f, "  bmCapabilitiesFlags: 0x{:02X}", self.capabilities_flags)?;
+        write!(f, "  Power Data:")?;
+        for byte in &self.data {
+            write!(f, " {:02X}", byte)?;
+        }
+        writeln!(f)
+    }
+}
+
 // Now add common class-specific descriptors:
 
 // Communications Device Class (CDC) Descriptor
@@ -925,18 +1987,248 @@ impl fmt::Display for VideoStreamingDescriptor {
     }
 }
 
+// One entry in the descriptor hierarchy: Configuration -> IAD function -> interface
+// number -> alternate setting -> endpoint, with class-specific descriptors under the
+// interface or endpoint they follow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DescriptorNode {
+    pub label: String,
+    pub descriptor: Option<USBDescriptor>, // None for grouping nodes such as "Interface 1"
+    pub children: Vec<DescriptorNode>,
+}
+
+impl DescriptorNode {
+    pub fn leaf(descriptor: USBDescriptor) -> Self {
+        DescriptorNode {
+            label: descriptor.name(),
+            descriptor: Some(descriptor),
+            children: Vec::new(),
+        }
+    }
+    
+    pub fn group(label: String, children: Vec<DescriptorNode>) -> Self {
+        DescriptorNode {
+            label,
+            descriptor: None,
+            children,
+        }
+    }
+    
+    pub fn configuration(config: &ConfigurationDescriptor) -> Self {
+        let mut label = format!("Configuration {}", config.configuration_value);
+        if let Some(name) = &config.configuration_string {
+            label.push_str(&format!(": {}", name));
+        }
+        
+        // Interfaces covered by an IAD are grouped under their function
+        let mut children: Vec<DescriptorNode> = Vec::new();
+        if let Some(otg) = &config.otg {
+            children.push(Self::leaf(USBDescriptor::Otg(otg.clone())));
+        }
+        let mut current_function: Option<(u8, DescriptorNode)> = None;
+        for number in config.interface_numbers() {
+            let interface = Self::interface(config, number);
+            match config.association_for(number) {
+                Some(association) => {
+                    if current_function.as_ref().map(|(first, _)| *first) != Some(association.first_interface) {
+                        if let Some((_, function)) = current_function.take() {
+                            children.push(function);
+                        }
+                        current_function = Some((association.first_interface, Self::function(association)));
+                    }
+                    if let Some((_, function)) = current_function.as_mut() {
+                        function.children.push(interface);
+                    }
+                },
+                None => {
+                    if let Some((_, function)) = current_function.take() {
+                        children.push(function);
+                    }
+                    children.push(interface);
+                },
+            }
+        }
+        if let Some((_, function)) = current_function {
+            children.push(function);
+        }
+        
+        // An IAD pointing at interfaces that never showed up still gets listed
+        for association in &config.associations {
+            if !config.interfaces.iter().any(|interface| association.contains(interface.interface_number)) {
+                children.push(Self::function(association));
+            }
+        }
+        
+        DescriptorNode {
+            label,
+            descriptor: Some(USBDescriptor::Configuration(config.clone())),
+            children,
+        }
+    }
+    
+    fn function(association: &InterfaceAssociationDescriptor) -> Self {
+        let name = association.function_string.clone()
+            .unwrap_or_else(|| association.function_class.name().to_string());
+        DescriptorNode {
+            label: format!("Function: {} (Interfaces {}-{})", name, association.first_interface, association.last_interface()),
+            descriptor: Some(USBDescriptor::InterfaceAssociation(association.clone())),
+            children: Vec::new(),
+        }
+    }
+    
+    fn interface(config: &ConfigurationDescriptor, interface_number: u8) -> Self {
+        let settings = config.alternate_settings(interface_number);
+        let name = settings.first()
+            .map(|interface| interface.interface_string.clone()
+                .unwrap_or_else(|| interface.interface_class.name().to_string()))
+            .unwrap_or_default();
+        
+        Self::group(
+            format!("Interface {}: {}", interface_number, name),
+            settings.into_iter().map(Self::alternate_setting).collect(),
+        )
+    }
+    
+    fn alternate_setting(interface: &InterfaceDescriptor) -> Self {
+        let mut children: Vec<DescriptorNode> = Vec::new();
+        if let Some(power) = &interface.interface_power {
+            children.push(Self::leaf(USBDescriptor::InterfacePower(power.clone())));
+        }
+        children.extend(interface.class_specific.iter().map(|raw| Self::class_specific(interface, raw)));
+        
+        for endpoint in &interface.endpoints {
+            let mut endpoint_children = Vec::new();
+            if let Some(companion) = &endpoint.ss_companion {
+                endpoint_children.push(Self::leaf(USBDescriptor::SuperSpeedEndpointCompanion(companion.clone())));
+            }
+            if let Some(companion) = &endpoint.ssp_isochronous_companion {
+                endpoint_children.push(Self::leaf(USBDescriptor::SuperSpeedPlusIsochronousEndpointCompanion(companion.clone())));
+            }
+            if let Some(companion) = &endpoint.eusb2_isochronous_companion {
+                endpoint_children.push(Self::leaf(USBDescriptor::Eusb2IsochronousEndpointCompanion(companion.clone())));
+            }
+            endpoint_children.extend(endpoint.class_specific.iter().map(|raw| Self::class_specific(interface, raw)));
+            
+            children.push(DescriptorNode {
+                label: format!("Endpoint 0x{:02X} {} {}", endpoint.endpoint_address,
+                               endpoint.direction.name(), endpoint.transfer_type.name()),
+                descriptor: Some(USBDescriptor::Endpoint(endpoint.clone())),
+                children: endpoint_children,
+            });
+        }
+        
+        DescriptorNode {
+            label: format!("Alternate Setting {}: {} endpoint{}", interface.alternate_setting,
+                           interface.endpoints.len(), if interface.endpoints.len() == 1 { "" } else { "s" }),
+            descriptor: Some(USBDescriptor::Interface(interface.clone())),
+            children,
+        }
+    }
+    
+    fn class_specific(interface: &InterfaceDescriptor, raw: &[u8]) -> Self {
+        let descriptor = class_specific_descriptor(interface, raw);
+        let label = match &descriptor {
+            USBDescriptor::Unknown { descriptor_type, .. } => {
+                format!("Class-Specific Descriptor (0x{:02X})", descriptor_type.get_value())
+            },
+            other => other.name(),
+        };
+        DescriptorNode {
+            label,
+            descriptor: Some(descriptor),
+            children: Vec::new(),
+        }
+    }
+    
+    // Every descriptor in the tree, parents before their children
+    pub fn flatten(nodes: &[DescriptorNode]) -> Vec<USBDescriptor> {
+        let mut descriptors = Vec::new();
+        for node in nodes {
+            if let Some(descriptor) = &node.descriptor {
+                descriptors.push(descriptor.clone());
+            }
+            descriptors.extend(Self::flatten(&node.children));
+        }
+        descriptors
+    }
+    
+    // Look up a node by its child index at each level
+    pub fn find<'a>(nodes: &'a [DescriptorNode], path: &[usize]) -> Option<&'a DescriptorNode> {
+        let (first, rest) = path.split_first()?;
+        let node = nodes.get(*first)?;
+        if rest.is_empty() {
+            Some(node)
+        } else {
+            Self::find(&node.children, rest)
+        }
+    }
+}
+
+// Interpret a raw class-specific descriptor using the class of the interface it belongs to
+pub fn class_specific_descriptor(interface: &InterfaceDescriptor, raw: &[u8]) -> USBDescriptor {
+    let length = raw[0];
+    let descriptor_type = UsbDescriptorType::from(raw[1]);
+    let descriptor_subtype = raw.get(2).copied().unwrap_or(0);
+    let data = raw.get(3..).unwrap_or(&[]).to_vec();
+    
+    match (interface.interface_class, raw[1], interface.interface_subclass) {
+        (UsbDeviceClass::HumanInterfaceDevice, 0x21, _) => USBDescriptor::HID(raw[2..].to_vec()),
+        (UsbDeviceClass::Audio, 0x24, 0x01) => USBDescriptor::AudioControl(AudioControlDescriptor {
+            length, descriptor_type, descriptor_subtype, data,
+        }),
+        (UsbDeviceClass::Audio, 0x24, 0x02) => USBDescriptor::AudioStreaming(AudioStreamingDescriptor {
+            length, descriptor_type, descriptor_subtype, data,
+        }),
+        (UsbDeviceClass::Communications, 0x24, _) => USBDescriptor::CDC(CDCDescriptor {
+            length, descriptor_type, descriptor_subtype, data,
+        }),
+        (UsbDeviceClass::Video, 0x24, 0x01) => USBDescriptor::VideoControl(VideoControlDescriptor {
+            length, descriptor_type, descriptor_subtype, data,
+        }),
+        (UsbDeviceClass::Video, 0x24, 0x02) => USBDescriptor::VideoStreaming(VideoStreamingDescriptor {
+            length, descriptor_type, descriptor_subtype, data,
+        }),
+        (UsbDeviceClass::MassStorage, _, _) => USBDescriptor::MSC(MSCDescriptor {
+            length, descriptor_type, data: raw[2..].to_vec(),
+        }),
+        _ => USBDescriptor::Unknown { descriptor_type, data: raw.to_vec() },
+    }
+}
+
+// Descriptors found inside one configuration while linking
+#[derive(Default)]
+struct LinkedConfiguration {
+    otg: Option<OtgDescriptor>,
+    associations: Vec<InterfaceAssociationDescriptor>,
+    interfaces: Vec<InterfaceDescriptor>,
+}
+
 // Main structure to hold all parsed USB descriptors
 #[derive(Debug, Clone)]
 pub struct UsbDevice {
     pub device: Option<DeviceDescriptor>,
     pub configurations: Vec<ConfigurationDescriptor>,
-    pub strings: Vec<StringDescriptor>,
+    pub strings: BTreeMap<(u8, u16), StringDescriptor>, // (index, LANGID) -> string; string 0 is keyed (0, 0)
+    pub languages: Vec<u16>,                           // LANGIDs from string 0, the first one preferred
+    pub unindexed_strings: Vec<StringDescriptor>,      // Strings parsed without their GET_DESCRIPTOR request
     pub device_qualifier: Option<DeviceQualifierDescriptor>,
     
     // USB 3.0+ specific descriptors
     pub bos: Option<BOSDescriptor>,
     pub device_capabilities: Vec<DeviceCapabilityDescriptor>,
-    pub ss_endpoint_companions: Vec<SuperSpeedEndpointCompanionDescriptor>,
+    
+    // Descriptors fetched on their own rather than as part of a configuration
+    pub otg: Option<OtgDescriptor>,
+    pub debug: Option<DebugDescriptor>,
+    
+    // Hub descriptor (only present for hub devices)
+    pub hub: Option<HubDescriptor>,
+    
+    // Microsoft OS descriptors (string 0xEE, BOS platform capability and vendor-request responses)
+    pub ms_os: MsOsDescriptors,
+    
+    // WebUSB platform capability and URL descriptors
+    pub webusb: Option<WebUsb>,
     
     // Class-specific descriptors
     pub cdc_descriptors: Vec<CDCDescriptor>,
@@ -955,13 +2247,22 @@ impl UsbDevice {
         UsbDevice {
             device: None,
             configurations: Vec::new(),
-            strings: Vec::new(),
+            strings: BTreeMap::new(),
+            languages: Vec::new(),
+            unindexed_strings: Vec::new(),
             device_qualifier: None,
             
             // USB 3.0+ specific descriptors
             bos: None,
             device_capabilities: Vec::new(),
-            ss_endpoint_companions: Vec::new(),
+            
+            otg: None,
+            debug: None,
+            
+            hub: None,
+            
+            ms_os: MsOsDescriptors::default(),
+            webusb: None,
             
             // Class-specific descriptors
             cdc_descriptors: Vec::new(),
@@ -977,83 +2278,107 @@ impl UsbDevice {
     
     // Get all descriptors in a structured format for display
     pub fn get_all_descriptors(&self) -> Vec<USBDescriptor> {
-        let mut descriptors = Vec::new();
+        DescriptorNode::flatten(&self.descriptor_tree())
+    }
+    
+    // Arrange the descriptors the way the device nests them
+    pub fn descriptor_tree(&self) -> Vec<DescriptorNode> {
+        let mut nodes = Vec::new();
         
         // Add device descriptor if available
         if let Some(device) = &self.device {
-            descriptors.push(USBDescriptor::Device(device.clone()));
+            let mut node = DescriptorNode::leaf(USBDescriptor::Device(device.clone()));
+            node.label = format!("Device {:04X}:{:04X}", device.vendor_id, device.product_id);
+            nodes.push(node);
         }
         
         // Add device qualifier if available
         if let Some(qualifier) = &self.device_qualifier {
-            descriptors.push(USBDescriptor::DeviceQualifier(qualifier.clone()));
+            nodes.push(DescriptorNode::leaf(USBDescriptor::DeviceQualifier(qualifier.clone())));
         }
         
-        // Add BOS descriptor if available
+        // Add BOS descriptor with its device capabilities
         if let Some(bos) = &self.bos {
-            descriptors.push(USBDescriptor::BOS(bos.clone()));
-        }
-        
-        // Add device capabilities
-        for cap in &self.device_capabilities {
-            descriptors.push(USBDescriptor::DeviceCapability(cap.clone()));
+            let mut node = DescriptorNode::leaf(USBDescriptor::BOS(bos.clone()));
+            node.children = self.device_capabilities.iter()
+                .map(|cap| {
+                    let mut child = DescriptorNode::leaf(USBDescriptor::DeviceCapability(cap.clone()));
+                    child.label = format!("{} Capability", cap.capability_name());
+                    child
+                })
+                .collect();
+            nodes.push(node);
         }
         
-        // Add configurations and their interfaces/endpoints
+        // Add configurations with their functions, interfaces and endpoints
         for config in &self.configurations {
-            descriptors.push(USBDescriptor::Configuration(config.clone()));
-            
-            for interface in &config.interfaces {
-                descriptors.push(USBDescriptor::Interface(interface.clone()));
-                
-                for endpoint in &interface.endpoints {
-                    descriptors.push(USBDescriptor::Endpoint(endpoint.clone()));
-                }
-            }
-        }
-        
-        // Add SuperSpeed endpoint companions
-        for companion in &self.ss_endpoint_companions {
-            descriptors.push(USBDescriptor::SuperSpeedEndpointCompanion(companion.clone()));
+            nodes.push(DescriptorNode::configuration(config));
         }
         
-        // Add class-specific descriptors
-        for desc in &self.cdc_descriptors {
-            descriptors.push(USBDescriptor::CDC(desc.clone()));
-        }
-        
-        for desc in &self.msc_descriptors {
-            descriptors.push(USBDescriptor::MSC(desc.clone()));
+        // Add standalone OTG and debug descriptors
+        if let Some(otg) = &self.otg {
+            nodes.push(DescriptorNode::leaf(USBDescriptor::Otg(otg.clone())));
         }
         
-        for desc in &self.audio_control_descriptors {
-            descriptors.push(USBDescriptor::AudioControl(desc.clone()));
+        if let Some(debug) = &self.debug {
+            nodes.push(DescriptorNode::leaf(USBDescriptor::Debug(debug.clone())));
         }
         
-        for desc in &self.audio_streaming_descriptors {
-            descriptors.push(USBDescriptor::AudioStreaming(desc.clone()));
+        // Add hub descriptor if available
+        if let Some(hub) = &self.hub {
+            nodes.push(DescriptorNode::leaf(USBDescriptor::Hub(hub.clone())));
         }
         
-        for desc in &self.video_control_descriptors {
-            descriptors.push(USBDescriptor::VideoControl(desc.clone()));
-        }
-        
-        for desc in &self.video_streaming_descriptors {
-            descriptors.push(USBDescriptor::VideoStreaming(desc.clone()));
-        }
-        
-        // Add string descriptors
-        for string in &self.strings {
-            descriptors.push(USBDescriptor::String(string.clone()));
+        // Add class-specific descriptors
+        let class_descriptors = self.cdc_descriptors.iter().cloned().map(USBDescriptor::CDC)
+            .chain(self.msc_descriptors.iter().cloned().map(USBDescriptor::MSC))
+            .chain(self.audio_control_descriptors.iter().cloned().map(USBDescriptor::AudioControl))
+            .chain(self.audio_streaming_descriptors.iter().cloned().map(USBDescriptor::AudioStreaming))
+            .chain(self.video_control_descriptors.iter().cloned().map(USBDescriptor::VideoControl))
+            .chain(self.video_streaming_descriptors.iter().cloned().map(USBDescriptor::VideoStreaming));
+        nodes.extend(class_descriptors.map(DescriptorNode::leaf));
+        
+        // Add string descriptors, grouped by language once more than one was captured
+        if !self.strings.is_empty() || !self.unindexed_strings.is_empty() {
+            let language_ids: BTreeSet<u16> = self.strings.keys()
+                .filter(|(index, _)| *index != 0)
+                .map(|(_, language_id)| *language_id)
+                .collect();
+            let string_node = |string: &StringDescriptor| {
+                let mut node = DescriptorNode::leaf(USBDescriptor::String(string.clone()));
+                node.label = if string.unknown_index {
+                    format!("String (unknown index): \"{}\"", string.string)
+                } else if string.string_index == 0 {
+                    format!("Languages: {}", string.string)
+                } else {
+                    format!("String {}: \"{}\"", string.string_index, string.string)
+                };
+                node
+            };
+            
+            let mut strings: Vec<DescriptorNode> = self.strings.get(&(0, 0)).map(string_node).into_iter().collect();
+            if language_ids.len() > 1 {
+                for language_id in language_ids {
+                    let translated = self.strings.values()
+                        .filter(|string| string.string_index != 0 && string.language_id == language_id)
+                        .map(string_node)
+                        .collect();
+                    strings.push(DescriptorNode::group(describe_langid(language_id), translated));
+                }
+            } else {
+                strings.extend(self.strings.values().filter(|string| string.string_index != 0).map(string_node));
+            }
+            strings.extend(self.unindexed_strings.iter().map(string_node));
+            nodes.push(DescriptorNode::group("Strings".to_string(), strings));
         }
         
-        descriptors
+        nodes
     }
     
     // Get contextual hints for this device
     #[allow(dead_code)]
     pub fn get_device_hints(&self) -> Vec<String> {
-        use crate::usb::hints::get_descriptor_hints;
+        use crate::usb::hints::{get_descriptor_hints, UsbHints};
         
         let mut hints = Vec::new();
         
@@ -1070,6 +2395,22 @@ impl UsbDevice {
                 hints.push(format!("Product: {}", product));
             }
             
+            // Strings read in more than one language
+            for (field, index) in [("Manufacturer", device.manufacturer_index), ("Product", device.product_index),
+                                   ("Serial Number", device.serial_number_index)] {
+                let translations = self.string_translations(index);
+                if translations.len() > 1 {
+                    for (language_id, text) in translations {
+                        hints.push(format!("{} ({}): {}", field, describe_langid(language_id), text));
+                    }
+                }
+            }
+            
+            if !self.languages.is_empty() {
+                let languages: Vec<String> = self.languages.iter().map(|langid| describe_langid(*langid)).collect();
+                hints.push(format!("String languages: {}", languages.join(", ")));
+            }
+            
             // Add USB version information
             hints.push(format!("USB Version: {}", device.usb_version_string()));
             
@@ -1117,6 +2458,52 @@ impl UsbDevice {
             hints.push(get_descriptor_hints(&UsbDescriptorType::Configuration));
         }
         
+        // Add OTG and debug port hints
+        if let Some(otg) = self.configurations.iter().find_map(|config| config.otg.as_ref()).or(self.otg.as_ref()) {
+            let protocols = otg.protocols();
+            hints.push(format!("On-The-Go dual-role device ({})",
+                               if protocols.is_empty() { "no OTG protocols".to_string() } else { protocols.join(", ") }));
+            hints.push(get_descriptor_hints(&UsbDescriptorType::Otg));
+        }
+        
+        if let Some(debug) = &self.debug {
+            hints.push(format!("Debug port on endpoints 0x{:02X} (IN) and 0x{:02X} (OUT)",
+                               debug.debug_in_endpoint, debug.debug_out_endpoint));
+        }
+        
+        // Add BOS capability hints
+        if let Some(bos) = &self.bos {
+            hints.push(format!("BOS with {} device capabilit{}", bos.device_capabilities.len(),
+                               if bos.device_capabilities.len() == 1 { "y" } else { "ies" }));
+            for cap in &bos.device_capabilities {
+                hints.push(format!("{} capability", cap.capability_name()));
+                hints.extend(UsbHints::for_device_capability(&cap.capability()));
+            }
+            hints.push(get_descriptor_hints(&UsbDescriptorType::Bos));
+        }
+        
+        // Add Microsoft OS descriptor hints
+        hints.extend(self.ms_os.hints());
+        
+        // Add WebUSB hints
+        if let Some(webusb) = &self.webusb {
+            match webusb.landing_page() {
+                Some(url) => hints.push(format!("WebUSB landing page: {}", url)),
+                None if webusb.platform.landing_page_index != 0 => {
+                    hints.push(format!("WebUSB landing page: URL {} not read yet", webusb.platform.landing_page_index))
+                },
+                None => hints.push("WebUSB device without a landing page".to_string()),
+            }
+            hints.extend(webusb.errors.iter().map(|error| format!("Warning: WebUSB {}", error)));
+        }
+        
+        // Add hub hints
+        if let Some(hub) = &self.hub {
+            hints.push(format!("Hub with {} downstream port{}", hub.num_ports, if hub.num_ports != 1 { "s" } else { "" }));
+            hints.push(hub.power_switching_mode().to_string());
+            hints.push(get_descriptor_hints(&hub.descriptor_type));
+        }
+        
         hints
     }
     
@@ -1157,10 +2544,12 @@ impl UsbDevice {
                     }
                 },
                 UsbDescriptorType::String => {
-                    // For string descriptors, we need to keep track of their index
-                    let index = self.strings.len() as u8;
-                    if let Ok(string) = StringDescriptor::parse(descriptor_data, index) {
-                        self.strings.push(string);
+                    // Without the GET_DESCRIPTOR request the index and LANGID are unknown;
+                    // add_string_descriptor files strings under their real index
+                    if let Some(vendor_code) = ms_os::os_string_vendor_code(descriptor_data) {
+                        self.ms_os.vendor_code = Some(vendor_code);
+                    } else if let Ok(string) = StringDescriptor::parse_unindexed(descriptor_data) {
+                        self.unindexed_strings.push(string);
                     }
                 },
                 UsbDescriptorType::DeviceQualifier => {
@@ -1168,8 +2557,43 @@ impl UsbDevice {
                         self.device_qualifier = Some(qualifier);
                     }
                 },
+                UsbDescriptorType::Bos => {
+                    // The capabilities follow the header in the same response
+                    if let Ok(bos) = BOSDescriptor::parse(descriptor_data) {
+                        self.bos = Some(bos);
+                        self.device_capabilities.clear();
+                    }
+                },
+                UsbDescriptorType::DeviceCapability => {
+                    if let Ok(cap) = DeviceCapabilityDescriptor::parse(descriptor_data) {
+                        let capability = cap.capability();
+                        if let DeviceCapability::Platform { uuid, data } = &capability {
+                            if uuid == MS_OS_20_PLATFORM_UUID {
+                                self.ms_os.platform = MsOs20PlatformInfo::parse_platform_data(data);
+                            }
+                        }
+                        if let Some(platform) = capability.webusb() {
+                            self.webusb = Some(WebUsb::new(platform));
+                        }
+                        if let Some(ref mut bos) = self.bos {
+                            bos.device_capabilities.push(cap.clone());
+                        }
+                        self.device_capabilities.push(cap);
+                    }
+                },
+                UsbDescriptorType::Debug => {
+                    if let Ok(debug) = DebugDescriptor::parse(descriptor_data) {
+                        self.debug = Some(debug);
+                    }
+                },
+                UsbDescriptorType::Hub | UsbDescriptorType::SuperSpeedHub => {
+                    // Hub descriptors are fetched with a class GET_DESCRIPTOR request
+                    if let Ok(hub) = HubDescriptor::parse(descriptor_data) {
+                        self.hub = Some(hub);
+                    }
+                },
                 _ => {
-                    // We'll process Interface and Endpoint descriptors when linking everything
+                    // We'll process descriptors inside a configuration when linking everything
                 }
             }
             
@@ -1181,105 +2605,194 @@ impl UsbDevice {
         Ok(())
     }
     
+    // Add a string descriptor fetched with GET_DESCRIPTOR(STRING, index) in the language given by wIndex
+    pub fn add_string_descriptor(&mut self, data: &[u8], index: u8, language_id: u16) {
+        self.raw_descriptors.push(data.to_vec());
+        self.insert_string(data, index, language_id);
+        self.link_descriptors();
+    }
+    
+    fn insert_string(&mut self, data: &[u8], index: u8, language_id: u16) {
+        // The Microsoft OS string (index 0xEE) isn't a display string
+        if let Some(vendor_code) = ms_os::os_string_vendor_code(data) {
+            self.ms_os.vendor_code = Some(vendor_code);
+            return;
+        }
+        
+        if let Ok(string) = StringDescriptor::parse(data, index, language_id) {
+            if index == 0 {
+                self.languages = string.language_ids.clone();
+            }
+            self.strings.insert((string.string_index, string.language_id), string);
+        }
+    }
+    
+    // Every captured translation of a string, by LANGID
+    pub fn string_translations(&self, index: u8) -> Vec<(u16, &str)> {
+        if index == 0 {
+            return Vec::new();
+        }
+        self.strings.range((index, 0)..=(index, u16::MAX))
+            .map(|((_, language_id), string)| (*language_id, string.string.as_str()))
+            .collect()
+    }
+    
     // Link descriptors together (configurations -> interfaces -> endpoints)
     // and fill in string descriptors
     fn link_descriptors(&mut self) {
-        // Process each configuration descriptor
-        for i in 0..self.configurations.len() {
-            let mut interfaces = Vec::new();
-            let mut current_interface: Option<InterfaceDescriptor> = None;
+        // Each configuration owns the descriptors that follow it, up to the next
+        // configuration or a descriptor that can't be part of a configuration
+        let mut linked: Vec<LinkedConfiguration> = Vec::new();
+        let mut current_interface: Option<InterfaceDescriptor> = None;
+        let mut in_configuration = false;
+        
+        for raw_desc in &self.raw_descriptors {
+            if raw_desc.len() < 2 {
+                continue;
+            }
+            
+            let desc_type = UsbDescriptorType::from(raw_desc[1]);
             
-            // Find all interface and endpoint descriptors for this configuration
-            for raw_desc in &self.raw_descriptors {
-                if raw_desc.len() < 2 {
-                    continue;
+            // Close the current interface before starting anything that isn't one of its children
+            if matches!(desc_type, UsbDescriptorType::Configuration
+                | UsbDescriptorType::InterfaceAssociation
+                | UsbDescriptorType::Interface) || !in_configuration {
+                if let (Some(iface), Some(LinkedConfiguration { interfaces, .. })) = (current_interface.take(), linked.last_mut()) {
+                    interfaces.push(iface);
                 }
-                
-                let desc_type = UsbDescriptorType::from(raw_desc[1]);
-                
-                match desc_type {
-                    UsbDescriptorType::Interface => {
-                        // If we have a current interface, add it to our list
-                        if let Some(iface) = current_interface.take() {
-                            interfaces.push(iface);
-                        }
-                        
-                        // Parse new interface
-                        if let Ok(iface) = InterfaceDescriptor::parse(raw_desc) {
-                            current_interface = Some(iface);
-                        }
-                    },
-                    UsbDescriptorType::Endpoint => {
-                        // If we have a current interface, add this endpoint to it
-                        if let Some(ref mut iface) = current_interface {
-                            if let Ok(endpoint) = EndpointDescriptor::parse(raw_desc) {
-                                iface.endpoints.push(endpoint);
-                            }
+            }
+            
+            match desc_type {
+                UsbDescriptorType::Configuration => {
+                    // Only descriptors that parsed made it into self.configurations
+                    in_configuration = ConfigurationDescriptor::parse(raw_desc).is_ok();
+                    if in_configuration {
+                        linked.push(LinkedConfiguration::default());
+                    }
+                },
+                UsbDescriptorType::Otg if !in_configuration => {
+                    // Fetched on its own with GET_DESCRIPTOR(OTG)
+                    if let Ok(otg) = OtgDescriptor::parse(raw_desc) {
+                        self.otg = Some(otg);
+                    }
+                },
+                _ if !in_configuration => {},
+                UsbDescriptorType::Otg => {
+                    if let (Ok(otg), Some(configuration)) = (OtgDescriptor::parse(raw_desc), linked.last_mut()) {
+                        configuration.otg = Some(otg);
+                    }
+                },
+                UsbDescriptorType::InterfaceAssociation => {
+                    if let (Ok(association), Some(configuration)) = (InterfaceAssociationDescriptor::parse(raw_desc), linked.last_mut()) {
+                        configuration.associations.push(association);
+                    }
+                },
+                UsbDescriptorType::Interface => {
+                    current_interface = InterfaceDescriptor::parse(raw_desc).ok();
+                },
+                UsbDescriptorType::InterfacePower => {
+                    if let Some(ref mut iface) = current_interface {
+                        iface.interface_power = InterfacePowerDescriptor::parse(raw_desc).ok();
+                    }
+                },
+                UsbDescriptorType::Endpoint => {
+                    // If we have a current interface, add this endpoint to it
+                    if let Some(ref mut iface) = current_interface {
+                        if let Ok(endpoint) = EndpointDescriptor::parse(raw_desc) {
+                            iface.endpoints.push(endpoint);
                         }
-                    },
-                    _ => {
-                        // For other descriptor types within a configuration,
-                        // check if it might be a class-specific descriptor
-                        if let Some(ref mut iface) = current_interface {
-                            if desc_type.get_value() >= 0x21 && desc_type.get_value() <= 0x2F {
-                                // This is likely a class-specific descriptor
-                                iface.class_specific.push(raw_desc.clone());
+                    }
+                },
+                UsbDescriptorType::SuperspeedUsbEndpointCompanion => {
+                    if let Some(endpoint) = current_interface.as_mut().and_then(|iface| iface.endpoints.last_mut()) {
+                        endpoint.ss_companion = SuperSpeedEndpointCompanionDescriptor::parse(raw_desc).ok();
+                    }
+                },
+                UsbDescriptorType::SuperspeedPlusIsochronousEndpointCompanion => {
+                    if let Some(endpoint) = current_interface.as_mut().and_then(|iface| iface.endpoints.last_mut()) {
+                        endpoint.ssp_isochronous_companion = SuperSpeedPlusIsochronousEndpointCompanionDescriptor::parse(raw_desc).ok();
+                    }
+                },
+                UsbDescriptorType::Eusb2IsochronousEndpointCompanion => {
+                    if let Some(endpoint) = current_interface.as_mut().and_then(|iface| iface.endpoints.last_mut()) {
+                        endpoint.eusb2_isochronous_companion = Eusb2IsochronousEndpointCompanionDescriptor::parse(raw_desc).ok();
+                    }
+                },
+                UsbDescriptorType::Device
+                | UsbDescriptorType::Debug
+                | UsbDescriptorType::String
+                | UsbDescriptorType::DeviceQualifier
+                | UsbDescriptorType::Bos
+                | UsbDescriptorType::DeviceCapability
+                | UsbDescriptorType::Hub
+                | UsbDescriptorType::SuperSpeedHub => {
+                    // These come from their own GET_DESCRIPTOR responses
+                    in_configuration = false;
+                },
+                _ => {
+                    // Class-specific descriptors belong to the endpoint they follow,
+                    // or to the interface when no endpoint came yet
+                    if let Some(ref mut iface) = current_interface {
+                        if desc_type.get_value() >= 0x21 && desc_type.get_value() <= 0x2F {
+                            match iface.endpoints.last_mut() {
+                                Some(endpoint) => endpoint.class_specific.push(raw_desc.clone()),
+                                None => iface.class_specific.push(raw_desc.clone()),
                             }
                         }
                     }
                 }
             }
-            
-            // Add the last interface if there is one
-            if let Some(iface) = current_interface {
-                interfaces.push(iface);
-            }
-            
-            // Add interfaces to the configuration
-            if let Some(config) = self.configurations.get_mut(i) {
-                config.interfaces = interfaces;
-            }
+        }
+        
+        // Add the last interface if there is one
+        if let (Some(iface), Some(LinkedConfiguration { interfaces, .. })) = (current_interface, linked.last_mut()) {
+            interfaces.push(iface);
+        }
+        
+        // Add the linked descriptors to the configurations
+        for (config, linked) in self.configurations.iter_mut().zip(linked) {
+            config.otg = linked.otg;
+            config.associations = linked.associations;
+            config.interfaces = linked.interfaces;
         }
         
         // Fill in string descriptors
+        let strings = &self.strings;
+        let languages = &self.languages;
         if let Some(ref mut device) = self.device {
-            // Manufacturer string
-            if device.manufacturer_index > 0 && (device.manufacturer_index as usize) < self.strings.len() {
-                let string = &self.strings[device.manufacturer_index as usize];
-                device.manufacturer_string = Some(string.string.clone());
-            }
-            
-            // Product string
-            if device.product_index > 0 && (device.product_index as usize) < self.strings.len() {
-                let string = &self.strings[device.product_index as usize];
-                device.product_string = Some(string.string.clone());
-            }
-            
-            // Serial number string
-            if device.serial_number_index > 0 && (device.serial_number_index as usize) < self.strings.len() {
-                let string = &self.strings[device.serial_number_index as usize];
-                device.serial_number_string = Some(string.string.clone());
-            }
+            device.manufacturer_string = resolve_string(strings, languages, device.manufacturer_index);
+            device.product_string = resolve_string(strings, languages, device.product_index);
+            device.serial_number_string = resolve_string(strings, languages, device.serial_number_index);
         }
         
         // Configuration strings
         for config in &mut self.configurations {
-            if config.configuration_index > 0 && (config.configuration_index as usize) < self.strings.len() {
-                let string = &self.strings[config.configuration_index as usize];
-                config.configuration_string = Some(string.string.clone());
+            config.configuration_string = resolve_string(strings, languages, config.configuration_index);
+            
+            // Function strings
+            for association in &mut config.associations {
+                association.function_string = resolve_string(strings, languages, association.function_index);
             }
             
             // Interface strings
             for iface in &mut config.interfaces {
-                if iface.interface_index > 0 && (iface.interface_index as usize) < self.strings.len() {
-                    let string = &self.strings[iface.interface_index as usize];
-                    iface.interface_string = Some(string.string.clone());
-                }
+                iface.interface_string = resolve_string(strings, languages, iface.interface_index);
             }
         }
     }
 }
 
+// Look a string up by index, preferring the device's first language
+fn resolve_string(strings: &BTreeMap<(u8, u16), StringDescriptor>, languages: &[u16], index: u8) -> Option<String> {
+    if index == 0 {
+        return None;
+    }
+    languages.iter()
+        .find_map(|language_id| strings.get(&(index, *language_id)))
+        .or_else(|| strings.range((index, 0)..=(index, u16::MAX)).next().map(|(_, string)| string))
+        .map(|string| string.string.clone())
+}
+
 impl fmt::Display for UsbDevice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "USB Device Descriptors:")?;
@@ -1296,11 +2809,200 @@ impl fmt::Display for UsbDevice {
             writeln!(f, "{}", config)?;
         }
         
+        if let Some(ref bos) = self.bos {
+            writeln!(f, "{}", bos)?;
+        }
+        
+        if let Some(ref hub) = self.hub {
+            writeln!(f, "{}", hub)?;
+        }
+        
         writeln!(f, "String Descriptors:")?;
-        for string in &self.strings {
-            writeln!(f, "  [{}]: \"{}\"", string.string_index, string.string)?;
+        if !self.languages.is_empty() {
+            let languages: Vec<String> = self.languages.iter().map(|langid| describe_langid(*langid)).collect();
+            writeln!(f, "  Languages: {}", languages.join(", "))?;
+        }
+        for ((index, language_id), string) in &self.strings {
+            if *index != 0 {
+                writeln!(f, "  [{}] {}: \"{}\"", index, describe_langid(*language_id), string.string)?;
+            }
+        }
+        for string in &self.unindexed_strings {
+            writeln!(f, "  [unknown index]: \"{}\"", string.string)?;
         }
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTAINER_ID: [u8; 16] = [0x78, 0x56, 0x34, 0x12, 0xBC, 0x9A, 0xF0, 0xDE,
+                                    0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+
+    // BOS header followed by USB 2.0 Extension, SuperSpeed and Container ID capabilities
+    fn bos_set() -> Vec<u8> {
+        let mut data = vec![0x05, 0x0F, 0x2A, 0x00, 0x03];
+        data.extend_from_slice(&[0x07, 0x10, 0x02, 0x06, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(&[0x0A, 0x10, 0x03, 0x00, 0x0E, 0x00, 0x01, 0x0A, 0xFF, 0x07]);
+        data.extend_from_slice(&[0x14, 0x10, 0x04, 0x00]);
+        data.extend_from_slice(&CONTAINER_ID);
+        data
+    }
+
+    #[test]
+    fn parses_bos_descriptor_set() {
+        let mut device = UsbDevice::new();
+        device.parse_descriptors(&bos_set()).unwrap();
+
+        let bos = device.bos.as_ref().unwrap();
+        assert_eq!(bos.total_length, 42);
+        assert_eq!(bos.num_device_caps, 3);
+        assert_eq!(bos.device_capabilities.len(), 3);
+
+        let capabilities: Vec<DeviceCapability> = bos.device_capabilities.iter().map(|cap| cap.capability()).collect();
+        assert_eq!(capabilities[0], DeviceCapability::Usb2Extension {
+            lpm: true,
+            besl: true,
+            baseline_besl: None,
+            deep_besl: None,
+        });
+        assert_eq!(capabilities[1], DeviceCapability::SuperSpeed {
+            ltm: false,
+            speeds_supported: 0x000E,
+            functionality_support: 1,
+            u1_exit_latency: 0x0A,
+            u2_exit_latency: 0x07FF,
+        });
+        assert_eq!(capabilities[2], DeviceCapability::ContainerId("12345678-9ABC-DEF0-0123-456789ABCDEF".to_string()));
+        assert_eq!(DeviceCapability::speed_names(0x000E), vec!["Full Speed", "High Speed", "SuperSpeed (5 Gb/s)"]);
+    }
+
+    #[test]
+    fn short_capabilities_stay_undecoded() {
+        assert_eq!(DeviceCapability::decode(CAPABILITY_SUPERSPEED_USB, &[0x00, 0x0E]),
+                   DeviceCapability::Other { capability_type: CAPABILITY_SUPERSPEED_USB, data: vec![0x00, 0x0E] });
+        assert!(BOSDescriptor::parse(&[0x05, 0x0F, 0x05]).is_err());
+        assert!(DeviceCapabilityDescriptor::parse(&[0x03, 0x0F, 0x02]).is_err());
+    }
+
+    #[test]
+    fn out_of_range_functionality_support_is_reserved() {
+        let capability = DeviceCapability::decode(CAPABILITY_SUPERSPEED_USB, &[0x00, 0x0E, 0x00, 0xC8, 0x0A, 0xFF, 0x07]);
+        assert!(format!("{}", capability).contains("bFunctionalitySupport: 200 (Reserved)"));
+    }
+
+    // WebUSB Platform capability: bcdVersion 1.00, vendor code 0x01, landing page URL 1
+    fn webusb_capability() -> Vec<u8> {
+        let mut data = vec![0x18, 0x10, CAPABILITY_PLATFORM, 0x00];
+        data.extend_from_slice(&[0x38, 0xB6, 0x08, 0x34, 0xA9, 0x09, 0xA0, 0x47,
+                                 0x8B, 0xFD, 0xA0, 0x76, 0x88, 0x15, 0xB6, 0x65]);
+        data.extend_from_slice(&[0x00, 0x01, 0x01, 0x01]);
+        data
+    }
+
+    fn url_descriptor(scheme: u8, url: &str) -> Vec<u8> {
+        let mut data = vec![3 + url.len() as u8, WEBUSB_URL_DESCRIPTOR_TYPE, scheme];
+        data.extend_from_slice(url.as_bytes());
+        data
+    }
+
+    #[test]
+    fn parses_webusb_platform_capability() {
+        let mut data = vec![0x05, 0x0F, 0x1D, 0x00, 0x01];
+        data.extend_from_slice(&webusb_capability());
+        let mut device = UsbDevice::new();
+        device.parse_descriptors(&data).unwrap();
+
+        let bos = device.bos.as_ref().unwrap();
+        assert!(bos.platform_capability(WEBUSB_PLATFORM_UUID).is_some());
+        assert_eq!(platform_uuid_name(WEBUSB_PLATFORM_UUID), Some("WebUSB"));
+
+        let webusb = device.webusb.as_ref().unwrap();
+        assert_eq!(webusb.platform, WebUsbPlatform { version: 0x0100, vendor_code: 0x01, landing_page_index: 1 });
+
+        // GET_URL is a vendor IN request with wIndex 2 and the URL index in wValue
+        let get_url = UsbSetupPacket::new(&[0xC0, 0x01, 0x01, 0x00, 0x02, 0x00, 0xFF, 0x00]).unwrap();
+        assert_eq!(webusb.platform.url_request_index(&get_url), Some(1));
+        let other = UsbSetupPacket::new(&[0xC0, 0x01, 0x01, 0x00, 0x07, 0x00, 0xFF, 0x00]).unwrap();
+        assert_eq!(webusb.platform.url_request_index(&other), None);
+    }
+
+    #[test]
+    fn parses_webusb_url_descriptors() {
+        let url = WebUsbUrlDescriptor::parse(&url_descriptor(1, "example.com/app")).unwrap();
+        assert_eq!(url.full_url(), "https://example.com/app");
+        assert_eq!(WebUsbUrlDescriptor::parse(&url_descriptor(255, "localhost")).unwrap().full_url(), "localhost");
+
+        assert!(WebUsbUrlDescriptor::parse(&url_descriptor(2, "example.com")).is_err());
+        assert!(WebUsbUrlDescriptor::parse(&url_descriptor(1, "")).is_err());
+        let mut wrong_type = url_descriptor(1, "example.com");
+        wrong_type[1] = 0x04;
+        assert!(WebUsbUrlDescriptor::parse(&wrong_type).is_err());
+        let mut too_long = url_descriptor(1, "example.com");
+        too_long[0] += 1;
+        assert!(WebUsbUrlDescriptor::parse(&too_long).is_err());
+    }
+
+    #[test]
+    fn webusb_landing_page_comes_from_its_url_index() {
+        let platform = WebUsbPlatform::parse(&[0x00, 0x01, 0x01, 0x02]).unwrap();
+        let mut webusb = WebUsb::new(platform);
+        webusb.add_url_response(1, &url_descriptor(1, "example.com/other"));
+        assert_eq!(webusb.landing_page(), None);
+
+        webusb.add_url_response(2, &url_descriptor(0, "example.com/start"));
+        assert_eq!(webusb.landing_page(), Some("http://example.com/start".to_string()));
+
+        webusb.add_url_response(3, &[0x03, 0x03]);
+        assert_eq!(webusb.errors.len(), 1);
+    }
+
+    fn string_descriptor(text: &str) -> Vec<u8> {
+        let mut data = vec![0x00, 0x03];
+        data.extend(text.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        data[0] = data.len() as u8;
+        data
+    }
+
+    // Device descriptor with iManufacturer 1 and iProduct 2
+    const DEVICE: [u8; 18] = [0x12, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x40, 0x34, 0x12,
+                              0x78, 0x56, 0x00, 0x01, 0x01, 0x02, 0x00, 0x01];
+
+    #[test]
+    fn keys_strings_by_index_and_language() {
+        let mut device = UsbDevice::new();
+        device.parse_descriptors(&DEVICE).unwrap();
+        // String 0: German first, then US English
+        device.add_string_descriptor(&[0x06, 0x03, 0x07, 0x04, 0x09, 0x04], 0, 0);
+        device.add_string_descriptor(&string_descriptor("Acme"), 1, 0x0409);
+        device.add_string_descriptor(&string_descriptor("Widget"), 2, 0x0409);
+        device.add_string_descriptor(&string_descriptor("Apparat"), 2, 0x0407);
+
+        assert_eq!(device.languages, vec![0x0407, 0x0409]);
+        assert!(device.strings[&(0, 0)].is_language_list());
+        assert_eq!(device.strings[&(2, 0x0407)].string, "Apparat");
+        assert_eq!(device.strings[&(2, 0x0409)].string, "Widget");
+        assert_eq!(device.string_translations(2), vec![(0x0407, "Apparat"), (0x0409, "Widget")]);
+        assert!(device.string_translations(0).is_empty());
+
+        // The first language wins, falling back to any translation
+        let descriptor = device.device.as_ref().unwrap();
+        assert_eq!(descriptor.product_string.as_deref(), Some("Apparat"));
+        assert_eq!(descriptor.manufacturer_string.as_deref(), Some("Acme"));
+        assert_eq!(descriptor.serial_number_string, None);
+    }
+
+    #[test]
+    fn strings_without_their_request_are_unindexed() {
+        let mut device = UsbDevice::new();
+        device.parse_descriptors(&string_descriptor("Acme")).unwrap();
+        assert!(device.strings.is_empty());
+        let string = &device.unindexed_strings[0];
+        assert!(string.unknown_index);
+        assert!(!string.is_language_list());
+        assert_eq!(string.string, "Acme");
+        assert!(string.to_string().contains("Index: unknown"));
+    }
+}
@@ -0,0 +1,188 @@
+// Device state compliance checking
+// USB 2.0 section 9.1.1 defines the visible device states. After reset a device is in the
+// Default state at address 0, SET_ADDRESS moves it to Address, and SET_CONFIGURATION with a
+// non-zero value moves it to Configured. Section 9.4 lists which requests are valid in each
+// state; anything else is a request error. Violations are reported per transaction.
+
+use std::fmt;
+use super::bus::{BusModel, DEFAULT_ADDRESS};
+use super::mitm_traffic::{
+    UsbControlRecipient,
+    UsbControlRequestType,
+    UsbStandardRequest,
+    UsbTransaction,
+};
+
+// Highest address SET_ADDRESS may assign
+const MAX_DEVICE_ADDRESS: u16 = 127;
+
+// Chapter 9 device states (Attached and Powered can't be told apart from traffic)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceState {
+    Default,
+    Address,
+    Configured,
+    Suspended,
+}
+
+impl fmt::Display for DeviceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceState::Default => write!(f, "Default"),
+            DeviceState::Address => write!(f, "Address"),
+            DeviceState::Configured => write!(f, "Configured"),
+            DeviceState::Suspended => write!(f, "Suspended"),
+        }
+    }
+}
+
+// A request or transfer that isn't allowed in the device's current state
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateViolation {
+    // SET_CONFIGURATION while the device is still at the default address
+    ConfigurationBeforeAddress,
+    // SET_CONFIGURATION with a value none of the configuration descriptors has
+    UnknownConfiguration(u8),
+    // SET_ADDRESS outside 0-127
+    InvalidAddress(u16),
+    // SET_ADDRESS on a configured device; behaviour is unspecified
+    AddressWhileConfigured,
+    // GET_INTERFACE/SET_INTERFACE before the device is configured
+    InterfaceRequestUnconfigured { request: UsbStandardRequest, state: DeviceState },
+    // SET_INTERFACE to an interface or alternate setting the active configuration doesn't have
+    UnknownAlternateSetting { interface: u8, alternate_setting: u8 },
+    // Data or an endpoint-directed request for a non-zero endpoint before configuration
+    EndpointUnconfigured { endpoint: u8, state: DeviceState },
+    // Traffic on an endpoint that isn't part of the active configuration
+    UnknownEndpoint { endpoint: u8 },
+    // Traffic while the bus is suspended, without resume signalling first
+    TrafficWhileSuspended,
+}
+
+impl fmt::Display for StateViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateViolation::ConfigurationBeforeAddress => {
+                write!(f, "SET_CONFIGURATION in Default state (before SET_ADDRESS)")
+            },
+            StateViolation::UnknownConfiguration(value) => {
+                write!(f, "SET_CONFIGURATION to configuration {}, which the device doesn't report", value)
+            },
+            StateViolation::InvalidAddress(address) => {
+                write!(f, "SET_ADDRESS to invalid address {}", address)
+            },
+            StateViolation::AddressWhileConfigured => {
+                write!(f, "SET_ADDRESS in Configured state (behaviour unspecified)")
+            },
+            StateViolation::InterfaceRequestUnconfigured { request, state } => {
+                write!(f, "{} in {} state (only valid when Configured)", request, state)
+            },
+            StateViolation::UnknownAlternateSetting { interface, alternate_setting } => {
+                write!(f, "SET_INTERFACE to interface {} alternate setting {}, which the configuration doesn't have",
+                       interface, alternate_setting)
+            },
+            StateViolation::EndpointUnconfigured { endpoint, state } => {
+                write!(f, "Endpoint {} used in {} state (only endpoint 0 is valid before configuration)",
+                       endpoint, state)
+            },
+            StateViolation::UnknownEndpoint { endpoint } => {
+                write!(f, "Endpoint {} isn't part of the active configuration", endpoint)
+            },
+            StateViolation::TrafficWhileSuspended => {
+                write!(f, "Traffic in {} state without resume signalling", DeviceState::Suspended)
+            },
+        }
+    }
+}
+
+// Check one transaction against its device's state before the bus model applies it.
+// Devices whose state can't be known (first seen mid-session) are only checked for
+// problems that don't depend on the state.
+pub fn check_transaction(bus: &BusModel, transaction: &UsbTransaction, suspended: bool) -> Vec<StateViolation> {
+    let mut violations = Vec::new();
+    let address = transaction.device_address;
+    let device = bus.device(address);
+
+    let state = match device {
+        Some(device) => device.state(),
+        None if address == DEFAULT_ADDRESS => Some(DeviceState::Default),
+        None => None,
+    };
+
+    if suspended {
+        violations.push(StateViolation::TrafficWhileSuspended);
+    }
+
+    let unconfigured = matches!(state, Some(DeviceState::Default) | Some(DeviceState::Address));
+
+    if transaction.endpoint != 0 {
+        if unconfigured {
+            violations.push(StateViolation::EndpointUnconfigured {
+                endpoint: transaction.endpoint,
+                state: state.unwrap_or(DeviceState::Default),
+            });
+        } else if state == Some(DeviceState::Configured)
+            && device.and_then(|device| device.active_configuration()).is_some()
+            && bus.resolve_endpoint(transaction).is_none() {
+            violations.push(StateViolation::UnknownEndpoint { endpoint: transaction.endpoint });
+        }
+        return violations;
+    }
+
+    let setup = match &transaction.setup_packet {
+        Some(setup) if setup.request_type == UsbControlRequestType::Standard => setup,
+        _ => return violations,
+    };
+
+    match setup.standard_request {
+        Some(UsbStandardRequest::SetAddress) => {
+            if setup.wValue > MAX_DEVICE_ADDRESS {
+                violations.push(StateViolation::InvalidAddress(setup.wValue));
+            }
+            if state == Some(DeviceState::Configured) {
+                violations.push(StateViolation::AddressWhileConfigured);
+            }
+        },
+        Some(UsbStandardRequest::SetConfiguration) => {
+            let value = (setup.wValue & 0xFF) as u8;
+            if state == Some(DeviceState::Default) {
+                violations.push(StateViolation::ConfigurationBeforeAddress);
+            }
+            let known = device.map(|device| &device.device.configurations)
+                .filter(|configurations| !configurations.is_empty());
+            if let Some(configurations) = known {
+                if value != 0 && !configurations.iter().any(|config| config.configuration_value == value) {
+                    violations.push(StateViolation::UnknownConfiguration(value));
+                }
+            }
+        },
+        Some(request @ (UsbStandardRequest::SetInterface | UsbStandardRequest::GetInterface)) => {
+            if let (true, Some(state)) = (unconfigured, state) {
+                violations.push(StateViolation::InterfaceRequestUnconfigured { request, state });
+            } else if request == UsbStandardRequest::SetInterface {
+                let interface = (setup.wIndex & 0xFF) as u8;
+                let alternate_setting = (setup.wValue & 0xFF) as u8;
+                if let Some(configuration) = device.and_then(|device| device.active_configuration()) {
+                    let exists = configuration.interfaces.iter().any(|candidate| {
+                        candidate.interface_number == interface && candidate.alternate_setting == alternate_setting
+                    });
+                    if !exists {
+                        violations.push(StateViolation::UnknownAlternateSetting { interface, alternate_setting });
+                    }
+                }
+            }
+        },
+        // Endpoint-directed requests may only name endpoint 0 before configuration
+        Some(UsbStandardRequest::GetStatus | UsbStandardRequest::ClearFeature |
+             UsbStandardRequest::SetFeature | UsbStandardRequest::SynchFrame)
+            if setup.recipient == UsbControlRecipient::Endpoint => {
+            let endpoint = (setup.wIndex & 0x0F) as u8;
+            if let (true, Some(state)) = (unconfigured && endpoint != 0, state) {
+                violations.push(StateViolation::EndpointUnconfigured { endpoint, state });
+            }
+        },
+        _ => {},
+    }
+
+    violations
+}
@@ -0,0 +1,472 @@
+// Enumeration sequence recognition and timing compliance
+// The host enumerates a new device with a fixed sequence: reset, GET_DESCRIPTOR(Device),
+// SET_ADDRESS, more GET_DESCRIPTOR requests and finally SET_CONFIGURATION. USB 2.0 puts
+// timing limits on most of these steps (sections 7.1.7.3, 7.1.7.5, 7.1.7.7, 9.2.6.3 and
+// 9.2.6.4). The analyzer measures them from timestamped traffic and bus events and keeps
+// a pass/fail report per device.
+// Transactions only carry the host receive time, which batched USB delivery squeezes
+// together, so they are placed in bus time by counting SOFs instead. Intervals that start
+// at a bus event are measured from the first SOF after it, and from the analyzer's own
+// event timestamps where the interval runs between two bus events. Without SOFs the host
+// time is all there is; those results are shown as approximate and never fail.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use super::bus_event::{BusEvent, BusEventKind};
+use super::mitm_traffic::{
+    UsbControlRequestType,
+    UsbDirection,
+    UsbSetupPacket,
+    UsbStandardRequest,
+    UsbTransaction,
+    UsbTransferStatus,
+};
+
+// Timing limits in seconds
+const RESET_MIN_SECONDS: f64 = 0.010;            // TDRST, reset from a downstream port
+const RESET_RECOVERY_SECONDS: f64 = 0.010;       // TRSTRCY, reset to first request
+const ATTACH_DEBOUNCE_SECONDS: f64 = 0.100;      // TATTDB, attach debounce before the host starts
+const SET_ADDRESS_RECOVERY_SECONDS: f64 = 0.002; // TDSETADDR, SET_ADDRESS status to new address
+const NO_DATA_REQUEST_SECONDS: f64 = 0.050;      // Requests without a data stage
+const DATA_PACKET_SECONDS: f64 = 0.500;          // Each data stage packet
+const STATUS_STAGE_SECONDS: f64 = 0.050;         // Status stage after the last data packet
+const CONTROL_TRANSFER_SECONDS: f64 = 5.0;       // Whole control transfer
+const RESUME_RECOVERY_SECONDS: f64 = 0.010;      // TRSMRCY, resume to first traffic
+
+// Standard descriptor types requested during enumeration
+const DEVICE_DESCRIPTOR: u8 = 1;
+const CONFIGURATION_DESCRIPTOR: u8 = 2;
+const STRING_DESCRIPTOR: u8 = 3;
+
+// Enumeration requests recognised from a setup packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumerationStep {
+    GetDeviceDescriptor,
+    GetConfigurationDescriptor,
+    GetStringDescriptor(u8),         // String index
+    GetDescriptor { descriptor_type: u8, index: u8 },
+    SetAddress(u8),
+    SetConfiguration(u8),
+}
+
+impl EnumerationStep {
+    // Recognise a standard enumeration request from the raw setup fields
+    pub fn from_setup(bm_request_type: u8, b_request: u8, w_value: u16) -> Option<Self> {
+        // Only standard requests (type bits 5-6 clear) take part in enumeration
+        if (bm_request_type >> 5) & 0x03 != 0 {
+            return None;
+        }
+
+        let descriptor_type = (w_value >> 8) as u8;
+        let index = (w_value & 0xFF) as u8;
+
+        match b_request {
+            0x06 => Some(match descriptor_type {
+                DEVICE_DESCRIPTOR => EnumerationStep::GetDeviceDescriptor,
+                CONFIGURATION_DESCRIPTOR => EnumerationStep::GetConfigurationDescriptor,
+                STRING_DESCRIPTOR => EnumerationStep::GetStringDescriptor(index),
+                _ => EnumerationStep::GetDescriptor { descriptor_type, index },
+            }),
+            0x05 => Some(EnumerationStep::SetAddress((w_value & 0x7F) as u8)),
+            0x09 => Some(EnumerationStep::SetConfiguration(index)),
+            _ => None,
+        }
+    }
+
+    pub fn from_setup_packet(setup: &UsbSetupPacket) -> Option<Self> {
+        Self::from_setup(setup.bmRequestType, setup.bRequest, setup.wValue)
+    }
+}
+
+// Which way a limit goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingLimit {
+    AtLeast(f64),
+    AtMost(f64),
+}
+
+impl TimingLimit {
+    pub fn allows(&self, measured: f64) -> bool {
+        match self {
+            TimingLimit::AtLeast(limit) => measured >= *limit,
+            TimingLimit::AtMost(limit) => measured <= *limit,
+        }
+    }
+}
+
+impl fmt::Display for TimingLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimingLimit::AtLeast(limit) => write!(f, "min {}", format_duration(*limit)),
+            TimingLimit::AtMost(limit) => write!(f, "max {}", format_duration(*limit)),
+        }
+    }
+}
+
+// The timing rules checked during enumeration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingRule {
+    ResetDuration,
+    ResetRecovery,
+    AttachToFirstSetup,
+    SetAddressRecovery,
+    NoDataRequest,
+    DataStagePacket,
+    StatusStage,
+    ControlTransfer,
+    ResumeRecovery,
+}
+
+impl TimingRule {
+    pub fn limit(&self) -> TimingLimit {
+        match self {
+            TimingRule::ResetDuration => TimingLimit::AtLeast(RESET_MIN_SECONDS),
+            TimingRule::ResetRecovery => TimingLimit::AtLeast(RESET_RECOVERY_SECONDS),
+            TimingRule::AttachToFirstSetup => TimingLimit::AtLeast(ATTACH_DEBOUNCE_SECONDS),
+            TimingRule::SetAddressRecovery => TimingLimit::AtLeast(SET_ADDRESS_RECOVERY_SECONDS),
+            TimingRule::NoDataRequest => TimingLimit::AtMost(NO_DATA_REQUEST_SECONDS),
+            TimingRule::DataStagePacket => TimingLimit::AtMost(DATA_PACKET_SECONDS),
+            TimingRule::StatusStage => TimingLimit::AtMost(STATUS_STAGE_SECONDS),
+            TimingRule::ControlTransfer => TimingLimit::AtMost(CONTROL_TRANSFER_SECONDS),
+            TimingRule::ResumeRecovery => TimingLimit::AtLeast(RESUME_RECOVERY_SECONDS),
+        }
+    }
+}
+
+impl fmt::Display for TimingRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimingRule::ResetDuration => write!(f, "Reset duration"),
+            TimingRule::ResetRecovery => write!(f, "Reset recovery"),
+            TimingRule::AttachToFirstSetup => write!(f, "Attach to first SETUP"),
+            TimingRule::SetAddressRecovery => write!(f, "SET_ADDRESS recovery"),
+            TimingRule::NoDataRequest => write!(f, "Request without data stage"),
+            TimingRule::DataStagePacket => write!(f, "Data stage packet"),
+            TimingRule::StatusStage => write!(f, "Status stage"),
+            TimingRule::ControlTransfer => write!(f, "Control transfer"),
+            TimingRule::ResumeRecovery => write!(f, "Resume recovery"),
+        }
+    }
+}
+
+fn format_duration(seconds: f64) -> String {
+    if seconds >= 1.0 {
+        format!("{:.3} s", seconds)
+    } else {
+        format!("{:.3} ms", seconds * 1000.0)
+    }
+}
+
+// The clock a measurement was taken on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingClock {
+    Analyzer,     // Analyzer timestamps on bus events
+    Frames(f64),  // (Micro)frames counted from SOFs, to within one frame (the value, in seconds)
+    HostTime,     // Host receive time, distorted by batched delivery
+}
+
+// One measurement against one rule
+#[derive(Debug, Clone)]
+pub struct TimingCheck {
+    pub rule: TimingRule,
+    pub timestamp: f64,
+    pub measured: f64,
+    pub clock: TimingClock,
+    pub request: Option<String>, // Request the measurement belongs to
+}
+
+impl TimingCheck {
+    pub fn is_approximate(&self) -> bool {
+        self.clock == TimingClock::HostTime
+    }
+
+    // Frame-counted measurements pass when the limit is within their resolution, and
+    // host-time measurements are never failed
+    pub fn passed(&self) -> bool {
+        match (self.clock, self.rule.limit()) {
+            (TimingClock::Analyzer, limit) => limit.allows(self.measured),
+            (TimingClock::Frames(resolution), TimingLimit::AtLeast(_)) => self.rule.limit().allows(self.measured + resolution),
+            (TimingClock::Frames(resolution), TimingLimit::AtMost(_)) => self.rule.limit().allows(self.measured - resolution),
+            (TimingClock::HostTime, _) => true,
+        }
+    }
+}
+
+impl fmt::Display for TimingCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.clock {
+            TimingClock::Analyzer => write!(f, "{} {}: {} ({})",
+                                            if self.passed() { "PASS" } else { "FAIL" },
+                                            self.rule, format_duration(self.measured), self.rule.limit())?,
+            TimingClock::Frames(resolution) => write!(f, "{} {}: {} ({}, SOF-counted ±{})",
+                                                      if self.passed() { "PASS" } else { "FAIL" },
+                                                      self.rule, format_duration(self.measured), self.rule.limit(),
+                                                      format_duration(resolution))?,
+            TimingClock::HostTime => write!(f, "APPROX {}: ~{} ({}, host time, not checked)",
+                                            self.rule, format_duration(self.measured), self.rule.limit())?,
+        }
+        match &self.request {
+            Some(request) => write!(f, " - {}", request),
+            None => Ok(()),
+        }
+    }
+}
+
+// All timing checks for one device
+#[derive(Debug, Clone)]
+pub struct TimingReport {
+    pub address: u8,
+    pub checks: Vec<TimingCheck>,
+}
+
+impl TimingReport {
+    pub fn failure_count(&self) -> usize {
+        self.checks.iter().filter(|check| !check.passed()).count()
+    }
+
+    pub fn approximate_count(&self) -> usize {
+        self.checks.iter().filter(|check| check.is_approximate()).count()
+    }
+}
+
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures = self.failure_count();
+        let approximate = self.approximate_count();
+        write!(f, "Device {}: {} passed, {} failed",
+               self.address, self.checks.len() - failures - approximate, failures)?;
+        if approximate > 0 {
+            write!(f, ", {} approximate", approximate)?;
+        }
+        Ok(())
+    }
+}
+
+// A point in the capture: host receive time, plus SOF-counted bus time once it is known
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    host: f64,
+    frames: Option<f64>,
+}
+
+impl Mark {
+    // Interval to a later mark, on the SOF count when both ends have one
+    fn until(&self, end: Mark, resolution: f64) -> (f64, TimingClock) {
+        match (self.frames, end.frames) {
+            (Some(start), Some(end)) => (end - start, TimingClock::Frames(resolution)),
+            _ => (end.host - self.host, TimingClock::HostTime),
+        }
+    }
+}
+
+// A control transfer whose stages are still arriving
+#[derive(Debug, Clone)]
+struct PendingControl {
+    setup: UsbSetupPacket,
+    start: Mark,
+    last_activity: Mark,
+    longest_data_gap: Option<(f64, TimingClock)>,
+}
+
+// Measures enumeration timing from bus events and packet-level transactions
+#[derive(Debug, Clone, Default)]
+pub struct EnumerationTimingAnalyzer {
+    reports: BTreeMap<u8, TimingReport>,
+    attached_at: Option<f64>,                 // Waiting for the first SETUP after attach
+    attach_to_reset: Option<f64>,             // Attach to the end of reset, by analyzer time
+    reset_ended_at: Option<Mark>,             // Waiting for the first SETUP after reset
+    resume_ended_at: Option<Mark>,            // Waiting for the first traffic after resume
+    set_address: Option<(u8, u8, Option<Mark>)>, // Old address, new address, status stage time
+    pending: HashMap<u8, PendingControl>,     // Control transfers by device address
+    resolution: f64,                          // Length of one SOF-counted (micro)frame
+}
+
+impl EnumerationTimingAnalyzer {
+    pub fn new() -> Self {
+        EnumerationTimingAnalyzer::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = EnumerationTimingAnalyzer::default();
+    }
+
+    pub fn reports(&self) -> impl Iterator<Item = &TimingReport> {
+        self.reports.values()
+    }
+
+    fn record(&mut self, address: u8, rule: TimingRule, timestamp: f64, (measured, clock): (f64, TimingClock),
+              request: Option<String>) {
+        self.reports.entry(address)
+            .or_insert_with(|| TimingReport { address, checks: Vec::new() })
+            .checks.push(TimingCheck { rule, timestamp, measured, clock, request });
+    }
+
+    // Feed the bus time of each SOF. Intervals starting at a bus event are counted from
+    // the first SOF after it, since the host only starts sending SOFs again once the
+    // reset or resume is over.
+    pub fn process_sof(&mut self, bus_time: f64, resolution: f64) {
+        self.resolution = resolution;
+        for mark in [&mut self.reset_ended_at, &mut self.resume_ended_at].into_iter().flatten() {
+            mark.frames.get_or_insert(bus_time);
+        }
+    }
+
+    // Feed one bus event. Returns true when a report changed.
+    pub fn process_bus_event(&mut self, event: &BusEvent) -> bool {
+        match (&event.kind, event.duration) {
+            (BusEventKind::DeviceAttached(_), _) => {
+                self.attached_at = Some(event.timestamp);
+                self.attach_to_reset = None;
+                false
+            },
+            (BusEventKind::BusReset, duration) => {
+                // Recovery is only timed when the analyzer stamped the reset
+                let ended = duration.map(|duration| event.timestamp + duration);
+                self.reset_ended_at = ended.map(|host| Mark { host, frames: None });
+                // Both ends are analyzer timestamps, so this part of the attach interval is exact
+                self.attach_to_reset = ended.zip(self.attached_at).map(|(ended, attached)| ended - attached);
+                self.pending.clear();
+                self.set_address = None;
+                // Reset puts the device back at the default address
+                if let Some(duration) = duration {
+                    self.record(0, TimingRule::ResetDuration, event.timestamp, (duration, TimingClock::Analyzer), None);
+                }
+                duration.is_some()
+            },
+            (BusEventKind::Resume | BusEventKind::RemoteWakeup, Some(duration)) => {
+                self.resume_ended_at = Some(Mark { host: event.timestamp + duration, frames: None });
+                false
+            },
+            (BusEventKind::DeviceDetached | BusEventKind::VbusDisconnected, _) => {
+                self.attached_at = None;
+                self.attach_to_reset = None;
+                self.reset_ended_at = None;
+                self.set_address = None;
+                self.pending.clear();
+                false
+            },
+            _ => false,
+        }
+    }
+
+    // Feed one transaction with its SOF-counted bus time, if SOFs have been seen. Stage
+    // timing needs a packet-level capture, where each stage arrives as its own transaction.
+    // Returns true when a report changed.
+    pub fn process_transaction(&mut self, transaction: &UsbTransaction, bus_time: Option<f64>) -> bool {
+        let address = transaction.device_address;
+        let now = transaction.timestamp;
+        let here = Mark { host: now, frames: bus_time };
+        let resolution = self.resolution;
+        let mut changed = false;
+
+        if let Some(resumed) = self.resume_ended_at.take() {
+            self.record(address, TimingRule::ResumeRecovery, now, resumed.until(here, resolution), None);
+            changed = true;
+        }
+
+        if let Some(setup) = &transaction.setup_packet {
+            let request = Some(setup.request_description.clone());
+
+            let recovery = self.reset_ended_at.take().map(|reset| reset.until(here, resolution));
+            if let Some(attached) = self.attached_at.take() {
+                // Attach to reset end by analyzer time, then reset end to SETUP by SOF count
+                let measured = match (self.attach_to_reset.take(), recovery) {
+                    (Some(lead), Some((recovery, clock @ TimingClock::Frames(_)))) => (lead + recovery, clock),
+                    _ => (now - attached, TimingClock::HostTime),
+                };
+                self.record(address, TimingRule::AttachToFirstSetup, now, measured, request.clone());
+                changed = true;
+            }
+            if let Some(recovery) = recovery {
+                self.record(address, TimingRule::ResetRecovery, now, recovery, request.clone());
+                changed = true;
+            }
+
+            // The device's report follows it to its new address
+            if let Some((from, to, Some(completed))) = self.set_address {
+                if address == to {
+                    if let Some(mut report) = self.reports.remove(&from) {
+                        report.address = to;
+                        report.checks.extend(self.reports.remove(&to).map(|old| old.checks).unwrap_or_default());
+                        self.reports.insert(to, report);
+                    }
+                    self.record(to, TimingRule::SetAddressRecovery, now, completed.until(here, resolution), request);
+                    self.set_address = None;
+                    changed = true;
+                }
+            }
+
+            if let Some(EnumerationStep::SetAddress(new_address)) = EnumerationStep::from_setup_packet(setup) {
+                // Transactions that carry the whole transfer complete at their own timestamp
+                let completed = transaction.status_packet.as_ref()
+                    .filter(|status| status.status == UsbTransferStatus::ACK && !transaction.fields.contains_key("Token"))
+                    .map(|_| here);
+                self.set_address = Some((address, new_address, completed));
+            }
+
+            if transaction.fields.contains_key("Token") && transaction.endpoint == 0 {
+                self.pending.insert(address, PendingControl {
+                    setup: setup.clone(),
+                    start: here,
+                    last_activity: here,
+                    longest_data_gap: None,
+                });
+            }
+            return changed;
+        }
+
+        if transaction.endpoint != 0 {
+            return changed;
+        }
+
+        // NAKed attempts are retries; the stage completes with the acknowledged one
+        let accepted = matches!(transaction.status_packet.as_ref().map(|status| status.status),
+                                Some(UsbTransferStatus::ACK) | Some(UsbTransferStatus::NYET));
+        let pending = match self.pending.get_mut(&address) {
+            Some(pending) if accepted => pending,
+            _ => return changed,
+        };
+
+        let has_data_stage = pending.setup.wLength > 0;
+        if has_data_stage && transaction.direction() == pending.setup.direction {
+            let gap = pending.last_activity.until(here, resolution);
+            if pending.longest_data_gap.is_none_or(|(longest, _)| gap.0 > longest) {
+                pending.longest_data_gap = Some(gap);
+            }
+            pending.last_activity = here;
+            return changed;
+        }
+
+        // Anything else acknowledged on EP0 is the status stage
+        if !has_data_stage && transaction.direction() != UsbDirection::DeviceToHost {
+            return changed;
+        }
+        let pending = match self.pending.remove(&address) {
+            Some(pending) => pending,
+            None => return changed,
+        };
+        let request = Some(pending.setup.request_description.clone());
+
+        if has_data_stage {
+            if let Some(gap) = pending.longest_data_gap {
+                self.record(address, TimingRule::DataStagePacket, now, gap, request.clone());
+            }
+            self.record(address, TimingRule::StatusStage, now, pending.last_activity.until(here, resolution), request.clone());
+            self.record(address, TimingRule::ControlTransfer, now, pending.start.until(here, resolution), request);
+        } else {
+            self.record(address, TimingRule::NoDataRequest, now, pending.start.until(here, resolution), request);
+        }
+
+        // SET_ADDRESS takes effect once its status stage completes
+        if pending.setup.request_type == UsbControlRequestType::Standard
+            && pending.setup.standard_request == Some(UsbStandardRequest::SetAddress) {
+            if let Some((from, _, completed)) = self.set_address.as_mut() {
+                if *from == address {
+                    *completed = Some(here);
+                }
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,301 @@
+// Host OS fingerprinting from enumeration patterns
+// Every host stack enumerates a new device in its own way: how much of the device
+// descriptor it reads first, whether it resets again before SET_ADDRESS, how it fetches
+// the configuration descriptor and which optional descriptors it asks for. The observed
+// sequence is scored against a built-in signature table. A capture has one host, so
+// observations from every device on the bus count towards the same fingerprint.
+
+use std::fmt;
+use super::bus_event::{BusEvent, BusEventKind};
+use super::enumeration::EnumerationStep;
+use super::mitm_traffic::UsbTransaction;
+
+// Descriptor types and string indices that only some hosts request
+const DEVICE_QUALIFIER_DESCRIPTOR: u8 = 6;
+const BOS_DESCRIPTOR: u8 = 15;
+const MS_OS_STRING_INDEX: u8 = 0xEE;
+
+// Observable properties of the enumeration sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostFeature {
+    FirstDeviceRequestLength(u16), // wLength of the first GET_DESCRIPTOR(Device)
+    ResetBeforeSetAddress,         // Bus reset between the first descriptor read and SET_ADDRESS
+    SetAddressFirst,               // SET_ADDRESS before any GET_DESCRIPTOR
+    ConfigurationHeaderFirst,      // First GET_DESCRIPTOR(Configuration) asks for 9 bytes
+    ConfigurationFullFirst,        // First GET_DESCRIPTOR(Configuration) asks for 255 bytes or more
+    LanguageListRequested,         // String descriptor 0
+    MsOsStringRequested,           // String descriptor 0xEE (Microsoft OS 1.0)
+    BosRequested,
+    DeviceQualifierRequested,
+}
+
+impl fmt::Display for HostFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostFeature::FirstDeviceRequestLength(length) => {
+                write!(f, "first device descriptor request for {} bytes", length)
+            },
+            HostFeature::ResetBeforeSetAddress => write!(f, "reset before SET_ADDRESS"),
+            HostFeature::SetAddressFirst => write!(f, "SET_ADDRESS before any descriptor"),
+            HostFeature::ConfigurationHeaderFirst => write!(f, "9-byte configuration header first"),
+            HostFeature::ConfigurationFullFirst => write!(f, "full configuration descriptor first"),
+            HostFeature::LanguageListRequested => write!(f, "string 0 (language list) requested"),
+            HostFeature::MsOsStringRequested => write!(f, "MS OS string descriptor (0xEE) requested"),
+            HostFeature::BosRequested => write!(f, "BOS descriptor requested"),
+            HostFeature::DeviceQualifierRequested => write!(f, "device qualifier requested"),
+        }
+    }
+}
+
+// One expectation of a signature: whether the feature should be present, and its weight
+#[derive(Debug, Clone, Copy)]
+pub struct FeatureExpectation {
+    pub feature: HostFeature,
+    pub present: bool,
+    pub weight: u32,
+}
+
+const fn expect(feature: HostFeature, present: bool, weight: u32) -> FeatureExpectation {
+    FeatureExpectation { feature, present, weight }
+}
+
+// A known host enumeration pattern
+#[derive(Debug, Clone, Copy)]
+pub struct HostSignature {
+    pub os: &'static str,
+    pub stack: &'static str,
+    pub expectations: &'static [FeatureExpectation],
+}
+
+// Built-in signature table
+pub const HOST_SIGNATURES: &[HostSignature] = &[
+    HostSignature {
+        os: "Windows",
+        stack: "usbhub3/usbport",
+        expectations: &[
+            expect(HostFeature::FirstDeviceRequestLength(64), true, 3),
+            expect(HostFeature::ResetBeforeSetAddress, true, 2),
+            expect(HostFeature::ConfigurationFullFirst, true, 3),
+            expect(HostFeature::ConfigurationHeaderFirst, false, 1),
+            expect(HostFeature::MsOsStringRequested, true, 4),
+            expect(HostFeature::BosRequested, true, 1),
+            expect(HostFeature::DeviceQualifierRequested, true, 1),
+        ],
+    },
+    HostSignature {
+        os: "Linux",
+        stack: "usbcore (new enumeration scheme)",
+        expectations: &[
+            expect(HostFeature::FirstDeviceRequestLength(64), true, 3),
+            expect(HostFeature::ResetBeforeSetAddress, true, 2),
+            expect(HostFeature::ConfigurationHeaderFirst, true, 3),
+            expect(HostFeature::ConfigurationFullFirst, false, 1),
+            expect(HostFeature::MsOsStringRequested, false, 2),
+            expect(HostFeature::LanguageListRequested, true, 1),
+            expect(HostFeature::BosRequested, true, 1),
+        ],
+    },
+    HostSignature {
+        os: "Linux",
+        stack: "usbcore (old enumeration scheme)",
+        expectations: &[
+            expect(HostFeature::SetAddressFirst, true, 4),
+            expect(HostFeature::FirstDeviceRequestLength(8), true, 2),
+            expect(HostFeature::ConfigurationHeaderFirst, true, 2),
+            expect(HostFeature::MsOsStringRequested, false, 2),
+        ],
+    },
+    HostSignature {
+        os: "macOS",
+        stack: "IOUSBHostFamily",
+        expectations: &[
+            expect(HostFeature::FirstDeviceRequestLength(8), true, 3),
+            expect(HostFeature::ResetBeforeSetAddress, false, 1),
+            expect(HostFeature::SetAddressFirst, false, 1),
+            expect(HostFeature::ConfigurationHeaderFirst, true, 2),
+            expect(HostFeature::MsOsStringRequested, false, 2),
+            expect(HostFeature::LanguageListRequested, true, 1),
+        ],
+    },
+];
+
+// A signature's score against the observed features
+#[derive(Debug, Clone)]
+pub struct FingerprintMatch {
+    pub signature: &'static HostSignature,
+    pub score: u32,
+    pub possible: u32,              // Total weight of the features that could be checked
+    pub matched: Vec<HostFeature>,
+    pub contradicted: Vec<HostFeature>,
+}
+
+impl FingerprintMatch {
+    pub fn confidence(&self) -> f64 {
+        if self.possible == 0 {
+            0.0
+        } else {
+            self.score as f64 / self.possible as f64
+        }
+    }
+}
+
+impl fmt::Display for FingerprintMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {:.0}% match", self.signature.os, self.signature.stack, self.confidence() * 100.0)?;
+        if !self.matched.is_empty() {
+            let matched: Vec<String> = self.matched.iter().map(|feature| format!("{}", feature)).collect();
+            write!(f, " - {}", matched.join(", "))?;
+        }
+        if !self.contradicted.is_empty() {
+            let contradicted: Vec<String> = self.contradicted.iter().map(|feature| format!("{}", feature)).collect();
+            write!(f, "; unexpected: {}", contradicted.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// Collects enumeration observations and matches them against the signature table
+#[derive(Debug, Clone, Default)]
+pub struct HostFingerprinter {
+    first_device_request: Option<u16>,
+    first_configuration_request: Option<u16>,
+    descriptor_seen: bool,              // A GET_DESCRIPTOR has been seen
+    set_address_seen: bool,
+    set_address_first: Option<bool>,
+    reset_pending: bool,                // Reset seen after the first descriptor read
+    reset_before_set_address: Option<bool>,
+    bus_events_seen: bool,              // Without bus events, resets can't be observed
+    language_list: bool,
+    ms_os_string: bool,
+    bos: bool,
+    device_qualifier: bool,
+    request_count: usize,
+}
+
+impl HostFingerprinter {
+    pub fn new() -> Self {
+        HostFingerprinter::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = HostFingerprinter::default();
+    }
+
+    pub fn process_bus_event(&mut self, event: &BusEvent) {
+        self.bus_events_seen = true;
+        if event.kind == BusEventKind::BusReset && self.descriptor_seen && !self.set_address_seen {
+            self.reset_pending = true;
+        }
+    }
+
+    // Feed one transaction. Returns true when it added an observation.
+    pub fn process_transaction(&mut self, transaction: &UsbTransaction) -> bool {
+        let setup = match &transaction.setup_packet {
+            Some(setup) => setup,
+            None => return false,
+        };
+        let step = match EnumerationStep::from_setup_packet(setup) {
+            Some(step) => step,
+            None => return false,
+        };
+        self.request_count += 1;
+
+        match step {
+            EnumerationStep::GetDeviceDescriptor => {
+                self.first_device_request.get_or_insert(setup.wLength);
+            },
+            EnumerationStep::GetConfigurationDescriptor => {
+                self.first_configuration_request.get_or_insert(setup.wLength);
+            },
+            EnumerationStep::GetStringDescriptor(0) => self.language_list = true,
+            EnumerationStep::GetStringDescriptor(MS_OS_STRING_INDEX) => self.ms_os_string = true,
+            EnumerationStep::GetDescriptor { descriptor_type: BOS_DESCRIPTOR, .. } => self.bos = true,
+            EnumerationStep::GetDescriptor { descriptor_type: DEVICE_QUALIFIER_DESCRIPTOR, .. } => {
+                self.device_qualifier = true;
+            },
+            EnumerationStep::SetAddress(_) => {
+                if !self.set_address_seen {
+                    self.set_address_first = Some(!self.descriptor_seen);
+                    if self.descriptor_seen && self.bus_events_seen {
+                        self.reset_before_set_address = Some(self.reset_pending);
+                    }
+                }
+                self.set_address_seen = true;
+            },
+            _ => {},
+        }
+
+        if matches!(step, EnumerationStep::GetDeviceDescriptor | EnumerationStep::GetConfigurationDescriptor |
+                          EnumerationStep::GetStringDescriptor(_) | EnumerationStep::GetDescriptor { .. }) {
+            self.descriptor_seen = true;
+        }
+
+        true
+    }
+
+    // Whether a feature was observed; None while there isn't enough traffic to tell
+    fn observe(&self, feature: HostFeature) -> Option<bool> {
+        // Optional descriptors only count as missing once enumeration is well under way
+        let enumerated = self.set_address_seen && self.first_configuration_request.is_some();
+        let optional = |seen: bool| if seen || enumerated { Some(seen) } else { None };
+
+        match feature {
+            HostFeature::FirstDeviceRequestLength(length) => self.first_device_request.map(|first| first == length),
+            HostFeature::ResetBeforeSetAddress => self.reset_before_set_address,
+            HostFeature::SetAddressFirst => self.set_address_first,
+            HostFeature::ConfigurationHeaderFirst => self.first_configuration_request.map(|length| length == 9),
+            HostFeature::ConfigurationFullFirst => self.first_configuration_request.map(|length| length >= 255),
+            HostFeature::LanguageListRequested => optional(self.language_list),
+            HostFeature::MsOsStringRequested => optional(self.ms_os_string),
+            HostFeature::BosRequested => optional(self.bos),
+            HostFeature::DeviceQualifierRequested => optional(self.device_qualifier),
+        }
+    }
+
+    // Every signature scored against the observations, best match first
+    pub fn matches(&self) -> Vec<FingerprintMatch> {
+        let mut matches: Vec<FingerprintMatch> = HOST_SIGNATURES.iter().map(|signature| {
+            let mut result = FingerprintMatch {
+                signature,
+                score: 0,
+                possible: 0,
+                matched: Vec::new(),
+                contradicted: Vec::new(),
+            };
+            for expectation in signature.expectations {
+                let observed = match self.observe(expectation.feature) {
+                    Some(observed) => observed,
+                    None => continue,
+                };
+                result.possible += expectation.weight;
+                if observed == expectation.present {
+                    result.score += expectation.weight;
+                    if observed {
+                        result.matched.push(expectation.feature);
+                    }
+                } else if observed {
+                    result.contradicted.push(expectation.feature);
+                }
+            }
+            result
+        }).collect();
+
+        matches.sort_by(|a, b| b.confidence().partial_cmp(&a.confidence()).unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.possible.cmp(&a.possible)));
+        matches
+    }
+
+    // One-line verdict for collapsed display
+    pub fn summary(&self) -> String {
+        if self.request_count == 0 {
+            return "Host: no enumeration seen".to_string();
+        }
+        match self.matches().first() {
+            Some(best) if best.possible > 0 => {
+                format!("Host: likely {} ({}), {:.0}% match from {} enumeration requests",
+                        best.signature.os, best.signature.stack, best.confidence() * 100.0, self.request_count)
+            },
+            _ => format!("Host: unknown ({} enumeration requests)", self.request_count),
+        }
+    }
+}
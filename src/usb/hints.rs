@@ -2,6 +2,7 @@ use crate::usb::{
     UsbDescriptorType, UsbDeviceClass, UsbEndpointType, UsbIsoSyncType,
     DeviceDescriptor, ConfigurationDescriptor, InterfaceDescriptor, EndpointDescriptor
 };
+use crate::usb::descriptors::{platform_uuid_name, DeviceCapability};
 
 // This module provides contextual hints and explanations for USB descriptors
 // These hints help users understand the meaning and implications of different USB values
@@ -164,6 +165,18 @@ impl UsbHints {
             UsbDescriptorType::InterfacePower => 
                 "The Interface Power Descriptor defines power management capabilities for a specific interface.".to_string(),
             
+            UsbDescriptorType::Otg => 
+                "The OTG Descriptor tells a dual-role host which On-The-Go protocols the device supports: \
+                Session Request (SRP), Host Negotiation (HNP) and Attach Detection (ADP).".to_string(),
+            
+            UsbDescriptorType::Debug => 
+                "The Debug Descriptor names the bulk endpoints of a debug port, used for early kernel \
+                consoles over USB. Most devices stall the request.".to_string(),
+            
+            UsbDescriptorType::InterfaceAssociation => 
+                "The Interface Association Descriptor (IAD) groups consecutive interfaces into a single function, \
+                such as the control and streaming interfaces of a webcam or a CDC ACM serial port.".to_string(),
+            
             UsbDescriptorType::Hid => 
                 "The HID Descriptor is specific to Human Interface Devices like keyboards and mice. \
                 It defines report formats and other HID-specific information.".to_string(),
@@ -180,6 +193,10 @@ impl UsbHints {
                 "The Hub Descriptor defines characteristics of a USB hub, including the number of ports \
                 and power characteristics.".to_string(),
             
+            UsbDescriptorType::SuperSpeedHub => 
+                "The SuperSpeed Hub Descriptor (USB 3.x) describes the SuperSpeed side of a hub, \
+                including the number of ports, header decode latency and the delay the hub adds.".to_string(),
+            
             UsbDescriptorType::Bos => 
                 "The Binary Device Object Store (BOS) descriptor (USB 3.0) provides a way to access device-level \
                 capabilities, like USB 2.0 extension capabilities.".to_string(),
@@ -196,6 +213,10 @@ impl UsbHints {
                 "This descriptor provides additional information specific to SuperSpeed Plus \
                 isochronous endpoints.".to_string(),
             
+            UsbDescriptorType::Eusb2IsochronousEndpointCompanion => 
+                "The eUSB2 Isochronous Endpoint Companion follows a high-speed isochronous IN endpoint and \
+                carries the larger bytes-per-interval allowed by the double isochronous bandwidth ECN.".to_string(),
+            
             UsbDescriptorType::Unknown(_) => 
                 "This is an unknown or vendor-specific descriptor type. It may contain proprietary \
                 or device-specific information.".to_string(),
@@ -465,4 +486,107 @@ impl UsbHints {
         
         hints
     }
-}
\ No newline at end of file
+    
+    // Get hints for a decoded BOS device capability
+    pub fn for_device_capability(capability: &DeviceCapability) -> Vec<String> {
+        let mut hints = Vec::new();
+        
+        match capability {
+            DeviceCapability::Usb2Extension { lpm, besl, baseline_besl, deep_besl } => {
+                if *lpm {
+                    hints.push("The device supports USB 2.0 Link Power Management, so the host may put the link into L1 sleep between transfers.".to_string());
+                } else {
+                    hints.push("No Link Power Management: the link only leaves L0 through full suspend.".to_string());
+                }
+                if *besl {
+                    hints.push("BESL (Best Effort Service Latency) values are used instead of HIRD for L1 resume timing.".to_string());
+                }
+                if let Some(besl) = baseline_besl {
+                    hints.push(format!("Recommended baseline BESL {} - the host should use at least this resume latency for L1.", besl));
+                }
+                if let Some(besl) = deep_besl {
+                    hints.push(format!("Recommended deep BESL {} - for a deeper L1 sleep with longer resume.", besl));
+                }
+            },
+            DeviceCapability::SuperSpeed { ltm, speeds_supported, u1_exit_latency, u2_exit_latency, .. } => {
+                hints.push(format!("Operates at: {}.", DeviceCapability::speed_names(*speeds_supported).join(", ")));
+                hints.push(format!(
+                    "Link exits U1 within {} µs and U2 within {} µs. The host uses these latencies to decide when low-power link states are worth entering.",
+                    u1_exit_latency, u2_exit_latency
+                ));
+                if *ltm {
+                    hints.push("The device can send Latency Tolerance Messages to let the host save power while it is idle.".to_string());
+                }
+            },
+            DeviceCapability::SuperSpeedPlus { min_rx_lanes, min_tx_lanes, sublink_speeds, .. } => {
+                let fastest = sublink_speeds.iter().map(|speed| speed.bits_per_second()).max().unwrap_or(0);
+                hints.push(format!("SuperSpeed Plus device with sublink speeds up to {} Gb/s.", fastest / 1_000_000_000));
+                if *min_rx_lanes > 1 || *min_tx_lanes > 1 {
+                    hints.push("Full functionality needs a dual-lane (USB 3.2 Gen x2) link.".to_string());
+                }
+                if sublink_speeds.iter().any(|speed| speed.asymmetric) {
+                    hints.push("Some sublinks are asymmetric, running at different RX and TX speeds.".to_string());
+                }
+            },
+            DeviceCapability::ContainerId(uuid) => {
+                hints.push(format!(
+                    "Container ID {{{}}} identifies this physical device. Functions reporting the same ID over different buses or ports are grouped as one device by the host.",
+                    uuid
+                ));
+            },
+            DeviceCapability::Platform { uuid, data } => {
+                if let Some(webusb) = capability.webusb() {
+                    hints.push(format!(
+                        "WebUSB: browsers read the device's URLs with vendor request 0x{:02X} (GET_URL).",
+                        webusb.vendor_code
+                    ));
+                    if webusb.landing_page_index != 0 {
+                        hints.push(format!(
+                            "URL descriptor {} is the landing page a browser may offer when the device is plugged in.",
+                            webusb.landing_page_index
+                        ));
+                    }
+                }
+                match platform_uuid_name(uuid) {
+                    Some(name) => hints.push(format!("{} platform capability ({} bytes of platform data).", name, data.len())),
+                    None => hints.push(format!("Platform capability with unrecognised UUID {{{}}}; only the matching platform software interprets it.", uuid)),
+                }
+            },
+            DeviceCapability::PowerDelivery { attributes, .. } => {
+                hints.push("The device reports USB Power Delivery capabilities.".to_string());
+                if attributes & (1 << 3) != 0 {
+                    hints.push("It can act as a power provider.".to_string());
+                }
+                if attributes & (1 << 4) != 0 {
+                    hints.push("It can act as a power consumer.".to_string());
+                }
+                if attributes & (1 << 9) != 0 {
+                    hints.push(format!("It is battery powered ({} batteries).", (attributes >> 11) & 0x07));
+                }
+            },
+            DeviceCapability::Billboard { alternate_modes, vconn_power, .. } => {
+                hints.push(format!(
+                    "Billboard: the device advertises {} USB Type-C alternate mode{} and reports whether each was entered.",
+                    alternate_modes.len(), if alternate_modes.len() == 1 { "" } else { "s" }
+                ));
+                hints.push(format!("VCONN power required: {}.", DeviceCapability::vconn_power_name(*vconn_power)));
+                for mode in alternate_modes.iter().filter(|mode| mode.state != 3) {
+                    hints.push(format!("Alternate mode SVID 0x{:04X} was not configured: {}.", mode.svid, mode.state_name()));
+                }
+            },
+            DeviceCapability::ConfigurationSummary { class, configuration_indices, .. } => {
+                hints.push(format!(
+                    "Configuration Summary: a {} function is available in configuration index{} {}. Hosts use this to pick a configuration without reading them all.",
+                    class.name(),
+                    if configuration_indices.len() == 1 { "" } else { "es" },
+                    configuration_indices.iter().map(|index| index.to_string()).collect::<Vec<_>>().join(", ")
+                ));
+            },
+            DeviceCapability::Other { capability_type, data } => {
+                hints.push(format!("Capability type 0x{:02X} is not decoded ({} bytes).", capability_type, data.len()));
+            },
+        }
+        
+        hints
+    }
+}
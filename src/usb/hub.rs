@@ -0,0 +1,965 @@
+// USB Hub class support
+// Based on USB 2.0 specification chapter 11 and USB 3.2 specification chapter 10
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::descriptor_types::UsbDescriptorType;
+use super::decoder::Speed;
+use super::mitm_traffic::{
+    UsbControlRecipient,
+    UsbControlRequestType,
+    UsbDirection,
+    UsbSetupPacket,
+    UsbStandardRequest,
+    UsbTransaction,
+    UsbTransferStatus,
+    UsbTransferType,
+};
+
+// Hub class-specific request codes (bRequest)
+pub const HUB_GET_STATUS: u8 = 0x00;
+pub const HUB_CLEAR_FEATURE: u8 = 0x01;
+pub const HUB_SET_FEATURE: u8 = 0x03;
+pub const HUB_GET_DESCRIPTOR: u8 = 0x06;
+pub const HUB_SET_DESCRIPTOR: u8 = 0x07;
+pub const HUB_CLEAR_TT_BUFFER: u8 = 0x08;
+pub const HUB_RESET_TT: u8 = 0x09;
+pub const HUB_GET_TT_STATE: u8 = 0x0A;
+pub const HUB_STOP_TT: u8 = 0x0B;
+pub const HUB_SET_HUB_DEPTH: u8 = 0x0C;
+pub const HUB_GET_PORT_ERR_COUNT: u8 = 0x0D;
+
+// Port feature selectors used with SET_PORT_FEATURE / CLEAR_PORT_FEATURE
+pub const PORT_ENABLE: u16 = 1;
+pub const PORT_SUSPEND: u16 = 2;
+pub const PORT_RESET: u16 = 4;
+pub const PORT_POWER: u16 = 8;
+pub const BH_PORT_RESET: u16 = 28;
+
+// Selectors that acknowledge a port change bit: C_PORT_CONNECTION through C_PORT_RESET,
+// C_PORT_LINK_STATE, C_PORT_CONFIG_ERROR and C_BH_PORT_RESET
+pub const C_PORT_SELECTORS: [u16; 8] = [16, 17, 18, 19, 20, 25, 26, 29];
+
+// Get the name of a port feature selector
+pub fn port_feature_name(selector: u16) -> &'static str {
+    match selector {
+        0 => "PORT_CONNECTION",
+        1 => "PORT_ENABLE",
+        2 => "PORT_SUSPEND",
+        3 => "PORT_OVER_CURRENT",
+        4 => "PORT_RESET",
+        5 => "PORT_LINK_STATE",
+        8 => "PORT_POWER",
+        9 => "PORT_LOW_SPEED",
+        16 => "C_PORT_CONNECTION",
+        17 => "C_PORT_ENABLE",
+        18 => "C_PORT_SUSPEND",
+        19 => "C_PORT_OVER_CURRENT",
+        20 => "C_PORT_RESET",
+        21 => "PORT_TEST",
+        22 => "PORT_INDICATOR",
+        23 => "PORT_U1_TIMEOUT",
+        24 => "PORT_U2_TIMEOUT",
+        25 => "C_PORT_LINK_STATE",
+        26 => "C_PORT_CONFIG_ERROR",
+        27 => "PORT_REMOTE_WAKE_MASK",
+        28 => "BH_PORT_RESET",
+        29 => "C_BH_PORT_RESET",
+        30 => "FORCE_LINKPM_ACCEPT",
+        _ => "UNKNOWN_PORT_FEATURE",
+    }
+}
+
+// Get the name of a hub feature selector
+pub fn hub_feature_name(selector: u16) -> &'static str {
+    match selector {
+        0 => "C_HUB_LOCAL_POWER",
+        1 => "C_HUB_OVER_CURRENT",
+        _ => "UNKNOWN_HUB_FEATURE",
+    }
+}
+
+// Names of the bits in wPortStatus (USB 2.0 hubs)
+const USB2_PORT_STATUS_BITS: &[(u16, &str)] = &[
+    (0x0001, "PORT_CONNECTION"),
+    (0x0002, "PORT_ENABLE"),
+    (0x0004, "PORT_SUSPEND"),
+    (0x0008, "PORT_OVER_CURRENT"),
+    (0x0010, "PORT_RESET"),
+    (0x0100, "PORT_POWER"),
+    (0x0200, "PORT_LOW_SPEED"),
+    (0x0400, "PORT_HIGH_SPEED"),
+    (0x0800, "PORT_TEST"),
+    (0x1000, "PORT_INDICATOR"),
+];
+
+// Names of the bits in wPortStatus (SuperSpeed hubs)
+const SS_PORT_STATUS_BITS: &[(u16, &str)] = &[
+    (0x0001, "PORT_CONNECTION"),
+    (0x0002, "PORT_ENABLE"),
+    (0x0008, "PORT_OVER_CURRENT"),
+    (0x0010, "PORT_RESET"),
+    (0x0200, "PORT_POWER"),
+];
+
+// Names of the bits in wPortChange (USB 2.0 hubs)
+const USB2_PORT_CHANGE_BITS: &[(u16, &str)] = &[
+    (0x0001, "C_PORT_CONNECTION"),
+    (0x0002, "C_PORT_ENABLE"),
+    (0x0004, "C_PORT_SUSPEND"),
+    (0x0008, "C_PORT_OVER_CURRENT"),
+    (0x0010, "C_PORT_RESET"),
+];
+
+// Names of the bits in wPortChange (SuperSpeed hubs)
+const SS_PORT_CHANGE_BITS: &[(u16, &str)] = &[
+    (0x0001, "C_PORT_CONNECTION"),
+    (0x0008, "C_PORT_OVER_CURRENT"),
+    (0x0010, "C_PORT_RESET"),
+    (0x0020, "C_BH_PORT_RESET"),
+    (0x0040, "C_PORT_LINK_STATE"),
+    (0x0080, "C_PORT_CONFIG_ERROR"),
+];
+
+// Names of the bits in wHubStatus / wHubChange
+const HUB_STATUS_BITS: &[(u16, &str)] = &[
+    (0x0001, "HUB_LOCAL_POWER"),
+    (0x0002, "HUB_OVER_CURRENT"),
+];
+
+const HUB_CHANGE_BITS: &[(u16, &str)] = &[
+    (0x0001, "C_HUB_LOCAL_POWER"),
+    (0x0002, "C_HUB_OVER_CURRENT"),
+];
+
+fn bit_names(value: u16, table: &[(u16, &'static str)]) -> Vec<&'static str> {
+    table.iter()
+        .filter(|(mask, _)| value & mask != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+fn join_names(names: &[&str]) -> String {
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(" | ")
+    }
+}
+
+// Hub Descriptor (0x29) and SuperSpeed Hub Descriptor (0x2A)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubDescriptor {
+    pub length: u8,                    // Descriptor size in bytes
+    pub descriptor_type: UsbDescriptorType, // HUB (0x29) or SUPERSPEED_HUB (0x2A)
+    pub num_ports: u8,                 // Number of downstream facing ports
+    pub hub_characteristics: u16,      // Power switching, compound device, over-current, TT think time
+    pub power_on_to_power_good: u8,    // Time (in 2ms units) from power-on to power-good on a port
+    pub hub_control_current: u8,       // Max current requirements of the hub controller
+    pub header_decode_latency: Option<u8>, // SuperSpeed only: hub packet header decode latency
+    pub hub_delay: Option<u16>,        // SuperSpeed only: average delay in ns introduced by the hub
+    pub device_removable: Vec<u8>,     // Bitmap of non-removable devices (bit 0 reserved)
+}
+
+impl HubDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 7 {
+            return Err(format!("Invalid hub descriptor length: {}", data.len()));
+        }
+
+        let descriptor_type = UsbDescriptorType::from(data[1]);
+        let length = data[0];
+        let num_ports = data[2];
+        let hub_characteristics = u16::from_le_bytes([data[3], data[4]]);
+        let power_on_to_power_good = data[5];
+        let hub_control_current = data[6];
+
+        match descriptor_type {
+            UsbDescriptorType::Hub => {
+                // DeviceRemovable is one bit per port plus the reserved bit 0, rounded up to bytes
+                let removable_len = (num_ports as usize / 8) + 1;
+                let end = std::cmp::min(data.len(), 7 + removable_len);
+
+                Ok(HubDescriptor {
+                    length,
+                    descriptor_type,
+                    num_ports,
+                    hub_characteristics,
+                    power_on_to_power_good,
+                    hub_control_current,
+                    header_decode_latency: None,
+                    hub_delay: None,
+                    device_removable: data[7..end].to_vec(),
+                })
+            },
+            UsbDescriptorType::SuperSpeedHub => {
+                if data.len() < 12 {
+                    return Err(format!("Invalid SuperSpeed hub descriptor length: {}", data.len()));
+                }
+
+                Ok(HubDescriptor {
+                    length,
+                    descriptor_type,
+                    num_ports,
+                    hub_characteristics,
+                    power_on_to_power_good,
+                    hub_control_current,
+                    header_decode_latency: Some(data[7]),
+                    hub_delay: Some(u16::from_le_bytes([data[8], data[9]])),
+                    device_removable: data[10..12].to_vec(),
+                })
+            },
+            _ => Err(format!("Not a hub descriptor: 0x{:02X}", data[1])),
+        }
+    }
+
+    pub fn is_superspeed(&self) -> bool {
+        self.descriptor_type == UsbDescriptorType::SuperSpeedHub
+    }
+
+    pub fn power_switching_mode(&self) -> &'static str {
+        match self.hub_characteristics & 0x03 {
+            0 => "Ganged power switching",
+            1 => "Individual port power switching",
+            _ => "No power switching",
+        }
+    }
+
+    pub fn is_compound_device(&self) -> bool {
+        (self.hub_characteristics & 0x04) != 0
+    }
+
+    pub fn over_current_protection(&self) -> &'static str {
+        match (self.hub_characteristics >> 3) & 0x03 {
+            0 => "Global over-current protection",
+            1 => "Individual port over-current protection",
+            _ => "No over-current protection",
+        }
+    }
+
+    // TT think time in full-speed bit times (high-speed hubs only)
+    pub fn tt_think_time(&self) -> u8 {
+        (((self.hub_characteristics >> 5) & 0x03) as u8 + 1) * 8
+    }
+
+    pub fn port_indicators_supported(&self) -> bool {
+        (self.hub_characteristics & 0x80) != 0
+    }
+
+    pub fn power_on_to_power_good_ms(&self) -> u16 {
+        self.power_on_to_power_good as u16 * 2
+    }
+
+    // Check whether the device attached to a port is marked non-removable
+    pub fn is_port_removable(&self, port: u8) -> bool {
+        let byte = (port / 8) as usize;
+        let bit = port % 8;
+        match self.device_removable.get(byte) {
+            Some(value) => (value & (1 << bit)) == 0,
+            None => true,
+        }
+    }
+}
+
+impl fmt::Display for HubDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_superspeed() {
+            writeln!(f, "SuperSpeed Hub Descriptor:")?;
+        } else {
+            writeln!(f, "Hub Descriptor:")?;
+        }
+        writeln!(f, "  bLength: {} bytes", self.length)?;
+        writeln!(f, "  bDescriptorType: {} ({})", self.descriptor_type.name(), self.descriptor_type.get_value())?;
+        writeln!(f, "  bNbrPorts: {}", self.num_ports)?;
+        writeln!(f, "  wHubCharacteristics: 0x{:04X}", self.hub_characteristics)?;
+        writeln!(f, "    {}", self.power_switching_mode())?;
+        writeln!(f, "    Compound device: {}", if self.is_compound_device() { "Yes" } else { "No" })?;
+        writeln!(f, "    {}", self.over_current_protection())?;
+        if !self.is_superspeed() {
+            writeln!(f, "    TT think time: {} FS bit times", self.tt_think_time())?;
+            writeln!(f, "    Port indicators: {}", if self.port_indicators_supported() { "Supported" } else { "Not supported" })?;
+        }
+        writeln!(f, "  bPwrOn2PwrGood: {} ({}ms)", self.power_on_to_power_good, self.power_on_to_power_good_ms())?;
+        writeln!(f, "  bHubContrCurrent: {}mA", self.hub_control_current)?;
+        if let Some(latency) = self.header_decode_latency {
+            writeln!(f, "  bHubHdrDecLat: {}", latency)?;
+        }
+        if let Some(delay) = self.hub_delay {
+            writeln!(f, "  wHubDelay: {}ns", delay)?;
+        }
+        write!(f, "  DeviceRemovable:")?;
+        for byte in &self.device_removable {
+            write!(f, " {:02X}", byte)?;
+        }
+        writeln!(f)?;
+
+        // List the fixed ports, if any
+        let fixed: Vec<String> = (1..=self.num_ports)
+            .filter(|port| !self.is_port_removable(*port))
+            .map(|port| port.to_string())
+            .collect();
+        if !fixed.is_empty() {
+            writeln!(f, "    Non-removable ports: {}", fixed.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Port status as returned by GET_PORT_STATUS (wPortStatus + wPortChange)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HubPortStatus {
+    pub status: u16,
+    pub change: u16,
+    pub superspeed: bool,
+}
+
+impl HubPortStatus {
+    pub fn parse(data: &[u8], superspeed: bool) -> Option<Self> {
+        if data.len() < 4 {
+            return None;
+        }
+
+        Some(HubPortStatus {
+            status: u16::from_le_bytes([data[0], data[1]]),
+            change: u16::from_le_bytes([data[2], data[3]]),
+            superspeed,
+        })
+    }
+
+    pub fn status_names(&self) -> Vec<&'static str> {
+        if self.superspeed {
+            bit_names(self.status, SS_PORT_STATUS_BITS)
+        } else {
+            bit_names(self.status, USB2_PORT_STATUS_BITS)
+        }
+    }
+
+    pub fn change_names(&self) -> Vec<&'static str> {
+        if self.superspeed {
+            bit_names(self.change, SS_PORT_CHANGE_BITS)
+        } else {
+            bit_names(self.change, USB2_PORT_CHANGE_BITS)
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        (self.status & 0x0001) != 0
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        (self.status & 0x0002) != 0
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        // SuperSpeed ports report suspend through the link state instead
+        !self.superspeed && (self.status & 0x0004) != 0
+    }
+
+    pub fn is_over_current(&self) -> bool {
+        (self.status & 0x0008) != 0
+    }
+
+    pub fn is_resetting(&self) -> bool {
+        (self.status & 0x0010) != 0
+    }
+
+    pub fn is_powered(&self) -> bool {
+        if self.superspeed {
+            (self.status & 0x0200) != 0
+        } else {
+            (self.status & 0x0100) != 0
+        }
+    }
+
+    // Speed of the attached device, if one is connected
+    pub fn speed(&self) -> Option<Speed> {
+        if !self.is_connected() {
+            return None;
+        }
+
+        if self.superspeed {
+            match (self.status >> 10) & 0x07 {
+                0 => Some(Speed::Super),
+                _ => Some(Speed::SuperPlus),
+            }
+        } else if (self.status & 0x0200) != 0 {
+            Some(Speed::Low)
+        } else if (self.status & 0x0400) != 0 {
+            Some(Speed::High)
+        } else {
+            Some(Speed::Full)
+        }
+    }
+
+    // SuperSpeed link state (bits 8:5)
+    pub fn link_state(&self) -> Option<&'static str> {
+        if !self.superspeed {
+            return None;
+        }
+
+        Some(match (self.status >> 5) & 0x0F {
+            0x0 => "U0",
+            0x1 => "U1",
+            0x2 => "U2",
+            0x3 => "U3 (Suspended)",
+            0x4 => "eSS.Disabled",
+            0x5 => "Rx.Detect",
+            0x6 => "eSS.Inactive",
+            0x7 => "Polling",
+            0x8 => "Recovery",
+            0x9 => "Hot Reset",
+            0xA => "Compliance Mode",
+            0xB => "Loopback",
+            _ => "Reserved",
+        })
+    }
+}
+
+impl fmt::Display for HubPortStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Status: {}; Change: {}",
+               join_names(&self.status_names()),
+               join_names(&self.change_names()))?;
+        if let Some(link_state) = self.link_state() {
+            write!(f, "; Link: {}", link_state)?;
+        }
+        Ok(())
+    }
+}
+
+// Hub status as returned by GET_HUB_STATUS (wHubStatus + wHubChange)
+pub fn describe_hub_status(data: &[u8]) -> Option<String> {
+    if data.len() < 4 {
+        return None;
+    }
+
+    let status = u16::from_le_bytes([data[0], data[1]]);
+    let change = u16::from_le_bytes([data[2], data[3]]);
+
+    Some(format!("Status: {}; Change: {}",
+                 join_names(&bit_names(status, HUB_STATUS_BITS)),
+                 join_names(&bit_names(change, HUB_CHANGE_BITS))))
+}
+
+// Decode the hub status-change interrupt endpoint bitmap.
+// Bit 0 reports a hub status change, bit N reports a change on port N.
+pub fn decode_status_change_bitmap(data: &[u8]) -> Vec<u8> {
+    let mut changed = Vec::new();
+
+    for (byte_index, byte) in data.iter().enumerate() {
+        for bit in 0..8 {
+            if (byte & (1 << bit)) != 0 {
+                let index = byte_index * 8 + bit;
+                if index <= u8::MAX as usize {
+                    changed.push(index as u8);
+                }
+            }
+        }
+    }
+
+    changed
+}
+
+// Human-readable summary of a status-change bitmap
+pub fn describe_status_change_bitmap(data: &[u8]) -> String {
+    let changed = decode_status_change_bitmap(data);
+    if changed.is_empty() {
+        return "Hub status change: no changes".to_string();
+    }
+
+    let parts: Vec<String> = changed.iter()
+        .map(|index| if *index == 0 { "Hub".to_string() } else { format!("Port {}", index) })
+        .collect();
+
+    format!("Hub status change: {}", parts.join(", "))
+}
+
+// Check whether a setup packet looks like a hub class request.
+// Requests to the "Other" recipient are only defined for hub ports; the TT requests
+// address a port's transaction translator the same way (bmRequestType 0x23/0xA3).
+pub fn is_hub_request(request_type: UsbControlRequestType, recipient: UsbControlRecipient, b_request: u8, w_value: u16) -> bool {
+    if request_type != UsbControlRequestType::Class {
+        return false;
+    }
+
+    match recipient {
+        UsbControlRecipient::Other => matches!(b_request,
+            HUB_GET_STATUS | HUB_CLEAR_FEATURE | HUB_SET_FEATURE | HUB_GET_PORT_ERR_COUNT
+            | HUB_CLEAR_TT_BUFFER | HUB_RESET_TT | HUB_GET_TT_STATE | HUB_STOP_TT),
+        UsbControlRecipient::Device => match b_request {
+            HUB_GET_DESCRIPTOR | HUB_SET_DESCRIPTOR => {
+                matches!((w_value >> 8) as u8, 0x29 | 0x2A)
+            },
+            HUB_GET_STATUS | HUB_CLEAR_FEATURE | HUB_SET_FEATURE | HUB_SET_HUB_DEPTH => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// Describe a hub class request, returning None if it isn't one
+pub fn describe_hub_request(
+    request_type: UsbControlRequestType,
+    recipient: UsbControlRecipient,
+    b_request: u8,
+    w_value: u16,
+    w_index: u16,
+    w_length: u16,
+) -> Option<String> {
+    if !is_hub_request(request_type, recipient, b_request, w_value) {
+        return None;
+    }
+
+    let port = (w_index & 0xFF) as u8;
+
+    if recipient == UsbControlRecipient::Other {
+        return Some(match b_request {
+            HUB_GET_STATUS => format!("GET_PORT_STATUS (Port {})", port),
+            HUB_CLEAR_TT_BUFFER => format!("CLEAR_TT_BUFFER (Device {}, Endpoint {}, TT Port {})",
+                                           (w_value >> 4) & 0x7F, w_value & 0x0F, port),
+            HUB_RESET_TT => format!("RESET_TT (TT Port {})", port),
+            HUB_GET_TT_STATE => format!("GET_TT_STATE (TT Port {})", port),
+            HUB_STOP_TT => format!("STOP_TT (TT Port {})", port),
+            HUB_CLEAR_FEATURE => format!("CLEAR_PORT_FEATURE: {} (Port {})", port_feature_name(w_value), port),
+            HUB_SET_FEATURE => {
+                match w_value {
+                    21 => format!("SET_PORT_FEATURE: PORT_TEST (Port {}, Selector {})", port, w_index >> 8),
+                    22 => format!("SET_PORT_FEATURE: PORT_INDICATOR (Port {}, Selector {})", port, w_index >> 8),
+                    23 | 24 => format!("SET_PORT_FEATURE: {} (Port {}, Timeout {})", port_feature_name(w_value), port, w_index >> 8),
+                    _ => format!("SET_PORT_FEATURE: {} (Port {})", port_feature_name(w_value), port),
+                }
+            },
+            _ => format!("GET_PORT_ERR_COUNT (Port {})", port),
+        });
+    }
+
+    Some(match b_request {
+        HUB_GET_STATUS => "GET_HUB_STATUS".to_string(),
+        HUB_CLEAR_FEATURE => format!("CLEAR_HUB_FEATURE: {}", hub_feature_name(w_value)),
+        HUB_SET_FEATURE => format!("SET_HUB_FEATURE: {}", hub_feature_name(w_value)),
+        HUB_GET_DESCRIPTOR => {
+            if (w_value >> 8) == 0x2A {
+                format!("Get SUPERSPEED_HUB Descriptor (Length: {})", w_length)
+            } else {
+                format!("Get HUB Descriptor (Length: {})", w_length)
+            }
+        },
+        HUB_SET_DESCRIPTOR => "SET_HUB_DESCRIPTOR".to_string(),
+        _ => format!("SET_HUB_DEPTH: {}", w_value),
+    })
+}
+
+// Port connection events derived from hub traffic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HubPortEvent {
+    Connected(Option<Speed>),
+    Disconnected,
+    Enabled,
+    Disabled,
+    Suspended,
+    Resumed,
+    ResetStarted,
+    ResetComplete,
+    PoweredOn,
+    PoweredOff,
+    OverCurrent,
+    ChangeReported,         // Port bit set in the status-change bitmap
+    ChangeCleared(String),  // CLEAR_PORT_FEATURE on a C_PORT_* selector
+}
+
+impl fmt::Display for HubPortEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HubPortEvent::Connected(Some(speed)) => write!(f, "Device connected ({:?} speed)", speed),
+            HubPortEvent::Connected(None) => write!(f, "Device connected"),
+            HubPortEvent::Disconnected => write!(f, "Device disconnected"),
+            HubPortEvent::Enabled => write!(f, "Port enabled"),
+            HubPortEvent::Disabled => write!(f, "Port disabled"),
+            HubPortEvent::Suspended => write!(f, "Port suspended"),
+            HubPortEvent::Resumed => write!(f, "Port resumed"),
+            HubPortEvent::ResetStarted => write!(f, "Port reset started"),
+            HubPortEvent::ResetComplete => write!(f, "Port reset complete"),
+            HubPortEvent::PoweredOn => write!(f, "Port powered on"),
+            HubPortEvent::PoweredOff => write!(f, "Port powered off"),
+            HubPortEvent::OverCurrent => write!(f, "Over-current condition"),
+            HubPortEvent::ChangeReported => write!(f, "Status change reported"),
+            HubPortEvent::ChangeCleared(name) => write!(f, "{} acknowledged", name),
+        }
+    }
+}
+
+// One entry in a port's connection history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubPortHistoryEntry {
+    pub timestamp: f64,
+    pub transaction_id: u64,
+    pub hub_address: u8,
+    pub port: u8,
+    pub event: HubPortEvent,
+}
+
+impl fmt::Display for HubPortHistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.port == 0 {
+            write!(f, "Hub {}: {}", self.hub_address, self.event)
+        } else {
+            write!(f, "Hub {} Port {}: {}", self.hub_address, self.port, self.event)
+        }
+    }
+}
+
+// Last known state of a single downstream port
+#[derive(Debug, Clone, Default)]
+pub struct HubPortState {
+    pub last_status: Option<HubPortStatus>,
+    pub reset_pending: bool,
+    pub history: Vec<HubPortHistoryEntry>,
+}
+
+// State kept for each hub seen on the bus
+#[derive(Debug, Clone, Default)]
+pub struct HubState {
+    pub descriptor: Option<HubDescriptor>,
+    pub superspeed: bool,
+    pub ports: BTreeMap<u8, HubPortState>,
+}
+
+// Check a standard GET_DESCRIPTOR response for the hub class: bDeviceClass in a device
+// descriptor, or bInterfaceClass of any interface in a configuration
+fn reports_hub_class(descriptor_type: u8, data: &[u8]) -> bool {
+    const HUB_CLASS: u8 = 0x09;
+    match UsbDescriptorType::from(descriptor_type) {
+        UsbDescriptorType::Device => data.len() > 4 && data[1] == 0x01 && data[4] == HUB_CLASS,
+        UsbDescriptorType::Configuration => {
+            let mut offset = 0;
+            while offset + 6 <= data.len() && data[offset] > 0 {
+                if data[offset + 1] == 0x04 && data[offset + 5] == HUB_CLASS {
+                    return true;
+                }
+                offset += data[offset] as usize;
+            }
+            false
+        },
+        _ => false,
+    }
+}
+
+// Tracks hub requests and status-change interrupts to build per-port connection history
+#[derive(Debug, Clone, Default)]
+pub struct HubTracker {
+    hubs: HashMap<u8, HubState>,
+}
+
+impl HubTracker {
+    pub fn new() -> Self {
+        HubTracker {
+            hubs: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.hubs.clear();
+    }
+
+    // Check whether an address has been identified as a hub
+    pub fn is_hub(&self, address: u8) -> bool {
+        self.hubs.contains_key(&address)
+    }
+
+    pub fn hub(&self, address: u8) -> Option<&HubState> {
+        self.hubs.get(&address)
+    }
+
+    // Connection history for a single port
+    #[allow(dead_code)]
+    pub fn port_history(&self, hub_address: u8, port: u8) -> Vec<&HubPortHistoryEntry> {
+        self.hubs.get(&hub_address)
+            .and_then(|hub| hub.ports.get(&port))
+            .map(|state| state.history.iter().collect())
+            .unwrap_or_default()
+    }
+
+    // Full history across all hubs and ports, ordered by time
+    #[allow(dead_code)]
+    pub fn all_history(&self) -> Vec<&HubPortHistoryEntry> {
+        let mut history: Vec<&HubPortHistoryEntry> = self.hubs.values()
+            .flat_map(|hub| hub.ports.values())
+            .flat_map(|port| port.history.iter())
+            .collect();
+        history.sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap_or(std::cmp::Ordering::Equal));
+        history
+    }
+
+    // Feed a transaction into the tracker, returning any new port events
+    pub fn process_transaction(&mut self, transaction: &UsbTransaction) -> Vec<HubPortHistoryEntry> {
+        // Stalled requests didn't change anything on the hub
+        if let Some(status) = &transaction.status_packet {
+            if status.status == UsbTransferStatus::STALL {
+                return Vec::new();
+            }
+        }
+
+        match transaction.transfer_type {
+            UsbTransferType::Control => {
+                match &transaction.setup_packet {
+                    Some(setup) => self.process_setup(transaction, setup),
+                    None => Vec::new(),
+                }
+            },
+            UsbTransferType::Interrupt => self.process_interrupt(transaction),
+            _ => Vec::new(),
+        }
+    }
+
+    fn process_setup(&mut self, transaction: &UsbTransaction, setup: &UsbSetupPacket) -> Vec<HubPortHistoryEntry> {
+        let address = transaction.device_address;
+        let data = transaction.data_packet.as_ref().map(|d| d.get_data()).unwrap_or(&[]);
+
+        // A device or interface class of 0x09 identifies a hub before its hub descriptor is read
+        if setup.request_type == UsbControlRequestType::Standard
+            && setup.standard_request == Some(UsbStandardRequest::GetDescriptor) {
+            if reports_hub_class((setup.wValue >> 8) as u8, data) {
+                self.hubs.entry(address).or_default();
+            }
+            return Vec::new();
+        }
+
+        if !is_hub_request(setup.request_type, setup.recipient, setup.bRequest, setup.wValue) {
+            return Vec::new();
+        }
+
+        // Hub descriptor tells us the port count and whether this is a SuperSpeed hub
+        if setup.recipient == UsbControlRecipient::Device && setup.bRequest == HUB_GET_DESCRIPTOR {
+            if let Ok(descriptor) = HubDescriptor::parse(data) {
+                let hub = self.hubs.entry(address).or_default();
+                hub.superspeed = descriptor.is_superspeed();
+                hub.descriptor = Some(descriptor);
+            }
+            return Vec::new();
+        }
+
+        // Other class requests look the same for many classes, so only known hubs count
+        let hub = match self.hubs.get_mut(&address) {
+            Some(hub) => hub,
+            None => return Vec::new(),
+        };
+
+        if setup.recipient != UsbControlRecipient::Other {
+            return Vec::new();
+        }
+
+        let port = (setup.wIndex & 0xFF) as u8;
+        let superspeed = hub.superspeed;
+        let port_state = hub.ports.entry(port).or_default();
+        let mut events = Vec::new();
+
+        match setup.bRequest {
+            HUB_GET_STATUS => {
+                if let Some(status) = HubPortStatus::parse(data, superspeed) {
+                    let previous = port_state.last_status;
+                    let was_connected = previous.map(|s| s.is_connected()).unwrap_or(false);
+                    let was_enabled = previous.map(|s| s.is_enabled()).unwrap_or(false);
+                    let was_suspended = previous.map(|s| s.is_suspended()).unwrap_or(false);
+                    let was_over_current = previous.map(|s| s.is_over_current()).unwrap_or(false);
+                    let was_powered = previous.map(|s| s.is_powered());
+
+                    match was_powered {
+                        Some(false) if status.is_powered() => events.push(HubPortEvent::PoweredOn),
+                        Some(true) if !status.is_powered() => events.push(HubPortEvent::PoweredOff),
+                        _ => {},
+                    }
+
+                    if status.is_connected() && !was_connected {
+                        events.push(HubPortEvent::Connected(status.speed()));
+                    } else if !status.is_connected() && (was_connected || (status.change & 0x0001) != 0) {
+                        events.push(HubPortEvent::Disconnected);
+                    }
+
+                    if port_state.reset_pending && !status.is_resetting() {
+                        port_state.reset_pending = false;
+                        events.push(HubPortEvent::ResetComplete);
+                    }
+
+                    if status.is_enabled() && !was_enabled {
+                        events.push(HubPortEvent::Enabled);
+                    } else if !status.is_enabled() && was_enabled {
+                        events.push(HubPortEvent::Disabled);
+                    }
+
+                    if status.is_suspended() && !was_suspended {
+                        events.push(HubPortEvent::Suspended);
+                    } else if !status.is_suspended() && was_suspended {
+                        events.push(HubPortEvent::Resumed);
+                    }
+
+                    if status.is_over_current() && !was_over_current {
+                        events.push(HubPortEvent::OverCurrent);
+                    }
+
+                    port_state.last_status = Some(status);
+                }
+            },
+            HUB_SET_FEATURE => {
+                match setup.wValue {
+                    PORT_RESET | BH_PORT_RESET => {
+                        port_state.reset_pending = true;
+                        events.push(HubPortEvent::ResetStarted);
+                    },
+                    PORT_POWER => events.push(HubPortEvent::PoweredOn),
+                    PORT_SUSPEND => events.push(HubPortEvent::Suspended),
+                    _ => {},
+                }
+            },
+            HUB_CLEAR_FEATURE => {
+                match setup.wValue {
+                    PORT_ENABLE => events.push(HubPortEvent::Disabled),
+                    PORT_SUSPEND => events.push(HubPortEvent::Resumed),
+                    PORT_POWER => events.push(HubPortEvent::PoweredOff),
+                    selector if C_PORT_SELECTORS.contains(&selector) => {
+                        events.push(HubPortEvent::ChangeCleared(port_feature_name(selector).to_string()));
+                    },
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+
+        Self::record(port_state, transaction, address, port, events)
+    }
+
+    fn process_interrupt(&mut self, transaction: &UsbTransaction) -> Vec<HubPortHistoryEntry> {
+        // Only interpret interrupt IN data from devices we know are hubs
+        let address = transaction.device_address;
+        let hub = match self.hubs.get_mut(&address) {
+            Some(hub) => hub,
+            None => return Vec::new(),
+        };
+
+        let data = match &transaction.data_packet {
+            Some(data) if data.direction == UsbDirection::DeviceToHost => data.get_data(),
+            _ => return Vec::new(),
+        };
+
+        let mut entries = Vec::new();
+        for port in decode_status_change_bitmap(data) {
+            if let Some(descriptor) = &hub.descriptor {
+                if port > descriptor.num_ports {
+                    continue;
+                }
+            }
+
+            let port_state = hub.ports.entry(port).or_default();
+            entries.extend(Self::record(port_state, transaction, address, port, vec![HubPortEvent::ChangeReported]));
+        }
+
+        entries
+    }
+
+    fn record(
+        port_state: &mut HubPortState,
+        transaction: &UsbTransaction,
+        hub_address: u8,
+        port: u8,
+        events: Vec<HubPortEvent>,
+    ) -> Vec<HubPortHistoryEntry> {
+        let entries: Vec<HubPortHistoryEntry> = events.into_iter()
+            .map(|event| HubPortHistoryEntry {
+                timestamp: transaction.timestamp,
+                transaction_id: transaction.id,
+                hub_address,
+                port,
+                event,
+            })
+            .collect();
+
+        port_state.history.extend(entries.iter().cloned());
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usb::mitm_traffic::UsbDataPacket;
+
+    #[test]
+    fn parses_usb2_hub_descriptor() {
+        // 4 ports, individual power switching and over-current, 32 bit time TT, indicators,
+        // port 2 non-removable
+        let data = [0x09, 0x29, 0x04, 0xE9, 0x00, 0x32, 0x64, 0x04, 0xFF];
+        let descriptor = HubDescriptor::parse(&data).unwrap();
+
+        assert!(!descriptor.is_superspeed());
+        assert_eq!(descriptor.num_ports, 4);
+        assert_eq!(descriptor.power_switching_mode(), "Individual port power switching");
+        assert_eq!(descriptor.over_current_protection(), "Individual port over-current protection");
+        assert!(!descriptor.is_compound_device());
+        assert_eq!(descriptor.tt_think_time(), 32);
+        assert!(descriptor.port_indicators_supported());
+        assert_eq!(descriptor.power_on_to_power_good_ms(), 100);
+        assert_eq!(descriptor.device_removable, vec![0x04]);
+        assert!(descriptor.is_port_removable(1));
+        assert!(!descriptor.is_port_removable(2));
+    }
+
+    #[test]
+    fn parses_superspeed_hub_descriptor() {
+        let data = [0x0C, 0x2A, 0x04, 0x09, 0x00, 0x0A, 0x00, 0x10, 0x34, 0x12, 0x00, 0x00];
+        let descriptor = HubDescriptor::parse(&data).unwrap();
+
+        assert!(descriptor.is_superspeed());
+        assert_eq!(descriptor.header_decode_latency, Some(0x10));
+        assert_eq!(descriptor.hub_delay, Some(0x1234));
+        assert_eq!(descriptor.device_removable, vec![0x00, 0x00]);
+    }
+
+    #[test]
+    fn rejects_short_or_foreign_descriptors() {
+        assert!(HubDescriptor::parse(&[0x09, 0x29, 0x04]).is_err());
+        assert!(HubDescriptor::parse(&[0x0C, 0x2A, 0x04, 0x09, 0x00, 0x0A, 0x00, 0x10]).is_err());
+        assert!(HubDescriptor::parse(&[0x12, 0x01, 0x00, 0x02, 0x09, 0x00, 0x01, 0x40]).is_err());
+    }
+
+    #[test]
+    fn parses_port_status() {
+        // Connected, enabled, powered, high speed; connection change pending
+        let status = HubPortStatus::parse(&[0x03, 0x05, 0x01, 0x00], false).unwrap();
+        assert!(status.is_connected());
+        assert!(status.is_enabled());
+        assert!(status.is_powered());
+        assert_eq!(status.change_names(), vec!["C_PORT_CONNECTION"]);
+        assert!(HubPortStatus::parse(&[0x03, 0x05], false).is_none());
+    }
+
+    fn control(id: u64, address: u8, setup: [u8; 8], data: &[u8]) -> UsbTransaction {
+        let setup = UsbSetupPacket::new(&setup).unwrap();
+        let mut transaction = UsbTransaction::new(id, id as f64);
+        transaction.transfer_type = UsbTransferType::Control;
+        transaction.device_address = address;
+        if !data.is_empty() {
+            transaction.data_packet = Some(UsbDataPacket::new(data.to_vec(), setup.direction, 0));
+        }
+        transaction.setup_packet = Some(setup);
+        transaction
+    }
+
+    #[test]
+    fn only_change_selectors_are_acknowledged() {
+        let mut tracker = HubTracker::new();
+        tracker.process_transaction(&control(1, 3, [0xA0, 0x06, 0x00, 0x29, 0x00, 0x00, 0x09, 0x00],
+                                             &[0x09, 0x29, 0x04, 0xE9, 0x00, 0x32, 0x64, 0x00, 0xFF]));
+        assert!(tracker.is_hub(3));
+        assert!(!tracker.is_hub(4));
+
+        // CLEAR_PORT_FEATURE(C_PORT_CONNECTION) on port 1
+        let events = tracker.process_transaction(&control(2, 3, [0x23, 0x01, 0x10, 0x00, 0x01, 0x00, 0x00, 0x00], &[]));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, HubPortEvent::ChangeCleared("C_PORT_CONNECTION".to_string()));
+
+        // CLEAR_PORT_FEATURE(PORT_INDICATOR) doesn't acknowledge a change
+        let events = tracker.process_transaction(&control(3, 3, [0x23, 0x01, 0x16, 0x00, 0x01, 0x00, 0x00, 0x00], &[]));
+        assert!(events.is_empty());
+    }
+}
@@ -1,13 +1,9 @@
 use std::fmt;
 use std::collections::HashMap;
-use crate::usb::descriptors::{
-    USBDescriptor, 
-    DeviceDescriptor, 
-    ConfigurationDescriptor, 
-    InterfaceDescriptor, 
-    EndpointDescriptor, 
-    StringDescriptor
-};
+use crate::usb::descriptors::USBDescriptor;
+use crate::usb::pid::UsbPid;
+use crate::usb::standard_request;
+use crate::usb::{UsbDeviceClass, UsbEndpointType};
 use serde::{Deserialize, Serialize};
 
 // USB packet direction enum
@@ -50,6 +46,18 @@ impl fmt::Display for UsbTransferType {
     }
 }
 
+impl From<UsbEndpointType> for UsbTransferType {
+    fn from(endpoint_type: UsbEndpointType) -> Self {
+        match endpoint_type {
+            UsbEndpointType::Control => UsbTransferType::Control,
+            UsbEndpointType::Isochronous => UsbTransferType::Isochronous,
+            UsbEndpointType::Bulk => UsbTransferType::Bulk,
+            UsbEndpointType::Interrupt => UsbTransferType::Interrupt,
+            UsbEndpointType::Unknown(_) => UsbTransferType::Unknown,
+        }
+    }
+}
+
 // USB Control Request Type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UsbControlRequestType {
@@ -260,7 +268,7 @@ impl UsbSetupPacket {
         standard_request: Option<UsbStandardRequest>,
         w_value: u16,
         w_index: u16,
-        _w_length: u16
+        w_length: u16
     ) -> String {
         // For standard requests, we can give detailed information
         if let Some(std_request) = standard_request {
@@ -272,12 +280,15 @@ impl UsbSetupPacket {
                     match descriptor_type {
                         1 => format!("Get DEVICE Descriptor"),
                         2 => format!("Get CONFIGURATION Descriptor (Index: {})", descriptor_index),
-                        3 => format!("Get STRING Descriptor (Index: {})", descriptor_index),
+                        3 if descriptor_index == 0 => "Get STRING Descriptor (Index: 0, Language List)".to_string(),
+                        3 => format!("Get STRING Descriptor (Index: {}, LANGID: {})", descriptor_index,
+                                     standard_request::describe_langid(w_index)),
                         4 => format!("Get INTERFACE Descriptor (Index: {})", descriptor_index),
                         5 => format!("Get ENDPOINT Descriptor (Index: {})", descriptor_index),
                         6 => format!("Get DEVICE_QUALIFIER Descriptor"),
                         7 => format!("Get OTHER_SPEED_CONFIGURATION Descriptor"),
                         8 => format!("Get INTERFACE_POWER Descriptor"),
+                        15 => "Get BOS Descriptor".to_string(),
                         _ => format!("Get Unknown Descriptor (Type: {}, Index: {})", 
                                     descriptor_type, descriptor_index),
                     }
@@ -310,10 +321,24 @@ impl UsbSetupPacket {
                 UsbStandardRequest::GetInterface => {
                     format!("Get Interface: {}", w_index)
                 },
+                UsbStandardRequest::ClearFeature | UsbStandardRequest::SetFeature => {
+                    standard_request::describe_feature_request(std_request, recipient, w_value, w_index)
+                },
+                UsbStandardRequest::SynchFrame => {
+                    format!("Synch Frame of Endpoint {} {}", w_index & 0x0F,
+                            if (w_index & 0x80) != 0 { "IN" } else { "OUT" })
+                },
+                UsbStandardRequest::SetSel => {
+                    format!("Set System Exit Latency ({} bytes)", w_length)
+                },
+                UsbStandardRequest::SetIsochDelay => {
+                    format!("Set Isochronous Delay: {} ns", w_value)
+                },
                 _ => format!("{:?} ({})", std_request, b_request),
             }
         } else {
-            // For non-standard requests, just provide the basics
+            // For non-standard requests, just provide the basics. Hub requests are named
+            // later, once the device is known to be a hub.
             match request_type {
                 UsbControlRequestType::Class => {
                     format!("Class Request: 0x{:02X}, Value: 0x{:04X}, Index: 0x{:04X}", 
@@ -414,6 +439,28 @@ impl fmt::Display for UsbTransferStatus {
     }
 }
 
+// Endpoint details resolved from the device's active configuration and alternate setting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsbEndpointInfo {
+    pub endpoint_address: u8,          // Includes direction bit
+    pub transfer_type: UsbTransferType,
+    pub max_packet_size: u16,
+    pub interval: u8,
+    pub interface_number: u8,
+    pub alternate_setting: u8,
+    pub interface_class: UsbDeviceClass,
+    pub interface_subclass: u8,
+    pub interface_protocol: u8,
+}
+
+impl fmt::Display for UsbEndpointInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EP 0x{:02X} {}, max packet {} bytes, Interface {} Alt {} ({})",
+               self.endpoint_address, self.transfer_type, self.max_packet_size,
+               self.interface_number, self.alternate_setting, self.interface_class.name())
+    }
+}
+
 // A single USB transaction (Setup, Data, Status)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UsbTransaction {
@@ -422,6 +469,10 @@ pub struct UsbTransaction {
     pub setup_packet: Option<UsbSetupPacket>,
     pub data_packet: Option<UsbDataPacket>,
     pub status_packet: Option<UsbStatusPacket>,
+    #[serde(default)]
+    pub data_pid: Option<UsbPid>, // DATA0/DATA1/... when captured at packet level
+    #[serde(default)]
+    pub endpoint_info: Option<UsbEndpointInfo>, // Resolved from the device's descriptors
     pub timestamp: f64,
     pub device_address: u8,
     pub endpoint: u8,
@@ -436,6 +487,8 @@ impl UsbTransaction {
             setup_packet: None,
             data_packet: None,
             status_packet: None,
+            data_pid: None,
+            endpoint_info: None,
             timestamp,
             device_address: 0,
             endpoint: 0,
@@ -443,6 +496,22 @@ impl UsbTransaction {
         }
     }
     
+    // Direction of the transaction's data, from the token, data stage or setup packet
+    pub fn direction(&self) -> UsbDirection {
+        match self.fields.get("Token").map(|token| token.as_str()) {
+            Some("IN") => return UsbDirection::DeviceToHost,
+            Some(_) => return UsbDirection::HostToDevice,
+            None => {},
+        }
+        if let Some(data) = &self.data_packet {
+            return data.direction;
+        }
+        match &self.setup_packet {
+            Some(setup) => setup.direction,
+            None => UsbDirection::Unknown,
+        }
+    }
+    
     pub fn get_summary(&self) -> String {
         match self.transfer_type {
             UsbTransferType::Control => {
@@ -562,143 +631,6 @@ impl MitmTrafficData {
         
         hierarchy
     }
-    
-    // Analyze transactions to identify USB descriptors
-    #[allow(dead_code)]
-    pub fn extract_descriptors(&mut self) {
-        for transaction in &self.transactions {
-            if transaction.transfer_type == UsbTransferType::Control {
-                // Look for setup packets that might contain descriptors
-                if let Some(setup) = &transaction.setup_packet {
-                    // Check if this is a GET_DESCRIPTOR request
-                    if setup.request_type == UsbControlRequestType::Standard &&
-                       setup.standard_request == Some(UsbStandardRequest::GetDescriptor) {
-                        
-                        // Now check if there's a corresponding data packet with the descriptor
-                        if let Some(data) = &transaction.data_packet {
-                            if !data.get_data().is_empty() {
-                                // This might be a descriptor - try to parse it based on the type
-                                // We'll need to try parsing it as different descriptor types
-                                let descriptor_type = (setup.wValue >> 8) as u8;
-                                
-                                // Try to parse based on descriptor type
-                                match descriptor_type {
-                                    1 => { // Device Descriptor
-                                        if let Ok(device_desc) = DeviceDescriptor::parse(data.get_data()) {
-                                            self.descriptors.push(USBDescriptor::Device(device_desc));
-                                        }
-                                    },
-                                    2 => { // Configuration Descriptor
-                                        if let Ok(config_desc) = ConfigurationDescriptor::parse(data.get_data()) {
-                                            self.descriptors.push(USBDescriptor::Configuration(config_desc));
-                                        }
-                                    },
-                                    3 => { // String Descriptor
-                                        if let Ok(string_desc) = StringDescriptor::parse(data.get_data(), (setup.wValue & 0xFF) as u8) {
-                                            self.descriptors.push(USBDescriptor::String(string_desc));
-                                        }
-                                    },
-                                    4 => { // Interface Descriptor
-                                        if let Ok(interface_desc) = InterfaceDescriptor::parse(data.get_data()) {
-                                            self.descriptors.push(USBDescriptor::Interface(interface_desc));
-                                        }
-                                    },
-                                    5 => { // Endpoint Descriptor
-                                        if let Ok(endpoint_desc) = EndpointDescriptor::parse(data.get_data()) {
-                                            self.descriptors.push(USBDescriptor::Endpoint(endpoint_desc));
-                                        }
-                                    },
-                                    _ => {
-                                        // Other descriptor types not handled yet
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    
-    // Get transaction details suitable for display
-    #[allow(dead_code)]
-    pub fn get_transaction_details(&self, transaction_id: u64) -> HashMap<String, String> {
-        let mut details = HashMap::new();
-        
-        // Find the transaction by ID
-        if let Some(transaction) = self.transactions.iter().find(|t| t.id == transaction_id) {
-            // Add basic information
-            details.insert("Type".to_string(), format!("{}", transaction.transfer_type));
-            details.insert("Device".to_string(), format!("Address {}", transaction.device_address));
-            details.insert("Endpoint".to_string(), format!("0x{:02X}", transaction.endpoint));
-            
-            // Add detailed information based on transaction type
-            match transaction.transfer_type {
-                UsbTransferType::Control => {
-                    if let Some(setup) = &transaction.setup_packet {
-                        details.insert("Request".to_string(), format!("{}", setup.bRequest));
-                        details.insert("Request Type".to_string(), format!("{:?}", setup.request_type));
-                        details.insert("Direction".to_string(), format!("{}", setup.direction));
-                        details.insert("Value".to_string(), format!("0x{:04X}", setup.wValue));
-                        details.insert("Index".to_string(), format!("0x{:04X}", setup.wIndex));
-                        details.insert("Length".to_string(), format!("{}", setup.wLength));
-                    }
-                    
-                    if let Some(data) = &transaction.data_packet {
-                        let data_len = data.get_data_len();
-                        details.insert("Data Length".to_string(), format!("{} bytes", data_len));
-                        if !data.get_data().is_empty() {
-                            // Add hex dump of first few bytes
-                            let max_bytes = std::cmp::min(16, data_len);
-                            let hex_dump = data.get_data()[0..max_bytes]
-                                .iter()
-                                .map(|b| format!("{:02X}", b))
-                                .collect::<Vec<String>>()
-                                .join(" ");
-                            details.insert("Data".to_string(), format!("{}{}", 
-                                hex_dump, 
-                                if data_len > max_bytes { " ..." } else { "" }
-                            ));
-                        }
-                    }
-                    
-                    if let Some(status) = &transaction.status_packet {
-                        details.insert("Status".to_string(), format!("{:?}", status.status));
-                    }
-                },
-                
-                UsbTransferType::Bulk | UsbTransferType::Interrupt | UsbTransferType::Isochronous => {
-                    if let Some(data) = &transaction.data_packet {
-                        details.insert("Direction".to_string(), format!("{}", data.direction));
-                        let data_len = data.get_data_len();
-                        details.insert("Data Length".to_string(), format!("{} bytes", data_len));
-                        if !data.get_data().is_empty() {
-                            // Add hex dump of first few bytes
-                            let max_bytes = std::cmp::min(16, data_len);
-                            let hex_dump = data.get_data()[0..max_bytes]
-                                .iter()
-                                .map(|b| format!("{:02X}", b))
-                                .collect::<Vec<String>>()
-                                .join(" ");
-                            details.insert("Data".to_string(), format!("{}{}", 
-                                hex_dump, 
-                                if data_len > max_bytes { " ..." } else { "" }
-                            ));
-                        }
-                    }
-                },
-                
-                UsbTransferType::Unknown => {
-                    // Add any fields we have
-                    for (key, value) in &transaction.fields {
-                        details.insert(key.clone(), value.clone());
-                    }
-                }
-            }
-        }
-        
-        details
-    }
 }
 
 // Generate simulated MitM traffic for testing and fallback
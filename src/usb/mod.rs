@@ -1,9 +1,32 @@
+pub mod analyzer;
+pub mod android;
+pub mod bus;
+pub mod bus_event;
+pub mod class_request;
+pub mod control;
 pub mod descriptors;
 pub mod descriptor_types;
+pub mod device_state;
+pub mod enumeration;
+pub mod fingerprint;
 pub mod decoder;
 pub mod hints;
+pub mod hub;
 pub mod mitm_traffic;
+pub mod ms_os;
 pub mod packet_types;
+pub mod pid;
+pub mod replay;
+pub mod request_fields;
+pub mod script;
+pub mod serial_bridge;
+pub mod sof;
+pub mod split;
+pub mod stall;
+pub mod standard_request;
+pub mod toggle;
+pub mod transaction;
+pub mod vendor_schema;
 
 // Re-export commonly used types for easier access
 pub use self::descriptor_types::{
@@ -23,7 +46,7 @@ pub use self::descriptors::{
     // StringDescriptor and DeviceQualifierDescriptor are still available directly from descriptors module
 };
 
-pub use self::decoder::{DecodedUSBData, UsbDecoder, Speed};
+pub use self::decoder::{CaptureFormat, DecodedUSBData, UsbDecoder, Speed};
 
 // No need to re-export packet_types as it's already a public module
 
@@ -0,0 +1,827 @@
+// Microsoft OS descriptors
+// OS 1.0: Windows reads string descriptor 0xEE; a "MSFT100" signature there gives the vendor code
+// it then uses as bRequest for vendor requests fetching the Extended Compat ID (wIndex 4) and
+// Extended Properties (wIndex 5) feature descriptors.
+// OS 2.0: a BOS Platform capability with the MS OS 2.0 UUID gives the vendor code and length of
+// a descriptor set fetched with wIndex 7. The set holds device-wide features followed by
+// configuration and function subsets with their own features.
+// Both let a device ask for WinUSB (CompatibleID "WINUSB") and register DeviceInterfaceGUIDs
+// without an INF.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use super::descriptors::format_uuid;
+use super::mitm_traffic::{UsbControlRequestType, UsbDirection, UsbSetupPacket, UsbStandardRequest};
+use super::request_fields::RequestField;
+
+// String descriptor index Windows reads the OS 1.0 signature from
+pub const OS_STRING_INDEX: u8 = 0xEE;
+const OS_STRING_SIGNATURE: &str = "MSFT100";
+
+// wIndex of the feature descriptor requests
+pub const EXTENDED_COMPAT_ID_INDEX: u16 = 0x0004;
+pub const EXTENDED_PROPERTIES_INDEX: u16 = 0x0005;
+pub const MS_OS_20_DESCRIPTOR_INDEX: u16 = 0x0007;
+pub const MS_OS_20_SET_ALT_ENUMERATION: u16 = 0x0008;
+
+// MS OS 2.0 descriptor types
+const MS_OS_20_SET_HEADER_DESCRIPTOR: u16 = 0x00;
+const MS_OS_20_SUBSET_HEADER_CONFIGURATION: u16 = 0x01;
+const MS_OS_20_SUBSET_HEADER_FUNCTION: u16 = 0x02;
+const MS_OS_20_FEATURE_COMPATIBLE_ID: u16 = 0x03;
+const MS_OS_20_FEATURE_REG_PROPERTY: u16 = 0x04;
+const MS_OS_20_FEATURE_MIN_RESUME_TIME: u16 = 0x05;
+const MS_OS_20_FEATURE_MODEL_ID: u16 = 0x06;
+const MS_OS_20_FEATURE_CCGP_DEVICE: u16 = 0x07;
+const MS_OS_20_FEATURE_VENDOR_REVISION: u16 = 0x08;
+
+const DEVICE_INTERFACE_GUIDS: &str = "DeviceInterfaceGUIDs";
+const DEVICE_INTERFACE_GUID: &str = "DeviceInterfaceGUID";
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data.chunks_exact(2).map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]])).collect();
+    String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+}
+
+// Compatible IDs are 8 ASCII bytes padded with NULs
+fn ascii_id(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).trim_end_matches('\0').to_string()
+}
+
+fn windows_version_name(version: u32) -> &'static str {
+    match version {
+        0x06030000 => "Windows 8.1",
+        0x0A000000 => "Windows 10",
+        _ => "Unknown",
+    }
+}
+
+// Vendor code from an OS string descriptor, if the string carries the MSFT100 signature
+pub fn os_string_vendor_code(data: &[u8]) -> Option<u8> {
+    if data.len() < 18 || data[1] != 0x03 || utf16_string(&data[2..16]) != OS_STRING_SIGNATURE {
+        return None;
+    }
+    Some(data[16])
+}
+
+// The feature descriptor a vendor request fetches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MsOsFeature {
+    ExtendedCompatId,
+    ExtendedProperties { interface: u8 },
+    DescriptorSet,
+}
+
+impl fmt::Display for MsOsFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsOsFeature::ExtendedCompatId => write!(f, "Extended Compat ID"),
+            MsOsFeature::ExtendedProperties { interface } => write!(f, "Extended Properties (interface {})", interface),
+            MsOsFeature::DescriptorSet => write!(f, "MS OS 2.0 Descriptor Set"),
+        }
+    }
+}
+
+// A registry value from an Extended Properties section or an MS OS 2.0 registry property
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistryProperty {
+    pub data_type: u32,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl RegistryProperty {
+    pub fn data_type_name(&self) -> &'static str {
+        match self.data_type {
+            1 => "REG_SZ",
+            2 => "REG_EXPAND_SZ",
+            3 => "REG_BINARY",
+            4 => "REG_DWORD_LITTLE_ENDIAN",
+            5 => "REG_DWORD_BIG_ENDIAN",
+            6 => "REG_LINK",
+            7 => "REG_MULTI_SZ",
+            _ => "Reserved",
+        }
+    }
+
+    // String values; REG_MULTI_SZ gives one per string
+    pub fn strings(&self) -> Vec<String> {
+        match self.data_type {
+            1 | 2 | 6 => vec![utf16_string(&self.data)],
+            7 => utf16_string(&self.data).split('\0').filter(|value| !value.is_empty()).map(str::to_string).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn value(&self) -> String {
+        match self.data_type {
+            1 | 2 | 6 | 7 => self.strings().join(", "),
+            4 if self.data.len() >= 4 => format!("0x{:08X}", read_u32(&self.data, 0)),
+            5 if self.data.len() >= 4 => format!("0x{:08X}", u32::from_be_bytes([self.data[0], self.data[1], self.data[2], self.data[3]])),
+            _ => self.data.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" "),
+        }
+    }
+
+    // GUIDs a DeviceInterfaceGUID(s) property registers
+    pub fn interface_guids(&self) -> Vec<String> {
+        if self.name == DEVICE_INTERFACE_GUIDS || self.name == DEVICE_INTERFACE_GUID {
+            self.strings()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl fmt::Display for RegistryProperty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}) = {}", self.name, self.data_type_name(), self.value())
+    }
+}
+
+// One function section of an Extended Compat ID descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompatIdFunction {
+    pub first_interface: u8,
+    pub compatible_id: String,
+    pub sub_compatible_id: String,
+}
+
+// OS 1.0 Extended Compat ID feature descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedCompatId {
+    pub version: u16,
+    pub functions: Vec<CompatIdFunction>,
+}
+
+impl ExtendedCompatId {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 16 {
+            return Err(format!("Extended Compat ID header is {} bytes, expected 16", data.len()));
+        }
+        let length = read_u32(data, 0) as usize;
+        let index = read_u16(data, 6);
+        if index != EXTENDED_COMPAT_ID_INDEX {
+            return Err(format!("Extended Compat ID wIndex is 0x{:04X}, expected 0x0004", index));
+        }
+        let count = data[8] as usize;
+        if length != 16 + count * 24 {
+            return Err(format!("Extended Compat ID dwLength {} doesn't match {} function sections", length, count));
+        }
+        let functions = data[16..].chunks_exact(24)
+            .take(count)
+            .map(|section| CompatIdFunction {
+                first_interface: section[0],
+                compatible_id: ascii_id(&section[2..10]),
+                sub_compatible_id: ascii_id(&section[10..18]),
+            })
+            .collect();
+        Ok(ExtendedCompatId { version: read_u16(data, 4), functions })
+    }
+}
+
+// OS 1.0 Extended Properties feature descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedProperties {
+    pub version: u16,
+    pub properties: Vec<RegistryProperty>,
+}
+
+impl ExtendedProperties {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 10 {
+            return Err(format!("Extended Properties header is {} bytes, expected 10", data.len()));
+        }
+        let index = read_u16(data, 6);
+        if index != EXTENDED_PROPERTIES_INDEX {
+            return Err(format!("Extended Properties wIndex is 0x{:04X}, expected 0x0005", index));
+        }
+        let count = read_u16(data, 8) as usize;
+        let mut properties = Vec::new();
+        let mut offset = 10;
+        while properties.len() < count {
+            if offset + 14 > data.len() {
+                return Err(format!("Extended Properties has {} of {} sections", properties.len(), count));
+            }
+            let size = read_u32(data, offset) as usize;
+            let name_length = read_u16(data, offset + 8) as usize;
+            let data_length_offset = offset + 10 + name_length;
+            if size < 14 || offset + size > data.len() || data_length_offset + 4 > offset + size {
+                return Err(format!("Extended Properties section {} has an invalid dwSize {}", properties.len(), size));
+            }
+            let data_length = read_u32(data, data_length_offset) as usize;
+            let value_offset = data_length_offset + 4;
+            if value_offset + data_length > offset + size {
+                return Err(format!("Extended Properties section {} data overruns its dwSize", properties.len()));
+            }
+            properties.push(RegistryProperty {
+                data_type: read_u32(data, offset + 4),
+                name: utf16_string(&data[offset + 10..data_length_offset]),
+                data: data[value_offset..value_offset + data_length].to_vec(),
+            });
+            offset += size;
+        }
+        Ok(ExtendedProperties { version: read_u16(data, 4), properties })
+    }
+}
+
+// One descriptor set advertised by the MS OS 2.0 Platform capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsOs20PlatformInfo {
+    pub windows_version: u32,
+    pub total_length: u16,
+    pub vendor_code: u8,
+    pub alt_enum_code: u8,
+}
+
+impl MsOs20PlatformInfo {
+    // Parse the CapabilityData of the Platform capability (one 8-byte entry per Windows version)
+    pub fn parse_platform_data(data: &[u8]) -> Vec<MsOs20PlatformInfo> {
+        data.chunks_exact(8)
+            .map(|entry| MsOs20PlatformInfo {
+                windows_version: read_u32(entry, 0),
+                total_length: read_u16(entry, 4),
+                vendor_code: entry[6],
+                alt_enum_code: entry[7],
+            })
+            .collect()
+    }
+}
+
+// A feature descriptor inside an MS OS 2.0 descriptor set
+#[derive(Debug, Clone, PartialEq)]
+pub enum MsOs20Feature {
+    CompatibleId { compatible_id: String, sub_compatible_id: String },
+    RegistryProperty(RegistryProperty),
+    MinResumeTime { recovery_ms: u8, signaling_ms: u8 },
+    ModelId(String),
+    CcgpDevice,
+    VendorRevision(u16),
+    Unknown { descriptor_type: u16, data: Vec<u8> },
+}
+
+impl fmt::Display for MsOs20Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsOs20Feature::CompatibleId { compatible_id, sub_compatible_id } if sub_compatible_id.is_empty() => {
+                write!(f, "CompatibleID {}", compatible_id)
+            },
+            MsOs20Feature::CompatibleId { compatible_id, sub_compatible_id } => {
+                write!(f, "CompatibleID {} / {}", compatible_id, sub_compatible_id)
+            },
+            MsOs20Feature::RegistryProperty(property) => write!(f, "Registry Property {}", property),
+            MsOs20Feature::MinResumeTime { recovery_ms, signaling_ms } => {
+                write!(f, "Minimum Resume Time: {} ms recovery, {} ms signaling", recovery_ms, signaling_ms)
+            },
+            MsOs20Feature::ModelId(model) => write!(f, "Model ID {{{}}}", model),
+            MsOs20Feature::CcgpDevice => write!(f, "CCGP Device"),
+            MsOs20Feature::VendorRevision(revision) => write!(f, "Vendor Revision {}", revision),
+            MsOs20Feature::Unknown { descriptor_type, data } => {
+                write!(f, "Unknown descriptor 0x{:04X} ({} bytes)", descriptor_type, data.len())
+            },
+        }
+    }
+}
+
+// Features that apply to one function (interface group) of a configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct MsOs20FunctionSubset {
+    pub first_interface: u8,
+    pub features: Vec<MsOs20Feature>,
+}
+
+// Features that apply to one configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct MsOs20ConfigurationSubset {
+    pub configuration_index: u8,
+    pub features: Vec<MsOs20Feature>,
+    pub functions: Vec<MsOs20FunctionSubset>,
+}
+
+// An MS OS 2.0 descriptor set
+#[derive(Debug, Clone, PartialEq)]
+pub struct MsOs20DescriptorSet {
+    pub windows_version: u32,
+    pub total_length: u16,
+    pub features: Vec<MsOs20Feature>,           // Device-wide
+    pub configurations: Vec<MsOs20ConfigurationSubset>,
+}
+
+impl MsOs20DescriptorSet {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 10 || read_u16(data, 2) != MS_OS_20_SET_HEADER_DESCRIPTOR {
+            return Err("MS OS 2.0 descriptor set doesn't start with a set header".to_string());
+        }
+        let total_length = read_u16(data, 8);
+        if total_length as usize > data.len() {
+            return Err(format!("MS OS 2.0 descriptor set is {} bytes, header says {}", data.len(), total_length));
+        }
+        let mut set = MsOs20DescriptorSet {
+            windows_version: read_u32(data, 4),
+            total_length,
+            features: Vec::new(),
+            configurations: Vec::new(),
+        };
+
+        // Subsets give their total length, so features go to the innermost subset still open
+        let end = total_length as usize;
+        let mut configuration_end = 0;
+        let mut function_end = 0;
+        let mut offset = read_u16(data, 0) as usize;
+        while offset + 4 <= end {
+            let length = read_u16(data, offset) as usize;
+            let descriptor_type = read_u16(data, offset + 2);
+            if length < 4 || offset + length > end {
+                return Err(format!("MS OS 2.0 descriptor at offset {} has an invalid wLength {}", offset, length));
+            }
+            let descriptor = &data[offset..offset + length];
+
+            match descriptor_type {
+                MS_OS_20_SUBSET_HEADER_CONFIGURATION if length >= 8 => {
+                    configuration_end = offset + read_u16(descriptor, 6) as usize;
+                    function_end = 0;
+                    set.configurations.push(MsOs20ConfigurationSubset {
+                        configuration_index: descriptor[4],
+                        features: Vec::new(),
+                        functions: Vec::new(),
+                    });
+                },
+                MS_OS_20_SUBSET_HEADER_FUNCTION if length >= 8 => {
+                    function_end = offset + read_u16(descriptor, 6) as usize;
+                    let function = MsOs20FunctionSubset { first_interface: descriptor[4], features: Vec::new() };
+                    match set.configurations.last_mut() {
+                        Some(configuration) if offset < configuration_end => configuration.functions.push(function),
+                        _ => return Err(format!("MS OS 2.0 function subset at offset {} is outside a configuration subset", offset)),
+                    }
+                },
+                _ => {
+                    let feature = Self::parse_feature(descriptor_type, descriptor)?;
+                    let configuration = set.configurations.last_mut().filter(|_| offset < configuration_end);
+                    match configuration {
+                        Some(configuration) => match configuration.functions.last_mut().filter(|_| offset < function_end) {
+                            Some(function) => function.features.push(feature),
+                            None => configuration.features.push(feature),
+                        },
+                        None => set.features.push(feature),
+                    }
+                },
+            }
+            offset += length;
+        }
+        Ok(set)
+    }
+
+    fn parse_feature(descriptor_type: u16, descriptor: &[u8]) -> Result<MsOs20Feature, String> {
+        let too_short = || format!("MS OS 2.0 descriptor 0x{:04X} is too short ({} bytes)", descriptor_type, descriptor.len());
+        Ok(match descriptor_type {
+            MS_OS_20_FEATURE_COMPATIBLE_ID => {
+                if descriptor.len() < 20 {
+                    return Err(too_short());
+                }
+                MsOs20Feature::CompatibleId {
+                    compatible_id: ascii_id(&descriptor[4..12]),
+                    sub_compatible_id: ascii_id(&descriptor[12..20]),
+                }
+            },
+            MS_OS_20_FEATURE_REG_PROPERTY => {
+                if descriptor.len() < 10 {
+                    return Err(too_short());
+                }
+                let name_length = read_u16(descriptor, 6) as usize;
+                let data_length_offset = 8 + name_length;
+                if data_length_offset + 2 > descriptor.len() {
+                    return Err(too_short());
+                }
+                let data_length = read_u16(descriptor, data_length_offset) as usize;
+                let value_offset = data_length_offset + 2;
+                if value_offset + data_length > descriptor.len() {
+                    return Err("MS OS 2.0 registry property data overruns its wLength".to_string());
+                }
+                MsOs20Feature::RegistryProperty(RegistryProperty {
+                    data_type: read_u16(descriptor, 4) as u32,
+                    name: utf16_string(&descriptor[8..data_length_offset]),
+                    data: descriptor[value_offset..value_offset + data_length].to_vec(),
+                })
+            },
+            MS_OS_20_FEATURE_MIN_RESUME_TIME if descriptor.len() >= 6 => MsOs20Feature::MinResumeTime {
+                recovery_ms: descriptor[4],
+                signaling_ms: descriptor[5],
+            },
+            MS_OS_20_FEATURE_MODEL_ID if descriptor.len() >= 20 => MsOs20Feature::ModelId(format_uuid(&descriptor[4..20])),
+            MS_OS_20_FEATURE_CCGP_DEVICE => MsOs20Feature::CcgpDevice,
+            MS_OS_20_FEATURE_VENDOR_REVISION if descriptor.len() >= 6 => MsOs20Feature::VendorRevision(read_u16(descriptor, 4)),
+            MS_OS_20_FEATURE_MIN_RESUME_TIME | MS_OS_20_FEATURE_MODEL_ID | MS_OS_20_FEATURE_VENDOR_REVISION => return Err(too_short()),
+            _ => MsOs20Feature::Unknown { descriptor_type, data: descriptor[4..].to_vec() },
+        })
+    }
+
+    // Every feature in the set with where it applies ("Device", "Configuration 0", "Function 2")
+    pub fn all_features(&self) -> Vec<(String, &MsOs20Feature)> {
+        let mut features: Vec<(String, &MsOs20Feature)> = self.features.iter()
+            .map(|feature| ("Device".to_string(), feature))
+            .collect();
+        for configuration in &self.configurations {
+            features.extend(configuration.features.iter()
+                .map(|feature| (format!("Configuration {}", configuration.configuration_index), feature)));
+            for function in &configuration.functions {
+                features.extend(function.features.iter()
+                    .map(|feature| (format!("Function {}", function.first_interface), feature)));
+            }
+        }
+        features
+    }
+}
+
+// Everything learned about a device's Microsoft OS descriptors
+#[derive(Debug, Clone, Default)]
+pub struct MsOsDescriptors {
+    pub vendor_code: Option<u8>,                            // OS 1.0, from string 0xEE
+    pub platform: Vec<MsOs20PlatformInfo>,                  // OS 2.0, from the BOS Platform capability
+    pub compat_id: Option<ExtendedCompatId>,
+    pub properties: BTreeMap<u8, ExtendedProperties>,       // Interface -> OS 1.0 properties
+    pub descriptor_set: Option<MsOs20DescriptorSet>,
+    pub errors: Vec<String>,
+}
+
+impl MsOsDescriptors {
+    // The feature a vendor request fetches, if its bRequest is one of the device's vendor codes
+    pub fn feature_for_request(&self, setup: &UsbSetupPacket) -> Option<MsOsFeature> {
+        if setup.request_type != UsbControlRequestType::Vendor || setup.direction != UsbDirection::DeviceToHost {
+            return None;
+        }
+        let os_10 = self.vendor_code == Some(setup.bRequest);
+        let os_20 = self.platform.iter().any(|platform| platform.vendor_code == setup.bRequest);
+        match setup.wIndex {
+            EXTENDED_COMPAT_ID_INDEX if os_10 => Some(MsOsFeature::ExtendedCompatId),
+            EXTENDED_PROPERTIES_INDEX if os_10 => Some(MsOsFeature::ExtendedProperties { interface: (setup.wValue >> 8) as u8 }),
+            MS_OS_20_DESCRIPTOR_INDEX if os_20 => Some(MsOsFeature::DescriptorSet),
+            _ => None,
+        }
+    }
+
+    // Forget the feature descriptor responses, keeping the vendor codes
+    pub fn clear_responses(&mut self) {
+        self.compat_id = None;
+        self.properties.clear();
+        self.descriptor_set = None;
+        self.errors.clear();
+    }
+
+    // Store a feature descriptor response. A read of just the header is skipped.
+    pub fn add_response(&mut self, feature: MsOsFeature, data: &[u8]) {
+        if declared_length(feature, data).map(|length| data.len() < length).unwrap_or(false) {
+            return;
+        }
+        let result = match feature {
+            MsOsFeature::ExtendedCompatId => ExtendedCompatId::parse(data).map(|compat_id| self.compat_id = Some(compat_id)),
+            MsOsFeature::ExtendedProperties { interface } => ExtendedProperties::parse(data)
+                .map(|properties| { self.properties.insert(interface, properties); }),
+            MsOsFeature::DescriptorSet => MsOs20DescriptorSet::parse(data).map(|set| self.descriptor_set = Some(set)),
+        };
+        if let Err(error) = result {
+            self.errors.push(error);
+        }
+    }
+
+    // DeviceInterfaceGUIDs registered through either version, with where they apply
+    pub fn device_interface_guids(&self) -> Vec<(String, String)> {
+        let mut guids = Vec::new();
+        for (interface, properties) in &self.properties {
+            for property in &properties.properties {
+                guids.extend(property.interface_guids().into_iter().map(|guid| (format!("Interface {}", interface), guid)));
+            }
+        }
+        if let Some(set) = &self.descriptor_set {
+            for (scope, feature) in set.all_features() {
+                if let MsOs20Feature::RegistryProperty(property) = feature {
+                    guids.extend(property.interface_guids().into_iter().map(|guid| (scope.clone(), guid)));
+                }
+            }
+        }
+        guids
+    }
+
+    // Device hints for the MS OS descriptors
+    pub fn hints(&self) -> Vec<String> {
+        let mut hints = Vec::new();
+        if let Some(code) = self.vendor_code {
+            hints.push(format!("Microsoft OS 1.0 descriptors: vendor code 0x{:02X}", code));
+        }
+        for platform in &self.platform {
+            hints.push(format!("Microsoft OS 2.0 descriptor set for {} (0x{:08X}): {} bytes, vendor code 0x{:02X}",
+                               windows_version_name(platform.windows_version), platform.windows_version,
+                               platform.total_length, platform.vendor_code));
+            if let Some(set) = &self.descriptor_set {
+                if set.total_length != platform.total_length {
+                    hints.push(format!("Warning: MS OS 2.0 set is {} bytes but the platform capability says {}",
+                                       set.total_length, platform.total_length));
+                }
+            }
+        }
+
+        let mut compatible_ids: Vec<(String, String)> = Vec::new();
+        if let Some(compat_id) = &self.compat_id {
+            compatible_ids.extend(compat_id.functions.iter()
+                .map(|function| (format!("Interface {}", function.first_interface), function.compatible_id.clone())));
+        }
+        if let Some(set) = &self.descriptor_set {
+            for (scope, feature) in set.all_features() {
+                match feature {
+                    MsOs20Feature::CompatibleId { compatible_id, .. } => compatible_ids.push((scope, compatible_id.clone())),
+                    MsOs20Feature::VendorRevision(revision) => {
+                        hints.push(format!("MS OS 2.0 vendor revision {}: Windows re-reads the descriptors when it changes", revision));
+                    },
+                    MsOs20Feature::CcgpDevice => hints.push("Windows treats the device as composite (CCGP)".to_string()),
+                    _ => {},
+                }
+            }
+        }
+        for (scope, compatible_id) in compatible_ids {
+            if compatible_id == "WINUSB" {
+                hints.push(format!("{}: CompatibleID WINUSB - Windows binds WinUSB without an INF", scope));
+            } else {
+                hints.push(format!("{}: CompatibleID {}", scope, compatible_id));
+            }
+        }
+        for (scope, guid) in self.device_interface_guids() {
+            hints.push(format!("{}: DeviceInterfaceGUID {} - applications open the device through it", scope, guid));
+        }
+        hints.extend(self.errors.iter().map(|error| format!("Warning: {}", error)));
+        hints
+    }
+}
+
+// Total length a feature descriptor's header declares
+fn declared_length(feature: MsOsFeature, data: &[u8]) -> Option<usize> {
+    match feature {
+        MsOsFeature::ExtendedCompatId | MsOsFeature::ExtendedProperties { .. } if data.len() >= 4 => Some(read_u32(data, 0) as usize),
+        MsOsFeature::DescriptorSet if data.len() >= 10 => Some(read_u16(data, 8) as usize),
+        _ => None,
+    }
+}
+
+// An MS OS descriptor request recognised in the traffic
+#[derive(Debug, Clone)]
+pub struct MsOsRequest {
+    pub description: String,
+    pub fields: Vec<RequestField>,
+}
+
+fn is_os_string_request(setup: &UsbSetupPacket) -> bool {
+    setup.request_type == UsbControlRequestType::Standard
+        && setup.standard_request == Some(UsbStandardRequest::GetDescriptor)
+        && setup.wValue == (0x03 << 8) | OS_STRING_INDEX as u16
+}
+
+// Recognise the OS string request and the vendor-code requests that follow it
+pub fn decode_request(descriptors: &MsOsDescriptors, setup: &UsbSetupPacket) -> Option<MsOsRequest> {
+    if is_os_string_request(setup) {
+        return Some(MsOsRequest {
+            description: "GET_DESCRIPTOR Microsoft OS String".to_string(),
+            fields: vec![("Microsoft OS".to_string(), "OS 1.0 signature probe (string 0xEE)".to_string())],
+        });
+    }
+    if setup.request_type == UsbControlRequestType::Vendor && setup.wIndex == MS_OS_20_SET_ALT_ENUMERATION
+        && descriptors.platform.iter().any(|platform| platform.alt_enum_code != 0 && platform.vendor_code == setup.bRequest) {
+        return Some(MsOsRequest {
+            description: "MS OS 2.0 SET_ALT_ENUMERATION".to_string(),
+            fields: vec![("Alternate Enumeration Code".to_string(), format!("0x{:02X}", setup.wValue >> 8))],
+        });
+    }
+    let feature = descriptors.feature_for_request(setup)?;
+    let mut fields = vec![
+        ("Vendor Code".to_string(), format!("0x{:02X}", setup.bRequest)),
+        ("Feature".to_string(), feature.to_string()),
+    ];
+    if feature != MsOsFeature::DescriptorSet {
+        fields.push(("Page".to_string(), format!("{}", setup.wValue & 0xFF)));
+    }
+    Some(MsOsRequest { description: format!("GET_MS_DESCRIPTOR {}", feature), fields })
+}
+
+// Decode the data stage of an MS OS descriptor request
+pub fn decode_response(descriptors: &MsOsDescriptors, setup: &UsbSetupPacket, data: &[u8]) -> Vec<RequestField> {
+    let mut fields = Vec::new();
+    if is_os_string_request(setup) {
+        match os_string_vendor_code(data) {
+            Some(code) => {
+                fields.push(("Signature".to_string(), OS_STRING_SIGNATURE.to_string()));
+                fields.push(("Vendor Code".to_string(), format!("0x{:02X}", code)));
+            },
+            None => fields.push(("Warning".to_string(), "string 0xEE has no MSFT100 signature".to_string())),
+        }
+        return fields;
+    }
+
+    let feature = match descriptors.feature_for_request(setup) {
+        Some(feature) => feature,
+        None => return fields,
+    };
+    // Windows reads the header first to learn the full length
+    if let Some(length) = declared_length(feature, data).filter(|length| data.len() < *length) {
+        fields.push(("Header".to_string(), format!("{} bytes in total", length)));
+        return fields;
+    }
+
+    match feature {
+        MsOsFeature::ExtendedCompatId => match ExtendedCompatId::parse(data) {
+            Ok(compat_id) => {
+                for function in compat_id.functions {
+                    let value = if function.sub_compatible_id.is_empty() {
+                        function.compatible_id
+                    } else {
+                        format!("{} / {}", function.compatible_id, function.sub_compatible_id)
+                    };
+                    fields.push((format!("Interface {}", function.first_interface), value));
+                }
+            },
+            Err(error) => fields.push(("Warning".to_string(), error)),
+        },
+        MsOsFeature::ExtendedProperties { .. } => match ExtendedProperties::parse(data) {
+            Ok(properties) => {
+                for property in properties.properties {
+                    fields.push((property.name.clone(), format!("{} ({})", property.value(), property.data_type_name())));
+                }
+            },
+            Err(error) => fields.push(("Warning".to_string(), error)),
+        },
+        MsOsFeature::DescriptorSet => match MsOs20DescriptorSet::parse(data) {
+            Ok(set) => {
+                fields.push(("Windows Version".to_string(),
+                             format!("0x{:08X} ({})", set.windows_version, windows_version_name(set.windows_version))));
+                for (scope, feature) in set.all_features() {
+                    fields.push((scope, feature.to_string()));
+                }
+            },
+            Err(error) => fields.push(("Warning".to_string(), error)),
+        },
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUID: &str = "{88BAE032-5A81-49F0-BC3D-A4FF138216D6}";
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    fn vendor_request(code: u8, w_value: u16, w_index: u16) -> UsbSetupPacket {
+        let value = w_value.to_le_bytes();
+        let index = w_index.to_le_bytes();
+        UsbSetupPacket::new(&[0xC0, code, value[0], value[1], index[0], index[1], 0xFF, 0x00]).unwrap()
+    }
+
+    #[test]
+    fn reads_vendor_code_from_os_string() {
+        let mut data = vec![0x12, 0x03];
+        data.extend_from_slice(&utf16("MSFT100"));
+        data.extend_from_slice(&[0x20, 0x00]);
+        assert_eq!(os_string_vendor_code(&data), Some(0x20));
+
+        let mut other = vec![0x12, 0x03];
+        other.extend_from_slice(&utf16("MSFT200"));
+        other.extend_from_slice(&[0x20, 0x00]);
+        assert_eq!(os_string_vendor_code(&other), None);
+    }
+
+    #[test]
+    fn parses_extended_compat_id() {
+        let mut data = vec![40, 0, 0, 0, 0x00, 0x01, 0x04, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&[0x00, 0x01]);
+        data.extend_from_slice(b"WINUSB\0\0");
+        data.extend_from_slice(&[0; 14]);
+
+        let compat_id = ExtendedCompatId::parse(&data).unwrap();
+        assert_eq!(compat_id.version, 0x0100);
+        assert_eq!(compat_id.functions, vec![CompatIdFunction {
+            first_interface: 0,
+            compatible_id: "WINUSB".to_string(),
+            sub_compatible_id: String::new(),
+        }]);
+
+        // dwLength has to match the function count
+        data[0] = 64;
+        assert!(ExtendedCompatId::parse(&data).is_err());
+    }
+
+    #[test]
+    fn parses_extended_properties() {
+        let name = utf16("DeviceInterfaceGUIDs\0");
+        let value = utf16(&format!("{}\0\0", GUID));
+        let size = 14 + name.len() + value.len();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&((10 + size) as u32).to_le_bytes());
+        data.extend_from_slice(&[0x00, 0x01, 0x05, 0x00, 0x01, 0x00]);
+        data.extend_from_slice(&(size as u32).to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(&value);
+
+        let properties = ExtendedProperties::parse(&data).unwrap();
+        assert_eq!(properties.properties.len(), 1);
+        let property = &properties.properties[0];
+        assert_eq!(property.data_type_name(), "REG_MULTI_SZ");
+        assert_eq!(property.interface_guids(), vec![GUID.to_string()]);
+
+        // A section that claims more data than it holds
+        let truncated = &data[..data.len() - 4];
+        assert!(ExtendedProperties::parse(truncated).is_err());
+    }
+
+    // Set header, a device-wide CompatibleID, then configuration 0 with function 1 holding
+    // a DeviceInterfaceGUIDs registry property
+    fn descriptor_set() -> Vec<u8> {
+        let name = utf16("DeviceInterfaceGUIDs\0");
+        let value = utf16(&format!("{}\0\0", GUID));
+        let mut property = Vec::new();
+        property.extend_from_slice(&((10 + name.len() + value.len()) as u16).to_le_bytes());
+        property.extend_from_slice(&[0x04, 0x00, 0x07, 0x00]);
+        property.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        property.extend_from_slice(&name);
+        property.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        property.extend_from_slice(&value);
+
+        let function_length = 8 + property.len();
+        let configuration_length = 8 + function_length;
+        let total_length = 10 + 20 + configuration_length;
+
+        let mut data = vec![0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x06];
+        data.extend_from_slice(&(total_length as u16).to_le_bytes());
+        data.extend_from_slice(&[0x14, 0x00, 0x03, 0x00]);
+        data.extend_from_slice(b"WINUSB\0\0");
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[0x08, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(&(configuration_length as u16).to_le_bytes());
+        data.extend_from_slice(&[0x08, 0x00, 0x02, 0x00, 0x01, 0x00]);
+        data.extend_from_slice(&(function_length as u16).to_le_bytes());
+        data.extend_from_slice(&property);
+        data
+    }
+
+    #[test]
+    fn parses_ms_os_20_descriptor_set() {
+        let data = descriptor_set();
+        let set = MsOs20DescriptorSet::parse(&data).unwrap();
+
+        assert_eq!(set.windows_version, 0x06030000);
+        assert_eq!(set.total_length as usize, data.len());
+        assert_eq!(set.features, vec![MsOs20Feature::CompatibleId {
+            compatible_id: "WINUSB".to_string(),
+            sub_compatible_id: String::new(),
+        }]);
+        assert_eq!(set.configurations.len(), 1);
+        assert!(set.configurations[0].features.is_empty());
+        assert_eq!(set.configurations[0].functions.len(), 1);
+        assert_eq!(set.configurations[0].functions[0].first_interface, 1);
+
+        let scopes: Vec<String> = set.all_features().into_iter().map(|(scope, _)| scope).collect();
+        assert_eq!(scopes, vec!["Device".to_string(), "Function 1".to_string()]);
+    }
+
+    #[test]
+    fn rejects_malformed_descriptor_sets() {
+        let mut data = descriptor_set();
+        // Total length past the end of the data
+        data[8] = data[8].wrapping_add(1);
+        assert!(MsOs20DescriptorSet::parse(&data).is_err());
+
+        assert!(MsOs20DescriptorSet::parse(&[0x0A, 0x00, 0x01, 0x00, 0, 0, 0, 0, 0x0A, 0x00]).is_err());
+    }
+
+    #[test]
+    fn collects_responses_for_vendor_code_requests() {
+        let mut descriptors = MsOsDescriptors {
+            platform: MsOs20PlatformInfo::parse_platform_data(&[0x00, 0x00, 0x03, 0x06, 0x7E, 0x00, 0x21, 0x00]),
+            ..Default::default()
+        };
+        descriptors.vendor_code = Some(0x20);
+
+        assert_eq!(descriptors.feature_for_request(&vendor_request(0x20, 0x0000, EXTENDED_COMPAT_ID_INDEX)),
+                   Some(MsOsFeature::ExtendedCompatId));
+        assert_eq!(descriptors.feature_for_request(&vendor_request(0x21, 0x0000, MS_OS_20_DESCRIPTOR_INDEX)),
+                   Some(MsOsFeature::DescriptorSet));
+        assert_eq!(descriptors.feature_for_request(&vendor_request(0x22, 0x0000, MS_OS_20_DESCRIPTOR_INDEX)), None);
+
+        // A read of the header alone is skipped; the full set is stored
+        let data = descriptor_set();
+        descriptors.add_response(MsOsFeature::DescriptorSet, &data[..10]);
+        assert!(descriptors.descriptor_set.is_none());
+        descriptors.add_response(MsOsFeature::DescriptorSet, &data);
+        assert!(descriptors.errors.is_empty());
+        assert_eq!(descriptors.device_interface_guids(), vec![("Function 1".to_string(), GUID.to_string())]);
+    }
+}
@@ -0,0 +1,309 @@
+// USB packet identifiers and raw packet parsing
+// Based on USB 2.0 specification chapter 8 (Protocol Layer)
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+// USB 2.0 packet identifiers (PID byte with its check nibble)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UsbPid {
+    // Token PIDs
+    Out,
+    In,
+    Sof,
+    Setup,
+    // Data PIDs
+    Data0,
+    Data1,
+    Data2,
+    MData,
+    // Handshake PIDs
+    Ack,
+    Nak,
+    Stall,
+    Nyet,
+    // Special PIDs
+    Pre,   // Also used as ERR in split transactions
+    Split,
+    Ping,
+}
+
+impl UsbPid {
+    // Decode a PID byte, rejecting bytes whose check nibble doesn't match
+    pub fn from_byte(value: u8) -> Option<Self> {
+        if (value >> 4) != (!value & 0x0F) {
+            return None;
+        }
+
+        match value & 0x0F {
+            0x1 => Some(UsbPid::Out),
+            0x9 => Some(UsbPid::In),
+            0x5 => Some(UsbPid::Sof),
+            0xD => Some(UsbPid::Setup),
+            0x3 => Some(UsbPid::Data0),
+            0xB => Some(UsbPid::Data1),
+            0x7 => Some(UsbPid::Data2),
+            0xF => Some(UsbPid::MData),
+            0x2 => Some(UsbPid::Ack),
+            0xA => Some(UsbPid::Nak),
+            0xE => Some(UsbPid::Stall),
+            0x6 => Some(UsbPid::Nyet),
+            0xC => Some(UsbPid::Pre),
+            0x8 => Some(UsbPid::Split),
+            0x4 => Some(UsbPid::Ping),
+            _ => None, // 0x0 is reserved
+        }
+    }
+
+    pub fn get_value(&self) -> u8 {
+        let pid = match self {
+            UsbPid::Out => 0x1,
+            UsbPid::In => 0x9,
+            UsbPid::Sof => 0x5,
+            UsbPid::Setup => 0xD,
+            UsbPid::Data0 => 0x3,
+            UsbPid::Data1 => 0xB,
+            UsbPid::Data2 => 0x7,
+            UsbPid::MData => 0xF,
+            UsbPid::Ack => 0x2,
+            UsbPid::Nak => 0xA,
+            UsbPid::Stall => 0xE,
+            UsbPid::Nyet => 0x6,
+            UsbPid::Pre => 0xC,
+            UsbPid::Split => 0x8,
+            UsbPid::Ping => 0x4,
+        };
+        pid | ((!pid & 0x0F) << 4)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UsbPid::Out => "OUT",
+            UsbPid::In => "IN",
+            UsbPid::Sof => "SOF",
+            UsbPid::Setup => "SETUP",
+            UsbPid::Data0 => "DATA0",
+            UsbPid::Data1 => "DATA1",
+            UsbPid::Data2 => "DATA2",
+            UsbPid::MData => "MDATA",
+            UsbPid::Ack => "ACK",
+            UsbPid::Nak => "NAK",
+            UsbPid::Stall => "STALL",
+            UsbPid::Nyet => "NYET",
+            UsbPid::Pre => "PRE/ERR",
+            UsbPid::Split => "SPLIT",
+            UsbPid::Ping => "PING",
+        }
+    }
+
+    // Tokens that address an endpoint (ADDR + ENDP + CRC5)
+    pub fn is_endpoint_token(&self) -> bool {
+        matches!(self, UsbPid::Out | UsbPid::In | UsbPid::Setup | UsbPid::Ping)
+    }
+
+    pub fn is_data(&self) -> bool {
+        matches!(self, UsbPid::Data0 | UsbPid::Data1 | UsbPid::Data2 | UsbPid::MData)
+    }
+
+    #[allow(dead_code)]
+    pub fn is_handshake(&self) -> bool {
+        matches!(self, UsbPid::Ack | UsbPid::Nak | UsbPid::Stall | UsbPid::Nyet)
+    }
+}
+
+impl fmt::Display for UsbPid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// CRC5 used by token, SOF and SPLIT packets (x^5 + x^2 + 1).
+// Returns the CRC bits in the order they appear in the packet bytes.
+pub fn crc5(value: u32, bits: u32) -> u8 {
+    let mut crc: u8 = 0x1F;
+
+    for i in 0..bits {
+        let bit = ((value >> i) & 1) as u8;
+        let top = (crc >> 4) & 1;
+        crc = (crc << 1) & 0x1F;
+        if bit ^ top != 0 {
+            crc ^= 0x05;
+        }
+    }
+
+    // The CRC is sent MSB first, so it appears bit-reversed in the LSB-first packet bytes
+    let crc = !crc & 0x1F;
+    let mut reversed = 0;
+    for i in 0..5 {
+        if (crc & (1 << i)) != 0 {
+            reversed |= 1 << (4 - i);
+        }
+    }
+    reversed
+}
+
+// CRC16 used by data packets (x^16 + x^15 + x^2 + 1)
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            if (crc & 1) != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+
+    !crc
+}
+
+// A single raw USB packet as seen on the wire (PID byte first, CRC included)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsbPacket {
+    pub timestamp: f64,
+    pub pid: UsbPid,
+    pub bytes: Vec<u8>,
+}
+
+impl UsbPacket {
+    // Parse a raw packet, checking its length and CRC against the PID.
+    // Returns None for anything that isn't a well-formed USB 2.0 packet.
+    pub fn parse(timestamp: f64, bytes: &[u8]) -> Option<Self> {
+        let pid = UsbPid::from_byte(*bytes.first()?)?;
+
+        let valid = match pid {
+            UsbPid::Ack | UsbPid::Nak | UsbPid::Stall | UsbPid::Nyet => bytes.len() == 1,
+            // PRE is a single byte; ERR (split handshake) shares the same PID
+            UsbPid::Pre => bytes.len() == 1,
+            UsbPid::Out | UsbPid::In | UsbPid::Sof | UsbPid::Setup | UsbPid::Ping => {
+                bytes.len() == 3 && {
+                    let value = u16::from_le_bytes([bytes[1], bytes[2]]);
+                    crc5((value & 0x07FF) as u32, 11) == (value >> 11) as u8
+                }
+            },
+            UsbPid::Split => {
+                bytes.len() == 4 && {
+                    let value = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], 0]);
+                    crc5(value & 0x7FFFF, 19) == (value >> 19) as u8
+                }
+            },
+            UsbPid::Data0 | UsbPid::Data1 | UsbPid::Data2 | UsbPid::MData => {
+                bytes.len() >= 3 && {
+                    let end = bytes.len() - 2;
+                    crc16(&bytes[1..end]) == u16::from_le_bytes([bytes[end], bytes[end + 1]])
+                }
+            },
+        };
+
+        if !valid {
+            return None;
+        }
+
+        Some(UsbPacket {
+            timestamp,
+            pid,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    // Device address of an OUT/IN/SETUP/PING token
+    pub fn token_address(&self) -> Option<u8> {
+        if !self.pid.is_endpoint_token() {
+            return None;
+        }
+        Some(self.bytes[1] & 0x7F)
+    }
+
+    // Endpoint number of an OUT/IN/SETUP/PING token (without direction bit)
+    pub fn token_endpoint(&self) -> Option<u8> {
+        if !self.pid.is_endpoint_token() {
+            return None;
+        }
+        let value = u16::from_le_bytes([self.bytes[1], self.bytes[2]]);
+        Some(((value >> 7) & 0x0F) as u8)
+    }
+
+    // 11-bit frame number carried by a SOF packet
+    pub fn frame_number(&self) -> Option<u16> {
+        if self.pid != UsbPid::Sof {
+            return None;
+        }
+        Some(u16::from_le_bytes([self.bytes[1], self.bytes[2]]) & 0x07FF)
+    }
+
+    // Payload of a data packet, without PID and CRC16
+    pub fn payload(&self) -> &[u8] {
+        if !self.pid.is_data() {
+            return &[];
+        }
+        &self.bytes[1..self.bytes.len() - 2]
+    }
+
+    pub fn summary(&self) -> String {
+        match self.pid {
+            UsbPid::Out | UsbPid::In | UsbPid::Setup | UsbPid::Ping => {
+                format!("{} Addr {} EP {}", self.pid,
+                        self.token_address().unwrap_or(0),
+                        self.token_endpoint().unwrap_or(0))
+            },
+            UsbPid::Sof => format!("SOF Frame {}", self.frame_number().unwrap_or(0)),
+            UsbPid::Data0 | UsbPid::Data1 | UsbPid::Data2 | UsbPid::MData => {
+                format!("{} ({} bytes)", self.pid, self.payload().len())
+            },
+            _ => self.pid.name().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc5_matches_usb_tokens() {
+        // SETUP to address 0 endpoint 0 is sent as 2D 00 10
+        assert_eq!(crc5(0, 11), 0x02);
+        // Address 0x15, endpoint 0xE
+        assert_eq!(crc5(0x15 | (0x0E << 7), 11), 0x1D);
+        // SOF frame 0x710
+        assert_eq!(crc5(0x710, 11), 0x05);
+    }
+
+    #[test]
+    fn crc16_matches_check_value() {
+        assert_eq!(crc16(b"123456789"), 0xB4C8);
+        assert_eq!(crc16(&[]), 0x0000);
+    }
+
+    #[test]
+    fn parses_tokens_and_checks_crc() {
+        let setup = UsbPacket::parse(0.0, &[0x2D, 0x00, 0x10]).unwrap();
+        assert_eq!(setup.pid, UsbPid::Setup);
+        assert_eq!(setup.token_address(), Some(0));
+        assert_eq!(setup.token_endpoint(), Some(0));
+
+        let sof = UsbPacket::parse(0.0, &[0xA5, 0x10, 0x2F]).unwrap();
+        assert_eq!(sof.frame_number(), Some(0x710));
+
+        // Corrupted CRC, bad PID check bits and wrong length
+        assert!(UsbPacket::parse(0.0, &[0x2D, 0x00, 0x18]).is_none());
+        assert!(UsbPacket::parse(0.0, &[0x2C, 0x00, 0x10]).is_none());
+        assert!(UsbPacket::parse(0.0, &[0xD2, 0x00]).is_none());
+    }
+
+    #[test]
+    fn parses_data_packets() {
+        let data = UsbPacket::parse(0.0, &[0xC3, 0x00, 0x00]).unwrap();
+        assert_eq!(data.pid, UsbPid::Data0);
+        assert!(data.payload().is_empty());
+
+        let crc = crc16(&[0x12, 0x34]).to_le_bytes();
+        let data = UsbPacket::parse(0.0, &[0x4B, 0x12, 0x34, crc[0], crc[1]]).unwrap();
+        assert_eq!(data.pid, UsbPid::Data1);
+        assert_eq!(data.payload(), &[0x12, 0x34]);
+        assert!(UsbPacket::parse(0.0, &[0x4B, 0x12, 0x34, crc[0], crc[1] ^ 0x01]).is_none());
+    }
+}
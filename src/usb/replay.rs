@@ -0,0 +1,87 @@
+// Headless replay of a saved capture
+// Runs the raw records of a capture through the stages the traffic view uses (bus events,
+// packet and split assembly, enumeration and control reassembly) without building any
+// display, so protocol analyzers can be run over a capture from the command line.
+
+use super::analyzer::AnalyzerTransfer;
+use super::bus::BusModel;
+use super::bus_event::{BusEventRecord, BusEventTracker};
+use super::control::ControlTransferAssembler;
+use super::decoder::CaptureFormat;
+use super::mitm_traffic::{decode_mitm_packet, UsbTransaction};
+use super::pid::UsbPacket;
+use super::split::SplitTracker;
+use super::transaction::TransactionAssembler;
+
+pub struct CaptureReplay {
+    format: CaptureFormat,
+    bus_event_tracker: BusEventTracker,
+    split_tracker: SplitTracker,
+    transaction_assembler: TransactionAssembler,
+    control_assembler: ControlTransferAssembler,
+    bus_model: BusModel,
+    transaction_count: u64,
+}
+
+impl CaptureReplay {
+    pub fn new(format: CaptureFormat) -> Self {
+        CaptureReplay {
+            format,
+            bus_event_tracker: BusEventTracker::new(),
+            split_tracker: SplitTracker::new(),
+            transaction_assembler: TransactionAssembler::new(),
+            control_assembler: ControlTransferAssembler::new(),
+            bus_model: BusModel::new(),
+            transaction_count: 0,
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.transaction_count += 1;
+        self.transaction_count
+    }
+
+    // Feed one captured record. Returns the transfers it completed.
+    pub fn process_record(&mut self, timestamp: f64, data: &[u8]) -> Vec<AnalyzerTransfer> {
+        let mut transactions = Vec::new();
+        if self.format == CaptureFormat::Packets {
+            if let Some(record) = BusEventRecord::parse(timestamp, data) {
+                for event in self.bus_event_tracker.process_record(&record) {
+                    self.bus_model.process_bus_event(&event);
+                }
+            } else if let Some(packet) = UsbPacket::parse(timestamp, data) {
+                for split in self.split_tracker.process_packet(&packet) {
+                    let id = self.next_id();
+                    transactions.push(split.to_transaction(id));
+                }
+                if let Some(packet_transaction) = self.transaction_assembler.process_packet(&packet) {
+                    let id = self.next_id();
+                    transactions.push(packet_transaction.to_transaction(id));
+                }
+            }
+        } else if let Some(transaction) = decode_mitm_packet(data, timestamp, self.transaction_count + 1) {
+            self.next_id();
+            transactions.push(transaction);
+        }
+
+        transactions.into_iter()
+            .filter_map(|transaction| self.process_transaction(transaction))
+            .collect()
+    }
+
+    fn process_transaction(&mut self, mut transaction: UsbTransaction) -> Option<AnalyzerTransfer> {
+        if let Some(info) = self.bus_model.resolve_endpoint(&transaction) {
+            transaction.transfer_type = info.transfer_type;
+            transaction.endpoint_info = Some(info);
+        }
+        self.bus_model.process_transaction(&transaction);
+
+        let max_packet_size0 = self.bus_model.device(transaction.device_address).and_then(|device| device.max_packet_size0());
+        let control_transfer = self.control_assembler.process_transaction(&transaction, max_packet_size0);
+        if let Some(transfer) = &control_transfer {
+            self.bus_model.process_control_transfer(&transfer.to_transaction());
+        }
+
+        AnalyzerTransfer::from_transaction(&self.bus_model, &transaction, control_transfer.as_ref())
+    }
+}
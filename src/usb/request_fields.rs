@@ -0,0 +1,19 @@
+// Shared helpers for decoded request fields
+// Standard, class and vendor request decoders all present their results as name/value
+// rows in the detail pane.
+
+// A decoded field: name and value as shown in the detail pane
+pub type RequestField = (String, String);
+
+pub fn field(name: &str, value: String) -> RequestField {
+    (name.to_string(), value)
+}
+
+pub fn flag(set: bool) -> String {
+    if set { "Yes".to_string() } else { "No".to_string() }
+}
+
+// Endpoint named by a wIndex endpoint number and direction bit, e.g. "EP1 IN"
+pub fn describe_endpoint(w_index: u16) -> String {
+    format!("EP{} {}", w_index & 0x0F, if (w_index & 0x80) != 0 { "IN" } else { "OUT" })
+}
@@ -0,0 +1,344 @@
+// Rhai analyzer scripts
+// Analyzers for in-house protocols can be written as Rhai scripts and dropped into the
+// script directory ($USBFLY_SCRIPTS, or "scripts" in the user's config directory).
+// A script defines
+//   fn register()         #{ name, vendor_id, product_id, interface, class, subclass, protocol }
+//                         where everything but vendor_id is optional
+//   fn init()             optional: the initial state, an empty map otherwise
+//   fn process(transfer)  () or #{ summary, fields, annotations, warnings, log }
+// process() runs with `this` bound to the script's state, which lives until the capture
+// is cleared or the scripts are reloaded. The transfer is a map with address, vendor_id,
+// product_id, interface, class, subclass, protocol (() when the interface is unknown),
+// endpoint, direction ("in"/"out"), type ("control", "bulk", "interrupt", "isochronous"),
+// setup (() outside control transfers), data (a blob) and timestamp. fields is a map or
+// an array of [name, value] pairs; annotations, warnings and log are strings or arrays.
+//
+//   fn register() { #{ name: "Widget", vendor_id: 0x1234, interface: 2 } }
+//   fn init() { #{ packets: 0 } }
+//   fn process(transfer) {
+//       this.packets += 1;
+//       if transfer.data.len() == 0 { return; }
+//       #{ summary: `opcode ${transfer.data[0]}`, fields: [["Packet", this.packets]] }
+//   }
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use super::analyzer::{AnalyzerNote, AnalyzerOutput, AnalyzerTransfer, DeviceIdentity, ProtocolAnalyzer};
+use super::mitm_traffic::{UsbControlRecipient, UsbControlRequestType, UsbDirection, UsbTransferType};
+
+// Environment variable overriding the script directory
+pub const SCRIPT_DIR_VAR: &str = "USBFLY_SCRIPTS";
+
+// Operations a single call may run, so a script stuck in a loop can't hang the capture
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+// The devices a script registered for
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScriptMatch {
+    vendor_id: u16,
+    product_id: Option<u16>,
+    interface: Option<u8>,
+    class: Option<u8>,
+    subclass: Option<u8>,
+    protocol: Option<u8>,
+}
+
+// An optional integer from register(), checked against the field's range
+fn registered<T: TryFrom<i64>>(map: &Map, key: &str) -> Result<Option<T>, String> {
+    let value = match map.get(key) {
+        Some(value) if !value.is_unit() => value,
+        _ => return Ok(None),
+    };
+    let number = value.as_int().map_err(|_| format!("register(): {} must be an integer", key))?;
+    T::try_from(number).map(Some).map_err(|_| format!("register(): {} {} is out of range", key, number))
+}
+
+impl ScriptMatch {
+    fn parse(map: &Map) -> Result<Self, String> {
+        Ok(ScriptMatch {
+            vendor_id: registered(map, "vendor_id")?.ok_or("register(): vendor_id is required")?,
+            product_id: registered(map, "product_id")?,
+            interface: registered(map, "interface")?,
+            class: registered(map, "class")?,
+            subclass: registered(map, "subclass")?,
+            protocol: registered(map, "protocol")?,
+        })
+    }
+
+    fn matches(&self, device: &DeviceIdentity) -> bool {
+        if device.vendor_id != self.vendor_id || self.product_id.is_some_and(|id| id != device.product_id) {
+            return false;
+        }
+        let constraints = [self.interface, self.class, self.subclass, self.protocol];
+        if constraints.iter().all(Option::is_none) {
+            return true;
+        }
+        // Interface constraints need a transfer the descriptors tie to an interface
+        match device.interface {
+            Some(interface) => {
+                let values = [interface.number, interface.class.get_value(), interface.subclass, interface.protocol];
+                constraints.iter().zip(values).all(|(wanted, value)| wanted.is_none_or(|wanted| wanted == value))
+            },
+            None => false,
+        }
+    }
+}
+
+fn direction_name(direction: UsbDirection) -> &'static str {
+    match direction {
+        UsbDirection::DeviceToHost => "in",
+        UsbDirection::HostToDevice => "out",
+        UsbDirection::Unknown => "unknown",
+    }
+}
+
+fn transfer_type_name(transfer_type: UsbTransferType) -> &'static str {
+    match transfer_type {
+        UsbTransferType::Control => "control",
+        UsbTransferType::Isochronous => "isochronous",
+        UsbTransferType::Bulk => "bulk",
+        UsbTransferType::Interrupt => "interrupt",
+        UsbTransferType::Unknown => "unknown",
+    }
+}
+
+// The transfer as the map handed to process()
+fn transfer_map(transfer: &AnalyzerTransfer) -> Map {
+    let device = &transfer.device;
+    let mut map = Map::new();
+    map.insert("address".into(), Dynamic::from_int(device.address as i64));
+    map.insert("vendor_id".into(), Dynamic::from_int(device.vendor_id as i64));
+    map.insert("product_id".into(), Dynamic::from_int(device.product_id as i64));
+    let interface = device.interface.map(|interface| {
+        [interface.number, interface.class.get_value(), interface.subclass, interface.protocol]
+    });
+    for (index, key) in ["interface", "class", "subclass", "protocol"].into_iter().enumerate() {
+        let value = interface.map_or(Dynamic::UNIT, |values| Dynamic::from_int(values[index] as i64));
+        map.insert(key.into(), value);
+    }
+    map.insert("endpoint".into(), Dynamic::from_int(transfer.endpoint as i64));
+    map.insert("direction".into(), direction_name(transfer.direction).into());
+    map.insert("type".into(), transfer_type_name(transfer.transfer_type).into());
+
+    let setup = transfer.setup.as_ref().map_or(Dynamic::UNIT, |setup| {
+        let mut fields = Map::new();
+        let request_type = match setup.request_type {
+            UsbControlRequestType::Standard => "standard",
+            UsbControlRequestType::Class => "class",
+            UsbControlRequestType::Vendor => "vendor",
+            UsbControlRequestType::Reserved => "reserved",
+        };
+        let recipient = match setup.recipient {
+            UsbControlRecipient::Device => "device",
+            UsbControlRecipient::Interface => "interface",
+            UsbControlRecipient::Endpoint => "endpoint",
+            UsbControlRecipient::Other => "other",
+            UsbControlRecipient::Reserved => "reserved",
+        };
+        fields.insert("request_type".into(), request_type.into());
+        fields.insert("recipient".into(), recipient.into());
+        fields.insert("request".into(), Dynamic::from_int(setup.bRequest as i64));
+        fields.insert("value".into(), Dynamic::from_int(setup.wValue as i64));
+        fields.insert("index".into(), Dynamic::from_int(setup.wIndex as i64));
+        fields.insert("length".into(), Dynamic::from_int(setup.wLength as i64));
+        Dynamic::from_map(fields)
+    });
+    map.insert("setup".into(), setup);
+    map.insert("data".into(), Dynamic::from_blob(transfer.data.clone()));
+    map.insert("timestamp".into(), Dynamic::from_float(transfer.timestamp));
+    map
+}
+
+// A string or an array of strings from process()
+fn strings(map: &Map, key: &str) -> Vec<String> {
+    match map.get(key) {
+        Some(value) if value.is_array() => value.clone().cast::<Array>().iter().map(|item| item.to_string()).collect(),
+        Some(value) if !value.is_unit() => vec![value.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+// One loaded script, acting as a protocol analyzer
+pub struct ScriptAnalyzer {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    registration: ScriptMatch,
+    state: Dynamic, // Bound to `this` in process()
+}
+
+impl ScriptAnalyzer {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let source = fs::read_to_string(path).map_err(|e| format!("{}", e))?;
+        let ast = engine.compile(source).map_err(|e| format!("{}", e))?;
+        if !Self::defines(&ast, "process", 1) {
+            return Err("no process(transfer) function".to_string());
+        }
+        if !Self::defines(&ast, "register", 0) {
+            return Err("no register() function".to_string());
+        }
+
+        let registration = engine.call_fn::<Map>(&mut Scope::new(), &ast, "register", ())
+            .map_err(|e| format!("register(): {}", e))?;
+        let name = match registration.get("name") {
+            Some(name) if !name.is_unit() => name.to_string(),
+            _ => path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default(),
+        };
+
+        let mut analyzer = ScriptAnalyzer {
+            name,
+            engine,
+            ast,
+            registration: ScriptMatch::parse(&registration)?,
+            state: Dynamic::UNIT,
+        };
+        analyzer.state = analyzer.initial_state()?;
+        Ok(analyzer)
+    }
+
+    fn defines(ast: &AST, name: &str, params: usize) -> bool {
+        ast.iter_functions().any(|function| function.name == name && function.params.len() == params)
+    }
+
+    fn initial_state(&self) -> Result<Dynamic, String> {
+        if !Self::defines(&self.ast, "init", 0) {
+            return Ok(Dynamic::from_map(Map::new()));
+        }
+        self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "init", ())
+            .map_err(|e| format!("init(): {}", e))
+    }
+
+    fn output(&self, summary: Option<String>, notes: Vec<AnalyzerNote>, log: Vec<String>) -> Option<AnalyzerOutput> {
+        Some(AnalyzerOutput { analyzer: self.name.clone(), summary, notes, log })
+    }
+}
+
+impl ProtocolAnalyzer for ScriptAnalyzer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn matches(&self, device: &DeviceIdentity) -> bool {
+        self.registration.matches(device)
+    }
+
+    fn process(&mut self, transfer: &AnalyzerTransfer) -> Option<AnalyzerOutput> {
+        let options = CallFnOptions::new().bind_this_ptr(&mut self.state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast,
+                                                                 "process", (transfer_map(transfer),));
+        let value = match result {
+            Ok(value) => value,
+            Err(e) => return self.output(None, vec![AnalyzerNote::Warning(format!("script error: {}", e))], Vec::new()),
+        };
+        if value.is_unit() {
+            return None;
+        }
+        let map = match value.try_cast::<Map>() {
+            Some(map) => map,
+            None => return self.output(None, vec![AnalyzerNote::Warning("process() must return a map or ()".to_string())],
+                                       Vec::new()),
+        };
+
+        let mut notes = Vec::new();
+        match map.get("fields") {
+            Some(fields) if fields.is_map() => {
+                for (name, value) in fields.clone().cast::<Map>() {
+                    notes.push(AnalyzerNote::Field((name.to_string(), value.to_string())));
+                }
+            },
+            Some(fields) if fields.is_array() => {
+                for pair in fields.clone().cast::<Array>() {
+                    match pair.try_cast::<Array>() {
+                        Some(pair) if pair.len() == 2 => {
+                            notes.push(AnalyzerNote::Field((pair[0].to_string(), pair[1].to_string())));
+                        },
+                        _ => notes.push(AnalyzerNote::Warning("fields entries must be [name, value]".to_string())),
+                    }
+                }
+            },
+            _ => {},
+        }
+        notes.extend(strings(&map, "annotations").into_iter().map(AnalyzerNote::Annotation));
+        notes.extend(strings(&map, "warnings").into_iter().map(AnalyzerNote::Warning));
+
+        let summary = map.get("summary").filter(|summary| !summary.is_unit()).map(|summary| summary.to_string());
+        self.output(summary, notes, strings(&map, "log"))
+    }
+
+    fn reset(&mut self) {
+        self.state = self.initial_state().unwrap_or_else(|_| Dynamic::from_map(Map::new()));
+    }
+}
+
+// Every script loaded from the script directory
+#[derive(Default)]
+pub struct ScriptSet {
+    scripts: Vec<ScriptAnalyzer>,
+    errors: Vec<String>, // Files that could not be loaded, with the reason
+}
+
+impl ScriptSet {
+    pub fn new() -> Self {
+        ScriptSet::default()
+    }
+
+    // The script directory: $USBFLY_SCRIPTS, or scripts in the user's config directory
+    pub fn script_dir() -> Option<PathBuf> {
+        if let Ok(dir) = std::env::var(SCRIPT_DIR_VAR) {
+            return Some(PathBuf::from(dir));
+        }
+        directories::ProjectDirs::from("com", "usbfly", "USBfly")
+            .map(|dirs| dirs.config_dir().join("scripts"))
+    }
+
+    // Load the script directory; a missing directory just means no scripts
+    pub fn load_default() -> Self {
+        match Self::script_dir() {
+            Some(dir) if dir.is_dir() => Self::load_dir(&dir),
+            _ => Self::new(),
+        }
+    }
+
+    // Load every .rhai file in a directory, in file name order
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut set = Self::new();
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map(|ext| ext == "rhai").unwrap_or(false))
+                .collect(),
+            Err(e) => {
+                set.errors.push(format!("{}: {}", dir.display(), e));
+                return set;
+            },
+        };
+        paths.sort();
+
+        for path in paths {
+            match ScriptAnalyzer::load(&path) {
+                Ok(script) => set.scripts.push(script),
+                Err(e) => set.errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        set
+    }
+
+    pub fn len(&self) -> usize {
+        self.scripts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn into_analyzers(self) -> Vec<Box<dyn ProtocolAnalyzer>> {
+        self.scripts.into_iter().map(|script| Box::new(script) as Box<dyn ProtocolAnalyzer>).collect()
+    }
+}
@@ -0,0 +1,648 @@
+// USB-serial bridge decoders
+// FTDI, Silicon Labs CP210x, WCH CH34x and Prolific PL2303 bridges configure the UART
+// with their own vendor control requests and move serial data over plain bulk endpoints.
+// The analyzer decodes the configuration requests, follows the line settings, strips
+// FTDI's 2-byte modem status header from bulk IN packets and turns the payloads into a
+// serial transcript.
+
+use std::collections::HashMap;
+use std::fmt;
+use super::analyzer::{AnalyzerNote, AnalyzerOutput, AnalyzerTransfer, DeviceIdentity, ProtocolAnalyzer};
+use super::mitm_traffic::{UsbControlRequestType, UsbDirection, UsbSetupPacket, UsbTransferType};
+use super::request_fields::{field, RequestField};
+use crate::data::vendor_ids::{self, FTDI_VID, PROLIFIC_VID, SILABS_VID, WCH_ALT_VID, WCH_VID};
+
+// FTDI parts that carry the baud rate's high divisor bits in the high byte of wIndex,
+// because the low byte selects the port
+const FTDI_MULTI_PORT_PIDS: [u16; 3] = [0x6010, 0x6011, 0x6014];
+
+const ANALYZER_NAME: &str = "Serial";
+
+// Payload shown on a transaction's row before it is cut short
+const SUMMARY_CHARS: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialChip {
+    Ftdi,
+    Cp210x,
+    Ch34x,
+    Pl2303,
+}
+
+impl SerialChip {
+    pub fn from_ids(vendor_id: u16, product_id: u16) -> Option<SerialChip> {
+        match (vendor_id, product_id) {
+            (FTDI_VID, 0x6001 | 0x6010 | 0x6011 | 0x6014 | 0x6015) => Some(SerialChip::Ftdi),
+            (SILABS_VID, 0xEA60 | 0xEA61 | 0xEA63 | 0xEA70 | 0xEA71 | 0xEA7A | 0xEA7B) => Some(SerialChip::Cp210x),
+            (WCH_VID | WCH_ALT_VID, 0x5523 | 0x7522 | 0x7523) => Some(SerialChip::Ch34x),
+            (PROLIFIC_VID, 0x2303 | 0x23A3 | 0x23B3 | 0x23C3 | 0x23D3 | 0x23E3 | 0x23F3) => Some(SerialChip::Pl2303),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SerialChip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerialChip::Ftdi => write!(f, "FTDI"),
+            SerialChip::Cp210x => write!(f, "CP210x"),
+            SerialChip::Ch34x => write!(f, "CH34x"),
+            SerialChip::Pl2303 => write!(f, "PL2303"),
+        }
+    }
+}
+
+// UART settings as far as the captured requests have revealed them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineSettings {
+    pub baud_rate: Option<u32>,
+    pub data_bits: Option<u8>,
+    pub parity: Option<&'static str>,
+    pub stop_bits: Option<&'static str>,
+}
+
+impl fmt::Display for LineSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.baud_rate {
+            Some(baud_rate) => write!(f, "{} baud", baud_rate)?,
+            None => write!(f, "? baud")?,
+        }
+        let parity = self.parity.and_then(|parity| parity.chars().next()).unwrap_or('?');
+        write!(f, " {}{}{}", self.data_bits.map(|bits| bits.to_string()).unwrap_or_else(|| "?".to_string()),
+               parity, self.stop_bits.unwrap_or("?"))
+    }
+}
+
+fn parity_name(code: u16) -> &'static str {
+    match code {
+        0 => "None",
+        1 => "Odd",
+        2 => "Even",
+        3 => "Mark",
+        4 => "Space",
+        _ => "Reserved",
+    }
+}
+
+fn stop_bits_name(code: u16) -> &'static str {
+    match code {
+        0 => "1",
+        1 => "1.5",
+        2 => "2",
+        _ => "Reserved",
+    }
+}
+
+fn on_off(set: bool) -> &'static str {
+    if set { "on" } else { "off" }
+}
+
+// DTR/RTS changes encoded as value bits with enable masks (FTDI MODEM_CTRL, CP210x SET_MHS)
+fn describe_handshake_lines(value: u16) -> String {
+    let mut lines = Vec::new();
+    if value & 0x0100 != 0 {
+        lines.push(format!("DTR {}", on_off(value & 0x0001 != 0)));
+    }
+    if value & 0x0200 != 0 {
+        lines.push(format!("RTS {}", on_off(value & 0x0002 != 0)));
+    }
+    if lines.is_empty() { "no change".to_string() } else { lines.join(", ") }
+}
+
+// Modem status bits shared by the FTDI header and CP210x GET_MDMSTS
+fn describe_modem_status(status: u8) -> String {
+    let lines: Vec<&str> = [(0x10, "CTS"), (0x20, "DSR"), (0x40, "RI"), (0x80, "DCD")].iter()
+        .filter(|(mask, _)| status & mask != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    if lines.is_empty() { "none asserted".to_string() } else { lines.join(" ") }
+}
+
+// Printable text with control characters escaped
+pub fn escape_serial(data: &[u8]) -> String {
+    let mut text = String::new();
+    for &byte in data {
+        match byte {
+            b'\r' => text.push_str("\\r"),
+            b'\n' => text.push_str("\\n"),
+            b'\t' => text.push_str("\\t"),
+            b'\\' => text.push_str("\\\\"),
+            0x20..=0x7E => text.push(byte as char),
+            _ => text.push_str(&format!("\\x{:02X}", byte)),
+        }
+    }
+    text
+}
+
+// A decoded configuration request
+struct BridgeRequest {
+    name: String,
+    fields: Vec<RequestField>,
+    warnings: Vec<String>,
+}
+
+impl BridgeRequest {
+    fn new(name: &str) -> Self {
+        BridgeRequest { name: name.to_string(), fields: Vec::new(), warnings: Vec::new() }
+    }
+
+    fn field(mut self, name: &str, value: String) -> Self {
+        self.fields.push(field(name, value));
+        self
+    }
+}
+
+// FTDI baud rate from the SET_BAUD_RATE divisor (3 MHz base clock, or 12 MHz on H parts)
+fn ftdi_baud_rate(w_value: u16, w_index: u16, multi_port: bool) -> u32 {
+    const FRACTIONS: [f64; 8] = [0.0, 0.5, 0.25, 0.125, 0.375, 0.625, 0.75, 0.875];
+    let high = if multi_port { w_index >> 8 } else { w_index };
+    let fraction = ((w_value >> 14) | ((high & 0x01) << 2)) as usize;
+    let divisor = (w_value & 0x3FFF) as f64 + FRACTIONS[fraction];
+    let clock = if high & 0x02 != 0 { 12_000_000.0 } else { 3_000_000.0 };
+    match (w_value & 0x3FFF, fraction) {
+        (0, 0) => clock as u32,
+        (1, 0) if clock < 12_000_000.0 => 2_000_000,
+        _ => (clock / divisor).round() as u32,
+    }
+}
+
+fn decode_ftdi(setup: &UsbSetupPacket, data: &[u8], multi_port: bool, line: &mut LineSettings) -> Option<BridgeRequest> {
+    let port = if multi_port { setup.wIndex & 0xFF } else { 0 };
+    let request = match setup.bRequest {
+        0x00 => BridgeRequest::new("RESET").field("Action", match setup.wValue {
+            0 => "Reset port".to_string(),
+            1 => "Purge RX buffer".to_string(),
+            2 => "Purge TX buffer".to_string(),
+            value => format!("Unknown ({})", value),
+        }),
+        0x01 => BridgeRequest::new("MODEM_CTRL").field("Lines", describe_handshake_lines(setup.wValue)),
+        0x02 => {
+            let flow = match setup.wIndex >> 8 {
+                0x00 => "None".to_string(),
+                0x01 => "RTS/CTS".to_string(),
+                0x02 => "DTR/DSR".to_string(),
+                0x04 => format!("XON/XOFF (XON 0x{:02X}, XOFF 0x{:02X})", setup.wValue & 0xFF, setup.wValue >> 8),
+                other => format!("Unknown (0x{:02X})", other),
+            };
+            BridgeRequest::new("SET_FLOW_CTRL").field("Flow Control", flow)
+        },
+        0x03 => {
+            let baud_rate = ftdi_baud_rate(setup.wValue, setup.wIndex, multi_port);
+            line.baud_rate = Some(baud_rate);
+            BridgeRequest::new("SET_BAUD_RATE")
+                .field("Baud Rate", format!("{}", baud_rate))
+                .field("Divisor", format!("0x{:04X}", setup.wValue))
+        },
+        0x04 => {
+            line.data_bits = Some((setup.wValue & 0xFF) as u8);
+            line.parity = Some(parity_name((setup.wValue >> 8) & 0x07));
+            line.stop_bits = Some(stop_bits_name((setup.wValue >> 11) & 0x07));
+            BridgeRequest::new("SET_DATA")
+                .field("Data Bits", format!("{}", setup.wValue & 0xFF))
+                .field("Parity", parity_name((setup.wValue >> 8) & 0x07).to_string())
+                .field("Stop Bits", stop_bits_name((setup.wValue >> 11) & 0x07).to_string())
+                .field("Break", on_off(setup.wValue & 0x4000 != 0).to_string())
+        },
+        0x05 => {
+            let mut request = BridgeRequest::new("GET_MODEM_STATUS");
+            if let Some(&status) = data.first() {
+                request = request.field("Modem Status", describe_modem_status(status));
+            }
+            request
+        },
+        0x06 | 0x07 => {
+            let name = if setup.bRequest == 0x06 { "SET_EVENT_CHAR" } else { "SET_ERROR_CHAR" };
+            BridgeRequest::new(name)
+                .field("Character", format!("0x{:02X}", setup.wValue & 0xFF))
+                .field("Enabled", on_off(setup.wValue & 0x0100 != 0).to_string())
+        },
+        0x09 => BridgeRequest::new("SET_LATENCY_TIMER").field("Latency", format!("{} ms", setup.wValue & 0xFF)),
+        0x0A => {
+            let mut request = BridgeRequest::new("GET_LATENCY_TIMER");
+            if let Some(&latency) = data.first() {
+                request = request.field("Latency", format!("{} ms", latency));
+            }
+            request
+        },
+        0x0B => {
+            let mode = match setup.wValue >> 8 {
+                0x00 => "Reset (UART)",
+                0x01 => "Async bit-bang",
+                0x02 => "MPSSE",
+                0x04 => "Sync bit-bang",
+                0x08 => "MCU host bus",
+                0x10 => "Fast opto-isolated serial",
+                0x20 => "CBUS bit-bang",
+                0x40 => "Single channel sync FIFO",
+                _ => "Unknown",
+            };
+            BridgeRequest::new("SET_BITMODE")
+                .field("Mode", mode.to_string())
+                .field("Pin Mask", format!("0x{:02X}", setup.wValue & 0xFF))
+        },
+        0x0C => {
+            let mut request = BridgeRequest::new("READ_PINS");
+            if let Some(&pins) = data.first() {
+                request = request.field("Pins", format!("0x{:02X}", pins));
+            }
+            request
+        },
+        0x90 => BridgeRequest::new("READ_EEPROM").field("Word Address", format!("0x{:02X}", setup.wIndex)),
+        0x91 => BridgeRequest::new("WRITE_EEPROM")
+            .field("Word Address", format!("0x{:02X}", setup.wIndex))
+            .field("Value", format!("0x{:04X}", setup.wValue)),
+        0x92 => BridgeRequest::new("ERASE_EEPROM"),
+        _ => return None,
+    };
+    Some(if port > 0 { request.field("Port", format!("{}", (b'A' + (port as u8 - 1).min(25)) as char)) } else { request })
+}
+
+fn decode_cp210x(setup: &UsbSetupPacket, data: &[u8], line: &mut LineSettings) -> Option<BridgeRequest> {
+    Some(match setup.bRequest {
+        0x00 => BridgeRequest::new("IFC_ENABLE").field("UART", if setup.wValue & 0x01 != 0 {
+            "Enabled".to_string()
+        } else {
+            "Disabled".to_string()
+        }),
+        0x01 => {
+            // Divisor of the 3.6864 MHz baud rate generator
+            let baud_rate = if setup.wValue == 0 { 0 } else { 3_686_400 / setup.wValue as u32 };
+            line.baud_rate = Some(baud_rate);
+            BridgeRequest::new("SET_BAUDDIV").field("Baud Rate", format!("{}", baud_rate))
+        },
+        0x02 => BridgeRequest::new("GET_BAUDDIV"),
+        0x03 => {
+            let stop_bits = stop_bits_name(setup.wValue & 0x0F);
+            let parity = parity_name((setup.wValue >> 4) & 0x0F);
+            let data_bits = (setup.wValue >> 8) as u8;
+            line.stop_bits = Some(stop_bits);
+            line.parity = Some(parity);
+            line.data_bits = Some(data_bits);
+            BridgeRequest::new("SET_LINE_CTL")
+                .field("Data Bits", format!("{}", data_bits))
+                .field("Parity", parity.to_string())
+                .field("Stop Bits", stop_bits.to_string())
+        },
+        0x04 => BridgeRequest::new("GET_LINE_CTL"),
+        0x05 => BridgeRequest::new("SET_BREAK").field("Break", on_off(setup.wValue & 0x01 != 0).to_string()),
+        0x06 => BridgeRequest::new("IMM_CHAR").field("Character", format!("0x{:02X}", setup.wValue & 0xFF)),
+        0x07 => BridgeRequest::new("SET_MHS").field("Lines", describe_handshake_lines(setup.wValue)),
+        0x08 => {
+            let mut request = BridgeRequest::new("GET_MDMSTS");
+            if let Some(&status) = data.first() {
+                request = request.field("Modem Status", describe_modem_status(status))
+                    .field("DTR", on_off(status & 0x01 != 0).to_string())
+                    .field("RTS", on_off(status & 0x02 != 0).to_string());
+            }
+            request
+        },
+        0x09 => BridgeRequest::new("SET_XON"),
+        0x0A => BridgeRequest::new("SET_XOFF"),
+        0x0D => BridgeRequest::new("SET_EVENTMASK").field("Mask", format!("0x{:04X}", setup.wValue)),
+        0x0E => BridgeRequest::new("GET_EVENTMASK"),
+        0x0F => BridgeRequest::new("GET_PROPS"),
+        0x10 => {
+            let mut request = BridgeRequest::new("GET_COMM_STATUS");
+            if data.len() >= 4 {
+                let errors = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                request = request.field("Errors", format!("0x{:08X}", errors));
+                for (mask, name) in [(0x01, "Break"), (0x02, "Framing error"), (0x04, "Hardware overrun"),
+                                     (0x08, "Queue overrun"), (0x10, "Parity error")] {
+                    if errors & mask != 0 {
+                        request.warnings.push(name.to_string());
+                    }
+                }
+            }
+            request
+        },
+        0x11 => BridgeRequest::new("RESET"),
+        0x12 => BridgeRequest::new("PURGE").field("Queues", format!("0x{:02X}", setup.wValue)),
+        0x13 => BridgeRequest::new("SET_FLOW"),
+        0x14 => BridgeRequest::new("GET_FLOW"),
+        0x19 => BridgeRequest::new("SET_CHARS"),
+        0x1D => BridgeRequest::new("GET_BAUDRATE"),
+        0x1E => {
+            let mut request = BridgeRequest::new("SET_BAUDRATE");
+            if data.len() >= 4 {
+                let baud_rate = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                line.baud_rate = Some(baud_rate);
+                request = request.field("Baud Rate", format!("{}", baud_rate));
+            }
+            request
+        },
+        0xFF => BridgeRequest::new("VENDOR_SPECIFIC").field("Function", format!("0x{:04X}", setup.wValue)),
+        _ => return None,
+    })
+}
+
+// CH34x baud rate register pair 0x1312: divisor in the high byte, prescaler in the low bits
+fn ch34x_baud_rate(value: u16) -> Option<u32> {
+    let prescaler = (value & 0x03) as u32;
+    let factor = ((value >> 2) & 0x01) as u32;
+    let divisor = 256 - (value >> 8) as u32;
+    if divisor == 0 || 12 < 3 * prescaler + factor {
+        return None;
+    }
+    Some(48_000_000 / ((1 << (12 - 3 * prescaler - factor)) * divisor))
+}
+
+fn decode_ch34x(setup: &UsbSetupPacket, data: &[u8], line: &mut LineSettings) -> Option<BridgeRequest> {
+    Some(match setup.bRequest {
+        0x5F => {
+            let mut request = BridgeRequest::new("READ_VERSION");
+            if let Some(&version) = data.first() {
+                request = request.field("Version", format!("0x{:02X}", version));
+            }
+            request
+        },
+        0xA1 => BridgeRequest::new("SERIAL_INIT"),
+        0x95 => BridgeRequest::new("READ_REG").field("Registers", format!("0x{:04X}", setup.wValue)),
+        0x9A => {
+            let mut request = BridgeRequest::new("WRITE_REG")
+                .field("Registers", format!("0x{:04X}", setup.wValue))
+                .field("Value", format!("0x{:04X}", setup.wIndex));
+            match setup.wValue {
+                0x1312 => {
+                    if let Some(baud_rate) = ch34x_baud_rate(setup.wIndex) {
+                        line.baud_rate = Some(baud_rate);
+                        request = request.field("Baud Rate", format!("{}", baud_rate));
+                    }
+                },
+                0x2518 => {
+                    // Line control register
+                    let lcr = setup.wIndex & 0xFF;
+                    let parity = if lcr & 0x08 == 0 {
+                        "None"
+                    } else {
+                        match (lcr >> 4) & 0x03 {
+                            0 => "Odd",
+                            1 => "Even",
+                            2 => "Mark",
+                            _ => "Space",
+                        }
+                    };
+                    let data_bits = 5 + (lcr & 0x03) as u8;
+                    let stop_bits = if lcr & 0x04 != 0 { "2" } else { "1" };
+                    line.data_bits = Some(data_bits);
+                    line.parity = Some(parity);
+                    line.stop_bits = Some(stop_bits);
+                    request = request.field("Data Bits", format!("{}", data_bits))
+                        .field("Parity", parity.to_string())
+                        .field("Stop Bits", stop_bits.to_string())
+                        .field("RX", on_off(lcr & 0x80 != 0).to_string())
+                        .field("TX", on_off(lcr & 0x40 != 0).to_string());
+                },
+                _ => {},
+            }
+            request
+        },
+        0xA4 => {
+            // Handshake outputs are active low
+            BridgeRequest::new("MODEM_CTRL")
+                .field("DTR", on_off(setup.wValue & 0x20 == 0).to_string())
+                .field("RTS", on_off(setup.wValue & 0x40 == 0).to_string())
+        },
+        _ => return None,
+    })
+}
+
+fn decode_pl2303(setup: &UsbSetupPacket, data: &[u8], line: &mut LineSettings) -> Option<BridgeRequest> {
+    // Line settings use the CDC ACM class requests, configuration uses vendor register access
+    Some(match (setup.request_type, setup.bRequest) {
+        (UsbControlRequestType::Vendor, 0x01) => {
+            let name = if setup.direction == UsbDirection::DeviceToHost { "VENDOR_READ" } else { "VENDOR_WRITE" };
+            let mut request = BridgeRequest::new(name).field("Register", format!("0x{:04X}", setup.wValue));
+            if setup.direction == UsbDirection::HostToDevice {
+                request = request.field("Value", format!("0x{:04X}", setup.wIndex));
+            } else if let Some(&value) = data.first() {
+                request = request.field("Value", format!("0x{:02X}", value));
+            }
+            request
+        },
+        (UsbControlRequestType::Class, 0x20) | (UsbControlRequestType::Class, 0x21) => {
+            let name = if setup.bRequest == 0x20 { "SET_LINE_CODING" } else { "GET_LINE_CODING" };
+            let mut request = BridgeRequest::new(name);
+            if data.len() >= 7 {
+                line.baud_rate = Some(u32::from_le_bytes([data[0], data[1], data[2], data[3]]));
+                line.stop_bits = Some(stop_bits_name(data[4] as u16));
+                line.parity = Some(parity_name(data[5] as u16));
+                line.data_bits = Some(data[6]);
+                request.fields.extend(super::class_request::describe_line_coding(data));
+            }
+            request
+        },
+        (UsbControlRequestType::Class, 0x22) => BridgeRequest::new("SET_CONTROL_LINE_STATE")
+            .field("DTR", on_off(setup.wValue & 0x01 != 0).to_string())
+            .field("RTS", on_off(setup.wValue & 0x02 != 0).to_string()),
+        (UsbControlRequestType::Class, 0x23) => BridgeRequest::new("SEND_BREAK")
+            .field("Break", on_off(setup.wValue != 0).to_string()),
+        _ => return None,
+    })
+}
+
+// State kept per bridge on the bus
+#[derive(Debug, Clone)]
+struct BridgeState {
+    chip: SerialChip,
+    multi_port: bool,
+    line: LineSettings,
+    vendor: String,
+    tx_bytes: usize,
+    rx_bytes: usize,
+}
+
+impl BridgeState {
+    fn new(chip: SerialChip, device: &DeviceIdentity) -> Self {
+        BridgeState {
+            chip,
+            multi_port: chip == SerialChip::Ftdi && FTDI_MULTI_PORT_PIDS.contains(&device.product_id),
+            line: LineSettings::default(),
+            vendor: vendor_ids::lookup_vendor(device.vendor_id).unwrap_or_default(),
+            tx_bytes: 0,
+            rx_bytes: 0,
+        }
+    }
+}
+
+// Decodes USB-serial bridges and keeps a transcript of their serial data
+#[derive(Debug, Clone, Default)]
+pub struct SerialBridgeAnalyzer {
+    bridges: HashMap<u8, BridgeState>, // Device address -> bridge
+}
+
+impl SerialBridgeAnalyzer {
+    pub fn new() -> Self {
+        SerialBridgeAnalyzer::default()
+    }
+
+    fn bridge(&mut self, device: &DeviceIdentity) -> Option<&mut BridgeState> {
+        let chip = SerialChip::from_ids(device.vendor_id, device.product_id)?;
+        let bridge = self.bridges.entry(device.address).or_insert_with(|| BridgeState::new(chip, device));
+        // A different bridge may have been given the same address
+        if bridge.chip != chip {
+            *bridge = BridgeState::new(chip, device);
+        }
+        Some(bridge)
+    }
+
+    fn process_control(&mut self, transfer: &AnalyzerTransfer, setup: &UsbSetupPacket) -> Option<AnalyzerOutput> {
+        if !matches!(setup.request_type, UsbControlRequestType::Vendor | UsbControlRequestType::Class) {
+            return None;
+        }
+        let bridge = self.bridge(&transfer.device)?;
+        let before = bridge.line.clone();
+        let request = match (bridge.chip, setup.request_type) {
+            (SerialChip::Ftdi, UsbControlRequestType::Vendor) => decode_ftdi(setup, &transfer.data, bridge.multi_port, &mut bridge.line),
+            (SerialChip::Cp210x, UsbControlRequestType::Vendor) => decode_cp210x(setup, &transfer.data, &mut bridge.line),
+            (SerialChip::Ch34x, UsbControlRequestType::Vendor) => decode_ch34x(setup, &transfer.data, &mut bridge.line),
+            (SerialChip::Pl2303, _) => decode_pl2303(setup, &transfer.data, &mut bridge.line),
+            _ => None,
+        }?;
+
+        let mut notes: Vec<AnalyzerNote> = request.fields.into_iter().map(AnalyzerNote::Field).collect();
+        notes.extend(request.warnings.into_iter().map(AnalyzerNote::Warning));
+        let mut log = Vec::new();
+        if bridge.line != before {
+            notes.push(AnalyzerNote::Annotation(format!("Line settings now {}", bridge.line)));
+            log.push(format!("[{:.6}] -- Addr {} {} line settings: {} --", transfer.timestamp,
+                             transfer.device.address, bridge.chip, bridge.line));
+        }
+
+        Some(AnalyzerOutput {
+            analyzer: ANALYZER_NAME.to_string(),
+            summary: Some(format!("{} {}", bridge.chip, request.name)),
+            notes,
+            log,
+        })
+    }
+
+    fn process_data(&mut self, transfer: &AnalyzerTransfer) -> Option<AnalyzerOutput> {
+        if !matches!(transfer.transfer_type, UsbTransferType::Bulk | UsbTransferType::Interrupt | UsbTransferType::Unknown) {
+            return None;
+        }
+        let bridge = self.bridge(&transfer.device)?;
+        let is_in = transfer.direction == UsbDirection::DeviceToHost;
+        let mut notes = Vec::new();
+
+        // PL2303 reports UART state on its interrupt endpoint rather than carrying data there
+        if transfer.transfer_type == UsbTransferType::Interrupt {
+            if bridge.chip != SerialChip::Pl2303 || transfer.data.len() < 10 {
+                return None;
+            }
+            let state = transfer.data[8];
+            notes.push(AnalyzerNote::Field(("UART State".to_string(), format!("0x{:02X}", state))));
+            for (mask, name) in [(0x10, "Framing error"), (0x20, "Parity error"), (0x40, "Overrun"), (0x04, "Break")] {
+                if state & mask != 0 {
+                    notes.push(AnalyzerNote::Warning(name.to_string()));
+                }
+            }
+            return Some(AnalyzerOutput {
+                analyzer: ANALYZER_NAME.to_string(),
+                summary: Some("UART state".to_string()),
+                notes,
+                log: Vec::new(),
+            });
+        }
+
+        // FTDI prefixes every bulk IN packet with modem and line status
+        let payload = if bridge.chip == SerialChip::Ftdi && is_in {
+            let packet_size = transfer.max_packet_size.unwrap_or(64).max(3) as usize;
+            let mut payload = Vec::new();
+            for packet in transfer.data.chunks(packet_size) {
+                if packet.len() < 2 {
+                    continue;
+                }
+                let (modem, line) = (packet[0], packet[1]);
+                if payload.is_empty() && notes.is_empty() {
+                    notes.push(AnalyzerNote::Field(("Modem Status".to_string(), describe_modem_status(modem))));
+                }
+                for (mask, name) in [(0x02, "Overrun error"), (0x04, "Parity error"), (0x08, "Framing error"),
+                                     (0x10, "Break received"), (0x80, "Receiver FIFO error")] {
+                    let warning = AnalyzerNote::Warning(name.to_string());
+                    if line & mask != 0 && !notes.contains(&warning) {
+                        notes.push(warning);
+                    }
+                }
+                payload.extend_from_slice(&packet[2..]);
+            }
+            // Status-only packets are the chip's idle polling; only errors are worth showing
+            if payload.is_empty() && !notes.iter().any(|note| matches!(note, AnalyzerNote::Warning(_))) {
+                return None;
+            }
+            payload
+        } else {
+            transfer.data.clone()
+        };
+
+        let label = if is_in { "RX" } else { "TX" };
+        if is_in {
+            bridge.rx_bytes += payload.len();
+        } else {
+            bridge.tx_bytes += payload.len();
+        }
+
+        let text = escape_serial(&payload);
+        let short: String = if text.chars().count() > SUMMARY_CHARS {
+            format!("{}...", text.chars().take(SUMMARY_CHARS).collect::<String>())
+        } else {
+            text.clone()
+        };
+        notes.insert(0, AnalyzerNote::Field((format!("{} ({} bytes)", label, payload.len()), format!("\"{}\"", text))));
+
+        let log = if payload.is_empty() {
+            Vec::new()
+        } else {
+            vec![format!("[{:.6}] Addr {} EP{} {}: {}", transfer.timestamp, transfer.device.address,
+                         transfer.endpoint, label, text)]
+        };
+
+        Some(AnalyzerOutput {
+            analyzer: ANALYZER_NAME.to_string(),
+            summary: Some(format!("{} \"{}\"", label, short)),
+            notes,
+            log,
+        })
+    }
+
+    // One entry per bridge: chip, line settings and bytes moved
+    pub fn summary(&self) -> String {
+        let mut addresses: Vec<&u8> = self.bridges.keys().collect();
+        addresses.sort();
+        let bridges: Vec<String> = addresses.into_iter().filter_map(|address| {
+            let bridge = self.bridges.get(address)?;
+            Some(format!("Addr {} {}{} {}, {} bytes TX, {} bytes RX", address, bridge.chip,
+                         if bridge.vendor.is_empty() { String::new() } else { format!(" ({})", bridge.vendor) },
+                         bridge.line, bridge.tx_bytes, bridge.rx_bytes))
+        }).collect();
+        bridges.join("; ")
+    }
+}
+
+impl ProtocolAnalyzer for SerialBridgeAnalyzer {
+    fn name(&self) -> &str {
+        ANALYZER_NAME
+    }
+
+    fn matches(&self, device: &DeviceIdentity) -> bool {
+        SerialChip::from_ids(device.vendor_id, device.product_id).is_some()
+    }
+
+    fn process(&mut self, transfer: &AnalyzerTransfer) -> Option<AnalyzerOutput> {
+        let output = match &transfer.setup {
+            Some(setup) => self.process_control(transfer, setup),
+            None => self.process_data(transfer),
+        }?;
+        if output.notes.is_empty() && output.summary.is_none() {
+            return None;
+        }
+        Some(output)
+    }
+
+    fn reset(&mut self) {
+        self.bridges.clear();
+    }
+
+    fn log_title(&self) -> String {
+        format!("Serial transcript: {}", self.summary())
+    }
+}
@@ -0,0 +1,308 @@
+// Start-of-Frame tracking
+// The host sends a SOF packet carrying an 11-bit frame number every 1 ms on a full-speed
+// bus and every 125 µs on a high-speed bus, where the same frame number is repeated for
+// the eight microframes of each frame (USB 2.0 specification sections 8.4.3 and 8.4.3.1).
+// The SOF stream gives every other packet a position in bus time.
+// Raw packets carry only the host receive time, so interval checks and frame placement
+// are approximate: USB transfer batching and host scheduling show up as jitter.
+
+use std::collections::VecDeque;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::pid::{UsbPacket, UsbPid};
+
+// Duration of a full-speed frame and a high-speed microframe in seconds
+pub const FRAME_SECONDS: f64 = 0.001;
+pub const MICROFRAME_SECONDS: f64 = 0.000125;
+
+// Frame numbers wrap after 11 bits
+const FRAME_NUMBER_MODULUS: u32 = 2048;
+
+// SOF intervals deviating from nominal by more than this fraction are counted as irregular
+const INTERVAL_TOLERANCE: f64 = 0.25;
+
+// Intervals shorter than this can only come from a high-speed bus
+const HIGH_SPEED_THRESHOLD: f64 = 0.0005;
+
+// Recent SOFs kept for frame lookups: about 1 s of high-speed or 8 s of full-speed traffic
+const HISTORY_LIMIT: usize = 8192;
+
+// Anomalies kept for display; later ones are only counted
+const ANOMALY_LIMIT: usize = 1024;
+
+// A SOF packet with its reconstructed microframe
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SofRecord {
+    pub timestamp: f64,
+    pub frame: u16,
+    pub microframe: Option<u8>, // Only known on a high-speed bus
+}
+
+// Position of a packet in bus time, relative to the most recent SOF
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrameTime {
+    pub frame: u16,
+    pub microframe: Option<u8>,
+    pub offset: f64, // Seconds since the start of the (micro)frame
+}
+
+impl fmt::Display for FrameTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.microframe {
+            Some(microframe) => write!(f, "Frame {}.{}", self.frame, microframe)?,
+            None => write!(f, "Frame {}", self.frame)?,
+        }
+        write!(f, " +~{:.1} µs", self.offset * 1_000_000.0)
+    }
+}
+
+// Problems found in the SOF stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SofAnomaly {
+    // One or more SOFs were not seen between two consecutive SOF packets
+    Missing { timestamp: f64, frame: u16, missing: u32 },
+    // The frame number did not advance
+    Repeated { timestamp: f64, frame: u16 },
+}
+
+impl SofAnomaly {
+    pub fn timestamp(&self) -> f64 {
+        match self {
+            SofAnomaly::Missing { timestamp, .. } |
+            SofAnomaly::Repeated { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+impl fmt::Display for SofAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SofAnomaly::Missing { frame, missing, .. } => {
+                write!(f, "{} SOF{} missing before frame {}", missing,
+                       if *missing == 1 { "" } else { "s" }, frame)
+            },
+            SofAnomaly::Repeated { frame, .. } => {
+                write!(f, "Frame number {} repeated", frame)
+            },
+        }
+    }
+}
+
+// Follows the SOF stream, reconstructs frame/microframe numbers and flags gaps
+#[derive(Debug, Clone, Default)]
+pub struct SofTracker {
+    history: VecDeque<SofRecord>, // Most recent SOFs, kept for timing lookups
+    first: Option<SofRecord>,
+    sof_count: usize,
+    position: u64, // (Micro)frames since the first SOF, missed ones included
+    anomalies: Vec<SofAnomaly>, // The first ANOMALY_LIMIT missing or repeated SOFs
+    high_speed: Option<bool>, // None until two SOFs have been seen
+    missing_count: u32,
+    // Intervals off nominal by host receive time. Batched delivery makes these common,
+    // so they are only counted.
+    irregular_count: u32,
+}
+
+impl SofTracker {
+    pub fn new() -> Self {
+        SofTracker {
+            history: VecDeque::new(),
+            first: None,
+            sof_count: 0,
+            position: 0,
+            anomalies: Vec::new(),
+            high_speed: None,
+            missing_count: 0,
+            irregular_count: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.first = None;
+        self.sof_count = 0;
+        self.position = 0;
+        self.anomalies.clear();
+        self.high_speed = None;
+        self.missing_count = 0;
+        self.irregular_count = 0;
+    }
+
+    pub fn sof_count(&self) -> usize {
+        self.sof_count
+    }
+
+    #[allow(dead_code)]
+    pub fn is_high_speed(&self) -> Option<bool> {
+        self.high_speed
+    }
+
+    #[allow(dead_code)]
+    pub fn missing_count(&self) -> u32 {
+        self.missing_count
+    }
+
+    #[allow(dead_code)]
+    pub fn irregular_count(&self) -> u32 {
+        self.irregular_count
+    }
+
+    pub fn anomalies(&self) -> &[SofAnomaly] {
+        &self.anomalies
+    }
+
+    pub fn first(&self) -> Option<&SofRecord> {
+        self.first.as_ref()
+    }
+
+    pub fn last(&self) -> Option<&SofRecord> {
+        self.history.back()
+    }
+
+    fn push(&mut self, record: SofRecord) {
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(record);
+        self.first.get_or_insert(record);
+        self.sof_count += 1;
+    }
+
+    // Bus time of the current (micro)frame, counted in SOF intervals from the first SOF.
+    // Unlike receive times this isn't disturbed by how the host batches delivery.
+    pub fn bus_time(&self) -> Option<f64> {
+        self.history.back().map(|_| self.position as f64 * self.nominal_interval())
+    }
+
+    // Nominal SOF interval for the detected bus speed
+    pub fn nominal_interval(&self) -> f64 {
+        if self.high_speed == Some(true) {
+            MICROFRAME_SECONDS
+        } else {
+            FRAME_SECONDS
+        }
+    }
+
+    // Feed one raw packet. Non-SOF packets are ignored; use frame_at() to place them.
+    // Returns any anomaly detected at this SOF.
+    pub fn process_packet(&mut self, packet: &UsbPacket) -> Option<SofAnomaly> {
+        if packet.pid != UsbPid::Sof {
+            return None;
+        }
+        let frame = packet.frame_number()?;
+
+        let last = match self.history.back() {
+            Some(last) => *last,
+            None => {
+                self.push(SofRecord { timestamp: packet.timestamp, frame, microframe: None });
+                return None;
+            },
+        };
+
+        let interval = packet.timestamp - last.timestamp;
+
+        // The first pair of SOFs tells us the bus speed
+        if self.high_speed.is_none() {
+            let high_speed = frame == last.frame || (interval > 0.0 && interval < HIGH_SPEED_THRESHOLD);
+            self.high_speed = Some(high_speed);
+            if high_speed {
+                if let Some(first) = self.history.back_mut() {
+                    first.microframe = Some(0);
+                }
+                if let Some(first) = self.first.as_mut() {
+                    first.microframe = Some(0);
+                }
+            }
+        }
+
+        let nominal = self.nominal_interval();
+        let slots_by_time = ((interval / nominal).round() as u32).max(1);
+
+        let (microframe, slots) = if self.high_speed == Some(true) {
+            let last_microframe = last.microframe.unwrap_or(0) as u32;
+            let last_position = last.frame as u32 * 8 + last_microframe;
+            let modulus = FRAME_NUMBER_MODULUS * 8;
+            let predicted = (last_position + slots_by_time) % modulus;
+
+            // Trust the timestamps when they agree with the frame number
+            let microframe = if predicted / 8 == frame as u32 {
+                predicted % 8
+            } else if frame == last.frame {
+                (last_microframe + 1).min(7)
+            } else {
+                0
+            };
+
+            let position = frame as u32 * 8 + microframe;
+            (Some(microframe as u8), (position + modulus - last_position) % modulus)
+        } else {
+            (None, (frame as u32 + FRAME_NUMBER_MODULUS - last.frame as u32) % FRAME_NUMBER_MODULUS)
+        };
+
+        self.position += slots as u64;
+        let anomaly = if slots == 0 {
+            Some(SofAnomaly::Repeated { timestamp: packet.timestamp, frame })
+        } else if slots > 1 {
+            self.missing_count += slots - 1;
+            Some(SofAnomaly::Missing { timestamp: packet.timestamp, frame, missing: slots - 1 })
+        } else {
+            if (interval - nominal).abs() > nominal * INTERVAL_TOLERANCE {
+                self.irregular_count += 1;
+            }
+            None
+        };
+
+        self.push(SofRecord { timestamp: packet.timestamp, frame, microframe });
+        if let Some(anomaly) = &anomaly {
+            if self.anomalies.len() < ANOMALY_LIMIT {
+                self.anomalies.push(anomaly.clone());
+            }
+        }
+        anomaly
+    }
+
+    // Reconstruct the frame (and microframe) a packet with this timestamp belongs to.
+    // Time past the most recent SOF is extrapolated using the nominal interval. Both
+    // timestamps are host receive times, so the result is approximate. Timestamps older
+    // than the retained history can't be placed.
+    pub fn frame_at(&self, timestamp: f64) -> Option<FrameTime> {
+        let index = self.history.partition_point(|sof| sof.timestamp <= timestamp);
+        let sof = self.history.get(index.checked_sub(1)?)?;
+
+        let nominal = self.nominal_interval();
+        let offset = timestamp - sof.timestamp;
+        let elapsed = (offset / nominal).floor() as u32;
+
+        let (frame, microframe) = match sof.microframe {
+            Some(microframe) => {
+                let position = (sof.frame as u32 * 8 + microframe as u32 + elapsed) % (FRAME_NUMBER_MODULUS * 8);
+                ((position / 8) as u16, Some((position % 8) as u8))
+            },
+            None => (((sof.frame as u32 + elapsed) % FRAME_NUMBER_MODULUS) as u16, None),
+        };
+
+        Some(FrameTime {
+            frame,
+            microframe,
+            offset: offset - elapsed as f64 * nominal,
+        })
+    }
+
+    // One-line description of the SOF stream for collapsed display
+    pub fn summary(&self) -> String {
+        let (first, last) = match (self.first(), self.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return "Start of Frame: none".to_string(),
+        };
+
+        let speed = match self.high_speed {
+            Some(true) => "high speed",
+            Some(false) => "full speed",
+            None => "speed unknown",
+        };
+
+        format!("Start of Frame: {} SOFs, frames {}-{} ({}), {} missing, {} irregular (host time)",
+                self.sof_count(), first.frame, last.frame, speed,
+                self.missing_count, self.irregular_count)
+    }
+}
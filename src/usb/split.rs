@@ -0,0 +1,704 @@
+// Split transaction (SSPLIT/CSPLIT) decoding
+// Full- and low-speed devices behind a high-speed hub are reached through the hub's
+// Transaction Translator. On the high-speed side each FS/LS transaction appears as a
+// start-split and one or more complete-splits (USB 2.0 specification section 11.14).
+
+use std::collections::HashMap;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::decoder::Speed;
+use super::mitm_traffic::{
+    UsbDataPacket,
+    UsbDirection,
+    UsbSetupPacket,
+    UsbStatusPacket,
+    UsbTransaction,
+    UsbTransferStatus,
+    UsbTransferType,
+};
+use super::pid::{UsbPacket, UsbPid};
+
+// Periodic complete-splits are scheduled from microframe Y+2 through Y+4
+// after the start-split in microframe Y.
+const PERIODIC_CSPLIT_FIRST: u64 = 2;
+const PERIODIC_CSPLIT_LAST: u64 = 4;
+
+// A full-size isochronous IN takes up to seven microframes on the full-speed side,
+// so its complete-splits can carry on through Y+8
+const ISOCHRONOUS_IN_CSPLIT_LAST: u64 = 8;
+
+// Last microframe after the start-split that may carry a complete-split
+fn csplit_window_last(endpoint_type: SplitEndpointType, token: UsbPid) -> u64 {
+    if endpoint_type == SplitEndpointType::Isochronous && token == UsbPid::In {
+        ISOCHRONOUS_IN_CSPLIT_LAST
+    } else {
+        PERIODIC_CSPLIT_LAST
+    }
+}
+
+// Endpoint type field (ET) of the SPLIT token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SplitEndpointType {
+    Control,
+    Isochronous,
+    Bulk,
+    Interrupt,
+}
+
+impl SplitEndpointType {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0x03 {
+            0 => SplitEndpointType::Control,
+            1 => SplitEndpointType::Isochronous,
+            2 => SplitEndpointType::Bulk,
+            _ => SplitEndpointType::Interrupt,
+        }
+    }
+
+    pub fn is_periodic(&self) -> bool {
+        matches!(self, SplitEndpointType::Isochronous | SplitEndpointType::Interrupt)
+    }
+
+    pub fn transfer_type(&self) -> UsbTransferType {
+        match self {
+            SplitEndpointType::Control => UsbTransferType::Control,
+            SplitEndpointType::Isochronous => UsbTransferType::Isochronous,
+            SplitEndpointType::Bulk => UsbTransferType::Bulk,
+            SplitEndpointType::Interrupt => UsbTransferType::Interrupt,
+        }
+    }
+}
+
+impl fmt::Display for SplitEndpointType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitEndpointType::Control => write!(f, "Control"),
+            SplitEndpointType::Isochronous => write!(f, "Isochronous"),
+            SplitEndpointType::Bulk => write!(f, "Bulk"),
+            SplitEndpointType::Interrupt => write!(f, "Interrupt"),
+        }
+    }
+}
+
+// Decoded SPLIT special token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitToken {
+    pub hub_address: u8,   // Address of the high-speed hub
+    pub complete: bool,    // SC bit: false = start-split, true = complete-split
+    pub port: u8,          // Hub port the FS/LS device is attached to
+    pub start_bit: bool,   // S bit: speed for interrupt/control/bulk, "start" for isochronous OUT
+    pub end_bit: bool,     // E bit: "end" for isochronous OUT start-splits
+    pub endpoint_type: SplitEndpointType,
+}
+
+impl SplitToken {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 4 {
+            return Err(format!("Invalid SPLIT token length: {}", data.len()));
+        }
+
+        if UsbPid::from_byte(data[0]) != Some(UsbPid::Split) {
+            return Err(format!("Not a SPLIT token: PID 0x{:02X}", data[0]));
+        }
+
+        Ok(SplitToken {
+            hub_address: data[1] & 0x7F,
+            complete: (data[1] & 0x80) != 0,
+            port: data[2] & 0x7F,
+            start_bit: (data[2] & 0x80) != 0,
+            end_bit: (data[3] & 0x01) != 0,
+            endpoint_type: SplitEndpointType::from_bits(data[3] >> 1),
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        if self.complete { "CSPLIT" } else { "SSPLIT" }
+    }
+
+    // Speed of the downstream device. Only meaningful for non-isochronous endpoints,
+    // where the S bit selects low speed.
+    pub fn speed(&self) -> Speed {
+        if self.endpoint_type != SplitEndpointType::Isochronous && self.start_bit {
+            Speed::Low
+        } else {
+            Speed::Full
+        }
+    }
+
+    // Position of an isochronous OUT start-split payload within the FS transaction
+    pub fn isochronous_position(&self) -> Option<&'static str> {
+        if self.complete || self.endpoint_type != SplitEndpointType::Isochronous {
+            return None;
+        }
+
+        Some(match (self.start_bit, self.end_bit) {
+            (false, false) => "middle",
+            (false, true) => "end",
+            (true, false) => "begin",
+            (true, true) => "all",
+        })
+    }
+}
+
+impl fmt::Display for SplitToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Hub {} Port {} ({}", self.name(), self.hub_address, self.port, self.endpoint_type)?;
+        match self.isochronous_position() {
+            Some(position) => write!(f, ", payload {}", position)?,
+            None if self.endpoint_type != SplitEndpointType::Isochronous => {
+                write!(f, ", {:?} speed", self.speed())?
+            },
+            None => {},
+        }
+        write!(f, ")")
+    }
+}
+
+// Logical full/low-speed transaction reconstructed from its split phases
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitTransaction {
+    pub hub_address: u8,
+    pub port: u8,
+    pub device_address: u8,             // Downstream FS/LS device address
+    pub endpoint: u8,                   // Endpoint number (without direction bit)
+    pub token: UsbPid,                  // SETUP, OUT or IN
+    pub endpoint_type: SplitEndpointType,
+    pub speed: Speed,
+    pub start_timestamp: f64,
+    pub complete_timestamp: Option<f64>,
+    pub start_microframe: Option<u64>,  // Bus microframe of the start-split, counted from SOFs
+    pub start_attempts: u32,            // SSPLITs issued (NAKed start-splits are retried)
+    pub complete_attempts: u32,         // CSPLITs issued (NYET responses cause retries)
+    pub data: Vec<u8>,
+    pub result: Option<UsbPid>,         // Final handshake, or data PID for IN transactions
+    pub violations: Vec<String>,        // Split timing and pairing problems
+}
+
+impl SplitTransaction {
+    pub fn direction(&self) -> UsbDirection {
+        match self.token {
+            UsbPid::In => UsbDirection::DeviceToHost,
+            _ => UsbDirection::HostToDevice,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_complete(&self) -> bool {
+        self.complete_timestamp.is_some()
+    }
+
+    // Convert into a regular transaction attributed to the downstream device
+    pub fn to_transaction(&self, id: u64) -> UsbTransaction {
+        let mut transaction = UsbTransaction::new(id, self.start_timestamp);
+        transaction.transfer_type = self.endpoint_type.transfer_type();
+        transaction.device_address = self.device_address;
+        transaction.endpoint = self.endpoint;
+
+        if self.token == UsbPid::Setup {
+            transaction.setup_packet = UsbSetupPacket::new(&self.data);
+        } else if !self.data.is_empty() || self.result.map(|pid| pid.is_data()).unwrap_or(false) {
+            transaction.data_packet = Some(UsbDataPacket::new(self.data.clone(), self.direction(), self.endpoint));
+        }
+
+        let status = match self.result {
+            Some(UsbPid::Ack) => Some(UsbTransferStatus::ACK),
+            Some(UsbPid::Nak) => Some(UsbTransferStatus::NAK),
+            Some(UsbPid::Stall) => Some(UsbTransferStatus::STALL),
+            Some(UsbPid::Nyet) => Some(UsbTransferStatus::NYET),
+            Some(pid) if pid.is_data() => Some(UsbTransferStatus::ACK),
+            Some(_) => Some(UsbTransferStatus::Unknown),
+            None => None,
+        };
+        if let Some(status) = status {
+            transaction.status_packet = Some(UsbStatusPacket {
+                status,
+                endpoint: self.endpoint,
+            });
+        }
+
+        transaction.data_pid = self.result.filter(|pid| pid.is_data());
+        transaction.fields.insert("Split Hub".to_string(), format!("{}", self.hub_address));
+        transaction.fields.insert("Split Port".to_string(), format!("{}", self.port));
+        transaction.fields.insert("Speed".to_string(), format!("{:?}", self.speed));
+        transaction.fields.insert("Split Attempts".to_string(),
+                                  format!("{} start, {} complete", self.start_attempts, self.complete_attempts));
+        if let Some(complete) = self.complete_timestamp {
+            transaction.fields.insert("Split Latency".to_string(),
+                                      format!("{:.1} µs", (complete - self.start_timestamp) * 1_000_000.0));
+        }
+        if !self.violations.is_empty() {
+            transaction.fields.insert("Split Violations".to_string(), self.violations.join("; "));
+        }
+
+        transaction
+    }
+}
+
+impl fmt::Display for SplitTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Addr {} EP {} via Hub {} Port {} ({}, {:?} speed)",
+               self.token, self.device_address, self.endpoint,
+               self.hub_address, self.port, self.endpoint_type, self.speed)?;
+        match self.result {
+            Some(result) => write!(f, " -> {}", result)?,
+            None => write!(f, " -> incomplete")?,
+        }
+        for violation in &self.violations {
+            write!(f, " [{}]", violation)?;
+        }
+        Ok(())
+    }
+}
+
+// Key identifying an outstanding split on a TT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SplitKey {
+    hub_address: u8,
+    port: u8,
+    device_address: u8,
+    endpoint: u8,
+    token: UsbPid,
+}
+
+// One SPLIT + token + (data) + (handshake) sequence on the high-speed bus
+#[derive(Debug, Clone)]
+struct SplitPhase {
+    split: SplitToken,
+    split_timestamp: f64,
+    microframe: Option<u64>, // None until the first SOF has been seen
+    token: Option<(UsbPid, u8, u8)>, // (PID, address, endpoint)
+    data_pid: Option<UsbPid>,
+    data: Vec<u8>,
+    handshake: Option<UsbPid>,
+}
+
+// Pairs start-splits with their complete-splits and reports the logical FS/LS transactions
+#[derive(Debug, Clone, Default)]
+pub struct SplitTracker {
+    current: Option<SplitPhase>,
+    pending: HashMap<SplitKey, SplitTransaction>,
+    microframe: Option<u64>, // SOFs seen so far; split timing is only checked once SOFs arrive
+}
+
+impl SplitTracker {
+    pub fn new() -> Self {
+        SplitTracker {
+            current: None,
+            pending: HashMap::new(),
+            microframe: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.pending.clear();
+        self.microframe = None;
+    }
+
+    // Number of start-splits still waiting for their complete-split
+    #[allow(dead_code)]
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Feed one raw packet from the high-speed bus. Returns logical transactions
+    // that finished (successfully or not) as a result of this packet.
+    pub fn process_packet(&mut self, packet: &UsbPacket) -> Vec<SplitTransaction> {
+        let mut finished = Vec::new();
+
+        match packet.pid {
+            UsbPid::Split => {
+                finished.extend(self.finish_phase());
+
+                if let Ok(split) = SplitToken::parse(&packet.bytes) {
+                    self.current = Some(SplitPhase {
+                        split,
+                        split_timestamp: packet.timestamp,
+                        microframe: self.microframe,
+                        token: None,
+                        data_pid: None,
+                        data: Vec::new(),
+                        handshake: None,
+                    });
+                }
+            },
+            UsbPid::Setup | UsbPid::Out | UsbPid::In => {
+                let waiting_for_token = self.current.as_ref().map(|p| p.token.is_none()).unwrap_or(false);
+                if waiting_for_token {
+                    if let Some(phase) = self.current.as_mut() {
+                        phase.token = Some((
+                            packet.pid,
+                            packet.token_address().unwrap_or(0),
+                            packet.token_endpoint().unwrap_or(0),
+                        ));
+                    }
+                } else {
+                    // A regular high-speed transaction ends any split phase in progress
+                    finished.extend(self.finish_phase());
+                }
+            },
+            UsbPid::Data0 | UsbPid::Data1 | UsbPid::Data2 | UsbPid::MData => {
+                if let Some(phase) = self.current.as_mut() {
+                    if phase.token.is_some() {
+                        phase.data_pid = Some(packet.pid);
+                        phase.data = packet.payload().to_vec();
+                    }
+                }
+            },
+            UsbPid::Ack | UsbPid::Nak | UsbPid::Stall | UsbPid::Nyet | UsbPid::Pre => {
+                if let Some(phase) = self.current.as_mut() {
+                    if phase.token.is_some() {
+                        phase.handshake = Some(packet.pid);
+                    }
+                }
+                finished.extend(self.finish_phase());
+            },
+            UsbPid::Sof => {
+                // Every high-speed SOF starts a new microframe
+                finished.extend(self.finish_phase());
+                let microframe = self.microframe.map_or(0, |microframe| microframe + 1);
+                self.microframe = Some(microframe);
+                finished.extend(self.expire_periodic(microframe));
+            },
+            UsbPid::Ping => {
+                finished.extend(self.finish_phase());
+            },
+        }
+
+        finished
+    }
+
+    // Report every start-split that never saw a matching complete-split
+    #[allow(dead_code)]
+    pub fn flush(&mut self) -> Vec<SplitTransaction> {
+        let mut finished = self.finish_phase();
+        for (_, mut transaction) in self.pending.drain() {
+            transaction.violations.push("Start-split was never completed".to_string());
+            finished.push(transaction);
+        }
+        finished.sort_by(|a, b| a.start_timestamp.partial_cmp(&b.start_timestamp).unwrap_or(std::cmp::Ordering::Equal));
+        finished
+    }
+
+    fn finish_phase(&mut self) -> Vec<SplitTransaction> {
+        let phase = match self.current.take() {
+            Some(phase) => phase,
+            None => return Vec::new(),
+        };
+
+        let (token, device_address, endpoint) = match phase.token {
+            Some(token) => token,
+            None => return Vec::new(), // SPLIT without a following token
+        };
+
+        let key = SplitKey {
+            hub_address: phase.split.hub_address,
+            port: phase.split.port,
+            device_address,
+            endpoint,
+            token,
+        };
+
+        if phase.split.complete {
+            self.finish_complete_split(key, &phase)
+        } else {
+            self.finish_start_split(key, &phase)
+        }
+    }
+
+    fn finish_start_split(&mut self, key: SplitKey, phase: &SplitPhase) -> Vec<SplitTransaction> {
+        let mut finished = Vec::new();
+
+        // A new start-split for the same endpoint while one is outstanding means the
+        // earlier one was abandoned by the host. NAKed non-periodic start-splits and
+        // multi-part isochronous OUT payloads legitimately repeat the start-split.
+        let continuation = phase.split.endpoint_type == SplitEndpointType::Isochronous
+            && key.token == UsbPid::Out
+            && !phase.split.start_bit;
+        if let Some(existing) = self.pending.get(&key) {
+            if existing.complete_attempts > 0 || (phase.split.endpoint_type.is_periodic() && !continuation) {
+                if let Some(mut abandoned) = self.pending.remove(&key) {
+                    abandoned.violations.push("Start-split was never completed".to_string());
+                    finished.push(abandoned);
+                }
+            }
+        }
+
+        let transaction = self.pending.entry(key).or_insert_with(|| SplitTransaction {
+            hub_address: key.hub_address,
+            port: key.port,
+            device_address: key.device_address,
+            endpoint: key.endpoint,
+            token: key.token,
+            endpoint_type: phase.split.endpoint_type,
+            speed: phase.split.speed(),
+            start_timestamp: phase.split_timestamp,
+            complete_timestamp: None,
+            start_microframe: phase.microframe,
+            start_attempts: 0,
+            complete_attempts: 0,
+            data: Vec::new(),
+            result: None,
+            violations: Vec::new(),
+        });
+
+        transaction.start_attempts += 1;
+
+        // OUT/SETUP data is delivered to the TT with the start-split
+        if key.token != UsbPid::In {
+            if phase.split.endpoint_type == SplitEndpointType::Isochronous && phase.split.start_bit {
+                transaction.data.clear();
+            }
+            if phase.split.endpoint_type == SplitEndpointType::Isochronous || transaction.data.is_empty() {
+                transaction.data.extend_from_slice(&phase.data);
+            }
+        }
+
+        match phase.handshake {
+            // The TT had no room for the transaction; the host will retry the start-split
+            Some(UsbPid::Nak) => {
+                transaction.start_timestamp = phase.split_timestamp;
+                transaction.start_microframe = phase.microframe;
+            },
+            Some(UsbPid::Stall) | Some(UsbPid::Pre) => {
+                transaction.result = phase.handshake;
+                if let Some(transaction) = self.pending.remove(&key) {
+                    finished.push(transaction);
+                }
+            },
+            _ => {
+                // Isochronous OUT transactions have no complete-split
+                if phase.split.endpoint_type == SplitEndpointType::Isochronous && key.token == UsbPid::Out {
+                    if phase.split.end_bit {
+                        if let Some(mut transaction) = self.pending.remove(&key) {
+                            transaction.complete_timestamp = Some(phase.split_timestamp);
+                            transaction.result = Some(UsbPid::Ack);
+                            finished.push(transaction);
+                        }
+                    }
+                } else {
+                    transaction.start_timestamp = phase.split_timestamp;
+                    transaction.start_microframe = phase.microframe;
+                }
+            },
+        }
+
+        finished
+    }
+
+    fn finish_complete_split(&mut self, key: SplitKey, phase: &SplitPhase) -> Vec<SplitTransaction> {
+        let mut transaction = match self.pending.remove(&key) {
+            Some(transaction) => transaction,
+            None => {
+                // Complete-split without any start-split we saw
+                return vec![SplitTransaction {
+                    hub_address: key.hub_address,
+                    port: key.port,
+                    device_address: key.device_address,
+                    endpoint: key.endpoint,
+                    token: key.token,
+                    endpoint_type: phase.split.endpoint_type,
+                    speed: phase.split.speed(),
+                    start_timestamp: phase.split_timestamp,
+                    complete_timestamp: Some(phase.split_timestamp),
+                    start_microframe: phase.microframe,
+                    start_attempts: 0,
+                    complete_attempts: 1,
+                    data: phase.data.clone(),
+                    result: phase.handshake.or(phase.data_pid),
+                    violations: vec!["Complete-split without a matching start-split".to_string()],
+                }];
+            },
+        };
+
+        transaction.complete_attempts += 1;
+
+        // Check the complete-split is inside the scheduling window for periodic endpoints,
+        // counting microframes by the SOFs between the two splits
+        let elapsed = match (transaction.start_microframe, phase.microframe) {
+            (Some(start), Some(now)) if transaction.endpoint_type.is_periodic() => Some(now.saturating_sub(start)),
+            _ => None,
+        };
+        if let Some(elapsed) = elapsed {
+            let last = csplit_window_last(transaction.endpoint_type, key.token);
+            if elapsed > last {
+                transaction.violations.push(format!(
+                    "CSPLIT {} microframes after SSPLIT, outside the Y+{}..Y+{} window",
+                    elapsed, PERIODIC_CSPLIT_FIRST, last));
+            } else if transaction.complete_attempts == 1 && elapsed < PERIODIC_CSPLIT_FIRST {
+                transaction.violations.push(format!(
+                    "CSPLIT issued {} microframes after SSPLIT, before the TT could complete it", elapsed));
+            }
+        }
+
+        if key.token == UsbPid::In {
+            if let Some(data_pid) = phase.data_pid {
+                transaction.data.extend_from_slice(&phase.data);
+
+                // MDATA means more data follows in the next complete-split
+                if data_pid == UsbPid::MData {
+                    self.pending.insert(key, transaction);
+                    return Vec::new();
+                }
+
+                transaction.result = Some(data_pid);
+                transaction.complete_timestamp = Some(phase.split_timestamp);
+                return vec![transaction];
+            }
+        }
+
+        match phase.handshake {
+            // Not finished yet on the full/low-speed side
+            Some(UsbPid::Nyet) | None => {
+                self.pending.insert(key, transaction);
+                Vec::new()
+            },
+            Some(handshake) => {
+                transaction.result = Some(handshake);
+                transaction.complete_timestamp = Some(phase.split_timestamp);
+                if handshake == UsbPid::Pre {
+                    transaction.violations.push("TT reported an error (ERR handshake)".to_string());
+                }
+                vec![transaction]
+            },
+        }
+    }
+
+    // Periodic start-splits whose complete-split window has passed without any CSPLIT
+    fn expire_periodic(&mut self, microframe: u64) -> Vec<SplitTransaction> {
+        let expired: Vec<SplitKey> = self.pending.iter()
+            .filter(|(_, t)| t.endpoint_type.is_periodic() && t.complete_attempts == 0)
+            .filter(|(_, t)| t.start_microframe.is_some_and(|start| {
+                microframe - start > csplit_window_last(t.endpoint_type, t.token) + 1
+            }))
+            .map(|(key, _)| *key)
+            .collect();
+
+        expired.into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .filter(|t| !(t.endpoint_type == SplitEndpointType::Isochronous && t.token == UsbPid::Out))
+            .map(|mut t| {
+                t.violations.push("Missed CSPLIT window: no complete-split was issued".to_string());
+                t
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usb::pid::{crc16, crc5};
+
+    fn split_bytes(complete: bool, hub: u8, port: u8, start: bool, end: bool, endpoint_type: u8) -> Vec<u8> {
+        let value = hub as u32 | (complete as u32) << 7 | (port as u32) << 8
+            | (start as u32) << 15 | (end as u32) << 16 | (endpoint_type as u32) << 17;
+        let value = value | (crc5(value, 19) as u32) << 19;
+        let bytes = value.to_le_bytes();
+        vec![UsbPid::Split.get_value(), bytes[0], bytes[1], bytes[2]]
+    }
+
+    fn packet(bytes: &[u8]) -> UsbPacket {
+        UsbPacket::parse(0.0, bytes).unwrap()
+    }
+
+    fn token(pid: UsbPid, address: u8, endpoint: u8) -> UsbPacket {
+        let value = address as u16 | (endpoint as u16) << 7;
+        let value = value | (crc5(value as u32, 11) as u16) << 11;
+        let bytes = value.to_le_bytes();
+        packet(&[pid.get_value(), bytes[0], bytes[1]])
+    }
+
+    fn data(pid: UsbPid, payload: &[u8]) -> UsbPacket {
+        let mut bytes = vec![pid.get_value()];
+        bytes.extend_from_slice(payload);
+        bytes.extend_from_slice(&crc16(payload).to_le_bytes());
+        packet(&bytes)
+    }
+
+    fn sof(frame: u16) -> UsbPacket {
+        let value = frame | (crc5(frame as u32, 11) as u16) << 11;
+        let bytes = value.to_le_bytes();
+        packet(&[UsbPid::Sof.get_value(), bytes[0], bytes[1]])
+    }
+
+    #[test]
+    fn parses_start_and_complete_splits() {
+        let start = SplitToken::parse(&split_bytes(false, 2, 1, false, false, 3)).unwrap();
+        assert_eq!(start.hub_address, 2);
+        assert_eq!(start.port, 1);
+        assert!(!start.complete);
+        assert_eq!(start.endpoint_type, SplitEndpointType::Interrupt);
+        assert_eq!(start.speed(), Speed::Full);
+        assert_eq!(start.isochronous_position(), None);
+
+        let complete = SplitToken::parse(&split_bytes(true, 2, 1, true, false, 3)).unwrap();
+        assert!(complete.complete);
+        assert_eq!(complete.name(), "CSPLIT");
+        assert_eq!(complete.speed(), Speed::Low);
+
+        let isochronous = SplitToken::parse(&split_bytes(false, 5, 4, true, true, 1)).unwrap();
+        assert_eq!(isochronous.endpoint_type, SplitEndpointType::Isochronous);
+        assert_eq!(isochronous.speed(), Speed::Full);
+        assert_eq!(isochronous.isochronous_position(), Some("all"));
+    }
+
+    #[test]
+    fn split_tokens_pass_the_crc_check() {
+        let bytes = split_bytes(false, 2, 1, false, false, 3);
+        assert!(UsbPacket::parse(0.0, &bytes).is_some());
+
+        let mut corrupted = bytes.clone();
+        corrupted[3] ^= 0x80;
+        assert!(UsbPacket::parse(0.0, &corrupted).is_none());
+    }
+
+    #[test]
+    fn rejects_short_and_foreign_tokens() {
+        assert!(SplitToken::parse(&[0x78, 0x02, 0x01]).is_err());
+        assert!(SplitToken::parse(&[0x69, 0x02, 0x01, 0x00]).is_err());
+    }
+
+    // Run a periodic IN split with its first complete-split `delay` microframes after the
+    // start-split, and return the finished transaction
+    fn periodic_in(endpoint_type: u8, delay: u16) -> SplitTransaction {
+        let mut tracker = SplitTracker::new();
+        let mut finished = Vec::new();
+        finished.extend(tracker.process_packet(&sof(0)));
+        finished.extend(tracker.process_packet(&packet(&split_bytes(false, 2, 1, false, false, endpoint_type))));
+        finished.extend(tracker.process_packet(&token(UsbPid::In, 5, 1)));
+        finished.extend(tracker.process_packet(&packet(&[UsbPid::Ack.get_value()])));
+        for _ in 0..delay {
+            finished.extend(tracker.process_packet(&sof(0)));
+        }
+        finished.extend(tracker.process_packet(&packet(&split_bytes(true, 2, 1, false, false, endpoint_type))));
+        finished.extend(tracker.process_packet(&token(UsbPid::In, 5, 1)));
+        finished.extend(tracker.process_packet(&data(UsbPid::Data0, &[0x01, 0x02])));
+        finished.extend(tracker.process_packet(&sof(1)));
+        assert_eq!(finished.len(), 1);
+        finished.remove(0)
+    }
+
+    #[test]
+    fn pairs_complete_split_inside_window() {
+        let transaction = periodic_in(3, 2);
+        assert_eq!(transaction.device_address, 5);
+        assert_eq!(transaction.endpoint, 1);
+        assert_eq!(transaction.data, vec![0x01, 0x02]);
+        assert_eq!(transaction.result, Some(UsbPid::Data0));
+        assert!(transaction.violations.is_empty());
+    }
+
+    #[test]
+    fn flags_complete_split_before_y_plus_2() {
+        let transaction = periodic_in(3, 1);
+        assert_eq!(transaction.violations.len(), 1);
+        assert!(transaction.violations[0].contains("before the TT"));
+    }
+
+    #[test]
+    fn isochronous_in_window_runs_through_y_plus_8() {
+        assert!(periodic_in(1, 8).violations.is_empty());
+        assert!(!periodic_in(3, 5).violations.is_empty());
+    }
+}
@@ -0,0 +1,422 @@
+// Endpoint halt and STALL tracking
+// A STALL on endpoint 0 is a protocol stall: the device rejected one control request and
+// the next SETUP clears it. A STALL on any other endpoint is a functional stall: the
+// endpoint stays halted until the host sends CLEAR_FEATURE(ENDPOINT_HALT), SET_CONFIGURATION
+// or SET_INTERFACE, or resets the bus (USB 2.0 sections 8.4.5 and 9.4.5).
+
+use std::collections::HashMap;
+use std::fmt;
+use super::bus::BusDevice;
+use super::bus_event::{BusEvent, BusEventKind};
+use super::mitm_traffic::{
+    UsbControlRecipient,
+    UsbControlRequestType,
+    UsbDirection,
+    UsbStandardRequest,
+    UsbTransaction,
+    UsbTransferStatus,
+};
+
+// Feature selector for ENDPOINT_HALT
+const ENDPOINT_HALT: u16 = 0;
+
+// Kind of stall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallKind {
+    Protocol,   // EP0 rejected a control request
+    Functional, // A bulk or interrupt endpoint halted
+    HostHalt,   // The host halted the endpoint with SET_FEATURE(ENDPOINT_HALT)
+}
+
+impl fmt::Display for StallKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StallKind::Protocol => write!(f, "Protocol STALL"),
+            StallKind::Functional => write!(f, "Functional STALL"),
+            StallKind::HostHalt => write!(f, "Halted by host"),
+        }
+    }
+}
+
+// How a stall was recovered from
+#[derive(Debug, Clone, PartialEq)]
+pub enum StallRecovery {
+    NextSetup,                       // Protocol stalls clear on the next SETUP
+    ClearFeature,
+    Reconfigured(UsbStandardRequest), // SET_CONFIGURATION or SET_INTERFACE
+    BusReset,
+}
+
+impl fmt::Display for StallRecovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StallRecovery::NextSetup => write!(f, "next SETUP"),
+            StallRecovery::ClearFeature => write!(f, "CLEAR_FEATURE(ENDPOINT_HALT)"),
+            StallRecovery::Reconfigured(request) => write!(f, "{}", request),
+            StallRecovery::BusReset => write!(f, "bus reset"),
+        }
+    }
+}
+
+// One stall and what became of it
+#[derive(Debug, Clone)]
+pub struct StallRecord {
+    pub device_address: u8,
+    pub endpoint: u8,
+    pub direction: UsbDirection,
+    pub kind: StallKind,
+    pub timestamp: f64,
+    pub cause: String,               // Request or transfer the device refused
+    pub repeats: u32,                // Further transactions answered with STALL while halted
+    pub recovery: Option<(StallRecovery, f64)>, // How and when it was cleared
+}
+
+impl StallRecord {
+    pub fn recovery_time(&self) -> Option<f64> {
+        self.recovery.as_ref().map(|(_, timestamp)| timestamp - self.timestamp)
+    }
+}
+
+impl fmt::Display for StallRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.direction {
+            UsbDirection::DeviceToHost => " IN",
+            UsbDirection::HostToDevice => " OUT",
+            UsbDirection::Unknown => "",
+        };
+        write!(f, "{} on Addr {} EP{}{} - {}", self.kind, self.device_address, self.endpoint, direction, self.cause)?;
+        if self.repeats > 0 {
+            write!(f, " ({} more STALL{})", self.repeats, if self.repeats == 1 { "" } else { "s" })?;
+        }
+        match (&self.recovery, self.recovery_time()) {
+            (Some((recovery, _)), Some(time)) => write!(f, ", cleared by {} after {:.3} ms", recovery, time * 1000.0),
+            _ => write!(f, ", not cleared"),
+        }
+    }
+}
+
+// What a transaction meant for the halt state
+#[derive(Debug, Clone, PartialEq)]
+pub enum HaltEvent {
+    Stalled(StallKind),
+    ToHaltedEndpoint, // Traffic on an endpoint that is still halted
+    Cleared { endpoints: usize, recovery: StallRecovery },
+}
+
+impl fmt::Display for HaltEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HaltEvent::Stalled(kind) => write!(f, "{}", kind),
+            HaltEvent::ToHaltedEndpoint => write!(f, "Traffic to halted endpoint"),
+            HaltEvent::Cleared { endpoints, recovery } => {
+                write!(f, "{} halted endpoint{} cleared by {}", endpoints,
+                       if *endpoints == 1 { "" } else { "s" }, recovery)
+            },
+        }
+    }
+}
+
+// Tracks halt state per endpoint and keeps every stall for the summary
+#[derive(Debug, Clone, Default)]
+pub struct HaltTracker {
+    halted: HashMap<(u8, u8, bool), usize>, // (address, endpoint, is IN) -> index into stalls
+    protocol_stalls: HashMap<u8, usize>,    // Address -> protocol stall waiting for the next SETUP
+    last_request: HashMap<u8, String>,      // Address -> most recent control request
+    stalls: Vec<StallRecord>,
+}
+
+impl HaltTracker {
+    pub fn new() -> Self {
+        HaltTracker::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = HaltTracker::default();
+    }
+
+    pub fn stalls(&self) -> &[StallRecord] {
+        &self.stalls
+    }
+
+    pub fn halted_count(&self) -> usize {
+        self.halted.len()
+    }
+
+    fn recover(&mut self, index: usize, recovery: StallRecovery, timestamp: f64) {
+        if let Some(record) = self.stalls.get_mut(index) {
+            record.recovery.get_or_insert((recovery, timestamp));
+        }
+    }
+
+    // Clear every halted endpoint matching the filter
+    fn clear_halts(&mut self, recovery: StallRecovery, timestamp: f64,
+                   filter: impl Fn(&(u8, u8, bool)) -> bool) -> usize {
+        let cleared: Vec<(u8, u8, bool)> = self.halted.keys().filter(|key| filter(key)).copied().collect();
+        for key in &cleared {
+            if let Some(index) = self.halted.remove(key) {
+                self.recover(index, recovery.clone(), timestamp);
+            }
+        }
+        cleared.len()
+    }
+
+    fn record_stall(&mut self, transaction: &UsbTransaction, kind: StallKind, endpoint: u8,
+                    direction: UsbDirection, cause: String) -> usize {
+        self.stalls.push(StallRecord {
+            device_address: transaction.device_address,
+            endpoint,
+            direction,
+            kind,
+            timestamp: transaction.timestamp,
+            cause,
+            repeats: 0,
+            recovery: None,
+        });
+        self.stalls.len() - 1
+    }
+
+    pub fn process_bus_event(&mut self, event: &BusEvent) {
+        if event.kind == BusEventKind::BusReset {
+            self.clear_halts(StallRecovery::BusReset, event.timestamp, |_| true);
+            let protocol: Vec<usize> = self.protocol_stalls.drain().map(|(_, index)| index).collect();
+            for index in protocol {
+                self.recover(index, StallRecovery::BusReset, event.timestamp);
+            }
+        }
+    }
+
+    // Feed one transaction and report what it did to the halt state. The device's
+    // descriptors tell which endpoints SET_INTERFACE resets.
+    pub fn process_transaction(&mut self, transaction: &UsbTransaction, device: Option<&BusDevice>) -> Vec<HaltEvent> {
+        let mut events = Vec::new();
+        let address = transaction.device_address;
+        let now = transaction.timestamp;
+        let stalled = transaction.status_packet.as_ref().map(|status| status.status) == Some(UsbTransferStatus::STALL);
+
+        if let Some(setup) = &transaction.setup_packet {
+            if let Some(index) = self.protocol_stalls.remove(&address) {
+                self.recover(index, StallRecovery::NextSetup, now);
+            }
+            self.last_request.insert(address, setup.request_description.clone());
+
+            let standard = setup.request_type == UsbControlRequestType::Standard && !stalled;
+            let endpoint_request = setup.recipient == UsbControlRecipient::Endpoint && setup.wValue == ENDPOINT_HALT;
+            let target = ((setup.wIndex & 0x0F) as u8, (setup.wIndex & 0x80) != 0);
+
+            match setup.standard_request {
+                Some(UsbStandardRequest::ClearFeature) if standard && endpoint_request => {
+                    let count = self.clear_halts(StallRecovery::ClearFeature, now,
+                                                 |key| *key == (address, target.0, target.1));
+                    if count > 0 {
+                        events.push(HaltEvent::Cleared { endpoints: count, recovery: StallRecovery::ClearFeature });
+                    }
+                },
+                Some(UsbStandardRequest::SetFeature) if standard && endpoint_request && target.0 != 0 => {
+                    let direction = if target.1 { UsbDirection::DeviceToHost } else { UsbDirection::HostToDevice };
+                    let index = self.record_stall(transaction, StallKind::HostHalt, target.0, direction,
+                                                  setup.request_description.clone());
+                    self.halted.insert((address, target.0, target.1), index);
+                },
+                Some(request @ UsbStandardRequest::SetConfiguration) if standard => {
+                    let recovery = StallRecovery::Reconfigured(request);
+                    let count = self.clear_halts(recovery.clone(), now, |key| key.0 == address);
+                    if count > 0 {
+                        events.push(HaltEvent::Cleared { endpoints: count, recovery });
+                    }
+                },
+                // SET_INTERFACE resets the endpoints of the interface in wIndex. Without its
+                // descriptors the whole device is cleared rather than leaving stale halts.
+                Some(request @ UsbStandardRequest::SetInterface) if standard => {
+                    let recovery = StallRecovery::Reconfigured(request);
+                    let endpoints = device.and_then(|device| device.interface_endpoints((setup.wIndex & 0xFF) as u8));
+                    let count = self.clear_halts(recovery.clone(), now, |key| {
+                        key.0 == address && endpoints.as_ref().is_none_or(|endpoints| endpoints.contains(&(key.1, key.2)))
+                    });
+                    if count > 0 {
+                        events.push(HaltEvent::Cleared { endpoints: count, recovery });
+                    }
+                },
+                _ => {},
+            }
+
+            if stalled {
+                let index = self.record_stall(transaction, StallKind::Protocol, transaction.endpoint,
+                                              UsbDirection::Unknown, setup.request_description.clone());
+                self.protocol_stalls.insert(address, index);
+                events.push(HaltEvent::Stalled(StallKind::Protocol));
+            }
+            return events;
+        }
+
+        let direction = transaction.direction();
+        let key = (address, transaction.endpoint, direction == UsbDirection::DeviceToHost);
+
+        if transaction.endpoint == 0 {
+            // Data or status stage refused: the request that started the transfer is the cause
+            if stalled && !self.protocol_stalls.contains_key(&address) {
+                let cause = self.last_request.get(&address).cloned()
+                    .unwrap_or_else(|| "unknown control request".to_string());
+                let index = self.record_stall(transaction, StallKind::Protocol, 0, direction, cause);
+                self.protocol_stalls.insert(address, index);
+                events.push(HaltEvent::Stalled(StallKind::Protocol));
+            }
+            return events;
+        }
+
+        match self.halted.get(&key) {
+            Some(&index) => {
+                if stalled {
+                    if let Some(record) = self.stalls.get_mut(index) {
+                        record.repeats += 1;
+                    }
+                }
+                events.push(HaltEvent::ToHaltedEndpoint);
+            },
+            None if stalled => {
+                let cause = transaction.get_summary();
+                let index = self.record_stall(transaction, StallKind::Functional, transaction.endpoint, direction, cause);
+                self.halted.insert(key, index);
+                events.push(HaltEvent::Stalled(StallKind::Functional));
+            },
+            None => {},
+        }
+
+        events
+    }
+
+    // One-line description for collapsed display
+    pub fn summary(&self) -> String {
+        let count = |kind: StallKind| self.stalls.iter().filter(|stall| stall.kind == kind).count();
+        format!("Stalls: {} protocol, {} functional, {} host halt{}, {} endpoint{} still halted",
+                count(StallKind::Protocol), count(StallKind::Functional),
+                count(StallKind::HostHalt), if count(StallKind::HostHalt) == 1 { "" } else { "s" },
+                self.halted_count(), if self.halted_count() == 1 { "" } else { "s" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usb::bus::BusModel;
+    use crate::usb::mitm_traffic::{UsbDataPacket, UsbSetupPacket, UsbStatusPacket, UsbTransferType};
+
+    // Configuration with interface 0 (bulk EP1 IN) and interface 1 (bulk EP2 OUT)
+    const CONFIGURATION: [u8; 41] = [
+        0x09, 0x02, 0x29, 0x00, 0x02, 0x01, 0x00, 0x80, 0x32,
+        0x09, 0x04, 0x00, 0x00, 0x01, 0xFF, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00,
+        0x09, 0x04, 0x01, 0x00, 0x01, 0xFF, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x02, 0x02, 0x40, 0x00, 0x00,
+    ];
+
+    fn control(timestamp: f64, setup: [u8; 8], data: &[u8], status: UsbTransferStatus) -> UsbTransaction {
+        let setup = UsbSetupPacket::new(&setup).unwrap();
+        let mut transaction = UsbTransaction::new(0, timestamp);
+        transaction.transfer_type = UsbTransferType::Control;
+        transaction.device_address = 1;
+        if !data.is_empty() {
+            transaction.data_packet = Some(UsbDataPacket::new(data.to_vec(), setup.direction, 0));
+        }
+        transaction.setup_packet = Some(setup);
+        transaction.status_packet = Some(UsbStatusPacket { status, endpoint: 0 });
+        transaction
+    }
+
+    fn bulk(timestamp: f64, endpoint: u8, direction: UsbDirection, status: UsbTransferStatus) -> UsbTransaction {
+        let mut transaction = UsbTransaction::new(0, timestamp);
+        transaction.transfer_type = UsbTransferType::Bulk;
+        transaction.device_address = 1;
+        transaction.endpoint = endpoint;
+        transaction.data_packet = Some(UsbDataPacket::new(vec![0x00], direction, endpoint));
+        transaction.status_packet = Some(UsbStatusPacket { status, endpoint });
+        transaction
+    }
+
+    fn stalled_in(timestamp: f64) -> UsbTransaction {
+        bulk(timestamp, 1, UsbDirection::DeviceToHost, UsbTransferStatus::STALL)
+    }
+
+    fn stalled_out(timestamp: f64) -> UsbTransaction {
+        bulk(timestamp, 2, UsbDirection::HostToDevice, UsbTransferStatus::STALL)
+    }
+
+    #[test]
+    fn protocol_stall_clears_on_next_setup() {
+        let mut tracker = HaltTracker::new();
+        let events = tracker.process_transaction(
+            &control(1.0, [0x80, 0x06, 0x00, 0x06, 0x00, 0x00, 0x0A, 0x00], &[], UsbTransferStatus::STALL), None);
+        assert_eq!(events, vec![HaltEvent::Stalled(StallKind::Protocol)]);
+        assert_eq!(tracker.halted_count(), 0);
+
+        tracker.process_transaction(
+            &control(1.5, [0x80, 0x06, 0x00, 0x01, 0x00, 0x00, 0x12, 0x00], &[], UsbTransferStatus::ACK), None);
+        let stall = &tracker.stalls()[0];
+        assert_eq!(stall.recovery, Some((StallRecovery::NextSetup, 1.5)));
+        assert_eq!(stall.recovery_time(), Some(0.5));
+    }
+
+    #[test]
+    fn functional_stall_holds_until_clear_feature() {
+        let mut tracker = HaltTracker::new();
+        assert_eq!(tracker.process_transaction(&stalled_in(1.0), None), vec![HaltEvent::Stalled(StallKind::Functional)]);
+        assert_eq!(tracker.process_transaction(&stalled_in(1.1), None), vec![HaltEvent::ToHaltedEndpoint]);
+        assert_eq!(tracker.halted_count(), 1);
+
+        // CLEAR_FEATURE(ENDPOINT_HALT) for the other direction leaves EP1 IN halted
+        let clear_out = control(1.2, [0x02, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00], &[], UsbTransferStatus::ACK);
+        assert!(tracker.process_transaction(&clear_out, None).is_empty());
+
+        let clear_in = control(1.3, [0x02, 0x01, 0x00, 0x00, 0x81, 0x00, 0x00, 0x00], &[], UsbTransferStatus::ACK);
+        assert_eq!(tracker.process_transaction(&clear_in, None),
+                   vec![HaltEvent::Cleared { endpoints: 1, recovery: StallRecovery::ClearFeature }]);
+        assert_eq!(tracker.halted_count(), 0);
+        assert_eq!(tracker.stalls()[0].repeats, 1);
+        assert!(tracker.process_transaction(&bulk(1.4, 1, UsbDirection::DeviceToHost, UsbTransferStatus::ACK), None).is_empty());
+    }
+
+    #[test]
+    fn host_halt_is_recorded() {
+        let mut tracker = HaltTracker::new();
+        tracker.process_transaction(
+            &control(1.0, [0x02, 0x03, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00], &[], UsbTransferStatus::ACK), None);
+        assert_eq!(tracker.stalls()[0].kind, StallKind::HostHalt);
+        assert_eq!(tracker.stalls()[0].direction, UsbDirection::HostToDevice);
+        assert_eq!(tracker.process_transaction(&stalled_out(1.1), None), vec![HaltEvent::ToHaltedEndpoint]);
+    }
+
+    #[test]
+    fn set_interface_clears_only_that_interface() {
+        let mut bus = BusModel::new();
+        bus.process_transaction(
+            &control(0.0, [0x80, 0x06, 0x00, 0x02, 0x00, 0x00, 0x29, 0x00], &CONFIGURATION, UsbTransferStatus::ACK));
+        let device = bus.device(1);
+
+        let mut tracker = HaltTracker::new();
+        tracker.process_transaction(&stalled_in(1.0), device);
+        tracker.process_transaction(&stalled_out(1.0), device);
+
+        let set_interface = control(2.0, [0x01, 0x0B, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00], &[], UsbTransferStatus::ACK);
+        let recovery = StallRecovery::Reconfigured(UsbStandardRequest::SetInterface);
+        assert_eq!(tracker.process_transaction(&set_interface, device),
+                   vec![HaltEvent::Cleared { endpoints: 1, recovery: recovery.clone() }]);
+        assert_eq!(tracker.halted_count(), 1);
+
+        // Without descriptors the whole device is cleared
+        assert_eq!(tracker.process_transaction(&set_interface, None),
+                   vec![HaltEvent::Cleared { endpoints: 1, recovery }]);
+        assert_eq!(tracker.halted_count(), 0);
+    }
+
+    #[test]
+    fn bus_reset_clears_everything() {
+        let mut tracker = HaltTracker::new();
+        tracker.process_transaction(&stalled_in(1.0), None);
+        tracker.process_transaction(
+            &control(1.1, [0x80, 0x06, 0x00, 0x06, 0x00, 0x00, 0x0A, 0x00], &[], UsbTransferStatus::STALL), None);
+
+        tracker.process_bus_event(&BusEvent { timestamp: 2.0, kind: BusEventKind::BusReset, duration: None });
+        assert_eq!(tracker.halted_count(), 0);
+        assert!(tracker.stalls().iter().all(|stall| stall.recovery == Some((StallRecovery::BusReset, 2.0))));
+        assert_eq!(tracker.summary(), "Stalls: 1 protocol, 1 functional, 0 host halts, 0 endpoints still halted");
+    }
+}
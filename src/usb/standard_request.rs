@@ -0,0 +1,248 @@
+// Standard request field decoding
+// Names the fields of chapter 9 standard requests (USB 2.0 section 9.4, USB 3.2 section
+// 9.4) and decodes their data stages: feature selectors and test modes, GET_STATUS bits,
+// GET_DESCRIPTOR type/index/LANGID, SET_SEL exit latencies, SET_ISOCH_DELAY and SYNCH_FRAME.
+
+use super::mitm_traffic::{UsbControlRecipient, UsbControlRequestType, UsbSetupPacket, UsbStandardRequest};
+use super::request_fields::{describe_endpoint, field, flag, RequestField};
+use super::UsbDescriptorType;
+
+// Feature selectors (USB 2.0 table 9-6, USB 3.2 table 9-7, OTG 2.0 table 6-2)
+pub const ENDPOINT_HALT: u16 = 0;
+pub const FUNCTION_SUSPEND: u16 = 0;
+pub const DEVICE_REMOTE_WAKEUP: u16 = 1;
+pub const TEST_MODE: u16 = 2;
+
+// Name of a feature selector for the request's recipient
+pub fn feature_selector_name(recipient: UsbControlRecipient, selector: u16) -> String {
+    let name = match (recipient, selector) {
+        (UsbControlRecipient::Endpoint, ENDPOINT_HALT) => "ENDPOINT_HALT",
+        (UsbControlRecipient::Interface, FUNCTION_SUSPEND) => "FUNCTION_SUSPEND",
+        (UsbControlRecipient::Device, DEVICE_REMOTE_WAKEUP) => "DEVICE_REMOTE_WAKEUP",
+        (UsbControlRecipient::Device, TEST_MODE) => "TEST_MODE",
+        (UsbControlRecipient::Device, 3) => "b_hnp_enable",
+        (UsbControlRecipient::Device, 4) => "a_hnp_support",
+        (UsbControlRecipient::Device, 5) => "a_alt_hnp_support",
+        (UsbControlRecipient::Device, 6) => "WUSB_DEVICE",
+        (UsbControlRecipient::Device, 48) => "U1_ENABLE",
+        (UsbControlRecipient::Device, 49) => "U2_ENABLE",
+        (UsbControlRecipient::Device, 50) => "LTM_ENABLE",
+        (UsbControlRecipient::Device, 51) => "B3_NTF_HOST_REL",
+        (UsbControlRecipient::Device, 52) => "B3_RSP_ENABLE",
+        (UsbControlRecipient::Device, 53) => "LDM_ENABLE",
+        _ => return format!("Unknown Feature {}", selector),
+    };
+    name.to_string()
+}
+
+// Test selector carried in the high byte of wIndex for SET_FEATURE(TEST_MODE)
+pub fn test_selector_name(selector: u8) -> String {
+    match selector {
+        0x01 => "Test_J".to_string(),
+        0x02 => "Test_K".to_string(),
+        0x03 => "Test_SE0_NAK".to_string(),
+        0x04 => "Test_Packet".to_string(),
+        0x05 => "Test_Force_Enable".to_string(),
+        0xC0..=0xFF => format!("Vendor Test 0x{:02X}", selector),
+        _ => format!("Reserved Test 0x{:02X}", selector),
+    }
+}
+
+// Language names for the LANGIDs devices commonly report (USB LANGID table)
+pub fn language_name(langid: u16) -> Option<&'static str> {
+    Some(match langid {
+        0x0404 => "Chinese (Taiwan)",
+        0x0407 => "German (Standard)",
+        0x0409 => "English (United States)",
+        0x040A => "Spanish (Traditional Sort)",
+        0x040C => "French (Standard)",
+        0x0410 => "Italian (Standard)",
+        0x0411 => "Japanese",
+        0x0412 => "Korean",
+        0x0413 => "Dutch (Netherlands)",
+        0x0416 => "Portuguese (Brazil)",
+        0x0419 => "Russian",
+        0x041D => "Swedish",
+        0x0804 => "Chinese (PRC)",
+        0x0809 => "English (United Kingdom)",
+        0x0C0A => "Spanish (Modern Sort)",
+        0x04FF => "HID (Usage Data Descriptor)",
+        _ => return None,
+    })
+}
+
+pub fn describe_langid(langid: u16) -> String {
+    match language_name(langid) {
+        Some(name) => format!("0x{:04X} {}", langid, name),
+        None => format!("0x{:04X}", langid),
+    }
+}
+
+fn recipient_target(recipient: UsbControlRecipient, w_index: u16) -> String {
+    match recipient {
+        UsbControlRecipient::Device => "Device".to_string(),
+        UsbControlRecipient::Interface => format!("Interface {}", w_index & 0xFF),
+        UsbControlRecipient::Endpoint => describe_endpoint(w_index),
+        _ => "Unknown".to_string(),
+    }
+}
+
+// One-line description of a feature request, e.g. "SET_FEATURE: TEST_MODE (Test_Packet)"
+pub fn describe_feature_request(request: UsbStandardRequest, recipient: UsbControlRecipient,
+                                w_value: u16, w_index: u16) -> String {
+    let name = feature_selector_name(recipient, w_value);
+    let target = recipient_target(recipient, w_index);
+    match (recipient, w_value) {
+        (UsbControlRecipient::Device, TEST_MODE) => {
+            format!("{}: {} ({})", request, name, test_selector_name((w_index >> 8) as u8))
+        },
+        (UsbControlRecipient::Interface, FUNCTION_SUSPEND) => {
+            format!("{}: {} ({}, {})", request, name, target, describe_suspend_options((w_index >> 8) as u8))
+        },
+        _ => format!("{}: {} ({})", request, name, target),
+    }
+}
+
+fn describe_suspend_options(options: u8) -> String {
+    let mut flags = Vec::new();
+    flags.push(if options & 0x01 != 0 { "Suspend" } else { "Normal" });
+    if options & 0x02 != 0 {
+        flags.push("Remote Wake Enabled");
+    }
+    flags.join(", ")
+}
+
+// Decode the fields of a standard request's setup stage
+pub fn request_fields(setup: &UsbSetupPacket) -> Vec<RequestField> {
+    let mut fields = Vec::new();
+    if setup.request_type != UsbControlRequestType::Standard {
+        return fields;
+    }
+    let request = match setup.standard_request {
+        Some(request) => request,
+        None => return fields,
+    };
+
+    fields.push(field("Request", format!("{} ({})", request, setup.bRequest)));
+    fields.push(field("Recipient", format!("{}", setup.recipient)));
+
+    match request {
+        UsbStandardRequest::GetStatus => {
+            fields.push(field("Target", recipient_target(setup.recipient, setup.wIndex)));
+            if setup.wValue == 1 {
+                fields.push(field("Status Type", "PTM Status".to_string()));
+            }
+        },
+        UsbStandardRequest::ClearFeature | UsbStandardRequest::SetFeature => {
+            fields.push(field("Feature Selector", format!("{} ({})",
+                feature_selector_name(setup.recipient, setup.wValue), setup.wValue)));
+            fields.push(field("Target", recipient_target(setup.recipient, setup.wIndex)));
+            match (setup.recipient, setup.wValue) {
+                (UsbControlRecipient::Device, TEST_MODE) => {
+                    fields.push(field("Test Selector", test_selector_name((setup.wIndex >> 8) as u8)));
+                },
+                (UsbControlRecipient::Interface, FUNCTION_SUSPEND) => {
+                    fields.push(field("Suspend Options", describe_suspend_options((setup.wIndex >> 8) as u8)));
+                },
+                _ => {},
+            }
+        },
+        UsbStandardRequest::SetAddress => {
+            fields.push(field("Device Address", format!("{}", setup.wValue)));
+        },
+        UsbStandardRequest::GetDescriptor | UsbStandardRequest::SetDescriptor => {
+            let descriptor_type = UsbDescriptorType::from((setup.wValue >> 8) as u8);
+            fields.push(field("Descriptor Type", format!("{} (0x{:02X})", descriptor_type.name(), setup.wValue >> 8)));
+            fields.push(field("Descriptor Index", format!("{}", setup.wValue & 0xFF)));
+            if descriptor_type == UsbDescriptorType::String && (setup.wValue & 0xFF) != 0 {
+                fields.push(field("Language ID", describe_langid(setup.wIndex)));
+            }
+            fields.push(field("Length", format!("{} bytes", setup.wLength)));
+        },
+        UsbStandardRequest::SetConfiguration => {
+            fields.push(field("Configuration Value", format!("{}", setup.wValue & 0xFF)));
+        },
+        UsbStandardRequest::GetInterface => {
+            fields.push(field("Interface", format!("{}", setup.wIndex & 0xFF)));
+        },
+        UsbStandardRequest::SetInterface => {
+            fields.push(field("Interface", format!("{}", setup.wIndex & 0xFF)));
+            fields.push(field("Alternate Setting", format!("{}", setup.wValue & 0xFF)));
+        },
+        UsbStandardRequest::SynchFrame => {
+            fields.push(field("Endpoint", describe_endpoint(setup.wIndex)));
+        },
+        UsbStandardRequest::SetIsochDelay => {
+            fields.push(field("Isochronous Delay", format!("{} ns", setup.wValue)));
+        },
+        _ => {},
+    }
+
+    fields
+}
+
+// Decode the data stage of a standard request
+pub fn response_fields(setup: &UsbSetupPacket, data: &[u8]) -> Vec<RequestField> {
+    let mut fields = Vec::new();
+    if setup.request_type != UsbControlRequestType::Standard {
+        return fields;
+    }
+
+    match setup.standard_request {
+        Some(UsbStandardRequest::GetStatus) if data.len() >= 2 => {
+            let status = u16::from_le_bytes([data[0], data[1]]);
+            fields.push(field("Status", format!("0x{:04X}", status)));
+            match setup.recipient {
+                UsbControlRecipient::Device => {
+                    fields.push(field("Self Powered", flag(status & 0x0001 != 0)));
+                    fields.push(field("Remote Wakeup", flag(status & 0x0002 != 0)));
+                    fields.push(field("U1 Enable", flag(status & 0x0004 != 0)));
+                    fields.push(field("U2 Enable", flag(status & 0x0008 != 0)));
+                    fields.push(field("LTM Enable", flag(status & 0x0010 != 0)));
+                },
+                UsbControlRecipient::Interface => {
+                    fields.push(field("Function Remote Wake Capable", flag(status & 0x0001 != 0)));
+                    fields.push(field("Function Remote Wakeup", flag(status & 0x0002 != 0)));
+                },
+                UsbControlRecipient::Endpoint => {
+                    fields.push(field("Halt", flag(status & 0x0001 != 0)));
+                },
+                _ => {},
+            }
+        },
+        Some(UsbStandardRequest::GetConfiguration) if !data.is_empty() => {
+            let value = data[0];
+            fields.push(field("Configuration Value", if value == 0 {
+                "0 (Not Configured)".to_string()
+            } else {
+                format!("{}", value)
+            }));
+        },
+        Some(UsbStandardRequest::GetInterface) if !data.is_empty() => {
+            fields.push(field("Alternate Setting", format!("{}", data[0])));
+        },
+        Some(UsbStandardRequest::SynchFrame) if data.len() >= 2 => {
+            fields.push(field("Frame Number", format!("{}", u16::from_le_bytes([data[0], data[1]]) & 0x07FF)));
+        },
+        Some(UsbStandardRequest::SetSel) if data.len() >= 6 => {
+            fields.push(field("U1 System Exit Latency", format!("{} µs", data[0])));
+            fields.push(field("U1 Device to Host Exit Latency", format!("{} µs", data[1])));
+            fields.push(field("U2 System Exit Latency", format!("{} µs", u16::from_le_bytes([data[2], data[3]]))));
+            fields.push(field("U2 Device to Host Exit Latency", format!("{} µs", u16::from_le_bytes([data[4], data[5]]))));
+        },
+        Some(UsbStandardRequest::GetDescriptor)
+            if (setup.wValue >> 8) as u8 == UsbDescriptorType::String.get_value() && (setup.wValue & 0xFF) == 0 => {
+            // String descriptor 0 lists the supported languages
+            let languages: Vec<String> = data.get(2..).unwrap_or(&[])
+                .chunks_exact(2)
+                .map(|langid| describe_langid(u16::from_le_bytes([langid[0], langid[1]])))
+                .collect();
+            if !languages.is_empty() {
+                fields.push(field("Languages", languages.join(", ")));
+            }
+        },
+        _ => {},
+    }
+
+    fields
+}
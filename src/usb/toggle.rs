@@ -0,0 +1,333 @@
+// Data toggle (DATA0/DATA1) tracking
+// Each non-isochronous endpoint keeps a sequence bit that alternates with every
+// successfully received data packet. A receiver that sees the wrong PID acknowledges
+// the packet but discards it as a duplicate (USB 2.0 section 8.6). The toggle is reset
+// by SETUP (control endpoints), SET_CONFIGURATION, SET_INTERFACE and
+// CLEAR_FEATURE(ENDPOINT_HALT) (USB 2.0 sections 9.4.5, 9.4.10 and 9.1.1.5).
+
+use std::collections::HashMap;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::bus::BusDevice;
+use super::mitm_traffic::{
+    UsbControlRecipient,
+    UsbControlRequestType,
+    UsbDirection,
+    UsbStandardRequest,
+    UsbTransaction,
+    UsbTransferStatus,
+    UsbTransferType,
+};
+use super::pid::UsbPid;
+
+// Feature selector for CLEAR_FEATURE(ENDPOINT_HALT)
+const ENDPOINT_HALT: u16 = 0;
+
+// Result of checking one data packet against the endpoint's toggle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ToggleCheck {
+    // PID matched the expected toggle
+    Ok(UsbPid),
+    // First data packet seen on this endpoint; nothing to compare against
+    Initial(UsbPid),
+    // Same PID and data as the previous packet: the receiver discards it as a duplicate
+    Retransmission(UsbPid),
+    // PID doesn't match the toggle and the data is new
+    Mismatch { expected: UsbPid, found: UsbPid },
+}
+
+impl ToggleCheck {
+    #[allow(dead_code)]
+    pub fn is_error(&self) -> bool {
+        matches!(self, ToggleCheck::Mismatch { .. })
+    }
+}
+
+impl fmt::Display for ToggleCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToggleCheck::Ok(pid) => write!(f, "{}", pid),
+            ToggleCheck::Initial(pid) => write!(f, "{} (first seen)", pid),
+            ToggleCheck::Retransmission(pid) => write!(f, "{} retransmission (discarded by receiver)", pid),
+            ToggleCheck::Mismatch { expected, found } => {
+                write!(f, "Toggle error: expected {}, got {}", expected, found)
+            },
+        }
+    }
+}
+
+// Toggle state for one endpoint direction
+#[derive(Debug, Clone, Default)]
+struct ToggleState {
+    expected: Option<UsbPid>, // None until the first packet or a reset
+    last_data: Option<(UsbPid, Vec<u8>)>,
+}
+
+// Tracks the data toggle of every endpoint seen in the traffic
+#[derive(Debug, Clone, Default)]
+pub struct DataToggleTracker {
+    // Keyed by device address, endpoint number and whether the endpoint is IN
+    endpoints: HashMap<(u8, u8, bool), ToggleState>,
+    mismatch_count: u32,
+    retransmission_count: u32,
+}
+
+impl DataToggleTracker {
+    pub fn new() -> Self {
+        DataToggleTracker::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.endpoints.clear();
+        self.mismatch_count = 0;
+        self.retransmission_count = 0;
+    }
+
+    #[allow(dead_code)]
+    pub fn mismatch_count(&self) -> u32 {
+        self.mismatch_count
+    }
+
+    #[allow(dead_code)]
+    pub fn retransmission_count(&self) -> u32 {
+        self.retransmission_count
+    }
+
+    fn reset(&mut self, address: u8, endpoint: u8, is_in: bool, pid: UsbPid) {
+        let state = self.endpoints.entry((address, endpoint, is_in)).or_default();
+        state.expected = Some(pid);
+        state.last_data = None;
+    }
+
+    // Reset every non-control endpoint of a device to DATA0
+    fn reset_device(&mut self, address: u8) {
+        for ((device, endpoint, _), state) in self.endpoints.iter_mut() {
+            if *device == address && *endpoint != 0 {
+                state.expected = Some(UsbPid::Data0);
+                state.last_data = None;
+            }
+        }
+    }
+
+    // Apply the toggle resets caused by a standard request
+    fn apply_setup(&mut self, transaction: &UsbTransaction, device: Option<&BusDevice>) {
+        let setup = match &transaction.setup_packet {
+            Some(setup) => setup,
+            None => return,
+        };
+        let address = transaction.device_address;
+
+        // SETUP is always DATA0; the data and status stages start at DATA1
+        self.reset(address, transaction.endpoint, true, UsbPid::Data1);
+        self.reset(address, transaction.endpoint, false, UsbPid::Data1);
+
+        if setup.request_type != UsbControlRequestType::Standard {
+            return;
+        }
+
+        match (setup.standard_request, setup.recipient) {
+            (Some(UsbStandardRequest::SetConfiguration), _) => self.reset_device(address),
+            // Only the endpoints of the interface in wIndex are reset. Without its
+            // descriptors every endpoint of the device is, rather than keeping stale toggles.
+            (Some(UsbStandardRequest::SetInterface), _) => {
+                match device.and_then(|device| device.interface_endpoints((setup.wIndex & 0xFF) as u8)) {
+                    Some(endpoints) => {
+                        for (endpoint, is_in) in endpoints {
+                            self.reset(address, endpoint, is_in, UsbPid::Data0);
+                        }
+                    },
+                    None => self.reset_device(address),
+                }
+            },
+            (Some(UsbStandardRequest::ClearFeature), UsbControlRecipient::Endpoint)
+                if setup.wValue == ENDPOINT_HALT => {
+                let endpoint = (setup.wIndex & 0x0F) as u8;
+                let is_in = (setup.wIndex & 0x80) != 0;
+                self.reset(address, endpoint, is_in, UsbPid::Data0);
+            },
+            _ => {},
+        }
+    }
+
+    // Check one transaction. Returns None for transactions without a data PID
+    // and for isochronous endpoints, which don't use the toggle. The device's
+    // descriptors tell which endpoints SET_INTERFACE resets.
+    pub fn process_transaction(&mut self, transaction: &UsbTransaction, device: Option<&BusDevice>) -> Option<ToggleCheck> {
+        if transaction.setup_packet.is_some() {
+            self.apply_setup(transaction, device);
+            return None;
+        }
+
+        let pid = transaction.data_pid?;
+        if transaction.transfer_type == UsbTransferType::Isochronous
+            || !matches!(pid, UsbPid::Data0 | UsbPid::Data1) {
+            return None;
+        }
+
+        // Only acknowledged packets advance the toggle; NYET also means the data was accepted
+        let accepted = matches!(transaction.status_packet.as_ref().map(|status| status.status),
+                                Some(UsbTransferStatus::ACK) | Some(UsbTransferStatus::NYET));
+
+        let data = transaction.data_packet.as_ref().map(|data| data.data.clone()).unwrap_or_default();
+        let key = (transaction.device_address, transaction.endpoint, transaction.direction() == UsbDirection::DeviceToHost);
+        let state = self.endpoints.entry(key).or_default();
+
+        let check = match state.expected {
+            None => ToggleCheck::Initial(pid),
+            Some(expected) if expected == pid => ToggleCheck::Ok(pid),
+            Some(expected) => {
+                let duplicate = state.last_data.as_ref()
+                    .map(|(last_pid, last_data)| *last_pid == pid && *last_data == data)
+                    .unwrap_or(false);
+                if duplicate {
+                    ToggleCheck::Retransmission(pid)
+                } else {
+                    ToggleCheck::Mismatch { expected, found: pid }
+                }
+            },
+        };
+
+        match &check {
+            ToggleCheck::Retransmission(_) => self.retransmission_count += 1,
+            ToggleCheck::Mismatch { .. } => self.mismatch_count += 1,
+            _ => {},
+        }
+
+        if accepted {
+            match &check {
+                // A duplicate doesn't move the receiver's sequence bit
+                ToggleCheck::Retransmission(_) => {},
+                // Resynchronise on the packet that was actually seen
+                _ => {
+                    state.expected = Some(if pid == UsbPid::Data0 { UsbPid::Data1 } else { UsbPid::Data0 });
+                    state.last_data = Some((pid, data));
+                },
+            }
+        }
+
+        Some(check)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usb::bus::BusModel;
+    use crate::usb::mitm_traffic::{UsbDataPacket, UsbSetupPacket, UsbStatusPacket};
+
+    // Configuration with interface 0 (bulk EP1 IN) and interface 1 (bulk EP2 OUT)
+    const CONFIGURATION: [u8; 41] = [
+        0x09, 0x02, 0x29, 0x00, 0x02, 0x01, 0x00, 0x80, 0x32,
+        0x09, 0x04, 0x00, 0x00, 0x01, 0xFF, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00,
+        0x09, 0x04, 0x01, 0x00, 0x01, 0xFF, 0x00, 0x00, 0x00,
+        0x07, 0x05, 0x02, 0x02, 0x40, 0x00, 0x00,
+    ];
+
+    fn control(setup: [u8; 8], data: &[u8]) -> UsbTransaction {
+        let setup = UsbSetupPacket::new(&setup).unwrap();
+        let mut transaction = UsbTransaction::new(0, 0.0);
+        transaction.transfer_type = UsbTransferType::Control;
+        transaction.device_address = 1;
+        if !data.is_empty() {
+            transaction.data_packet = Some(UsbDataPacket::new(data.to_vec(), setup.direction, 0));
+        }
+        transaction.setup_packet = Some(setup);
+        transaction.status_packet = Some(UsbStatusPacket { status: UsbTransferStatus::ACK, endpoint: 0 });
+        transaction
+    }
+
+    fn bulk(endpoint: u8, direction: UsbDirection, pid: UsbPid, data: &[u8], status: UsbTransferStatus) -> UsbTransaction {
+        let mut transaction = UsbTransaction::new(0, 0.0);
+        transaction.transfer_type = UsbTransferType::Bulk;
+        transaction.device_address = 1;
+        transaction.endpoint = endpoint;
+        transaction.data_packet = Some(UsbDataPacket::new(data.to_vec(), direction, endpoint));
+        transaction.data_pid = Some(pid);
+        transaction.status_packet = Some(UsbStatusPacket { status, endpoint });
+        transaction
+    }
+
+    fn bulk_in(pid: UsbPid, data: &[u8]) -> UsbTransaction {
+        bulk(1, UsbDirection::DeviceToHost, pid, data, UsbTransferStatus::ACK)
+    }
+
+    fn bulk_out(pid: UsbPid, data: &[u8]) -> UsbTransaction {
+        bulk(2, UsbDirection::HostToDevice, pid, data, UsbTransferStatus::ACK)
+    }
+
+    #[test]
+    fn follows_alternating_toggle() {
+        let mut tracker = DataToggleTracker::new();
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data1, &[1]), None), Some(ToggleCheck::Initial(UsbPid::Data1)));
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data0, &[2]), None), Some(ToggleCheck::Ok(UsbPid::Data0)));
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data1, &[3]), None), Some(ToggleCheck::Ok(UsbPid::Data1)));
+        assert_eq!(tracker.mismatch_count(), 0);
+    }
+
+    #[test]
+    fn tells_retransmissions_from_mismatches() {
+        let mut tracker = DataToggleTracker::new();
+        tracker.process_transaction(&bulk_in(UsbPid::Data0, &[1]), None);
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data0, &[1]), None),
+                   Some(ToggleCheck::Retransmission(UsbPid::Data0)));
+        // The duplicate didn't move the toggle
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data1, &[2]), None), Some(ToggleCheck::Ok(UsbPid::Data1)));
+
+        let check = tracker.process_transaction(&bulk_in(UsbPid::Data1, &[3]), None).unwrap();
+        assert!(check.is_error());
+        assert_eq!(check, ToggleCheck::Mismatch { expected: UsbPid::Data0, found: UsbPid::Data1 });
+        // Tracking resynchronises on the packet that was seen
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data0, &[4]), None), Some(ToggleCheck::Ok(UsbPid::Data0)));
+        assert_eq!(tracker.retransmission_count(), 1);
+        assert_eq!(tracker.mismatch_count(), 1);
+    }
+
+    #[test]
+    fn only_accepted_packets_advance_the_toggle() {
+        let mut tracker = DataToggleTracker::new();
+        tracker.process_transaction(&bulk_out(UsbPid::Data0, &[1]), None);
+        let nak = bulk(2, UsbDirection::HostToDevice, UsbPid::Data1, &[2], UsbTransferStatus::NAK);
+        assert_eq!(tracker.process_transaction(&nak, None), Some(ToggleCheck::Ok(UsbPid::Data1)));
+        assert_eq!(tracker.process_transaction(&bulk_out(UsbPid::Data1, &[2]), None), Some(ToggleCheck::Ok(UsbPid::Data1)));
+
+        let mut isochronous = bulk_in(UsbPid::Data0, &[1]);
+        isochronous.transfer_type = UsbTransferType::Isochronous;
+        assert_eq!(tracker.process_transaction(&isochronous, None), None);
+    }
+
+    #[test]
+    fn setup_and_clear_halt_reset_the_toggle() {
+        let mut tracker = DataToggleTracker::new();
+        tracker.process_transaction(&bulk_in(UsbPid::Data0, &[1]), None);
+
+        // CLEAR_FEATURE(ENDPOINT_HALT) on EP1 IN
+        tracker.process_transaction(&control([0x02, 0x01, 0x00, 0x00, 0x81, 0x00, 0x00, 0x00], &[]), None);
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data0, &[2]), None), Some(ToggleCheck::Ok(UsbPid::Data0)));
+
+        // The data stage after SETUP starts at DATA1
+        let mut data_stage = bulk(0, UsbDirection::DeviceToHost, UsbPid::Data1, &[0x12], UsbTransferStatus::ACK);
+        data_stage.transfer_type = UsbTransferType::Control;
+        assert_eq!(tracker.process_transaction(&data_stage, None), Some(ToggleCheck::Ok(UsbPid::Data1)));
+    }
+
+    #[test]
+    fn set_interface_resets_only_that_interface() {
+        let mut bus = BusModel::new();
+        bus.process_transaction(&control([0x80, 0x06, 0x00, 0x02, 0x00, 0x00, 0x29, 0x00], &CONFIGURATION));
+        let device = bus.device(1);
+
+        let mut tracker = DataToggleTracker::new();
+        tracker.process_transaction(&bulk_in(UsbPid::Data0, &[1]), device);
+        tracker.process_transaction(&bulk_out(UsbPid::Data0, &[1]), device);
+
+        // SET_INTERFACE(interface 1, alternate setting 0)
+        tracker.process_transaction(&control([0x01, 0x0B, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00], &[]), device);
+        assert_eq!(tracker.process_transaction(&bulk_out(UsbPid::Data0, &[2]), device), Some(ToggleCheck::Ok(UsbPid::Data0)));
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data1, &[2]), device), Some(ToggleCheck::Ok(UsbPid::Data1)));
+
+        // Without descriptors every endpoint of the device is reset
+        tracker.process_transaction(&control([0x01, 0x0B, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00], &[]), None);
+        assert_eq!(tracker.process_transaction(&bulk_in(UsbPid::Data0, &[3]), None), Some(ToggleCheck::Ok(UsbPid::Data0)));
+    }
+}
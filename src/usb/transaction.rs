@@ -0,0 +1,156 @@
+// Token-level transaction assembly
+// Groups raw packets into token + data + handshake transactions (USB 2.0 section 8.5)
+// for packet-level captures. Packets that belong to split transactions are left to
+// the SplitTracker.
+
+use std::fmt;
+use super::mitm_traffic::{
+    UsbDataPacket,
+    UsbDirection,
+    UsbSetupPacket,
+    UsbStatusPacket,
+    UsbTransaction,
+    UsbTransferStatus,
+    UsbTransferType,
+};
+use super::pid::{UsbPacket, UsbPid};
+
+// One token + data + handshake sequence on the bus
+#[derive(Debug, Clone)]
+pub struct PacketTransaction {
+    pub timestamp: f64,
+    pub token: UsbPid,              // SETUP, OUT, IN or PING
+    pub device_address: u8,
+    pub endpoint: u8,               // Endpoint number (without direction bit)
+    pub data_pid: Option<UsbPid>,
+    pub data: Vec<u8>,
+    pub handshake: Option<UsbPid>,  // None for isochronous or incomplete transactions
+}
+
+impl PacketTransaction {
+    pub fn direction(&self) -> UsbDirection {
+        match self.token {
+            UsbPid::In => UsbDirection::DeviceToHost,
+            _ => UsbDirection::HostToDevice,
+        }
+    }
+
+    // Convert into a regular transaction. Interrupt and bulk endpoints can't be told
+    // apart from the packets alone, so non-control endpoints are treated as bulk
+    // unless the transaction has no handshake (isochronous).
+    pub fn to_transaction(&self, id: u64) -> UsbTransaction {
+        let mut transaction = UsbTransaction::new(id, self.timestamp);
+        transaction.device_address = self.device_address;
+        transaction.endpoint = self.endpoint;
+        transaction.transfer_type = if self.endpoint == 0 {
+            UsbTransferType::Control
+        } else if self.handshake.is_none() && self.data_pid.is_some() {
+            UsbTransferType::Isochronous
+        } else {
+            UsbTransferType::Bulk
+        };
+
+        if self.token == UsbPid::Setup {
+            transaction.setup_packet = UsbSetupPacket::new(&self.data);
+        } else if self.data_pid.is_some() {
+            transaction.data_packet = Some(UsbDataPacket::new(self.data.clone(), self.direction(), self.endpoint));
+        }
+
+        // For IN transactions the host's ACK follows the data; a NAK/STALL replaces it
+        let status = match self.handshake {
+            Some(UsbPid::Ack) => Some(UsbTransferStatus::ACK),
+            Some(UsbPid::Nak) => Some(UsbTransferStatus::NAK),
+            Some(UsbPid::Stall) => Some(UsbTransferStatus::STALL),
+            Some(UsbPid::Nyet) => Some(UsbTransferStatus::NYET),
+            Some(_) => Some(UsbTransferStatus::Unknown),
+            None => None,
+        };
+        if let Some(status) = status {
+            transaction.status_packet = Some(UsbStatusPacket {
+                status,
+                endpoint: self.endpoint,
+            });
+        }
+
+        transaction.data_pid = self.data_pid;
+        transaction.fields.insert("Token".to_string(), self.token.name().to_string());
+
+        transaction
+    }
+}
+
+impl fmt::Display for PacketTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Addr {} EP {}", self.token, self.device_address, self.endpoint)?;
+        if let Some(data_pid) = self.data_pid {
+            write!(f, " {} ({} bytes)", data_pid, self.data.len())?;
+        }
+        match self.handshake {
+            Some(handshake) => write!(f, " -> {}", handshake),
+            None => Ok(()),
+        }
+    }
+}
+
+// Assembles raw packets into transactions as they arrive
+#[derive(Debug, Clone, Default)]
+pub struct TransactionAssembler {
+    current: Option<PacketTransaction>,
+    in_split: bool, // The next token belongs to a split transaction
+}
+
+impl TransactionAssembler {
+    pub fn new() -> Self {
+        TransactionAssembler::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.in_split = false;
+    }
+
+    // Feed one raw packet. Returns a transaction when this packet completes one
+    // (or starts the next while the previous one never got a handshake).
+    pub fn process_packet(&mut self, packet: &UsbPacket) -> Option<PacketTransaction> {
+        match packet.pid {
+            UsbPid::Split => {
+                let finished = self.current.take();
+                self.in_split = true;
+                finished
+            },
+            UsbPid::Out | UsbPid::In | UsbPid::Setup | UsbPid::Ping => {
+                let finished = self.current.take();
+                if self.in_split {
+                    // Token of a split transaction; its data and handshake are ignored too
+                    self.in_split = false;
+                    return finished;
+                }
+                self.current = Some(PacketTransaction {
+                    timestamp: packet.timestamp,
+                    token: packet.pid,
+                    device_address: packet.token_address().unwrap_or(0),
+                    endpoint: packet.token_endpoint().unwrap_or(0),
+                    data_pid: None,
+                    data: Vec::new(),
+                    handshake: None,
+                });
+                finished
+            },
+            UsbPid::Data0 | UsbPid::Data1 | UsbPid::Data2 | UsbPid::MData => {
+                if let Some(current) = self.current.as_mut() {
+                    if current.data_pid.is_none() && current.token != UsbPid::Ping {
+                        current.data_pid = Some(packet.pid);
+                        current.data = packet.payload().to_vec();
+                    }
+                }
+                None
+            },
+            UsbPid::Ack | UsbPid::Nak | UsbPid::Stall | UsbPid::Nyet => {
+                let mut finished = self.current.take()?;
+                finished.handshake = Some(packet.pid);
+                Some(finished)
+            },
+            UsbPid::Sof | UsbPid::Pre => self.current.take(),
+        }
+    }
+}
@@ -0,0 +1,478 @@
+// User-defined vendor request schemas
+// Vendor control requests have no standard meaning, so their layout is supplied by the
+// user: one JSON file per product, read from the config directory at startup. A schema
+// names bRequest codes, gives wValue/wIndex a name and value table, and lays out the data
+// stage as typed fields. Numbers may be written as JSON integers or "0x" hex strings.
+//
+//   {
+//     "name": "Acme Widget",
+//     "vendor_id": "0x1234",
+//     "product_id": "0x5678",          (optional; omit to match every product of the vendor)
+//     "requests": [{
+//       "request": "0x10", "name": "SET_LED", "direction": "out",
+//       "value": { "name": "LED", "values": { "0": "Red", "1": "Green" } },
+//       "index": { "name": "Brightness" },
+//       "data": [
+//         { "name": "Mode", "type": "u8", "values": { "0": "Off", "1": "On" } },
+//         { "name": "Period", "type": "u16", "endian": "big" },
+//         { "name": "Flags", "type": "bitfield", "size": 1,
+//           "bits": [{ "name": "Blink", "bit": 0 }, { "name": "Speed", "bit": 1, "width": 2 }] },
+//         { "name": "Label", "type": "string", "length": 16 }
+//       ]
+//     }]
+//   }
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer};
+use super::mitm_traffic::{UsbControlRequestType, UsbDirection, UsbSetupPacket};
+use super::request_fields::{flag, RequestField};
+
+// Environment variable that overrides the schema directory
+pub const SCHEMA_DIR_VAR: &str = "USBFLY_VENDOR_SCHEMAS";
+
+// Parse "0x1D50" or "7504"
+fn parse_number(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonNumber {
+    Number(u64),
+    Text(String),
+}
+
+impl JsonNumber {
+    fn value<E: serde::de::Error>(self) -> Result<u64, E> {
+        match self {
+            JsonNumber::Number(value) => Ok(value),
+            JsonNumber::Text(text) => parse_number(&text)
+                .ok_or_else(|| E::custom(format!("invalid number \"{}\"", text))),
+        }
+    }
+}
+
+fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    let value = JsonNumber::deserialize(deserializer)?.value()?;
+    T::try_from(value).map_err(|_| serde::de::Error::custom(format!("number {} out of range", value)))
+}
+
+fn optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    number(deserializer).map(Some)
+}
+
+// Value tables are JSON objects, so their keys arrive as strings
+fn value_names<'de, D>(deserializer: D) -> Result<BTreeMap<u64, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<String, String>::deserialize(deserializer)?.into_iter()
+        .map(|(key, name)| match parse_number(&key) {
+            Some(value) => Ok((value, name)),
+            None => Err(serde::de::Error::custom(format!("invalid value key \"{}\"", key))),
+        })
+        .collect()
+}
+
+fn describe_value(value: u64, values: &BTreeMap<u64, String>) -> String {
+    match values.get(&value) {
+        Some(name) => format!("{} ({})", name, value),
+        None if values.is_empty() => format!("{} (0x{:X})", value, value),
+        None => format!("Unknown (0x{:X})", value),
+    }
+}
+
+// Direction a request is defined for; requests that share a bRequest code can differ by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaDirection {
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+// Meaning of wValue or wIndex
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParameterSchema {
+    pub name: String,
+    #[serde(default, deserialize_with = "value_names")]
+    pub values: BTreeMap<u64, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    Bitfield,
+    String,
+    Bytes,
+}
+
+impl FieldType {
+    // Width of fixed-size types; strings and byte arrays take their length from the field
+    fn size(&self) -> Option<usize> {
+        match self {
+            FieldType::U8 | FieldType::I8 => Some(1),
+            FieldType::U16 | FieldType::I16 => Some(2),
+            FieldType::U32 | FieldType::I32 => Some(4),
+            FieldType::U64 | FieldType::I64 => Some(8),
+            FieldType::Bitfield | FieldType::String | FieldType::Bytes => None,
+        }
+    }
+
+    fn is_signed(&self) -> bool {
+        matches!(self, FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64)
+    }
+}
+
+// A named run of bits within a bitfield
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitSchema {
+    pub name: String,
+    #[serde(deserialize_with = "number")]
+    pub bit: u8,
+    #[serde(default = "default_width", deserialize_with = "number")]
+    pub width: u8,
+    #[serde(default, deserialize_with = "value_names")]
+    pub values: BTreeMap<u64, String>,
+}
+
+fn default_width() -> u8 {
+    1
+}
+
+// One data-stage field. Fields follow each other unless an offset is given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub offset: Option<usize>,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub length: Option<usize>, // Strings and byte arrays; the rest of the payload when omitted
+    #[serde(default, deserialize_with = "optional_number")]
+    pub size: Option<usize>,   // Bitfield width in bytes (default 1)
+    #[serde(default)]
+    pub endian: Endian,
+    #[serde(default, deserialize_with = "value_names")]
+    pub values: BTreeMap<u64, String>,
+    #[serde(default)]
+    pub bits: Vec<BitSchema>,
+}
+
+impl FieldSchema {
+    // Bytes the field covers at the given offset, or None when the payload is too short
+    fn extent(&self, offset: usize, available: usize) -> Option<usize> {
+        let length = match self.field_type {
+            FieldType::Bitfield => self.size.unwrap_or(1),
+            FieldType::String | FieldType::Bytes => self.length.unwrap_or(available.saturating_sub(offset)),
+            _ => self.field_type.size()?,
+        };
+        let end = offset.checked_add(length)?;
+        if end <= available { Some(length) } else { None }
+    }
+
+    fn read_unsigned(&self, bytes: &[u8]) -> u64 {
+        let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+        match self.endian {
+            Endian::Big => bytes.iter().fold(0, fold),
+            Endian::Little => bytes.iter().rev().fold(0, fold),
+        }
+    }
+
+    // Decode the field into one row, plus one row per named bit
+    fn decode(&self, bytes: &[u8], fields: &mut Vec<RequestField>) {
+        match self.field_type {
+            FieldType::String => {
+                let text = String::from_utf8_lossy(bytes);
+                fields.push((self.name.clone(), format!("\"{}\"", text.trim_end_matches('\0'))));
+            },
+            FieldType::Bytes => {
+                let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                fields.push((self.name.clone(), hex.join(" ")));
+            },
+            FieldType::Bitfield => {
+                let value = self.read_unsigned(bytes);
+                fields.push((self.name.clone(), format!("0x{:0width$X}", value, width = bytes.len() * 2)));
+                for bit in &self.bits {
+                    let mask = if bit.width >= 64 { u64::MAX } else { (1u64 << bit.width) - 1 };
+                    let bits = (value >> bit.bit) & mask;
+                    let text = if bit.width == 1 && bit.values.is_empty() {
+                        flag(bits != 0)
+                    } else {
+                        describe_value(bits, &bit.values)
+                    };
+                    fields.push((format!("{}.{}", self.name, bit.name), text));
+                }
+            },
+            field_type => {
+                let raw = self.read_unsigned(bytes);
+                let text = if field_type.is_signed() {
+                    // Sign-extend from the field width
+                    let shift = 64 - bytes.len() * 8;
+                    format!("{}", ((raw << shift) as i64) >> shift)
+                } else {
+                    describe_value(raw, &self.values)
+                };
+                fields.push((self.name.clone(), text));
+            },
+        }
+    }
+}
+
+// One vendor request
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestSchema {
+    #[serde(deserialize_with = "number")]
+    pub request: u8,
+    pub name: String,
+    #[serde(default)]
+    pub direction: Option<SchemaDirection>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub value: Option<ParameterSchema>,
+    #[serde(default)]
+    pub index: Option<ParameterSchema>,
+    #[serde(default)]
+    pub data: Vec<FieldSchema>,
+}
+
+impl RequestSchema {
+    fn matches(&self, setup: &UsbSetupPacket) -> bool {
+        let direction = match setup.direction {
+            UsbDirection::DeviceToHost => Some(SchemaDirection::In),
+            UsbDirection::HostToDevice => Some(SchemaDirection::Out),
+            UsbDirection::Unknown => None,
+        };
+        self.request == setup.bRequest && (self.direction.is_none() || self.direction == direction)
+    }
+
+    // Lay out the data stage; fields past the end of a short payload are reported as missing
+    pub fn decode_data(&self, data: &[u8]) -> Vec<RequestField> {
+        let mut fields = Vec::new();
+        let mut offset = 0;
+        for field in &self.data {
+            let start = field.offset.unwrap_or(offset);
+            match field.extent(start, data.len()) {
+                Some(length) => {
+                    field.decode(&data[start..start + length], &mut fields);
+                    offset = start + length;
+                },
+                None => {
+                    fields.push((field.name.clone(), "(missing)".to_string()));
+                    offset = data.len();
+                },
+            }
+        }
+        if offset < data.len() && !self.data.is_empty() {
+            fields.push(("Trailing Data".to_string(), format!("{} bytes", data.len() - offset)));
+        }
+        fields
+    }
+}
+
+// All requests of one product (or of every product of a vendor)
+#[derive(Debug, Clone, Deserialize)]
+pub struct VendorSchema {
+    pub name: String,
+    #[serde(deserialize_with = "number")]
+    pub vendor_id: u16,
+    #[serde(default, deserialize_with = "optional_number")]
+    pub product_id: Option<u16>,
+    #[serde(default)]
+    pub requests: Vec<RequestSchema>,
+}
+
+impl VendorSchema {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let schema: VendorSchema = serde_json::from_str(json).map_err(|e| format!("{}", e))?;
+        for request in &schema.requests {
+            for field in &request.data {
+                if field.field_type == FieldType::Bitfield && !matches!(field.size.unwrap_or(1), 1 | 2 | 4 | 8) {
+                    return Err(format!("{}: bitfield \"{}\" must be 1, 2, 4 or 8 bytes", request.name, field.name));
+                }
+                // An explicit offset and length must not run past the address space
+                let length = match field.field_type {
+                    FieldType::Bitfield => field.size.unwrap_or(1),
+                    FieldType::String | FieldType::Bytes => field.length.unwrap_or(0),
+                    field_type => field_type.size().unwrap_or(0),
+                };
+                if field.offset.unwrap_or(0).checked_add(length).is_none() {
+                    return Err(format!("{}: field \"{}\" offset plus length overflows", request.name, field.name));
+                }
+                // Named bits have to fit inside the bitfield
+                let bits = field.size.unwrap_or(1) * 8;
+                for bit in field.bits.iter().filter(|_| field.field_type == FieldType::Bitfield) {
+                    if bit.bit as usize >= bits || bit.bit as usize + bit.width as usize > bits {
+                        return Err(format!("{}: bit \"{}\" of \"{}\" does not fit in {} bits",
+                                           request.name, bit.name, field.name, bits));
+                    }
+                }
+            }
+        }
+        Ok(schema)
+    }
+
+    pub fn request(&self, setup: &UsbSetupPacket) -> Option<&RequestSchema> {
+        self.requests.iter().find(|request| request.matches(setup))
+    }
+}
+
+impl fmt::Display for VendorSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.product_id {
+            Some(product_id) => write!(f, "{} ({:04X}:{:04X})", self.name, self.vendor_id, product_id)?,
+            None => write!(f, "{} ({:04X}:*)", self.name, self.vendor_id)?,
+        }
+        write!(f, ", {} request{}", self.requests.len(), if self.requests.len() == 1 { "" } else { "s" })
+    }
+}
+
+// A vendor request decoded with its schema
+#[derive(Debug, Clone)]
+pub struct VendorRequest {
+    pub description: String,
+    pub fields: Vec<RequestField>,
+}
+
+// Every schema loaded from the config directory
+#[derive(Debug, Clone, Default)]
+pub struct VendorSchemaRegistry {
+    schemas: Vec<VendorSchema>,
+    errors: Vec<String>, // Files that could not be loaded, with the reason
+}
+
+impl VendorSchemaRegistry {
+    pub fn new() -> Self {
+        VendorSchemaRegistry::default()
+    }
+
+    // The schema directory: $USBFLY_VENDOR_SCHEMAS, or vendor_schemas in the user's config directory
+    pub fn schema_dir() -> Option<PathBuf> {
+        if let Ok(dir) = std::env::var(SCHEMA_DIR_VAR) {
+            return Some(PathBuf::from(dir));
+        }
+        directories::ProjectDirs::from("com", "usbfly", "USBfly")
+            .map(|dirs| dirs.config_dir().join("vendor_schemas"))
+    }
+
+    // Load the schema directory; a missing directory just means no schemas
+    pub fn load_default() -> Self {
+        match Self::schema_dir() {
+            Some(dir) if dir.is_dir() => Self::load_dir(&dir),
+            _ => Self::new(),
+        }
+    }
+
+    // Load every .json file in a directory, in file name order
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut registry = Self::new();
+        let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+                .collect(),
+            Err(e) => {
+                registry.errors.push(format!("{}: {}", dir.display(), e));
+                return registry;
+            },
+        };
+        paths.sort();
+
+        for path in paths {
+            let result = fs::read_to_string(&path).map_err(|e| format!("{}", e))
+                .and_then(|json| VendorSchema::parse(&json));
+            match result {
+                Ok(schema) => registry.schemas.push(schema),
+                Err(e) => registry.errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        registry
+    }
+
+    pub fn schemas(&self) -> &[VendorSchema] {
+        &self.schemas
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    // Schema for a device; a product-specific schema wins over a vendor-wide one
+    pub fn find(&self, vendor_id: u16, product_id: u16) -> Option<&VendorSchema> {
+        let vendor = self.schemas.iter().filter(|schema| schema.vendor_id == vendor_id);
+        vendor.clone().find(|schema| schema.product_id == Some(product_id))
+            .or_else(|| vendor.clone().find(|schema| schema.product_id.is_none()))
+    }
+
+    fn request(&self, vendor_id: u16, product_id: u16, setup: &UsbSetupPacket) -> Option<&RequestSchema> {
+        if setup.request_type != UsbControlRequestType::Vendor {
+            return None;
+        }
+        self.find(vendor_id, product_id)?.request(setup)
+    }
+
+    // Decode the setup stage of a vendor request
+    pub fn decode_request(&self, vendor_id: u16, product_id: u16, setup: &UsbSetupPacket) -> Option<VendorRequest> {
+        let request = self.request(vendor_id, product_id, setup)?;
+
+        let mut fields = vec![("Request".to_string(), format!("{} (0x{:02X})", request.name, setup.bRequest))];
+        if let Some(description) = &request.description {
+            fields.push(("Description".to_string(), description.clone()));
+        }
+        let mut parameters = Vec::new();
+        for (schema, value) in [(&request.value, setup.wValue), (&request.index, setup.wIndex)] {
+            if let Some(schema) = schema {
+                let text = describe_value(value as u64, &schema.values);
+                parameters.push(format!("{}: {}", schema.name, text));
+                fields.push((schema.name.clone(), text));
+            }
+        }
+        fields.push(("Length".to_string(), format!("{} bytes", setup.wLength)));
+
+        let description = if parameters.is_empty() {
+            format!("Vendor {}", request.name)
+        } else {
+            format!("Vendor {} ({})", request.name, parameters.join(", "))
+        };
+        Some(VendorRequest { description, fields })
+    }
+
+    // Decode the data stage of a vendor request
+    pub fn decode_data(&self, vendor_id: u16, product_id: u16, setup: &UsbSetupPacket, data: &[u8]) -> Vec<RequestField> {
+        match self.request(vendor_id, product_id, setup) {
+            Some(request) => request.decode_data(data),
+            None => Vec::new(),
+        }
+    }
+}